@@ -1,18 +1,30 @@
 //! アプリケーションの状態管理
 
-use crate::pdf::{PdfDocument, PdfOperations, RectAnnotation, Stamp, TextAnnotation};
-use crate::ui::{EditorPanel, FileExplorer};
+use crate::history::{EditCommand, History};
+use crate::pdf::{
+    AnnotationData, AnnotationKind, AnnotationRef, CommentReply, ImportedAnnotations, PdfDocument, PdfOperations, RenderArea, RenderJob,
+    RenderSource, SaveMode, StampLibrary, StampType,
+};
+use crate::ui::{
+    AnnotationList, AnnotationListResult, CommentList, EditorContext, EditorPanel, FileExplorer,
+    FolderSearch, PageStrip, TextureCache, ANNOTATION_LIST_WIDTH, COMMENT_LIST_WIDTH, PAGE_STRIP_WIDTH,
+};
 use eframe::egui::{self, Color32, TextureHandle, Vec2};
-use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::time::SystemTime;
 
+/// フォルダ内PDFサムネイルのキャッシュ上限（バイト）
+const THUMBNAIL_CACHE_BYTES: usize = 32 * 1024 * 1024;
+
 /// アプリケーション全体の状態
 pub struct PdfViewerApp {
     // UI パネル
     file_explorer: FileExplorer,
     editor_panel: EditorPanel,
+    page_strip: PageStrip,
+    comment_list: CommentList,
+    annotation_list: AnnotationList,
 
     // PDF ドキュメント
     current_document: Option<PdfDocument>,
@@ -21,28 +33,40 @@ pub struct PdfViewerApp {
 
     // 編集状態
     selected_page: usize,
-    stamps: Vec<Stamp>,
-    text_annotations: Vec<TextAnnotation>,
-    rect_annotations: Vec<RectAnnotation>,
+    annotations: AnnotationData,
     has_unsaved_changes: bool,
+    has_native_annotations: bool,  // 開いたPDFにPDF注釈があったか
+    history: History,  // 元に戻す・やり直し
 
     // UI 状態
     show_split_dialog: bool,
     show_stamp_panel: bool,
     show_text_panel: bool,
-    show_stamp_register_dialog: bool,
+    show_page_strip: bool,
+    show_comment_list: bool,
+    show_annotation_list: bool,
+    show_stamp_library_dialog: bool,
     split_start_page: String,
     split_end_page: String,
+    show_move_dialog: bool,
+    show_save_dialog: bool,
+    save_mode: SaveMode,
+    move_start_page: String,
+    move_end_page: String,
+    move_dest_page: String,
 
     // フォルダ内PDFサムネイル
     folder_pdfs: Vec<FolderPdfEntry>,
     selected_pdf_index: Option<usize>,
-    pdf_thumbnails: Vec<Option<TextureHandle>>,
+    pdf_thumbnails: TextureCache<PathBuf>,
     current_folder: Option<PathBuf>,
+    folder_search: FolderSearch,  // フォルダ以下のPDFの全文検索
 
     // カスタムスタンプ（PNG透過対応）
     custom_stamps: Vec<CustomStamp>,
     custom_stamp_textures: Vec<Option<TextureHandle>>,
+    stamp_library: StampLibrary,  // 登録した画像スタンプと認印（設定ディレクトリに保存）
+    library_drafts: Vec<(String, String)>,  // ライブラリ画面で編集中の (名前, 分類)
 
     // コンテキストメニュー
     context_menu_pdf: Option<(usize, egui::Pos2)>,
@@ -61,22 +85,12 @@ struct FolderPdfEntry {
     modified: SystemTime,
 }
 
-/// 注釈データ（保存用）
-#[derive(Serialize, Deserialize)]
-struct AnnotationData {
-    stamps: Vec<Stamp>,
-    texts: Vec<TextAnnotation>,
-    #[serde(default)]
-    rects: Vec<RectAnnotation>,  // 矩形注釈
-    #[serde(default)]
-    page_rotations: Vec<i32>,  // ページごとの回転角度
-}
-
 /// カスタムスタンプ（PNG透過対応）
 #[derive(Clone)]
 pub struct CustomStamp {
     pub name: String,
-    pub path: PathBuf,
+    pub path: PathBuf,  // ライブラリの画像（PDFから取り込んだスタンプは空）
+    pub category: String,
     pub image_data: Vec<u8>,
     pub width: u32,
     pub height: u32,
@@ -84,51 +98,82 @@ pub struct CustomStamp {
 
 impl PdfViewerApp {
     pub fn new(_cc: &eframe::CreationContext<'_>) -> Self {
-        Self {
+        let mut app = Self {
             file_explorer: FileExplorer::new(),
             editor_panel: EditorPanel::new(),
+            page_strip: PageStrip::new(),
+            comment_list: CommentList::new(),
+            annotation_list: AnnotationList::new(),
             current_document: None,
             current_pdf_path: None,
             documents: Vec::new(),
             selected_page: 0,
-            stamps: Vec::new(),
-            text_annotations: Vec::new(),
-            rect_annotations: Vec::new(),
+            annotations: AnnotationData::default(),
             has_unsaved_changes: false,
+            has_native_annotations: false,
+            history: History::default(),
             show_split_dialog: false,
             show_stamp_panel: false,
             show_text_panel: false,
-            show_stamp_register_dialog: false,
+            show_page_strip: true,
+            show_comment_list: false,
+            show_annotation_list: false,
+            show_stamp_library_dialog: false,
             split_start_page: String::new(),
             split_end_page: String::new(),
+            show_move_dialog: false,
+            show_save_dialog: false,
+            save_mode: SaveMode::default(),
+            move_start_page: String::new(),
+            move_end_page: String::new(),
+            move_dest_page: String::new(),
             folder_pdfs: Vec::new(),
             selected_pdf_index: None,
-            pdf_thumbnails: Vec::new(),
+            pdf_thumbnails: TextureCache::new("folder_pdf", THUMBNAIL_CACHE_BYTES),
             current_folder: None,
+            folder_search: FolderSearch::new(),
             custom_stamps: Vec::new(),
             custom_stamp_textures: Vec::new(),
+            stamp_library: StampLibrary::load(),
+            library_drafts: Vec::new(),
             context_menu_pdf: None,
             dragging_pdf: None,
             status_message: "準備完了".to_string(),
-        }
+        };
+        app.sync_library_stamps();
+        app
     }
 
     /// PDFファイルを開く
     pub fn open_pdf(&mut self, path: PathBuf) {
         match PdfDocument::open(&path) {
-            Ok(doc) => {
+            Ok(mut doc) => {
+                // 他のソフトで追加されたPDF注釈もエディタで編集できるよう取り込む
+                let imported = doc.take_annotations().unwrap_or_else(|e| {
+                    log::warn!("PDF注釈を取り込めませんでした: {}", e);
+                    ImportedAnnotations::default()
+                });
+
                 self.current_document = Some(doc);
                 self.current_pdf_path = Some(path.clone());
                 self.selected_page = 0;
+                self.history.clear();
+                self.editor_panel.clear_selection();
                 self.editor_panel.invalidate_cache();
+                self.page_strip.invalidate_cache();
                 
                 // 注釈ファイルを読み込み
-                self.stamps.clear();
-                self.text_annotations.clear();
+                self.annotations = AnnotationData::default();
                 self.load_annotations(&path);
+                let imported_count = self.add_imported_annotations(imported);
                 
+                self.has_native_annotations = imported_count > 0;
                 self.has_unsaved_changes = false;
-                self.status_message = format!("開きました: {}", path.display());
+                self.status_message = if imported_count > 0 {
+                    format!("開きました: {} (PDF注釈 {} 件)", path.display(), imported_count)
+                } else {
+                    format!("開きました: {}", path.display())
+                };
             }
             Err(e) => {
                 self.status_message = format!("エラー: {}", e);
@@ -137,10 +182,77 @@ impl PdfViewerApp {
         }
     }
 
-    /// 上書き保存（注釈を保存）
+    /// スタンプライブラリを保存し、結果をステータスに表示
+    fn save_stamp_library(&mut self, message: String) {
+        self.status_message = match self.stamp_library.save() {
+            Ok(()) => message,
+            Err(e) => {
+                log::error!("スタンプライブラリを保存できません: {}", e);
+                format!("エラー: {}", e)
+            }
+        };
+    }
+
+    /// カスタムスタンプの画像を名前から取得
+    fn custom_stamp_image(&self, name: &str) -> Option<image::DynamicImage> {
+        self.custom_stamps
+            .iter()
+            .find(|s| s.name == name)
+            .and_then(|s| image::RgbaImage::from_raw(s.width, s.height, s.image_data.clone()))
+            .map(image::DynamicImage::ImageRgba8)
+    }
+
+    /// 取り込んだPDF注釈を追加し、件数を返す
+    ///
+    /// 組み込み以外のスタンプは外観画像をカスタムスタンプとして登録する（同名の登録済みスタンプを優先）。
+    fn add_imported_annotations(&mut self, imported: ImportedAnnotations) -> usize {
+        for (name, image) in imported.stamp_images {
+            if self.custom_stamps.iter().any(|s| s.name == name) {
+                continue;
+            }
+            let rgba = image.to_rgba8();
+            let (width, height) = rgba.dimensions();
+            self.custom_stamps.push(CustomStamp {
+                name,
+                path: PathBuf::new(),
+                category: String::new(),
+                image_data: rgba.into_raw(),
+                width,
+                height,
+            });
+            self.custom_stamp_textures.push(None);
+        }
+
+        let data = imported.annotations;
+        let count = data.stamps.len()
+            + data.texts.len()
+            + data.rects.len()
+            + data.markups.len()
+            + data.shapes.len()
+            + data.comments.len();
+        self.annotations.extend(data);
+        count
+    }
+
+    /// 上書き保存（注釈を保存、ページ構成の変更があればPDFも保存）
+    ///
+    /// PDF注釈を取り込んだファイルや墨消しがある場合は、注釈をPDF注釈としてPDFに書き戻す。
     fn save_current(&mut self) {
         if let Some(ref path) = self.current_pdf_path.clone() {
-            self.save_annotations(&path);
+            // 墨消しは注釈ファイルに書くだけでは下の内容が残るため、PDFに書き込んで削除する
+            if self.has_native_annotations || self.annotations.rects.iter().any(|r| r.redact) {
+                self.save_native_annotations(path);
+                return;
+            }
+            if let Some(ref mut doc) = self.current_document {
+                if doc.is_edited() {
+                    if let Err(e) = doc.save(path) {
+                        self.status_message = format!("保存エラー: {}", e);
+                        return;
+                    }
+                }
+            }
+            self.save_annotations(path);
             self.has_unsaved_changes = false;
             self.status_message = format!("保存しました: {}", path.display());
         } else {
@@ -148,59 +260,66 @@ impl PdfViewerApp {
         }
     }
 
-    /// 注釈ファイルのパスを取得
-    fn get_annotations_path(pdf_path: &PathBuf) -> PathBuf {
-        let mut path = pdf_path.clone();
-        path.set_extension("annotations.json");
-        path
+    /// 注釈をPDF注釈として自身のファイルに保存し、開き直す（注釈ファイルは不要になるため削除）
+    fn save_native_annotations(&mut self, path: &Path) {
+        let Some(ref doc) = self.current_document else {
+            return;
+        };
+
+        let result = PdfOperations::export(doc, &self.annotations, SaveMode::Annotations, |name| {
+            self.custom_stamp_image(name)
+        })
+        .and_then(|mut exported| exported.save(path));
+
+        match result {
+            Ok(()) => {
+                // 書き込んだ注釈が二重にならないよう、注釈ファイルを消して開き直す
+                // （取り込んだPDF注釈があれば、以降の上書き保存もPDFに書き戻す）
+                let _ = std::fs::remove_file(AnnotationData::sidecar_path(path));
+                let page = self.selected_page;
+                self.open_pdf(path.to_path_buf());
+                if let Some(ref doc) = self.current_document {
+                    self.selected_page = page.min(doc.page_count().saturating_sub(1));
+                }
+                self.status_message = format!("保存しました: {}", path.display());
+            }
+            Err(e) => {
+                self.status_message = format!("保存エラー: {}", e);
+            }
+        }
     }
 
     /// 注釈を読み込み
     fn load_annotations(&mut self, pdf_path: &PathBuf) {
-        let ann_path = Self::get_annotations_path(pdf_path);
-        if ann_path.exists() {
-            if let Ok(content) = std::fs::read_to_string(&ann_path) {
-                if let Ok(data) = serde_json::from_str::<AnnotationData>(&content) {
-                    self.stamps = data.stamps;
-                    self.text_annotations = data.texts;
-                    self.rect_annotations = data.rects;
-                    
-                    // ページ回転情報を復元
-                    if let Some(ref mut doc) = self.current_document {
-                        for (page_idx, &rotation) in data.page_rotations.iter().enumerate() {
-                            doc.set_page_rotation(page_idx, rotation);
-                        }
-                    }
-                    
-                    self.status_message = "注釈を読み込みました".to_string();
+        if let Some(mut data) = AnnotationData::load(pdf_path) {
+            // ページ回転情報を復元（古い形式はファイルの /Rotate からの相対角度）
+            if let Some(ref mut doc) = self.current_document {
+                let file_rotations: Vec<i32> =
+                    (0..doc.page_count()).map(|i| doc.get_page_rotation(i)).collect();
+                data.migrate(&file_rotations);
+                for (page_idx, &rotation) in data.page_rotations.iter().enumerate() {
+                    doc.set_page_rotation(page_idx, rotation);
                 }
             }
+
+            self.annotations = data;
+            self.status_message = "注釈を読み込みました".to_string();
         }
     }
 
     /// 注釈を保存
-    fn save_annotations(&self, pdf_path: &PathBuf) {
-        let ann_path = Self::get_annotations_path(pdf_path);
-        
+    fn save_annotations(&mut self, pdf_path: &PathBuf) {
         // ページ回転情報を収集
-        let page_rotations: Vec<i32> = if let Some(ref doc) = self.current_document {
+        self.annotations.page_rotations = if let Some(ref doc) = self.current_document {
             (0..doc.page_count())
                 .map(|i| doc.get_page_rotation(i))
                 .collect()
         } else {
             Vec::new()
         };
-        
-        let data = AnnotationData {
-            stamps: self.stamps.clone(),
-            texts: self.text_annotations.clone(),
-            rects: self.rect_annotations.clone(),
-            page_rotations,
-        };
-        if let Ok(content) = serde_json::to_string_pretty(&data) {
-            if let Err(e) = std::fs::write(&ann_path, content) {
-                log::error!("注釈の保存に失敗: {}", e);
-            }
+
+        if let Err(e) = self.annotations.save(pdf_path) {
+            log::error!("注釈の保存に失敗: {}", e);
         }
     }
 
@@ -236,12 +355,28 @@ impl PdfViewerApp {
         }
     }
 
+    /// フォルダ内検索の結果を開き、一致した箇所を表示
+    fn open_search_hit(&mut self, path: PathBuf, page: usize, query: &str) {
+        if self.current_pdf_path.as_ref() != Some(&path) {
+            self.open_pdf(path.clone());
+            if self.current_pdf_path.as_ref() != Some(&path) {
+                return;
+            }
+        }
+        self.selected_pdf_index = self.folder_pdfs.iter().position(|e| e.path == path);
+        if let Some(ref doc) = self.current_document {
+            self.selected_page = page.min(doc.page_count().saturating_sub(1));
+        }
+        self.editor_panel.find_text(query, page);
+    }
+
     /// フォルダ内のPDFを更新（新しい順）
     pub fn update_folder_pdfs(&mut self, folder_path: &PathBuf) {
         self.folder_pdfs.clear();
         self.pdf_thumbnails.clear();
         self.selected_pdf_index = None;
         self.current_folder = Some(folder_path.clone());
+        self.folder_search.set_folder(folder_path);
 
         if let Ok(entries) = std::fs::read_dir(folder_path) {
             for entry in entries.flatten() {
@@ -258,33 +393,33 @@ impl PdfViewerApp {
 
         // 新しい順にソート（更新日時の降順）
         self.folder_pdfs.sort_by(|a, b| b.modified.cmp(&a.modified));
-        self.pdf_thumbnails.resize(self.folder_pdfs.len(), None);
     }
 
-    /// PDFを保存
+    /// 注釈を書き込んでPDFを保存（書き込み方法は保存ダイアログで選択）
     pub fn save_pdf(&mut self, path: &PathBuf) {
-        if let Some(ref mut doc) = self.current_document {
-            for stamp in &self.stamps {
-                if let Err(e) = PdfOperations::add_stamp(doc, stamp) {
-                    log::error!("スタンプ追加エラー: {}", e);
-                }
-            }
-            for annotation in &self.text_annotations {
-                if let Err(e) = PdfOperations::add_text(doc, annotation) {
-                    log::error!("テキスト追加エラー: {}", e);
-                }
-            }
+        let Some(ref doc) = self.current_document else {
+            return;
+        };
 
-            match doc.save(path) {
-                Ok(_) => {
-                    self.status_message = format!("保存しました: {}", path.display());
-                    self.has_unsaved_changes = false;
-                }
-                Err(e) => {
-                    self.status_message = format!("保存エラー: {}", e);
-                }
-            }
+        let result = PdfOperations::export(doc, &self.annotations, self.save_mode, |name| {
+            self.custom_stamp_image(name)
+        })
+        .and_then(|mut exported| exported.save(path));
+
+        if let Err(e) = result {
+            self.status_message = format!("保存エラー: {}", e);
+            return;
+        }
+
+        // 結合直後などファイル未保存のドキュメントや、自身のファイルに保存した場合は
+        // 書き込み済みの注釈が二重にならないよう、注釈ファイルを消して開き直す
+        if self.current_pdf_path.is_none() || self.current_pdf_path.as_ref() == Some(path) {
+            let _ = std::fs::remove_file(AnnotationData::sidecar_path(path));
+            self.open_pdf(path.clone());
+        } else {
+            self.has_unsaved_changes = false;
         }
+        self.status_message = format!("保存しました: {}", path.display());
     }
 
     /// 複数PDFを結合
@@ -295,11 +430,27 @@ impl PdfViewerApp {
         }
 
         match PdfOperations::merge(&self.documents) {
-            Ok(merged) => {
+            Ok((mut merged, annotations)) => {
+                // 結合元のPDF注釈も開いたときと同じように取り込む
+                let imported = merged.take_annotations().unwrap_or_else(|e| {
+                    log::warn!("PDF注釈を取り込めませんでした: {}", e);
+                    ImportedAnnotations::default()
+                });
+                self.status_message = format!(
+                    "PDFを結合しました ({} ページ) - 名前を付けて保存してください",
+                    merged.page_count()
+                );
                 self.current_document = Some(merged);
-                self.status_message = "PDFを結合しました".to_string();
+                self.current_pdf_path = None;
+                self.annotations = annotations;
+                self.has_native_annotations = self.add_imported_annotations(imported) > 0;
+                self.selected_page = 0;
+                self.has_unsaved_changes = true;
+                self.history.clear();
                 self.documents.clear();
+                self.editor_panel.clear_selection();
                 self.editor_panel.invalidate_cache();
+                self.page_strip.invalidate_cache();
             }
             Err(e) => {
                 self.status_message = format!("結合エラー: {}", e);
@@ -319,7 +470,7 @@ impl PdfViewerApp {
                     .set_file_name("split.pdf")
                     .save_file()
                 {
-                    match PdfOperations::split(doc, start - 1, end, &path) {
+                    match PdfOperations::split(doc, &self.annotations, start - 1, end, &path) {
                         Ok(_) => {
                             self.status_message =
                                 format!("分割しました (ページ {} - {})", start, end);
@@ -335,13 +486,62 @@ impl PdfViewerApp {
         }
     }
 
-    /// ページを回転
-    fn rotate_page(&mut self, page: usize, angle: i32) {
+    /// 注釈一覧の操作を反映（まとめて操作したものは1回で元に戻せる）
+    fn apply_annotation_list(&mut self, list: AnnotationListResult) {
+        if let Some((page, item)) = list.jump_to {
+            self.selected_page = page;
+            self.editor_panel.select_annotation(item);
+        }
+
+        let before = self.annotations.clone();
+        let mut edit_label = None;
+        if let Some(items) = list.delete {
+            let count = self.annotations.remove_items(&items);
+            self.editor_panel.clear_selection();
+            edit_label = Some("注釈の一括削除");
+            self.status_message = format!("{} 件の注釈を削除しました", count);
+        }
+        if let Some((items, page)) = list.move_to_page {
+            let count = self.annotations.move_items_to_page(&items, page);
+            edit_label = Some("注釈のページ移動");
+            self.status_message = format!("{} 件の注釈をページ {} へ移動しました", count, page + 1);
+        }
+        if let Some((items, color)) = list.recolor {
+            let count = self.annotations.recolor_items(&items, color);
+            if count == 0 {
+                self.status_message = "色を変えられる注釈（矩形・装飾・図形）が選択されていません".to_string();
+            } else {
+                edit_label = Some("注釈の色の変更");
+                self.status_message = format!("{} 件の注釈の色を変更しました", count);
+            }
+        }
+
+        if let Some(label) = edit_label {
+            self.has_unsaved_changes = true;
+            self.history.push(EditCommand::Annotations {
+                label: label.to_string(),
+                before,
+                after: self.annotations.clone(),
+            });
+        }
+    }
+
+    /// ページを回転（複数ページをまとめて回転できる）
+    fn rotate_pages(&mut self, pages: &[usize], angle: i32) {
         if let Some(ref mut doc) = self.current_document {
-            match PdfOperations::rotate_page(doc, page, angle) {
+            let rotated = pages
+                .iter()
+                .try_for_each(|&page| PdfOperations::rotate_page(doc, page, angle));
+            match rotated {
                 Ok(_) => {
-                    self.status_message = format!("ページ {} を {}° 回転しました", page + 1, angle);
-                    self.editor_panel.invalidate_cache();
+                    self.status_message = match pages {
+                        [page] => format!("ページ {} を {}° 回転しました", page + 1, angle),
+                        _ => format!("{} ページを {}° 回転しました", pages.len(), angle),
+                    };
+                    self.history.push(EditCommand::RotatePages {
+                        pages: pages.to_vec(),
+                        degrees: angle,
+                    });
                     self.has_unsaved_changes = true;
                 }
                 Err(e) => {
@@ -351,6 +551,189 @@ impl PdfViewerApp {
         }
     }
 
+    /// ページを削除（複数ページをまとめて削除できる）
+    fn delete_pages(&mut self, pages: &[usize]) {
+        if let Some(ref mut doc) = self.current_document {
+            let mut pages = pages.to_vec();
+            pages.sort_unstable();
+            pages.dedup();
+            if pages.len() >= doc.page_count() {
+                self.status_message = "全てのページは削除できません".to_string();
+                return;
+            }
+            // 元に戻せるよう削除するページを複製しておく
+            let removed = pages
+                .iter()
+                .map(|&page| PdfDocument::compose(&[(doc, page..page + 1)]))
+                .collect::<anyhow::Result<Vec<_>>>();
+            let removed = match removed {
+                Ok(removed) => removed,
+                Err(e) => {
+                    self.status_message = format!("削除エラー: {}", e);
+                    return;
+                }
+            };
+            let annotations = self.annotations.clone();
+            // 後ろから削除すれば前のページの番号はずれない
+            let deleted = pages
+                .iter()
+                .rev()
+                .try_for_each(|&page| PdfOperations::delete_page(doc, &mut self.annotations, page));
+            match deleted {
+                Ok(_) => {
+                    self.status_message = match pages[..] {
+                        [page] => format!("ページ {} を削除しました", page + 1),
+                        _ => format!("{} ページを削除しました", pages.len()),
+                    };
+                    self.history.push(EditCommand::DeletePages { pages, removed, annotations });
+                    self.selected_page = self.selected_page.min(doc.page_count() - 1);
+                    self.editor_panel.clear_selection();
+                    self.editor_panel.invalidate_cache();
+                    self.page_strip.invalidate_cache();
+                    self.has_unsaved_changes = true;
+                }
+                Err(e) => {
+                    self.status_message = format!("削除エラー: {}", e);
+                }
+            }
+        }
+    }
+
+    /// ページを移動（範囲指定で複数ページをまとめて移動）
+    fn move_pages(&mut self) {
+        if let Some(ref mut doc) = self.current_document {
+            let page_count = doc.page_count();
+            let start: usize = self.move_start_page.parse().unwrap_or(self.selected_page + 1);
+            let end: usize = self.move_end_page.parse().unwrap_or(start);
+            let dest: usize = self.move_dest_page.parse().unwrap_or(0);
+
+            if start == 0 || start > end || end > page_count || dest == 0 || dest > page_count {
+                self.status_message = "無効なページ範囲です".to_string();
+                return;
+            }
+
+            // 移動先は「移動後に先頭ページが来る位置」として扱う
+            let count = end - start + 1;
+            let to = (dest - 1).min(page_count - count);
+            match PdfOperations::move_pages(doc, &mut self.annotations, start - 1..end, to) {
+                Ok(_) => {
+                    if start - 1 != to {
+                        self.history.push(EditCommand::MovePages { pages: start - 1..end, to });
+                    }
+                    self.selected_page = to;
+                    self.status_message = if count == 1 {
+                        format!("ページ {} を {} 番目へ移動しました", start, to + 1)
+                    } else {
+                        format!("ページ {}-{} を {} 番目へ移動しました", start, end, to + 1)
+                    };
+                    self.editor_panel.clear_selection();
+                    self.editor_panel.invalidate_cache();
+                    self.page_strip.invalidate_cache();
+                    self.has_unsaved_changes = true;
+                }
+                Err(e) => {
+                    self.status_message = format!("移動エラー: {}", e);
+                }
+            }
+        }
+    }
+
+    /// 選んだページを `before` 番目のページの前へまとめて移動（ページ一覧のドラッグ）
+    fn reorder_pages(&mut self, pages: &[usize], before: usize) {
+        if let Some(ref mut doc) = self.current_document {
+            let order = PdfOperations::order_after_move(doc.page_count(), pages, before);
+            if order.iter().copied().eq(0..order.len()) {
+                return;
+            }
+            match PdfOperations::reorder_pages(doc, &mut self.annotations, &order) {
+                Ok(_) => {
+                    // 移動したページの移動後の番号
+                    let moved: Vec<usize> = order
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, old)| pages.contains(old))
+                        .map(|(new, _)| new)
+                        .collect();
+                    self.history.push(EditCommand::ReorderPages { order });
+                    self.selected_page = moved[0];
+                    self.page_strip.select_pages(&moved);
+                    self.status_message = match moved[..] {
+                        [page] => format!("ページを {} 番目へ移動しました", page + 1),
+                        _ => format!("{} ページを {} 番目へ移動しました", moved.len(), moved[0] + 1),
+                    };
+                    self.editor_panel.clear_selection();
+                    self.editor_panel.invalidate_cache();
+                    self.page_strip.invalidate_cache();
+                    self.has_unsaved_changes = true;
+                }
+                Err(e) => {
+                    self.status_message = format!("移動エラー: {}", e);
+                }
+            }
+        }
+    }
+
+    /// 選んだページを抜き出して別のPDFとして保存
+    fn extract_pages(&mut self, pages: &[usize]) {
+        if let Some(ref doc) = self.current_document {
+            if let Some(path) = rfd::FileDialog::new()
+                .add_filter("PDF", &["pdf"])
+                .set_file_name("extract.pdf")
+                .save_file()
+            {
+                match PdfOperations::extract(doc, &self.annotations, pages, &path) {
+                    Ok(_) => {
+                        self.status_message =
+                            format!("{} ページを抜き出して保存しました", pages.len());
+                    }
+                    Err(e) => {
+                        self.status_message = format!("抽出エラー: {}", e);
+                    }
+                }
+            }
+        }
+    }
+
+    /// 直前の編集を元に戻す
+    fn undo(&mut self) {
+        let Some(ref mut doc) = self.current_document else {
+            return;
+        };
+        match self.history.undo(doc, &mut self.annotations) {
+            Some(Ok(label)) => {
+                self.status_message = format!("元に戻しました: {}", label);
+                self.after_history_change();
+            }
+            Some(Err(e)) => self.status_message = format!("元に戻せませんでした: {}", e),
+            None => self.status_message = "元に戻す操作はありません".to_string(),
+        }
+    }
+
+    /// 元に戻した編集をやり直す
+    fn redo(&mut self) {
+        let Some(ref mut doc) = self.current_document else {
+            return;
+        };
+        match self.history.redo(doc, &mut self.annotations) {
+            Some(Ok(label)) => {
+                self.status_message = format!("やり直しました: {}", label);
+                self.after_history_change();
+            }
+            Some(Err(e)) => self.status_message = format!("やり直せませんでした: {}", e),
+            None => self.status_message = "やり直す操作はありません".to_string(),
+        }
+    }
+
+    /// 元に戻す・やり直しの後に表示を更新
+    fn after_history_change(&mut self) {
+        let page_count = self.current_document.as_ref().map_or(1, |d| d.page_count());
+        self.selected_page = self.selected_page.min(page_count.saturating_sub(1));
+        self.editor_panel.clear_selection();
+        self.editor_panel.invalidate_cache();
+        self.page_strip.invalidate_cache();
+        self.has_unsaved_changes = true;
+    }
+
     /// ファイル操作を実行
     fn handle_file_operations(&mut self, 
         file_moved: Option<(PathBuf, PathBuf)>,
@@ -420,29 +803,256 @@ impl PdfViewerApp {
         }
     }
 
-    /// カスタムスタンプを登録（PNG透過対応）
+    /// スタンプライブラリの画面（登録・名前と分類の変更・並べ替え・削除・共有）
+    fn show_stamp_library(&mut self, ctx: &egui::Context) {
+        // 事前にテクスチャを準備
+        let textures: Vec<Option<egui::TextureId>> = self
+            .stamp_library
+            .stamps
+            .iter()
+            .map(|stamp| {
+                self.custom_stamps
+                    .iter()
+                    .position(|s| s.name == stamp.name)
+                    .and_then(|i| self.custom_stamp_textures.get(i))
+                    .and_then(|t| t.as_ref().map(|t| t.id()))
+            })
+            .collect();
+        if self.library_drafts.len() != self.stamp_library.stamps.len() {
+            self.library_drafts = self
+                .stamp_library
+                .stamps
+                .iter()
+                .map(|s| (s.name.clone(), s.category.clone()))
+                .collect();
+        }
+        let categories = self.stamp_library.categories();
+        let count = self.stamp_library.stamps.len();
+
+        let mut add_stamp_path: Option<PathBuf> = None;
+        let mut rename: Option<(usize, String)> = None;
+        let mut recategorize: Option<(usize, String)> = None;
+        let mut move_stamp: Option<(usize, usize)> = None;
+        let mut remove_stamp: Option<usize> = None;
+        let mut import_path: Option<PathBuf> = None;
+        let mut export_path: Option<PathBuf> = None;
+        let mut close_dialog = false;
+
+        egui::Window::new("🖼 スタンプライブラリ")
+            .collapsible(false)
+            .resizable(true)
+            .show(ctx, |ui| {
+                ui.label("PNG画像（透過対応）をスタンプとして登録できます。登録した画像はライブラリに保存され、次回の起動時にも使えます。");
+                ui.separator();
+
+                if count > 0 {
+                    ui.label(format!("登録済みスタンプ: {} 個", count));
+                    egui::ScrollArea::vertical()
+                        .max_height(300.0)
+                        .show(ui, |ui| {
+                            egui::Grid::new("stamp_library").num_columns(4).striped(true).show(ui, |ui| {
+                                for (i, (name, category)) in self.library_drafts.iter_mut().enumerate() {
+                                    match textures[i] {
+                                        Some(id) => ui.add(egui::Image::new((id, Vec2::splat(40.0))).fit_to_exact_size(Vec2::splat(40.0))),
+                                        None => ui.label("🖼"),
+                                    };
+                                    // 名前・分類は入力を終えたときに反映
+                                    let response = ui.add(egui::TextEdit::singleline(name).desired_width(120.0));
+                                    if response.lost_focus() && *name != self.stamp_library.stamps[i].name {
+                                        rename = Some((i, name.clone()));
+                                    }
+                                    ui.horizontal(|ui| {
+                                        let response = ui.add(
+                                            egui::TextEdit::singleline(category)
+                                                .hint_text("分類")
+                                                .desired_width(90.0),
+                                        );
+                                        if response.lost_focus() && *category != self.stamp_library.stamps[i].category {
+                                            recategorize = Some((i, category.clone()));
+                                        }
+                                        // 既存の分類から選ぶ
+                                        ui.menu_button("▾", |ui| {
+                                            for c in &categories {
+                                                if ui.button(c).clicked() {
+                                                    recategorize = Some((i, c.clone()));
+                                                    ui.close_menu();
+                                                }
+                                            }
+                                            if ui.button("（分類なし）").clicked() {
+                                                recategorize = Some((i, String::new()));
+                                                ui.close_menu();
+                                            }
+                                        });
+                                    });
+                                    ui.horizontal(|ui| {
+                                        if ui.add_enabled(i > 0, egui::Button::new("⬆")).on_hover_text("前へ").clicked() {
+                                            move_stamp = Some((i, i - 1));
+                                        }
+                                        if ui.add_enabled(i + 1 < count, egui::Button::new("⬇")).on_hover_text("後ろへ").clicked() {
+                                            move_stamp = Some((i, i + 1));
+                                        }
+                                        if ui.button("🗑").on_hover_text("削除").clicked() {
+                                            remove_stamp = Some(i);
+                                        }
+                                    });
+                                    ui.end_row();
+                                }
+                            });
+                        });
+                    ui.separator();
+                }
+
+                ui.horizontal(|ui| {
+                    if ui.button("📂 PNG画像を追加...").clicked() {
+                        if let Some(path) = rfd::FileDialog::new()
+                            .add_filter("PNG画像", &["png"])
+                            .pick_file()
+                        {
+                            add_stamp_path = Some(path);
+                        }
+                    }
+                    if ui.button("📥 読み込み...").on_hover_text("書き出したライブラリのスタンプ・認印を追加します").clicked() {
+                        if let Some(path) = rfd::FileDialog::new()
+                            .add_filter("スタンプライブラリ", &["json"])
+                            .pick_file()
+                        {
+                            import_path = Some(path);
+                        }
+                    }
+                    if ui.button("📤 書き出し...").on_hover_text("スタンプ・認印を1つのファイルにまとめて共有できます").clicked() {
+                        if let Some(path) = rfd::FileDialog::new()
+                            .add_filter("スタンプライブラリ", &["json"])
+                            .set_file_name("stamps.json")
+                            .save_file()
+                        {
+                            export_path = Some(path);
+                        }
+                    }
+                    if ui.button("閉じる").clicked() {
+                        close_dialog = true;
+                    }
+                });
+            });
+
+        if let Some(path) = add_stamp_path {
+            self.register_custom_stamp(path);
+        }
+        if let Some((idx, name)) = rename {
+            self.rename_library_stamp(idx, &name);
+        }
+        if let Some((idx, category)) = recategorize {
+            if let Some(stamp) = self.stamp_library.stamps.get_mut(idx) {
+                stamp.category = category.trim().to_string();
+                let message = format!("スタンプ「{}」の分類を変更しました", stamp.name);
+                self.sync_library_stamps();
+                self.save_stamp_library(message);
+            }
+        }
+        if let Some((from, to)) = move_stamp {
+            self.stamp_library.move_stamp(from, to);
+            self.sync_library_stamps();
+            self.save_stamp_library("スタンプの順番を変更しました".to_string());
+        }
+        if let Some(idx) = remove_stamp {
+            if let Some(stamp) = self.stamp_library.remove_stamp(idx) {
+                self.sync_library_stamps();
+                self.save_stamp_library(format!("スタンプ「{}」を削除しました", stamp.name));
+            }
+        }
+        if let Some(path) = import_path {
+            match self.stamp_library.import(&path) {
+                Ok(count) => {
+                    self.sync_library_stamps();
+                    self.save_stamp_library(format!("スタンプライブラリを読み込みました（{} 個追加）", count));
+                }
+                Err(e) => {
+                    // 途中まで追加したスタンプは残す
+                    self.sync_library_stamps();
+                    self.status_message = format!("エラー: {}", e);
+                }
+            }
+        }
+        if let Some(path) = export_path {
+            self.status_message = match self.stamp_library.export(&path) {
+                Ok(()) => format!("スタンプライブラリを書き出しました: {}", path.display()),
+                Err(e) => format!("エラー: {}", e),
+            };
+        }
+        if close_dialog {
+            self.show_stamp_library_dialog = false;
+        }
+    }
+
+    /// カスタムスタンプを登録（PNG透過対応、画像はライブラリにコピーする）
     fn register_custom_stamp(&mut self, path: PathBuf) {
-        match image::open(&path) {
-            Ok(img) => {
-                let rgba = img.to_rgba8();
-                let (width, height) = rgba.dimensions();
-                let image_data = rgba.into_raw();
-                
+        let registered = std::fs::read(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|png| {
+                // 読めない画像はライブラリに入れない
+                image::load_from_memory(&png)?;
                 let name = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
-                self.custom_stamps.push(CustomStamp {
-                    name: name.clone(),
-                    path: path.clone(),
-                    image_data,
-                    width,
-                    height,
-                });
-                self.custom_stamp_textures.push(None);
-                self.status_message = format!("スタンプを登録しました: {}", name);
+                self.stamp_library.add_stamp(&name, "", png)
+            });
+        match registered {
+            Ok(name) => {
+                self.sync_library_stamps();
+                self.save_stamp_library(format!("スタンプを登録しました: {}", name));
             }
             Err(e) => {
-                self.status_message = format!("画像の読み込みエラー: {}", e);
+                self.status_message = format!("スタンプを登録できません: {}", e);
+            }
+        }
+    }
+
+    /// ライブラリの画像スタンプをカスタムスタンプに反映（ライブラリの順、PDFから取り込んだスタンプは後ろ）
+    fn sync_library_stamps(&mut self) {
+        let imported: Vec<CustomStamp> = std::mem::take(&mut self.custom_stamps)
+            .into_iter()
+            .filter(|s| s.path.as_os_str().is_empty())
+            .filter(|s| !self.stamp_library.stamps.iter().any(|l| l.name == s.name))
+            .collect();
+        for stamp in &self.stamp_library.stamps {
+            match image::load_from_memory(&stamp.image_data) {
+                Ok(img) => {
+                    let rgba = img.to_rgba8();
+                    let (width, height) = rgba.dimensions();
+                    self.custom_stamps.push(CustomStamp {
+                        name: stamp.name.clone(),
+                        path: stamp.path.clone(),
+                        category: stamp.category.clone(),
+                        image_data: rgba.into_raw(),
+                        width,
+                        height,
+                    });
+                }
+                Err(e) => log::warn!("スタンプ「{}」の画像を読み込めません: {}", stamp.name, e),
+            }
+        }
+        self.custom_stamps.extend(imported);
+        // 並びが変わるのでテクスチャは作り直す
+        self.custom_stamp_textures = vec![None; self.custom_stamps.len()];
+        self.library_drafts.clear();
+    }
+
+    /// ライブラリの画像スタンプの名前を変更（開いている文書のスタンプも新しい名前にする）
+    fn rename_library_stamp(&mut self, index: usize, name: &str) {
+        let Some(old) = self.stamp_library.stamps.get(index).map(|s| s.name.clone()) else {
+            return;
+        };
+        if let Err(e) = self.stamp_library.rename_stamp(index, name) {
+            self.status_message = format!("エラー: {}", e);
+            self.library_drafts.clear();
+            return;
+        }
+        let new = self.stamp_library.stamps[index].name.clone();
+        for stamp in &mut self.annotations.stamps {
+            if stamp.stamp_type == StampType::Custom(old.clone()) {
+                stamp.stamp_type = StampType::Custom(new.clone());
+                self.has_unsaved_changes = true;
             }
         }
+        self.sync_library_stamps();
+        self.save_stamp_library(format!("スタンプ「{}」を「{}」に変更しました", old, new));
     }
 
     /// カスタムスタンプのテクスチャを取得/生成
@@ -488,6 +1098,22 @@ fn copy_dir_all(src: &PathBuf, dest: &PathBuf) -> std::io::Result<()> {
 
 impl eframe::App for PdfViewerApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        // ショートカット（テキスト入力中は入力欄の元に戻すを優先）
+        if !ctx.wants_keyboard_input() {
+            let undo = egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::Z);
+            let redo = egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::Y);
+            let redo_shift = egui::KeyboardShortcut::new(
+                egui::Modifiers::COMMAND | egui::Modifiers::SHIFT,
+                egui::Key::Z,
+            );
+            // Ctrl+Shift+Z は Ctrl+Z にも一致するため先に判定する
+            if ctx.input_mut(|i| i.consume_shortcut(&redo_shift) || i.consume_shortcut(&redo)) {
+                self.redo();
+            } else if ctx.input_mut(|i| i.consume_shortcut(&undo)) {
+                self.undo();
+            }
+        }
+
         // メニューバー
         egui::TopBottomPanel::top("menu_bar").show(ctx, |ui| {
             egui::menu::bar(ui, |ui| {
@@ -509,13 +1135,7 @@ impl eframe::App for PdfViewerApp {
                     }
                     
                     if ui.button("📄 名前を付けて保存...").clicked() {
-                        if let Some(path) = rfd::FileDialog::new()
-                            .add_filter("PDF", &["pdf"])
-                            .set_file_name("output.pdf")
-                            .save_file()
-                        {
-                            self.save_pdf(&path);
-                        }
+                        self.show_save_dialog = true;
                         ui.close_menu();
                     }
                     
@@ -552,19 +1172,54 @@ impl eframe::App for PdfViewerApp {
                 ui.menu_button("編集", |ui| {
                     let has_doc = self.current_document.is_some();
                     
+                    let undo_label = self.history.undo_label();
+                    let undo_text = match undo_label {
+                        Some(ref label) => format!("↩ 元に戻す: {}", label),
+                        None => "↩ 元に戻す".to_string(),
+                    };
+                    let undo_button = egui::Button::new(undo_text).shortcut_text("Ctrl+Z");
+                    if ui.add_enabled(has_doc && undo_label.is_some(), undo_button).clicked() {
+                        self.undo();
+                        ui.close_menu();
+                    }
+                    let redo_label = self.history.redo_label();
+                    let redo_text = match redo_label {
+                        Some(ref label) => format!("↪ やり直し: {}", label),
+                        None => "↪ やり直し".to_string(),
+                    };
+                    let redo_button = egui::Button::new(redo_text).shortcut_text("Ctrl+Y");
+                    if ui.add_enabled(has_doc && redo_label.is_some(), redo_button).clicked() {
+                        self.redo();
+                        ui.close_menu();
+                    }
+                    ui.separator();
+                    
                     if ui.add_enabled(has_doc, egui::Button::new("🔄 90°回転")).clicked() {
                         let page = self.selected_page;
-                        self.rotate_page(page, 90);
+                        self.rotate_pages(&[page], 90);
                         ui.close_menu();
                     }
                     if ui.add_enabled(has_doc, egui::Button::new("🔄 180°回転")).clicked() {
                         let page = self.selected_page;
-                        self.rotate_page(page, 180);
+                        self.rotate_pages(&[page], 180);
                         ui.close_menu();
                     }
                     if ui.add_enabled(has_doc, egui::Button::new("🔄 270°回転")).clicked() {
                         let page = self.selected_page;
-                        self.rotate_page(page, 270);
+                        self.rotate_pages(&[page], 270);
+                        ui.close_menu();
+                    }
+                    if ui.add_enabled(has_doc, egui::Button::new("🗑 ページを削除")).clicked() {
+                        let page = self.selected_page;
+                        self.delete_pages(&[page]);
+                        ui.close_menu();
+                    }
+                    if ui.add_enabled(has_doc, egui::Button::new("↕ ページを移動...")).clicked() {
+                        let page = (self.selected_page + 1).to_string();
+                        self.move_start_page = page.clone();
+                        self.move_end_page = page;
+                        self.move_dest_page.clear();
+                        self.show_move_dialog = true;
                         ui.close_menu();
                     }
                     ui.separator();
@@ -577,8 +1232,8 @@ impl eframe::App for PdfViewerApp {
                         ui.close_menu();
                     }
                     ui.separator();
-                    if ui.button("🖼 スタンプを登録...").clicked() {
-                        self.show_stamp_register_dialog = true;
+                    if ui.button("🖼 スタンプライブラリ...").clicked() {
+                        self.show_stamp_library_dialog = true;
                         ui.close_menu();
                     }
                 });
@@ -660,13 +1315,13 @@ impl eframe::App for PdfViewerApp {
             let _ = self.get_custom_stamp_texture(ctx, i);
         }
         
-        // カスタムスタンプ情報（名前, テクスチャ, 幅, 高さ）
-        let custom_stamp_info: Vec<(String, Option<TextureHandle>, u32, u32)> = self.custom_stamps
+        // カスタムスタンプ情報（名前, テクスチャ, 幅, 高さ, 分類）
+        let custom_stamp_info: Vec<(String, Option<TextureHandle>, u32, u32, String)> = self.custom_stamps
             .iter()
             .enumerate()
             .map(|(i, s)| {
                 let tex = self.custom_stamp_textures.get(i).and_then(|t| t.clone());
-                (s.name.clone(), tex, s.width, s.height)
+                (s.name.clone(), tex, s.width, s.height, s.category.clone())
             })
             .collect();
         
@@ -706,6 +1361,16 @@ impl eframe::App for PdfViewerApp {
 
                         ui.separator();
 
+                        if ui.selectable_label(self.show_page_strip, "📑 ページ一覧").clicked() {
+                            self.show_page_strip = !self.show_page_strip;
+                        }
+                        if ui.selectable_label(self.show_annotation_list, "🗂 注釈一覧").clicked() {
+                            self.show_annotation_list = !self.show_annotation_list;
+                        }
+                        if ui.selectable_label(self.show_comment_list, "💬 コメント一覧").clicked() {
+                            self.show_comment_list = !self.show_comment_list;
+                        }
+
                         if ui.selectable_label(self.show_stamp_panel, "✅ スタンプ").clicked() {
                             self.show_stamp_panel = !self.show_stamp_panel;
                             self.show_text_panel = false;
@@ -718,35 +1383,117 @@ impl eframe::App for PdfViewerApp {
 
                     if prev_clicked {
                         self.selected_page -= 1;
-                        self.editor_panel.invalidate_cache();
                     }
                     if next_clicked {
                         self.selected_page += 1;
-                        self.editor_panel.invalidate_cache();
                     }
                     if rotate_clicked {
                         let page = self.selected_page;
-                        self.rotate_page(page, 90);
+                        self.rotate_pages(&[page], 90);
                     }
 
                     ui.separator();
 
+                    // ページ一覧
+                    let mut strip_result = None;
+                    if self.show_page_strip {
+                        egui::SidePanel::left("page_strip")
+                            .resizable(false)
+                            .exact_width(PAGE_STRIP_WIDTH)
+                            .show_inside(ui, |ui| {
+                                if let Some(ref doc) = self.current_document {
+                                    strip_result =
+                                        Some(self.page_strip.show(ui, doc, self.selected_page));
+                                }
+                            });
+                    }
+                    if let Some(strip) = strip_result {
+                        if let Some(page) = strip.select_page {
+                            self.selected_page = page;
+                        }
+                        if let Some((pages, angle)) = strip.rotate {
+                            self.rotate_pages(&pages, angle);
+                        }
+                        if let Some(pages) = strip.delete {
+                            self.delete_pages(&pages);
+                        }
+                        if let Some(pages) = strip.extract {
+                            self.extract_pages(&pages);
+                        }
+                        if let Some((pages, before)) = strip.reorder {
+                            self.reorder_pages(&pages, before);
+                        }
+                    }
+
+                    // コメント一覧
+                    if self.show_comment_list {
+                        let mut clicked_comment = None;
+                        egui::SidePanel::right("comment_list")
+                            .resizable(false)
+                            .exact_width(COMMENT_LIST_WIDTH)
+                            .show_inside(ui, |ui| {
+                                clicked_comment = self.comment_list.show(ui, &self.annotations.comments);
+                            });
+                        if let Some(idx) = clicked_comment {
+                            self.selected_page = self.annotations.comments[idx].page;
+                            self.editor_panel.select_annotation(AnnotationRef { kind: AnnotationKind::Comment, index: idx });
+                        }
+                    }
+
+                    // 注釈一覧
+                    let mut list_result = None;
+                    if self.show_annotation_list {
+                        egui::SidePanel::right("annotation_list")
+                            .resizable(false)
+                            .exact_width(ANNOTATION_LIST_WIDTH)
+                            .show_inside(ui, |ui| {
+                                list_result = Some(self.annotation_list.show(
+                                    ui,
+                                    &self.annotations,
+                                    page_count,
+                                    self.selected_page,
+                                ));
+                            });
+                    }
+                    if let Some(list) = list_result {
+                        self.apply_annotation_list(list);
+                    }
+
                     // プレビュー
                     let mut new_stamp = None;
                     let mut new_text = None;
                     let mut new_rect = None;
+                    let mut new_markup = None;
+                    let mut new_shape = None;
+                    let mut new_comment = None;
                     let mut delete_stamp = None;
                     let mut delete_text = None;
                     let mut delete_rect = None;
+                    let mut delete_markup = None;
+                    let mut delete_shape = None;
+                    let mut delete_comment = None;
                     let mut move_stamp = None;
                     let mut move_text = None;
                     let mut move_rect = None;
+                    let mut move_shape = None;
+                    let mut move_comment = None;
                     let mut resize_stamp = None;
                     let mut resize_text = None;
                     let mut resize_rect = None;
+                    let mut resize_shape = None;
+                    let mut redact_rect = None;
                     let mut edit_text = None;
+                    let mut edit_comment = None;
+                    let mut reply_comment = None;
+                    let mut comment_status = None;
                     let mut delete_custom_stamp = None;
                     let mut register_stamp_clicked = false;
+                    let mut new_name_seal = None;
+                    let mut delete_name_seal = None;
+                    let mut annotations_before = None;
+                    let mut visible_page = None;
+                    let mut go_to_page = None;
+                    let mut copied_text = None;
                     
                     egui::ScrollArea::both()
                         .auto_shrink([false, false])
@@ -756,147 +1503,344 @@ impl eframe::App for PdfViewerApp {
                                     ui,
                                     doc,
                                     self.selected_page,
-                                    &self.stamps,
-                                    &self.text_annotations,
-                                    &self.rect_annotations,
-                                    self.show_stamp_panel,
-                                    self.show_text_panel,
-                                    &custom_stamp_info,
+                                    EditorContext {
+                                        annotations: &self.annotations,
+                                        custom_stamps: &custom_stamp_info,
+                                        name_seals: &self.stamp_library.seals,
+                                        show_stamp_panel: self.show_stamp_panel,
+                                        show_text_panel: self.show_text_panel,
+                                    },
                                 );
+                                if editor_result.edits_annotations() {
+                                    annotations_before = Some(self.annotations.clone());
+                                }
                                 new_stamp = editor_result.new_stamp;
                                 new_text = editor_result.new_text;
                                 new_rect = editor_result.new_rect;
+                                new_markup = editor_result.new_markup;
+                                new_shape = editor_result.new_shape;
+                                new_comment = editor_result.new_comment;
                                 delete_stamp = editor_result.delete_stamp;
                                 delete_text = editor_result.delete_text;
                                 delete_rect = editor_result.delete_rect;
+                                delete_markup = editor_result.delete_markup;
+                                delete_shape = editor_result.delete_shape;
+                                delete_comment = editor_result.delete_comment;
                                 move_stamp = editor_result.move_stamp;
                                 move_text = editor_result.move_text;
                                 move_rect = editor_result.move_rect;
+                                move_shape = editor_result.move_shape;
+                                move_comment = editor_result.move_comment;
                                 resize_stamp = editor_result.resize_stamp;
                                 resize_text = editor_result.resize_text;
                                 resize_rect = editor_result.resize_rect;
+                                resize_shape = editor_result.resize_shape;
+                                redact_rect = editor_result.redact_rect;
+                                visible_page = editor_result.visible_page;
+                                go_to_page = editor_result.go_to_page;
+                                copied_text = editor_result.copied_text;
                                 edit_text = editor_result.edit_text;
+                                edit_comment = editor_result.edit_comment;
+                                reply_comment = editor_result.reply_comment;
+                                comment_status = editor_result.comment_status;
                                 delete_custom_stamp = editor_result.delete_custom_stamp;
                                 register_stamp_clicked = editor_result.register_stamp_clicked;
+                                new_name_seal = editor_result.new_name_seal;
+                                delete_name_seal = editor_result.delete_name_seal;
                             }
                         });
 
+                    // 連続・見開き表示でスクロールしたら表示中のページを選択ページにする
+                    if let Some(page) = visible_page {
+                        self.selected_page = page;
+                    }
+                    // 検索結果のページへ移動
+                    if let Some(page) = go_to_page {
+                        self.selected_page = page;
+                    }
+                    if let Some(count) = copied_text {
+                        self.status_message = format!("{} 文字をコピーしました", count);
+                    }
+
+                    // 元に戻す履歴に記録する操作名
+                    let mut edit_label = None;
+
                     // スタンプ追加
                     if let Some(stamp) = new_stamp {
-                        self.stamps.push(stamp);
+                        self.annotations.stamps.push(stamp);
+                        edit_label = Some("スタンプの追加");
                         self.has_unsaved_changes = true;
                         self.status_message = "スタンプを追加しました".to_string();
                     }
                     // テキスト追加
                     if let Some(annotation) = new_text {
-                        self.text_annotations.push(annotation);
+                        self.annotations.texts.push(annotation);
+                        edit_label = Some("テキストの追加");
                         self.has_unsaved_changes = true;
                         self.status_message = "テキストを追加しました".to_string();
                     }
                     // 矩形追加
                     if let Some(rect) = new_rect {
-                        self.rect_annotations.push(rect);
+                        self.annotations.rects.push(rect);
+                        edit_label = Some("矩形の追加");
                         self.has_unsaved_changes = true;
                         self.status_message = "矩形を追加しました".to_string();
                     }
+                    // 手書き・図形追加
+                    if let Some(shape) = new_shape {
+                        self.status_message = format!("{}を追加しました", shape.shape_type.label());
+                        self.annotations.shapes.push(shape);
+                        edit_label = Some("図形の追加");
+                        self.has_unsaved_changes = true;
+                    }
+                    // テキストの装飾追加
+                    if let Some(markup) = new_markup {
+                        self.status_message = format!("{}を追加しました", markup.markup_type.label());
+                        self.annotations.markups.push(markup);
+                        edit_label = Some("テキストの装飾");
+                        self.has_unsaved_changes = true;
+                    }
+                    // コメント追加（作成者は一覧パネルの名前）
+                    if let Some(mut comment) = new_comment {
+                        comment.author = self.comment_list.author().to_string();
+                        self.annotations.comments.push(comment);
+                        edit_label = Some("コメントの追加");
+                        self.has_unsaved_changes = true;
+                        self.status_message = "コメントを追加しました".to_string();
+                    }
                     // スタンプ削除
                     if let Some(idx) = delete_stamp {
-                        if idx < self.stamps.len() {
-                            self.stamps.remove(idx);
+                        if idx < self.annotations.stamps.len() {
+                            self.annotations.stamps.remove(idx);
+                            edit_label = Some("スタンプの削除");
                             self.has_unsaved_changes = true;
                             self.status_message = "スタンプを削除しました".to_string();
                         }
                     }
                     // テキスト削除
                     if let Some(idx) = delete_text {
-                        if idx < self.text_annotations.len() {
-                            self.text_annotations.remove(idx);
+                        if idx < self.annotations.texts.len() {
+                            self.annotations.texts.remove(idx);
+                            edit_label = Some("テキストの削除");
                             self.has_unsaved_changes = true;
                             self.status_message = "テキストを削除しました".to_string();
                         }
                     }
                     // 矩形削除
                     if let Some(idx) = delete_rect {
-                        if idx < self.rect_annotations.len() {
-                            self.rect_annotations.remove(idx);
+                        if idx < self.annotations.rects.len() {
+                            self.annotations.rects.remove(idx);
+                            edit_label = Some("矩形の削除");
                             self.has_unsaved_changes = true;
                             self.status_message = "矩形を削除しました".to_string();
                         }
                     }
+                    // 手書き・図形削除
+                    if let Some(idx) = delete_shape {
+                        if idx < self.annotations.shapes.len() {
+                            let shape = self.annotations.shapes.remove(idx);
+                            edit_label = Some("図形の削除");
+                            self.has_unsaved_changes = true;
+                            self.status_message = format!("{}を削除しました", shape.shape_type.label());
+                        }
+                    }
+                    // テキストの装飾削除
+                    if let Some(idx) = delete_markup {
+                        if idx < self.annotations.markups.len() {
+                            let markup = self.annotations.markups.remove(idx);
+                            edit_label = Some("テキストの装飾の削除");
+                            self.has_unsaved_changes = true;
+                            self.status_message = format!("{}を削除しました", markup.markup_type.label());
+                        }
+                    }
+                    // コメント削除（返信も含めて削除）
+                    if let Some(idx) = delete_comment {
+                        if idx < self.annotations.comments.len() {
+                            self.annotations.comments.remove(idx);
+                            edit_label = Some("コメントの削除");
+                            self.has_unsaved_changes = true;
+                            self.status_message = "コメントを削除しました".to_string();
+                        }
+                    }
                     // スタンプ移動
                     if let Some((idx, new_x, new_y)) = move_stamp {
-                        if idx < self.stamps.len() {
-                            self.stamps[idx].x = new_x;
-                            self.stamps[idx].y = new_y;
+                        if idx < self.annotations.stamps.len() {
+                            self.annotations.stamps[idx].x = new_x;
+                            self.annotations.stamps[idx].y = new_y;
+                            edit_label = Some("スタンプの移動");
                             self.has_unsaved_changes = true;
                         }
                     }
                     // テキスト移動
                     if let Some((idx, new_x, new_y)) = move_text {
-                        if idx < self.text_annotations.len() {
-                            self.text_annotations[idx].x = new_x;
-                            self.text_annotations[idx].y = new_y;
+                        if idx < self.annotations.texts.len() {
+                            self.annotations.texts[idx].x = new_x;
+                            self.annotations.texts[idx].y = new_y;
+                            edit_label = Some("テキストの移動");
                             self.has_unsaved_changes = true;
                         }
                     }
                     // 矩形移動
                     if let Some((idx, new_x, new_y)) = move_rect {
-                        if idx < self.rect_annotations.len() {
-                            self.rect_annotations[idx].x = new_x;
-                            self.rect_annotations[idx].y = new_y;
+                        if idx < self.annotations.rects.len() {
+                            self.annotations.rects[idx].x = new_x;
+                            self.annotations.rects[idx].y = new_y;
+                            edit_label = Some("矩形の移動");
                             self.has_unsaved_changes = true;
                         }
                     }
                     // スタンプリサイズ
                     if let Some((idx, new_w, new_h)) = resize_stamp {
-                        if idx < self.stamps.len() {
-                            self.stamps[idx].width = new_w;
-                            self.stamps[idx].height = new_h;
+                        if idx < self.annotations.stamps.len() {
+                            self.annotations.stamps[idx].width = new_w;
+                            self.annotations.stamps[idx].height = new_h;
+                            edit_label = Some("スタンプのサイズ変更");
                             self.has_unsaved_changes = true;
                             self.status_message = format!("スタンプサイズ変更: {:.0}x{:.0}", new_w, new_h);
                         }
                     }
                     // テキストリサイズ（フォントサイズ変更）
                     if let Some((idx, new_font_size)) = resize_text {
-                        if idx < self.text_annotations.len() {
-                            self.text_annotations[idx].font_size = new_font_size;
+                        if idx < self.annotations.texts.len() {
+                            self.annotations.texts[idx].font_size = new_font_size;
+                            edit_label = Some("フォントサイズ変更");
                             self.has_unsaved_changes = true;
                             self.status_message = format!("フォントサイズ変更: {:.0}", new_font_size);
                         }
                     }
                     // 矩形リサイズ
                     if let Some((idx, new_w, new_h)) = resize_rect {
-                        if idx < self.rect_annotations.len() {
-                            self.rect_annotations[idx].width = new_w;
-                            self.rect_annotations[idx].height = new_h;
+                        if idx < self.annotations.rects.len() {
+                            self.annotations.rects[idx].width = new_w;
+                            self.annotations.rects[idx].height = new_h;
+                            edit_label = Some("矩形のサイズ変更");
                             self.has_unsaved_changes = true;
                             self.status_message = format!("矩形サイズ変更: {:.0}x{:.0}", new_w, new_h);
                         }
                     }
+                    // 手書き・図形移動
+                    if let Some((idx, dx, dy)) = move_shape {
+                        if let Some(shape) = self.annotations.shapes.get_mut(idx) {
+                            shape.translate(dx, dy);
+                            edit_label = Some("図形の移動");
+                            self.has_unsaved_changes = true;
+                            self.status_message = format!("{}を移動しました", shape.shape_type.label());
+                        }
+                    }
+                    // 手書き・図形リサイズ
+                    if let Some((idx, points)) = resize_shape {
+                        if let Some(shape) = self.annotations.shapes.get_mut(idx) {
+                            shape.points = points;
+                            edit_label = Some("図形のサイズ変更");
+                            self.has_unsaved_changes = true;
+                            self.status_message = format!("{}のサイズを変更しました", shape.shape_type.label());
+                        }
+                    }
+                    // コメント移動
+                    if let Some((idx, new_x, new_y)) = move_comment {
+                        if let Some(comment) = self.annotations.comments.get_mut(idx) {
+                            comment.x = new_x;
+                            comment.y = new_y;
+                            edit_label = Some("コメントの移動");
+                            self.has_unsaved_changes = true;
+                        }
+                    }
+                    // 墨消しの切り替え
+                    if let Some((idx, redact)) = redact_rect {
+                        if idx < self.annotations.rects.len() {
+                            self.annotations.rects[idx].redact = redact;
+                            edit_label = Some("墨消しの切り替え");
+                            self.has_unsaved_changes = true;
+                            self.status_message = if redact {
+                                "矩形を墨消しにしました（保存時に下の内容を削除）".to_string()
+                            } else {
+                                "矩形の墨消しを解除しました".to_string()
+                            };
+                        }
+                    }
                     // テキスト編集
                     if let Some((idx, new_text, font_type, transparent)) = edit_text {
-                        if idx < self.text_annotations.len() {
-                            self.text_annotations[idx].text = new_text;
-                            self.text_annotations[idx].font_type = font_type;
-                            self.text_annotations[idx].transparent = transparent;
+                        if idx < self.annotations.texts.len() {
+                            self.annotations.texts[idx].text = new_text;
+                            self.annotations.texts[idx].font_type = font_type;
+                            self.annotations.texts[idx].transparent = transparent;
+                            edit_label = Some("テキストの編集");
                             self.has_unsaved_changes = true;
                             self.status_message = "テキストを編集しました".to_string();
                         }
                     }
+                    // コメント編集
+                    if let Some((idx, text)) = edit_comment {
+                        if let Some(comment) = self.annotations.comments.get_mut(idx) {
+                            comment.text = text;
+                            edit_label = Some("コメントの編集");
+                            self.has_unsaved_changes = true;
+                            self.status_message = "コメントを保存しました".to_string();
+                        }
+                    }
+                    // コメントへの返信
+                    if let Some((idx, text)) = reply_comment {
+                        let author = self.comment_list.author().to_string();
+                        if let Some(comment) = self.annotations.comments.get_mut(idx) {
+                            comment.replies.push(CommentReply::new(author, text));
+                            edit_label = Some("コメントへの返信");
+                            self.has_unsaved_changes = true;
+                            self.status_message = "返信を追加しました".to_string();
+                        }
+                    }
+                    // コメントの状態の切り替え
+                    if let Some((idx, status)) = comment_status {
+                        if let Some(comment) = self.annotations.comments.get_mut(idx) {
+                            comment.status = status;
+                            edit_label = Some("コメントの状態の変更");
+                            self.has_unsaved_changes = true;
+                            self.status_message = format!("コメントを「{}」にしました", status.label());
+                        }
+                    }
                     // カスタムスタンプ削除
                     if let Some(idx) = delete_custom_stamp {
                         if idx < self.custom_stamps.len() {
                             let name = self.custom_stamps[idx].name.clone();
-                            self.custom_stamps.remove(idx);
-                            if idx < self.custom_stamp_textures.len() {
-                                self.custom_stamp_textures.remove(idx);
+                            let message = format!("スタンプ「{}」を削除しました", name);
+                            match self.stamp_library.stamps.iter().position(|s| s.name == name) {
+                                Some(library_idx) => {
+                                    self.stamp_library.remove_stamp(library_idx);
+                                    self.sync_library_stamps();
+                                    self.save_stamp_library(message);
+                                }
+                                None => {
+                                    self.custom_stamps.remove(idx);
+                                    if idx < self.custom_stamp_textures.len() {
+                                        self.custom_stamp_textures.remove(idx);
+                                    }
+                                    self.status_message = message;
+                                }
                             }
-                            self.status_message = format!("スタンプ「{}」を削除しました", name);
                         }
                     }
-                    // スタンプ登録ダイアログ
+                    // スタンプライブラリ
                     if register_stamp_clicked {
-                        self.show_stamp_register_dialog = true;
+                        self.show_stamp_library_dialog = true;
+                    }
+                    // 認印をライブラリに追加・削除
+                    if let Some(seal) = new_name_seal {
+                        let name = seal.name.clone();
+                        self.stamp_library.add_seal(seal);
+                        self.save_stamp_library(format!("認印「{}」をライブラリに追加しました", name));
+                    }
+                    if let Some(idx) = delete_name_seal {
+                        if idx < self.stamp_library.seals.len() {
+                            let seal = self.stamp_library.seals.remove(idx);
+                            self.save_stamp_library(format!("認印「{}」を削除しました", seal.name));
+                        }
+                    }
+                    // 注釈の変更を元に戻せるよう記録
+                    if let (Some(before), Some(label)) = (annotations_before, edit_label) {
+                        self.history.push(EditCommand::Annotations {
+                            label: label.to_string(),
+                            before,
+                            after: self.annotations.clone(),
+                        });
                     }
                 } else {
                     ui.centered_and_justified(|ui| {
@@ -906,7 +1850,15 @@ impl eframe::App for PdfViewerApp {
             });
 
         // 中央パネル: フォルダ内PDFサムネイル一覧（新しい順）
+        self.pdf_thumbnails.poll(ctx);
         egui::CentralPanel::default().show(ctx, |ui| {
+            if self.current_folder.is_some() {
+                if let Some(hit) = self.folder_search.show(ui) {
+                    self.open_search_hit(hit.path, hit.page, &hit.query);
+                }
+                ui.separator();
+            }
+
             if self.folder_pdfs.is_empty() {
                 ui.centered_and_justified(|ui| {
                     ui.label("左側のフォルダを選択すると、PDFファイルが表示されます");
@@ -928,14 +1880,14 @@ impl eframe::App for PdfViewerApp {
                     .iter()
                     .enumerate()
                     .map(|(idx, entry)| {
-                        let tex_id = self.pdf_thumbnails.get(idx).and_then(|t| t.as_ref().map(|t| t.id()));
+                        let tex_id = self.pdf_thumbnails.get(&entry.path).map(|t| t.id());
                         (idx, entry.path.clone(), entry.name.clone(), self.selected_pdf_index == Some(idx), tex_id)
                     })
                     .collect();
 
                 let mut clicked_pdf: Option<(usize, PathBuf)> = None;
                 let mut right_clicked_pdf: Option<(usize, egui::Pos2)> = None;
-                let mut thumbnails_to_load: Vec<(usize, PathBuf)> = Vec::new();
+                let mut thumbnails_to_load: Vec<PathBuf> = Vec::new();
                 let mut drag_started_pdf: Option<PathBuf> = None;
                 let mut drag_ended = false;
 
@@ -1012,7 +1964,7 @@ impl eframe::App for PdfViewerApp {
                                                         egui::FontId::proportional(24.0),
                                                         Color32::from_gray(120),
                                                     );
-                                                    thumbnails_to_load.push((*idx, path.clone()));
+                                                    thumbnails_to_load.push(path.clone());
                                                 }
 
                                                 // ドラッグ開始
@@ -1097,20 +2049,14 @@ impl eframe::App for PdfViewerApp {
                     self.file_explorer.clear_drop_target();
                 }
 
-                // サムネイルは低解像度で高速に読み込み（1フレームにつき2件まで）
-                for (idx, path) in thumbnails_to_load.into_iter().take(2) {
-                    if let Ok(doc) = PdfDocument::open(&path) {
-                        if let Some(image) = doc.render_page_thumbnail(0, 100, 120) {
-                            let texture = ctx.load_texture(
-                                format!("folder_pdf_{}", idx),
-                                image,
-                                egui::TextureOptions::LINEAR,
-                            );
-                            if idx < self.pdf_thumbnails.len() {
-                                self.pdf_thumbnails[idx] = Some(texture);
-                            }
-                        }
-                    }
+                // サムネイルは低解像度でワーカースレッドに描画させる（表示順に処理）
+                for path in thumbnails_to_load {
+                    let job = || RenderJob {
+                        source: RenderSource::File(path.clone()),
+                        page: 0,
+                        area: RenderArea::Fit { width: 100, height: 120 },
+                    };
+                    self.pdf_thumbnails.request(ctx, &path, job, false);
                 }
 
                 if let Some((idx, path)) = clicked_pdf {
@@ -1224,71 +2170,74 @@ impl eframe::App for PdfViewerApp {
                 });
         }
 
-        // スタンプ登録ダイアログ
-        if self.show_stamp_register_dialog {
-            // 事前にテクスチャを準備
-            let stamp_textures: Vec<(String, Option<egui::TextureId>)> = self.custom_stamps
-                .iter()
-                .enumerate()
-                .map(|(i, s)| {
-                    let tex_id = self.custom_stamp_textures.get(i)
-                        .and_then(|t| t.as_ref().map(|t| t.id()));
-                    (s.name.clone(), tex_id)
-                })
-                .collect();
-            
-            let mut add_stamp_path: Option<PathBuf> = None;
-            let mut close_dialog = false;
-            
-            egui::Window::new("🖼 カスタムスタンプ登録")
+        // ページ移動ダイアログ
+        if self.show_move_dialog {
+            egui::Window::new("↕ ページ移動")
                 .collapsible(false)
-                .resizable(true)
+                .resizable(false)
                 .show(ctx, |ui| {
-                    ui.label("PNG画像（透過対応）を選択して、スタンプとして登録できます。");
-                    ui.separator();
-                    
-                    if !stamp_textures.is_empty() {
-                        ui.label(format!("登録済みスタンプ: {} 個", stamp_textures.len()));
-                        egui::ScrollArea::vertical()
-                            .max_height(200.0)
-                            .show(ui, |ui| {
-                                ui.horizontal_wrapped(|ui| {
-                                    for (name, tex_id) in &stamp_textures {
-                                        ui.group(|ui| {
-                                            ui.set_width(80.0);
-                                            if let Some(id) = tex_id {
-                                                ui.image((*id, Vec2::new(60.0, 60.0)));
-                                            }
-                                            ui.label(name);
-                                        });
-                                    }
-                                });
-                            });
+                    if let Some(ref doc) = self.current_document {
+                        ui.label(format!("総ページ数: {}", doc.page_count()));
                         ui.separator();
                     }
-                    
+
+                    ui.horizontal(|ui| {
+                        ui.label("開始ページ:");
+                        ui.text_edit_singleline(&mut self.move_start_page);
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("終了ページ:");
+                        ui.text_edit_singleline(&mut self.move_end_page);
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("移動先:");
+                        ui.text_edit_singleline(&mut self.move_dest_page);
+                    });
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        if ui.button("移動").clicked() {
+                            self.move_pages();
+                            self.show_move_dialog = false;
+                        }
+                        if ui.button("キャンセル").clicked() {
+                            self.show_move_dialog = false;
+                        }
+                    });
+                });
+        }
+
+        // 名前を付けて保存ダイアログ
+        if self.show_save_dialog {
+            egui::Window::new("📄 名前を付けて保存")
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    ui.label("スタンプ・テキスト・矩形の保存方法:");
+                    for mode in [SaveMode::Flatten, SaveMode::Annotations] {
+                        ui.radio_value(&mut self.save_mode, mode, mode.label());
+                    }
+                    ui.separator();
                     ui.horizontal(|ui| {
-                        if ui.button("📂 PNG画像を追加...").clicked() {
+                        if ui.button("保存...").clicked() {
                             if let Some(path) = rfd::FileDialog::new()
-                                .add_filter("PNG画像", &["png"])
-                                .pick_file()
+                                .add_filter("PDF", &["pdf"])
+                                .set_file_name("output.pdf")
+                                .save_file()
                             {
-                                add_stamp_path = Some(path);
+                                self.save_pdf(&path);
                             }
+                            self.show_save_dialog = false;
                         }
-                        
-                        if ui.button("閉じる").clicked() {
-                            close_dialog = true;
+                        if ui.button("キャンセル").clicked() {
+                            self.show_save_dialog = false;
                         }
                     });
                 });
-            
-            if let Some(path) = add_stamp_path {
-                self.register_custom_stamp(path);
-            }
-            if close_dialog {
-                self.show_stamp_register_dialog = false;
-            }
+        }
+
+        // スタンプライブラリ
+        if self.show_stamp_library_dialog {
+            self.show_stamp_library(ctx);
         }
     }
 }
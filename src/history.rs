@@ -0,0 +1,373 @@
+//! 元に戻す・やり直しの履歴
+//!
+//! 注釈の編集は変更前後の注釈データを丸ごと保持する（注釈は小さく、
+//! 種類が増えても同じ仕組みで戻せる）。ページ操作は逆操作で戻す。
+
+use crate::pdf::{AnnotationData, PageEditor, PdfDocument, PdfOperations};
+use anyhow::Result;
+use std::ops::Range;
+
+/// 保持する履歴の上限
+const MAX_HISTORY: usize = 100;
+
+/// 元に戻せる編集操作（`D` はページを操作するドキュメント）
+pub enum EditCommand<D = PdfDocument> {
+    /// 注釈の追加・削除・移動・変更
+    Annotations {
+        label: String,
+        before: AnnotationData,
+        after: AnnotationData,
+    },
+    /// ページの回転
+    RotatePages { pages: Vec<usize>, degrees: i32 },
+    /// ページの削除（`pages` は昇順。削除した各ページのコピーと削除前の注釈を保持）
+    DeletePages {
+        pages: Vec<usize>,
+        removed: Vec<D>,
+        annotations: AnnotationData,
+    },
+    /// 連続したページの移動（移動後に先頭ページが `to` 番目）
+    MovePages { pages: Range<usize>, to: usize },
+    /// ページの並べ替え（並べ替え後のページ順を元のページ番号で並べたもの）
+    ReorderPages { order: Vec<usize> },
+}
+
+impl<D: PageEditor> EditCommand<D> {
+    /// メニューやステータスに表示する操作名
+    pub fn label(&self) -> String {
+        match self {
+            EditCommand::Annotations { label, .. } => label.clone(),
+            EditCommand::RotatePages { pages, degrees } if pages.len() == 1 => {
+                format!("ページ {} の {}° 回転", pages[0] + 1, degrees)
+            }
+            EditCommand::RotatePages { pages, degrees } => {
+                format!("{} ページの {}° 回転", pages.len(), degrees)
+            }
+            EditCommand::DeletePages { pages, .. } if pages.len() == 1 => {
+                format!("ページ {} の削除", pages[0] + 1)
+            }
+            EditCommand::DeletePages { pages, .. } => format!("{} ページの削除", pages.len()),
+            EditCommand::MovePages { pages, .. } if pages.len() == 1 => {
+                format!("ページ {} の移動", pages.start + 1)
+            }
+            EditCommand::MovePages { pages, .. } => {
+                format!("ページ {}-{} の移動", pages.start + 1, pages.end)
+            }
+            EditCommand::ReorderPages { .. } => "ページの並べ替え".to_string(),
+        }
+    }
+
+    /// 操作を取り消す
+    fn undo(&self, doc: &mut D, annotations: &mut AnnotationData) -> Result<()> {
+        match self {
+            EditCommand::Annotations { before, .. } => *annotations = before.clone(),
+            EditCommand::RotatePages { pages, degrees } => {
+                for &page in pages {
+                    PdfOperations::rotate_page(doc, page, -degrees)?
+                }
+            }
+            EditCommand::DeletePages {
+                pages,
+                removed,
+                annotations: before,
+            } => {
+                // 昇順に元の位置へ戻せば、後のページの位置もずれない
+                for (&page, removed) in pages.iter().zip(removed) {
+                    doc.insert_pages(removed, page)?;
+                }
+                *annotations = before.clone();
+            }
+            EditCommand::MovePages { pages, to } => {
+                let moved = *to..*to + pages.len();
+                PdfOperations::move_pages(doc, annotations, moved, pages.start)?
+            }
+            EditCommand::ReorderPages { order } => {
+                let mut inverse = vec![0; order.len()];
+                for (new_page, &old_page) in order.iter().enumerate() {
+                    inverse[old_page] = new_page;
+                }
+                PdfOperations::reorder_pages(doc, annotations, &inverse)?
+            }
+        }
+        Ok(())
+    }
+
+    /// 取り消した操作をやり直す
+    fn redo(&self, doc: &mut D, annotations: &mut AnnotationData) -> Result<()> {
+        match self {
+            EditCommand::Annotations { after, .. } => *annotations = after.clone(),
+            EditCommand::RotatePages { pages, degrees } => {
+                for &page in pages {
+                    PdfOperations::rotate_page(doc, page, *degrees)?
+                }
+            }
+            EditCommand::DeletePages { pages, .. } => {
+                for &page in pages.iter().rev() {
+                    PdfOperations::delete_page(doc, annotations, page)?
+                }
+            }
+            EditCommand::MovePages { pages, to } => {
+                PdfOperations::move_pages(doc, annotations, pages.clone(), *to)?
+            }
+            EditCommand::ReorderPages { order } => {
+                PdfOperations::reorder_pages(doc, annotations, order)?
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 元に戻す・やり直しのスタック
+pub struct History<D = PdfDocument> {
+    undo_stack: Vec<EditCommand<D>>,
+    redo_stack: Vec<EditCommand<D>>,
+}
+
+impl<D> Default for History<D> {
+    fn default() -> Self {
+        Self {
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+        }
+    }
+}
+
+impl<D: PageEditor> History<D> {
+    /// 実行済みの操作を記録（やり直し履歴は破棄）
+    pub fn push(&mut self, command: EditCommand<D>) {
+        self.redo_stack.clear();
+        self.undo_stack.push(command);
+        if self.undo_stack.len() > MAX_HISTORY {
+            self.undo_stack.remove(0);
+        }
+    }
+
+    /// 履歴を全て破棄（別のドキュメントを開いたときなど）
+    pub fn clear(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+    }
+
+    /// 次に元に戻す操作の名前
+    pub fn undo_label(&self) -> Option<String> {
+        self.undo_stack.last().map(EditCommand::label)
+    }
+
+    /// 次にやり直す操作の名前
+    pub fn redo_label(&self) -> Option<String> {
+        self.redo_stack.last().map(EditCommand::label)
+    }
+
+    /// 直前の操作を元に戻し、その操作名を返す（履歴が無ければ None）
+    ///
+    /// 失敗した操作は履歴から取り除く。
+    pub fn undo(
+        &mut self,
+        doc: &mut D,
+        annotations: &mut AnnotationData,
+    ) -> Option<Result<String>> {
+        let command = self.undo_stack.pop()?;
+        Some(command.undo(doc, annotations).map(|()| {
+            let label = command.label();
+            self.redo_stack.push(command);
+            label
+        }))
+    }
+
+    /// 元に戻した操作をやり直し、その操作名を返す（履歴が無ければ None）
+    ///
+    /// 失敗した操作は履歴から取り除く。
+    pub fn redo(
+        &mut self,
+        doc: &mut D,
+        annotations: &mut AnnotationData,
+    ) -> Option<Result<String>> {
+        let command = self.redo_stack.pop()?;
+        Some(command.redo(doc, annotations).map(|()| {
+            let label = command.label();
+            self.undo_stack.push(command);
+            label
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdf::{Stamp, StampType};
+    use anyhow::bail;
+
+    /// PDFiumを使わないドキュメント（ページを番号と回転角度の組で持つ）
+    #[derive(Debug, Clone, PartialEq)]
+    struct FakeDocument {
+        pages: Vec<(usize, i32)>,
+    }
+
+    impl FakeDocument {
+        fn new(page_count: usize) -> Self {
+            Self {
+                pages: (0..page_count).map(|page| (page, 0)).collect(),
+            }
+        }
+    }
+
+    impl PageEditor for FakeDocument {
+        fn rotate_page(&mut self, page_index: usize, degrees: i32) -> Result<()> {
+            let Some((_, rotation)) = self.pages.get_mut(page_index) else {
+                bail!("無効なページ番号");
+            };
+            *rotation = (*rotation + degrees).rem_euclid(360);
+            Ok(())
+        }
+
+        fn delete_page(&mut self, page_index: usize) -> Result<()> {
+            if page_index >= self.pages.len() {
+                bail!("無効なページ番号");
+            }
+            self.pages.remove(page_index);
+            Ok(())
+        }
+
+        fn insert_pages(&mut self, source: &Self, at: usize) -> Result<()> {
+            self.pages.splice(at..at, source.pages.iter().copied());
+            Ok(())
+        }
+
+        fn move_pages(&mut self, pages: Range<usize>, to: usize) -> Result<Vec<usize>> {
+            let mut order: Vec<usize> = (0..self.pages.len()).filter(|i| !pages.contains(i)).collect();
+            order.splice(to..to, pages);
+            self.reorder_pages(&order)?;
+            Ok(order)
+        }
+
+        fn reorder_pages(&mut self, order: &[usize]) -> Result<()> {
+            self.pages = order.iter().map(|&page| self.pages[page]).collect();
+            Ok(())
+        }
+    }
+
+    /// 各ページに1つずつスタンプを置いた注釈
+    fn stamps_on(pages: &[usize]) -> AnnotationData {
+        AnnotationData {
+            stamps: pages.iter().map(|&page| Stamp::new(page, 0.0, 0.0, StampType::Approved)).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn stamp_pages(annotations: &AnnotationData) -> Vec<usize> {
+        annotations.stamps.iter().map(|s| s.page).collect()
+    }
+
+    #[test]
+    fn annotation_edit_is_undone_and_redone() {
+        let mut doc = FakeDocument::new(2);
+        let mut history = History::default();
+        let before = stamps_on(&[0]);
+        let after = stamps_on(&[0, 1]);
+        let mut annotations = after.clone();
+        history.push(EditCommand::Annotations {
+            label: "スタンプの追加".to_string(),
+            before,
+            after,
+        });
+
+        assert_eq!(history.undo(&mut doc, &mut annotations).unwrap().unwrap(), "スタンプの追加");
+        assert_eq!(stamp_pages(&annotations), vec![0]);
+        assert_eq!(history.redo_label().as_deref(), Some("スタンプの追加"));
+
+        history.redo(&mut doc, &mut annotations).unwrap().unwrap();
+        assert_eq!(stamp_pages(&annotations), vec![0, 1]);
+        assert!(history.redo(&mut doc, &mut annotations).is_none());
+    }
+
+    #[test]
+    fn reorder_is_undone_by_its_inverse() {
+        let original = FakeDocument::new(4);
+        let mut doc = original.clone();
+        let mut annotations = stamps_on(&[0, 1, 2, 3]);
+        let order = vec![2, 0, 3, 1];
+        PdfOperations::reorder_pages(&mut doc, &mut annotations, &order).unwrap();
+        let mut history = History::default();
+        history.push(EditCommand::ReorderPages { order });
+
+        history.undo(&mut doc, &mut annotations).unwrap().unwrap();
+        assert_eq!(doc, original);
+        assert_eq!(stamp_pages(&annotations), vec![0, 1, 2, 3]);
+
+        history.redo(&mut doc, &mut annotations).unwrap().unwrap();
+        assert_eq!(doc.pages.iter().map(|&(page, _)| page).collect::<Vec<_>>(), vec![2, 0, 3, 1]);
+        assert_eq!(stamp_pages(&annotations), vec![1, 3, 0, 2]);
+    }
+
+    #[test]
+    fn move_is_undone_by_moving_back() {
+        let original = FakeDocument::new(5);
+        let mut annotations = stamps_on(&[0, 1, 2, 3, 4]);
+        for (pages, to) in [(0..2, 3), (3..5, 0), (2..3, 2)] {
+            let mut doc = original.clone();
+            PdfOperations::move_pages(&mut doc, &mut annotations, pages.clone(), to).unwrap();
+            let mut history = History::default();
+            history.push(EditCommand::MovePages { pages, to });
+
+            history.undo(&mut doc, &mut annotations).unwrap().unwrap();
+            assert_eq!(doc, original);
+            assert_eq!(stamp_pages(&annotations), vec![0, 1, 2, 3, 4]);
+        }
+    }
+
+    #[test]
+    fn delete_is_undone_with_annotations_of_deleted_pages() {
+        let original = FakeDocument::new(4);
+        let mut doc = original.clone();
+        let mut annotations = stamps_on(&[0, 1, 2, 3]);
+        let before = annotations.clone();
+
+        // 削除する前に各ページのコピーを取っておく
+        let pages = vec![1, 3];
+        let removed = pages
+            .iter()
+            .map(|&page| FakeDocument {
+                pages: vec![doc.pages[page]],
+            })
+            .collect();
+        for &page in pages.iter().rev() {
+            PdfOperations::delete_page(&mut doc, &mut annotations, page).unwrap();
+        }
+        assert_eq!(stamp_pages(&annotations), vec![0, 1]);
+        let mut history = History::default();
+        history.push(EditCommand::DeletePages {
+            pages,
+            removed,
+            annotations: before,
+        });
+
+        history.undo(&mut doc, &mut annotations).unwrap().unwrap();
+        assert_eq!(doc, original);
+        assert_eq!(stamp_pages(&annotations), vec![0, 1, 2, 3]);
+
+        history.redo(&mut doc, &mut annotations).unwrap().unwrap();
+        assert_eq!(doc.pages, vec![(0, 0), (2, 0)]);
+        assert_eq!(stamp_pages(&annotations), vec![0, 1]);
+    }
+
+    #[test]
+    fn new_edit_clears_redo() {
+        let mut doc = FakeDocument::new(2);
+        let mut annotations = AnnotationData::default();
+        let mut history = History::default();
+        history.push(EditCommand::RotatePages {
+            pages: vec![0],
+            degrees: 90,
+        });
+        history.undo(&mut doc, &mut annotations).unwrap().unwrap();
+        assert!(history.redo_label().is_some());
+
+        history.push(EditCommand::RotatePages {
+            pages: vec![1],
+            degrees: 180,
+        });
+        assert!(history.redo_label().is_none());
+        assert!(history.redo(&mut doc, &mut annotations).is_none());
+        assert_eq!(history.undo_label().as_deref(), Some("ページ 2 の 180° 回転"));
+    }
+}
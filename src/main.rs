@@ -9,6 +9,7 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 mod app;
+mod history;
 mod pdf;
 mod resources;
 mod ui;
@@ -19,6 +20,9 @@ use eframe::egui;
 // 日本語フォントをバイナリに埋め込む
 pub const JAPANESE_FONT: &[u8] = include_bytes!("../assets/fonts/NotoSansJP-Regular.ttf");
 
+// 認印の文字のフォントファミリー（PDFへの書き込みと同じ楷書体、無ければゴシック体）
+pub const SEAL_FONT_FAMILY: &str = "Seal";
+
 // スタンプ画像を埋め込む
 pub const STAMP_APPROVED: &[u8] = include_bytes!("../assets/stamps/approved.png");
 pub const STAMP_REJECTED: &[u8] = include_bytes!("../assets/stamps/rejected.png");
@@ -77,5 +81,18 @@ fn setup_fonts(ctx: &egui::Context) {
         .or_default()
         .insert(0, "NotoSansJP".to_owned());
 
+    // 認印用のフォント（楷書体に無い文字や、楷書体が無い場合はゴシック体）
+    let mut seal_family = vec!["NotoSansJP".to_owned()];
+    if let Some(seal_font) = pdf::seal_font() {
+        fonts.font_data.insert(
+            SEAL_FONT_FAMILY.to_owned(),
+            egui::FontData::from_static(seal_font),
+        );
+        seal_family.insert(0, SEAL_FONT_FAMILY.to_owned());
+    }
+    fonts
+        .families
+        .insert(egui::FontFamily::Name(SEAL_FONT_FAMILY.into()), seal_family);
+
     ctx.set_fonts(fonts);
 }
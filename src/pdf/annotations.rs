@@ -0,0 +1,317 @@
+//! 注釈データの保存・読み込み（サイドカーファイル）
+
+use crate::pdf::{
+    CommentAnnotation, MarkupAnnotation, RectAnnotation, ShapeAnnotation, Stamp, TextAnnotation,
+};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// 注釈の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum AnnotationKind {
+    Stamp,
+    Text,
+    Rect,
+    Markup,
+    Shape,
+    Comment,
+}
+
+impl AnnotationKind {
+    /// 日本語ラベル
+    pub fn label(&self) -> &'static str {
+        match self {
+            AnnotationKind::Stamp => "スタンプ",
+            AnnotationKind::Text => "テキスト",
+            AnnotationKind::Rect => "矩形",
+            AnnotationKind::Markup => "装飾",
+            AnnotationKind::Shape => "図形",
+            AnnotationKind::Comment => "コメント",
+        }
+    }
+
+    /// すべての種類
+    pub fn all() -> [AnnotationKind; 6] {
+        [
+            AnnotationKind::Stamp,
+            AnnotationKind::Text,
+            AnnotationKind::Rect,
+            AnnotationKind::Markup,
+            AnnotationKind::Shape,
+            AnnotationKind::Comment,
+        ]
+    }
+}
+
+/// 注釈の参照（種類と、その種類の一覧での番号）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AnnotationRef {
+    pub kind: AnnotationKind,
+    pub index: usize,
+}
+
+/// 注釈ファイルの形式バージョン
+///
+/// - 0（フィールド無し）: `page_rotations` はPDFファイルの /Rotate からの相対角度
+/// - 1: `page_rotations` はPDFファイルの /Rotate を含む絶対角度
+pub const ANNOTATION_DATA_VERSION: u32 = 1;
+
+/// 注釈データ（`.annotations.json` に保存）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AnnotationData {
+    // 保存時は常に現行バージョンを書き込む
+    #[serde(default, serialize_with = "serialize_current_version")]
+    pub version: u32,  // 形式バージョン（読み込み後は `migrate` で現行にそろえる）
+    pub stamps: Vec<Stamp>,
+    pub texts: Vec<TextAnnotation>,
+    #[serde(default)]
+    pub rects: Vec<RectAnnotation>,  // 矩形注釈
+    #[serde(default)]
+    pub markups: Vec<MarkupAnnotation>,  // ハイライト・下線・取り消し線
+    #[serde(default)]
+    pub shapes: Vec<ShapeAnnotation>,  // 手書き・直線・矢印・楕円・多角形
+    #[serde(default)]
+    pub comments: Vec<CommentAnnotation>,  // 付箋コメント（返信を含む）
+    #[serde(default)]
+    pub page_rotations: Vec<i32>,  // ページごとの回転角度
+}
+
+impl AnnotationData {
+    /// 注釈ファイルのパスを取得
+    pub fn sidecar_path(pdf_path: &Path) -> PathBuf {
+        let mut path = pdf_path.to_path_buf();
+        path.set_extension("annotations.json");
+        path
+    }
+
+    /// 注釈ファイルを読み込み（存在しない・壊れている場合は None）
+    pub fn load(pdf_path: &Path) -> Option<Self> {
+        let content = std::fs::read_to_string(Self::sidecar_path(pdf_path)).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    /// 古い形式で読み込んだデータを現行の形式に変換
+    ///
+    /// `file_rotations` はPDFファイルに書かれている各ページの /Rotate。
+    /// バージョン0のページ回転はそこからの相対角度なので、足し合わせて絶対角度にする。
+    pub fn migrate(&mut self, file_rotations: &[i32]) {
+        if self.version < 1 {
+            for (rotation, &file_rotation) in self.page_rotations.iter_mut().zip(file_rotations) {
+                *rotation = (*rotation + file_rotation).rem_euclid(360);
+            }
+        }
+        self.version = ANNOTATION_DATA_VERSION;
+    }
+
+    /// 注釈ファイルを保存
+    pub fn save(&self, pdf_path: &Path) -> Result<()> {
+        let content = serde_json::to_string_pretty(self)?;
+        std::fs::write(Self::sidecar_path(pdf_path), content)
+            .context("注釈ファイルを保存できませんでした")
+    }
+
+    /// 注釈が1つも無いか
+    pub fn is_empty(&self) -> bool {
+        self.stamps.is_empty()
+            && self.texts.is_empty()
+            && self.rects.is_empty()
+            && self.markups.is_empty()
+            && self.shapes.is_empty()
+            && self.comments.is_empty()
+    }
+
+    /// 別の注釈データの注釈を末尾に追加（ページ回転は変更しない）
+    pub fn extend(&mut self, other: AnnotationData) {
+        self.stamps.extend(other.stamps);
+        self.texts.extend(other.texts);
+        self.rects.extend(other.rects);
+        self.markups.extend(other.markups);
+        self.shapes.extend(other.shapes);
+        self.comments.extend(other.comments);
+    }
+
+    /// 全注釈のページ番号を付け替える
+    ///
+    /// `map` が None を返したページの注釈は削除される。
+    pub fn remap_pages(&mut self, map: impl Fn(usize) -> Option<usize>) {
+        self.stamps.retain_mut(|s| remap(&mut s.page, &map));
+        self.texts.retain_mut(|t| remap(&mut t.page, &map));
+        self.rects.retain_mut(|r| remap(&mut r.page, &map));
+        self.markups.retain_mut(|m| remap(&mut m.page, &map));
+        self.shapes.retain_mut(|s| remap(&mut s.page, &map));
+        self.comments.retain_mut(|c| remap(&mut c.page, &map));
+    }
+
+    /// 種類ごとの注釈の数
+    pub fn counts(&self) -> [usize; 6] {
+        AnnotationKind::all().map(|kind| self.count(kind))
+    }
+
+    fn count(&self, kind: AnnotationKind) -> usize {
+        match kind {
+            AnnotationKind::Stamp => self.stamps.len(),
+            AnnotationKind::Text => self.texts.len(),
+            AnnotationKind::Rect => self.rects.len(),
+            AnnotationKind::Markup => self.markups.len(),
+            AnnotationKind::Shape => self.shapes.len(),
+            AnnotationKind::Comment => self.comments.len(),
+        }
+    }
+
+    /// 指定した注釈をまとめて削除し、削除した数を返す
+    pub fn remove_items(&mut self, items: &[AnnotationRef]) -> usize {
+        // 番号がずれないよう後ろから削除する
+        let mut items: Vec<AnnotationRef> = items.iter().copied().filter(|i| i.index < self.count(i.kind)).collect();
+        items.sort_unstable();
+        items.dedup();
+        for item in items.iter().rev() {
+            match item.kind {
+                AnnotationKind::Stamp => {
+                    self.stamps.remove(item.index);
+                }
+                AnnotationKind::Text => {
+                    self.texts.remove(item.index);
+                }
+                AnnotationKind::Rect => {
+                    self.rects.remove(item.index);
+                }
+                AnnotationKind::Markup => {
+                    self.markups.remove(item.index);
+                }
+                AnnotationKind::Shape => {
+                    self.shapes.remove(item.index);
+                }
+                AnnotationKind::Comment => {
+                    self.comments.remove(item.index);
+                }
+            }
+        }
+        items.len()
+    }
+
+    /// 指定した注釈をまとめて別のページへ移し、移した数を返す（ページ上の位置は変えない）
+    pub fn move_items_to_page(&mut self, items: &[AnnotationRef], page: usize) -> usize {
+        let mut moved = 0;
+        for item in items {
+            let target = match item.kind {
+                AnnotationKind::Stamp => self.stamps.get_mut(item.index).map(|a| &mut a.page),
+                AnnotationKind::Text => self.texts.get_mut(item.index).map(|a| &mut a.page),
+                AnnotationKind::Rect => self.rects.get_mut(item.index).map(|a| &mut a.page),
+                AnnotationKind::Markup => self.markups.get_mut(item.index).map(|a| &mut a.page),
+                AnnotationKind::Shape => self.shapes.get_mut(item.index).map(|a| &mut a.page),
+                AnnotationKind::Comment => self.comments.get_mut(item.index).map(|a| &mut a.page),
+            };
+            if let Some(target) = target {
+                *target = page;
+                moved += 1;
+            }
+        }
+        moved
+    }
+
+    /// 指定した注釈の色をまとめて変え、変えた数を返す
+    ///
+    /// 色を持つ矩形・装飾・図形だけが対象で、不透明度は元のまま残す。
+    pub fn recolor_items(&mut self, items: &[AnnotationRef], [r, g, b]: [u8; 3]) -> usize {
+        let mut recolored = 0;
+        for item in items {
+            let target = match item.kind {
+                AnnotationKind::Rect => self.rects.get_mut(item.index).map(|a| &mut a.color),
+                AnnotationKind::Markup => self.markups.get_mut(item.index).map(|a| &mut a.color),
+                AnnotationKind::Shape => self.shapes.get_mut(item.index).map(|a| &mut a.color),
+                _ => None,
+            };
+            if let Some(color) = target {
+                *color = [r, g, b, color[3]];
+                recolored += 1;
+            }
+        }
+        recolored
+    }
+}
+
+/// 形式バージョンとして常に現行のバージョンを書き込む
+fn serialize_current_version<S: serde::Serializer>(_: &u32, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u32(ANNOTATION_DATA_VERSION)
+}
+
+/// ページ番号を付け替え、残すべきなら true を返す
+fn remap(page: &mut usize, map: &impl Fn(usize) -> Option<usize>) -> bool {
+    match map(*page) {
+        Some(new_page) => {
+            *page = new_page;
+            true
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdf::StampType;
+
+    fn stamp(page: usize) -> Stamp {
+        Stamp::new(page, 10.0, 20.0, StampType::Approved)
+    }
+
+    fn rect(page: usize) -> RectAnnotation {
+        RectAnnotation {
+            page,
+            x: 0.0,
+            y: 0.0,
+            width: 10.0,
+            height: 10.0,
+            color: [255, 255, 255, 128],
+            redact: false,
+        }
+    }
+
+    #[test]
+    fn migrate_makes_legacy_rotations_absolute() {
+        let mut legacy: AnnotationData =
+            serde_json::from_str(r#"{"stamps": [], "texts": [], "page_rotations": [90, 0, 270]}"#).unwrap();
+        assert_eq!(legacy.version, 0);
+        legacy.migrate(&[90, 180, 180]);
+        assert_eq!(legacy.page_rotations, vec![180, 180, 90]);
+        assert_eq!(legacy.version, ANNOTATION_DATA_VERSION);
+
+        // 現行形式は既に絶対角度なので変えない
+        let saved = serde_json::to_string(&AnnotationData {
+            page_rotations: vec![90],
+            ..Default::default()
+        })
+        .unwrap();
+        let mut current: AnnotationData = serde_json::from_str(&saved).unwrap();
+        assert_eq!(current.version, ANNOTATION_DATA_VERSION);
+        current.migrate(&[180]);
+        assert_eq!(current.page_rotations, vec![90]);
+    }
+
+    #[test]
+    fn bulk_operations_apply_to_selected_items() {
+        let mut data = AnnotationData {
+            stamps: vec![stamp(0), stamp(1), stamp(2)],
+            rects: vec![rect(0), rect(3)],
+            ..Default::default()
+        };
+        let stamp_ref = |index| AnnotationRef { kind: AnnotationKind::Stamp, index };
+        let rect_ref = |index| AnnotationRef { kind: AnnotationKind::Rect, index };
+
+        // 色を持たないスタンプは色の変更の対象外
+        assert_eq!(data.recolor_items(&[stamp_ref(0), rect_ref(1)], [200, 0, 0]), 1);
+        assert_eq!(data.rects[1].color, [200, 0, 0, 128]);
+        assert_eq!(data.rects[0].color, [255, 255, 255, 128]);
+
+        assert_eq!(data.move_items_to_page(&[stamp_ref(2), rect_ref(0), rect_ref(5)], 1), 2);
+        assert_eq!(data.stamps[2].page, 1);
+        assert_eq!(data.rects[0].page, 1);
+
+        // 前の番号を先に指定しても後ろの番号の注釈が正しく消える
+        assert_eq!(data.remove_items(&[stamp_ref(0), stamp_ref(2), stamp_ref(2), rect_ref(9)]), 2);
+        assert_eq!(data.stamps.len(), 1);
+        assert_eq!(data.stamps[0].page, 1);
+        assert_eq!(data.counts(), [1, 0, 2, 0, 0, 0]);
+    }
+}
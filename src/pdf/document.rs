@@ -1,52 +1,310 @@
 //! PDF ドキュメント管理
 
+use super::flatten::{
+    draw_image, draw_markup, draw_rect, draw_seal, draw_shape, draw_text, Fonts, PageFrame,
+};
+use super::incremental::apply_patches;
+use super::native::{
+    needs_details, read_annotation, read_annotation_details, write_comment, write_markup, write_rect,
+    write_seal, write_shape, write_stamp, write_text, AnnotationDetails, ImportedAnnotations,
+    NativeAnnotation,
+};
+use super::redact::{redact_region, verify_redaction};
+use crate::pdf::{
+    AnnotationData, CommentAnnotation, CommentReply, FontType, MarkupAnnotation, PageText,
+    RectAnnotation, SaveMode, ShapeAnnotation, Stamp, StampType, TextAnnotation, TextChar, TextRect,
+};
 use anyhow::{Context, Result};
 use eframe::egui;
+use image::DynamicImage;
+use once_cell::sync::OnceCell;
+use parking_lot::{MappedMutexGuard, Mutex, MutexGuard};
+use pdfium_render::prelude::PdfDocument as PdfiumDocument;
 use pdfium_render::prelude::*;
+use std::ops::Range;
 use std::path::Path;
+use std::sync::Arc;
+use std::time::{Instant, SystemTime};
 
-/// PDFiumライブラリを取得
-fn get_pdfium() -> Result<Pdfium> {
-    let bindings = Pdfium::bind_to_library(Pdfium::pdfium_platform_library_name_at_path("./"))
-        .or_else(|_| Pdfium::bind_to_system_library())
-        .context("PDFiumライブラリを読み込めませんでした")?;
-    Ok(Pdfium::new(bindings))
+/// プロセス全体で共有するPDFiumバインディング
+static PDFIUM: OnceCell<Pdfium> = OnceCell::new();
+
+/// PDFiumライブラリを取得（初回のみバインド）
+fn get_pdfium() -> Result<&'static Pdfium> {
+    PDFIUM.get_or_try_init(|| {
+        let bindings = Pdfium::bind_to_library(Pdfium::pdfium_platform_library_name_at_path("./"))
+            .or_else(|_| Pdfium::bind_to_system_library())
+            .context("PDFiumライブラリを読み込めませんでした")?;
+        Ok(Pdfium::new(bindings))
+    })
+}
+
+/// ファイルの更新日時を取得
+fn file_modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// 回転角度（度）をPDFiumの回転設定に変換
+fn rotation_setting(degrees: i32) -> PdfPageRenderRotation {
+    match degrees.rem_euclid(360) {
+        90 => PdfPageRenderRotation::Degrees90,
+        180 => PdfPageRenderRotation::Degrees180,
+        270 => PdfPageRenderRotation::Degrees270,
+        _ => PdfPageRenderRotation::None,
+    }
+}
+
+/// ページの /Rotate を度単位で取得
+fn page_rotation_degrees(page: &PdfPage) -> i32 {
+    page.rotation().map(|r| r.as_degrees() as i32).unwrap_or(0)
+}
+
+/// 各ページの回転前サイズと /Rotate を読み取る
+fn read_page_layout(document: &PdfiumDocument) -> (Vec<(f32, f32)>, Vec<i32>) {
+    let mut page_sizes = Vec::with_capacity(document.pages().len() as usize);
+    let mut page_rotations = Vec::with_capacity(document.pages().len() as usize);
+
+    for page in document.pages().iter() {
+        // PDFiumのページ幅・高さは /Rotate 適用後の値なので、回転前に戻す
+        let rotation = page_rotation_degrees(&page);
+        let width = page.width().value;
+        let height = page.height().value;
+        if rotation == 90 || rotation == 270 {
+            page_sizes.push((height, width));
+        } else {
+            page_sizes.push((width, height));
+        }
+        page_rotations.push(rotation);
+    }
+
+    (page_sizes, page_rotations)
+}
+
+/// 指定したページを順番に `dest` の末尾へコピー
+///
+/// 連続したページはまとめてコピーし、共有リソースの重複を抑える。
+fn import_pages(dest: &mut PdfiumDocument, source: &PdfiumDocument, pages: &[usize]) -> Result<()> {
+    let mut start = 0;
+    while start < pages.len() {
+        let mut end = start + 1;
+        while end < pages.len() && pages[end] == pages[end - 1] + 1 {
+            end += 1;
+        }
+        let range = pages[start] as u16..=pages[end - 1] as u16;
+        let insert_at = dest.pages().len();
+        dest.pages_mut()
+            .copy_page_range_from_document(source, range, insert_at)
+            .context("ページをコピーできませんでした")?;
+        start = end;
+    }
+    Ok(())
+}
+
+/// `pages` の範囲を移動先 `to` に移した後のページ順（元のページ番号の列）
+fn move_order(page_count: usize, pages: Range<usize>, to: usize) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..page_count).filter(|i| !pages.contains(i)).collect();
+    let to = to.min(order.len());
+    order.splice(to..to, pages);
+    order
+}
+
+/// レンダリング結果を egui::ColorImage に変換
+fn to_color_image(bitmap: &PdfBitmap) -> egui::ColorImage {
+    let rgba = bitmap.as_image().to_rgba8();
+    let (width, height) = rgba.dimensions();
+    let pixels = rgba
+        .pixels()
+        .map(|p| egui::Color32::from_rgba_unmultiplied(p[0], p[1], p[2], p[3]))
+        .collect();
+    egui::ColorImage {
+        size: [width as usize, height as usize],
+        pixels,
+    }
+}
+
+/// ページの一部分の描画範囲
+///
+/// `scale` 倍（ポイントあたりのピクセル数）で回転後のページ全体を描いたときの、
+/// 左上 (`x`, `y`) から `width` x `height` ピクセルの範囲。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PageTile {
+    pub scale: f32,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// 取り込んだスタンプの外観を描画する倍率（ポイントあたりのピクセル数）
+const STAMP_RENDER_SCALE: f32 = 2.0;
+
+/// スタンプ注釈の外観を透過画像として描画
+///
+/// ページを複製したドキュメントで本文と他の注釈を消してから描画し、各スタンプの範囲を切り出す。
+/// `stamps` は (注釈番号, スタンプ) の組。
+fn render_stamp_appearances(
+    source: &PdfiumDocument,
+    page_index: usize,
+    frame: &PageFrame,
+    stamps: &[(usize, Stamp)],
+) -> Result<Vec<DynamicImage>> {
+    let mut scratch = get_pdfium()?
+        .create_new_pdf()
+        .context("PDFを作成できませんでした")?;
+    import_pages(&mut scratch, source, &[page_index])?;
+    let mut page = scratch
+        .pages()
+        .get(0)
+        .context("ページを取得できませんでした")?;
+    page.set_content_regeneration_strategy(PdfPageContentRegenerationStrategy::Manual);
+
+    while !page.objects().is_empty() {
+        page.objects_mut().remove_object_at_index(0)?;
+    }
+    for index in (0..page.annotations().len() as usize).rev() {
+        if stamps.iter().all(|(i, _)| *i != index) {
+            let annotation = page.annotations().get(index as PdfPageAnnotationIndex)?;
+            page.annotations_mut().delete_annotation(annotation)?;
+        }
+    }
+    page.set_rotation(rotation_setting(frame.rotation));
+
+    let render_config = PdfRenderConfig::new()
+        .scale_page_by_factor(STAMP_RENDER_SCALE)
+        .set_clear_color(PdfColor::new(255, 255, 255, 0))
+        .render_annotations(true);
+    let rendered = page
+        .render_with_config(&render_config)
+        .context("スタンプを描画できませんでした")?
+        .as_image();
+
+    Ok(stamps
+        .iter()
+        .map(|(_, stamp)| {
+            let (u, v) = frame.display_origin(stamp.x, stamp.y, stamp.width, stamp.height);
+            rendered.crop_imm(
+                (u * STAMP_RENDER_SCALE).max(0.0) as u32,
+                (v * STAMP_RENDER_SCALE).max(0.0) as u32,
+                (stamp.width * STAMP_RENDER_SCALE).ceil() as u32,
+                (stamp.height * STAMP_RENDER_SCALE).ceil() as u32,
+            )
+        })
+        .collect())
+}
+
+/// 読み込み済みのPDFiumドキュメント
+struct LoadedDocument {
+    document: PdfiumDocument<'static>,
+    // 読み込み時のファイル更新日時（ディスク上の変更検出用）
+    modified: Option<SystemTime>,
+    // ページ削除などでメモリ上のドキュメントが編集済みか
+    edited: bool,
+}
+
+impl LoadedDocument {
+    /// ファイルからドキュメントを読み込む
+    ///
+    /// ファイルはメモリに読み込んでから解析するため、読み込み後に
+    /// 元のファイルを上書きしても問題ない。
+    fn load(path: &Path) -> Result<Self> {
+        let pdfium = get_pdfium()?;
+        let modified = file_modified(path);
+        let bytes = std::fs::read(path).context("PDFファイルを読み込めませんでした")?;
+        let document = pdfium
+            .load_pdf_from_byte_vec(bytes, None)
+            .context("PDFファイルを開けませんでした")?;
+        Ok(Self {
+            document,
+            modified,
+            edited: false,
+        })
+    }
+
+    /// 独立したドキュメントとして複製（保存したバイト列を読み直すので文書全体を引き継ぐ）
+    fn duplicate(&self) -> Result<Self> {
+        let bytes = self
+            .document
+            .save_to_bytes()
+            .context("PDFを複製できませんでした")?;
+        let document = get_pdfium()?
+            .load_pdf_from_byte_vec(bytes, None)
+            .context("PDFを複製できませんでした")?;
+        Ok(Self {
+            document,
+            modified: self.modified,
+            edited: self.edited,
+        })
+    }
+
+    /// ディスク上のファイルが読み込み後に変更されたか
+    ///
+    /// メモリ上で編集済みの場合は編集内容を優先し、再読み込みしない。
+    fn is_stale(&self, path: &Path) -> bool {
+        !self.edited && file_modified(path) != self.modified
+    }
 }
 
 /// PDFドキュメントのラッパー
+///
+/// 読み込んだPDFiumドキュメントを保持し、ページ描画ごとの再解析を避ける。
+/// ディスク上のファイルが更新された場合のみ再読み込みする。
+///
+/// 複製（描画ワーカーに渡すものなど）は読み込み済みドキュメントを共有し、
+/// ページ削除や保存などで編集する側が、先に独立したドキュメントへ切り離す。
 pub struct PdfDocument {
     path: std::path::PathBuf,
     page_count: usize,
     page_sizes: Vec<(f32, f32)>,
     page_rotations: Vec<i32>, // 各ページの回転角度（0, 90, 180, 270）
+    loaded: Arc<Mutex<LoadedDocument>>,
 }
 
 impl PdfDocument {
     /// PDFファイルを開く
     pub fn open(path: &Path) -> Result<Self> {
-        let pdfium = get_pdfium()?;
+        let loaded = LoadedDocument::load(path)?;
+        let (page_sizes, page_rotations) = read_page_layout(&loaded.document);
 
-        let document = pdfium
-            .load_pdf_from_file(path, None)
-            .context("PDFファイルを開けませんでした")?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            page_count: page_sizes.len(),
+            page_sizes,
+            page_rotations,
+            loaded: Arc::new(Mutex::new(loaded)),
+        })
+    }
 
-        let page_count = document.pages().len();
+    /// 複数ドキュメントのページ範囲を順番に並べた新しいドキュメントを作成
+    ///
+    /// 作成したドキュメントはファイルを持たないため、`save` で保存する。
+    /// 各ページの回転角度は元のドキュメントから引き継ぐ。
+    pub fn compose(parts: &[(&PdfDocument, Range<usize>)]) -> Result<Self> {
+        let mut document = get_pdfium()?
+            .create_new_pdf()
+            .context("PDFを作成できませんでした")?;
+        let mut page_sizes = Vec::new();
+        let mut page_rotations = Vec::new();
 
-        let mut page_sizes = Vec::with_capacity(page_count as usize);
-        let mut page_rotations = Vec::with_capacity(page_count as usize);
-        
-        for page in document.pages().iter() {
-            let width = page.width().value;
-            let height = page.height().value;
-            page_sizes.push((width, height));
-            page_rotations.push(0); // 初期回転は0度
+        for (source, pages) in parts {
+            if pages.end > source.page_count {
+                return Err(anyhow::anyhow!("無効なページ範囲"));
+            }
+            let order: Vec<usize> = pages.clone().collect();
+            import_pages(&mut document, &*source.document()?, &order)?;
+            page_sizes.extend_from_slice(&source.page_sizes[pages.clone()]);
+            page_rotations.extend_from_slice(&source.page_rotations[pages.clone()]);
         }
 
         Ok(Self {
-            path: path.to_path_buf(),
-            page_count: page_count as usize,
+            path: std::path::PathBuf::new(),
+            page_count: page_sizes.len(),
             page_sizes,
             page_rotations,
+            loaded: Arc::new(Mutex::new(LoadedDocument {
+                document,
+                modified: None,
+                edited: true,
+            })),
         })
     }
 
@@ -98,6 +356,112 @@ impl PdfDocument {
         Ok(())
     }
 
+    /// ページを削除
+    pub fn delete_page(&mut self, page_index: usize) -> Result<()> {
+        if page_index >= self.page_count {
+            return Err(anyhow::anyhow!("無効なページ番号"));
+        }
+        if self.page_count == 1 {
+            return Err(anyhow::anyhow!("最後のページは削除できません"));
+        }
+
+        {
+            self.detach()?;
+            let document = self.document()?;
+            document
+                .pages()
+                .get(page_index as u16)
+                .and_then(|page| page.delete())
+                .context("ページを削除できませんでした")?;
+        }
+        self.loaded.lock().edited = true;
+
+        self.page_sizes.remove(page_index);
+        self.page_rotations.remove(page_index);
+        self.page_count -= 1;
+
+        Ok(())
+    }
+
+    /// 別ドキュメントの全ページを `at` 番目に挿入（削除したページの復元用）
+    ///
+    /// 挿入したページの回転角度は `source` から引き継ぐ。
+    pub fn insert_pages(&mut self, source: &PdfDocument, at: usize) -> Result<()> {
+        if at > self.page_count || source.page_count == 0 {
+            return Err(anyhow::anyhow!("無効なページ番号"));
+        }
+
+        {
+            let source_document = source.document()?;
+            self.detach()?;
+            let mut document = self.document()?;
+            document
+                .pages_mut()
+                .copy_page_range_from_document(
+                    &source_document,
+                    0..=(source.page_count - 1) as u16,
+                    at as u16,
+                )
+                .context("ページを挿入できませんでした")?;
+        }
+        self.loaded.lock().edited = true;
+
+        self.page_sizes.splice(at..at, source.page_sizes.iter().copied());
+        self.page_rotations.splice(at..at, source.page_rotations.iter().copied());
+        self.page_count += source.page_count;
+
+        Ok(())
+    }
+
+    /// 連続したページ範囲を移動（移動後に先頭ページが `to` 番目になる）
+    ///
+    /// ページ順を変えた新しいドキュメントを組み立てて置き換える。
+    /// 戻り値は移動後のページ順（元のページ番号の列）。
+    pub fn move_pages(&mut self, pages: Range<usize>, to: usize) -> Result<Vec<usize>> {
+        if pages.is_empty() || pages.end > self.page_count || to + pages.len() > self.page_count {
+            return Err(anyhow::anyhow!("無効なページ範囲"));
+        }
+
+        let order = move_order(self.page_count, pages.clone(), to);
+        if pages.start != to {
+            self.reorder_pages(&order)?;
+        }
+        Ok(order)
+    }
+
+    /// ページを並べ替え（`order` は並べ替え後のページ順を元のページ番号で並べたもの）
+    ///
+    /// ページ順を変えた新しいドキュメントを組み立てて置き換える。
+    pub fn reorder_pages(&mut self, order: &[usize]) -> Result<()> {
+        let mut sorted = order.to_vec();
+        sorted.sort_unstable();
+        if !sorted.iter().copied().eq(0..self.page_count) {
+            return Err(anyhow::anyhow!("無効なページ順"));
+        }
+
+        let mut rebuilt = get_pdfium()?
+            .create_new_pdf()
+            .context("PDFを作成できませんでした")?;
+        import_pages(&mut rebuilt, &*self.document()?, order)?;
+        // 複製と共有している元のドキュメントは変えず、組み立てたドキュメントに置き換える
+        let modified = self.loaded.lock().modified;
+        self.loaded = Arc::new(Mutex::new(LoadedDocument {
+            document: rebuilt,
+            modified,
+            edited: true,
+        }));
+
+        self.page_sizes = order.iter().map(|&i| self.page_sizes[i]).collect();
+        self.page_rotations = order.iter().map(|&i| self.page_rotations[i]).collect();
+
+        Ok(())
+    }
+
+    /// 保存されていないページ構成の変更があるか
+    pub fn is_edited(&self) -> bool {
+        self.loaded.lock().edited
+    }
+
     /// ページの回転角度を取得
     pub fn get_page_rotation(&self, page_index: usize) -> i32 {
         self.page_rotations.get(page_index).copied().unwrap_or(0)
@@ -106,10 +470,33 @@ impl PdfDocument {
     /// ページの回転角度を設定
     pub fn set_page_rotation(&mut self, page_index: usize, degrees: i32) {
         if page_index < self.page_rotations.len() {
-            self.page_rotations[page_index] = degrees % 360;
+            self.page_rotations[page_index] = degrees.rem_euclid(360);
         }
     }
 
+    /// 読み込み済みドキュメントを取得（ディスク上で変更されていれば再読み込み）
+    fn document(&self) -> Result<MappedMutexGuard<'_, PdfiumDocument<'static>>> {
+        let mut loaded = self.loaded.lock();
+        if loaded.is_stale(&self.path) {
+            log::info!("ファイルが更新されたため再読み込み: {}", self.path.display());
+            *loaded = LoadedDocument::load(&self.path)?;
+        }
+        Ok(MutexGuard::map(loaded, |l| &mut l.document))
+    }
+
+    /// 編集の前に、複製と共有している読み込み済みドキュメントを独立したものに切り離す
+    ///
+    /// 切り離した後の編集は複製（描画ワーカーに渡したものなど）には及ばない。
+    fn detach(&mut self) -> Result<()> {
+        if Arc::strong_count(&self.loaded) > 1 {
+            // ディスク上で変更されていれば読み直してから切り離す
+            drop(self.document()?);
+            let detached = self.loaded.lock().duplicate()?;
+            self.loaded = Arc::new(Mutex::new(detached));
+        }
+        Ok(())
+    }
+
     /// ページをレンダリング（回転対応）
     pub fn render_page(
         &self,
@@ -117,13 +504,15 @@ impl PdfDocument {
         width: u32,
         height: u32,
     ) -> Option<egui::ColorImage> {
-        let pdfium = get_pdfium().ok()?;
-        let document = pdfium.load_pdf_from_file(&self.path, None).ok()?;
+        let started = Instant::now();
+        let document = self.document().ok()?;
         let page = document.pages().get(page_index as u16).ok()?;
 
+        // ファイル上の /Rotate からの差分だけ追加で回転させる
         let rotation = self.page_rotations.get(page_index).copied().unwrap_or(0);
+        let rotation = (rotation - page_rotation_degrees(&page)).rem_euclid(360);
 
-        // ページサイズ
+        // ページサイズ（/Rotate 適用後）
         let page_width = page.width().value;
         let page_height = page.height().value;
 
@@ -142,38 +531,73 @@ impl PdfDocument {
         let render_width = (effective_width * scale) as i32;
         let render_height = (effective_height * scale) as i32;
 
-        // 回転設定
-        let rotation_setting = match rotation {
-            90 => PdfPageRenderRotation::Degrees90,
-            180 => PdfPageRenderRotation::Degrees180,
-            270 => PdfPageRenderRotation::Degrees270,
-            _ => PdfPageRenderRotation::None,
-        };
-
         // ページをレンダリング
         let render_config = PdfRenderConfig::new()
             .set_target_width(render_width)
             .set_target_height(render_height)
-            .rotate(rotation_setting, true)
+            .rotate(rotation_setting(rotation), true)
             .render_form_data(true)
             .render_annotations(true);
 
         let bitmap = page.render_with_config(&render_config).ok()?;
+        let image = to_color_image(&bitmap);
 
-        // egui::ColorImage に変換
-        let img = bitmap.as_image();
-        let rgba = img.to_rgba8();
-        let (img_width, img_height) = rgba.dimensions();
+        log::debug!(
+            "ページ {} をレンダリング: {}x{} ({:?})",
+            page_index + 1,
+            image.size[0],
+            image.size[1],
+            started.elapsed()
+        );
 
-        let pixels: Vec<egui::Color32> = rgba
-            .pixels()
-            .map(|p| egui::Color32::from_rgba_unmultiplied(p[0], p[1], p[2], p[3]))
-            .collect();
+        Some(image)
+    }
 
-        Some(egui::ColorImage {
-            size: [img_width as usize, img_height as usize],
-            pixels,
-        })
+    /// ページの一部（タイル）だけをレンダリング（回転対応）
+    ///
+    /// 拡大表示でページ全体を描画するとメモリが足りなくなるため、表示中の範囲だけを描く。
+    /// 変換行列を使う描画ではフォームの入力内容は描画されない。
+    pub fn render_tile(&self, page_index: usize, tile: &PageTile) -> Option<egui::ColorImage> {
+        let started = Instant::now();
+        let document = self.document().ok()?;
+        let page = document.pages().get(page_index as u16).ok()?;
+
+        // ファイル上の /Rotate からの差分だけ追加で回転させる
+        let rotation = self.page_rotations.get(page_index).copied().unwrap_or(0);
+        let rotation = (rotation - page_rotation_degrees(&page)).rem_euclid(360);
+
+        // PDFiumはページを /Rotate 適用後の左上原点・ポイント単位に置いてから行列を掛ける
+        let page_width = page.width().value;
+        let page_height = page.height().value;
+        let s = tile.scale;
+        let (x, y) = (tile.x as f32, tile.y as f32);
+        let (a, b, c, d, e, f) = match rotation {
+            90 => (0.0, s, -s, 0.0, s * page_height - x, -y),
+            180 => (-s, 0.0, 0.0, -s, s * page_width - x, s * page_height - y),
+            270 => (0.0, -s, s, 0.0, -x, s * page_width - y),
+            _ => (s, 0.0, 0.0, s, -x, -y),
+        };
+
+        let render_config = PdfRenderConfig::new()
+            .set_fixed_size(tile.width as i32, tile.height as i32)
+            .apply_matrix(PdfMatrix::new(a, b, c, d, e, f))
+            .ok()?
+            .render_annotations(true);
+
+        let bitmap = page.render_with_config(&render_config).ok()?;
+        let image = to_color_image(&bitmap);
+
+        log::debug!(
+            "ページ {} のタイル ({}, {}) をレンダリング: {}x{} ({:?})",
+            page_index + 1,
+            tile.x,
+            tile.y,
+            tile.width,
+            tile.height,
+            started.elapsed()
+        );
+
+        Some(image)
     }
 
     /// サムネイル用の小さいサイズでレンダリング
@@ -190,40 +614,358 @@ impl PdfDocument {
         self.render_page(page_index, w, h)
     }
 
+    /// ページの文字を位置付きで取り出す（検索・テキスト選択用）
+    ///
+    /// 位置は回転前のページ上の座標（左上原点）で、表示の回転は呼び出し側で反映する。
+    pub fn page_text(&self, page_index: usize) -> Result<PageText> {
+        let document = self.document()?;
+        let page = document
+            .pages()
+            .get(page_index as u16)
+            .context("ページを取得できませんでした")?;
+        let frame = self.page_frame(page_index, &page);
+        let (left, bottom) = frame.origin;
+        let text = page.text().context("テキストを読み取れませんでした")?;
+
+        let chars = text
+            .chars()
+            .iter()
+            .filter_map(|c| {
+                let ch = c.unicode_char()?;
+                let rect = c
+                    .loose_bounds()
+                    .map(|b| TextRect {
+                        x: b.left().value - left,
+                        y: bottom + frame.height - b.top().value,
+                        width: b.width().value,
+                        height: b.height().value,
+                    })
+                    .unwrap_or_default();
+                Some(TextChar { ch, rect })
+            })
+            .collect();
+        Ok(PageText {
+            page: page_index,
+            chars,
+        })
+    }
+
     /// ファイルパスを取得
     pub fn path(&self) -> &Path {
         &self.path
     }
 
-    /// ページ数を更新
-    pub fn refresh_page_count(&mut self) {
-        if let Ok(pdfium) = get_pdfium() {
-            if let Ok(document) = pdfium.load_pdf_from_file(&self.path, None) {
-                self.page_count = document.pages().len() as usize;
+    /// 各ページの回転角度を /Rotate としてドキュメントに書き込む
+    fn apply_rotations(&self, document: &PdfiumDocument) -> Result<()> {
+        for (page_index, &rotation) in self.page_rotations.iter().enumerate() {
+            let mut page = document
+                .pages()
+                .get(page_index as u16)
+                .context("ページを取得できませんでした")?;
+            if page_rotation_degrees(&page) != rotation {
+                page.set_rotation(rotation_setting(rotation));
+            }
+        }
+        Ok(())
+    }
+
+    /// 注釈の配置計算に使うページ情報
+    fn page_frame(&self, page_index: usize, page: &PdfPage) -> PageFrame {
+        let (width, height) = self.original_page_size(page_index);
+        let origin = page
+            .boundaries()
+            .bounding()
+            .map(|b| (b.bounds.left().value, b.bounds.bottom().value))
+            .unwrap_or((0.0, 0.0));
+        PageFrame {
+            width,
+            height,
+            origin,
+            rotation: self.get_page_rotation(page_index),
+        }
+    }
+
+    /// PDF注釈（/Stamp, /FreeText, /Square, /Text, /Highlight, /Underline, /StrikeOut, /Ink）をエディタの注釈として取り出す
+    ///
+    /// 取り出した注釈はメモリ上のドキュメントから削除し、以降はエディタ側で描画・編集する。
+    /// 組み込み以外のスタンプは外観を画像化して返す。
+    pub fn take_annotations(&mut self) -> Result<ImportedAnnotations> {
+        let mut imported = ImportedAnnotations::default();
+        self.detach()?;
+        let document = self.document()?;
+
+        // 全ページを読み取ってから削除する（途中で失敗しても元のドキュメントは変わらない）
+        let mut taken: Vec<(usize, Vec<usize>)> = Vec::new();
+        // 注釈辞書の値は、必要な注釈が見つかったときに1度だけ読み取る
+        let mut details: Option<Vec<Vec<AnnotationDetails>>> = None;
+        for page_index in 0..self.page_count {
+            let page = document
+                .pages()
+                .get(page_index as u16)
+                .context("ページを取得できませんでした")?;
+            if page.annotations().is_empty() {
+                continue;
+            }
+            let frame = self.page_frame(page_index, &page);
+            if details.is_none() && page.annotations().iter().any(|a| needs_details(a.annotation_type())) {
+                details = Some(read_annotation_details(get_pdfium()?, &document)?);
+            }
+            let page_details = details.as_ref().and_then(|d| d.get(page_index));
 
-                self.page_sizes.clear();
-                self.page_rotations.resize(self.page_count, 0);
-                
-                for page in document.pages().iter() {
-                    let width = page.width().value;
-                    let height = page.height().value;
-                    self.page_sizes.push((width, height));
+            let mut indices = Vec::new();
+            let mut custom_stamps: Vec<(usize, Stamp)> = Vec::new();
+            // 取り込んだ /Text 注釈の番号と、それが属するスレッド（コメントの位置）
+            let mut thread_roots: Vec<(usize, usize)> = Vec::new();
+            for (index, annotation) in page.annotations().iter().enumerate() {
+                let annotation_details = page_details.and_then(|d| d.get(index));
+                match read_annotation(&annotation, &frame, page_index, annotation_details) {
+                    Some(NativeAnnotation::Stamp(stamp)) => {
+                        let name = stamp.stamp_type.name();
+                        let known = imported.stamp_images.iter().any(|(n, _)| *n == name)
+                            || custom_stamps.iter().any(|(_, s)| s.stamp_type == stamp.stamp_type);
+                        if matches!(stamp.stamp_type, StampType::Custom(_)) && !known {
+                            custom_stamps.push((index, stamp.clone()));
+                        }
+                        imported.annotations.stamps.push(stamp);
+                    }
+                    Some(NativeAnnotation::Text(text)) => imported.annotations.texts.push(text),
+                    Some(NativeAnnotation::Rect(rect)) => imported.annotations.rects.push(rect),
+                    Some(NativeAnnotation::Markup(markup)) => imported.annotations.markups.push(markup),
+                    Some(NativeAnnotation::Shape(shape)) => imported.annotations.shapes.push(shape),
+                    Some(NativeAnnotation::Comment(comment)) => {
+                        // /IRT のある注釈は返信先のスレッドに加える
+                        let comments = &mut imported.annotations.comments;
+                        let parent = annotation_details.and_then(|d| d.in_reply_to).and_then(|target| {
+                            thread_roots.iter().find(|(i, _)| *i == target).map(|&(_, root)| root)
+                        });
+                        thread_roots.push((index, parent.unwrap_or(comments.len())));
+                        match parent.map(|root| &mut comments[root]) {
+                            Some(parent) => parent.replies.push(CommentReply {
+                                author: comment.author,
+                                created: comment.created,
+                                text: comment.text,
+                            }),
+                            None => comments.push(comment),
+                        }
+                    }
+                    None => continue,
                 }
+                indices.push(index);
+            }
+
+            // 取り込んだ注釈の /Popup も、親が無くなって残らないよう削除する
+            if let Some(page_details) = page_details {
+                let popups: Vec<usize> = page_details
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, d)| d.parent.is_some_and(|parent| indices.contains(&parent)))
+                    .map(|(index, _)| index)
+                    .filter(|index| !indices.contains(index))
+                    .collect();
+                indices.extend(popups);
+                indices.sort_unstable();
+            }
+
+            if !custom_stamps.is_empty() {
+                let images = render_stamp_appearances(&document, page_index, &frame, &custom_stamps)?;
+                for ((_, stamp), image) in custom_stamps.into_iter().zip(images) {
+                    imported.stamp_images.push((stamp.stamp_type.name(), image));
+                }
+            }
+            if !indices.is_empty() {
+                taken.push((page_index, indices));
+            }
+        }
+
+        for (page_index, indices) in &taken {
+            let mut page = document
+                .pages()
+                .get(*page_index as u16)
+                .context("ページを取得できませんでした")?;
+            page.set_content_regeneration_strategy(PdfPageContentRegenerationStrategy::Manual);
+            for &index in indices.iter().rev() {
+                let annotation = page
+                    .annotations()
+                    .get(index as PdfPageAnnotationIndex)
+                    .context("注釈を取得できませんでした")?;
+                page.annotations_mut()
+                    .delete_annotation(annotation)
+                    .context("注釈を削除できませんでした")?;
             }
         }
+        drop(document);
+
+        if !taken.is_empty() {
+            self.loaded.lock().edited = true;
+        }
+        Ok(imported)
+    }
+
+    /// 注釈をドキュメントに書き込む
+    ///
+    /// `mode` に応じてページ内容に書き込む（フラット化）か、PDF注釈として追加する。
+    /// 墨消しの矩形は下のページ内容を削除し、`mode` によらずページ内容として塗りつぶす。
+    /// スタンプ画像は `stamp_image` でスタンプ種別から取得し、見つからないものは書き込まない。
+    /// 日付印・認印は画像を使わず、枠・線・文字として書き込む。
+    /// 重なり順はエディタの表示と同じ（テキストの装飾、手書き・図形、スタンプ、テキスト、矩形の順）。
+    /// コメントはページ内容にできないため、`mode` によらずPDF注釈（/Text）として書き込む。
+    pub fn write_annotations(
+        &mut self,
+        annotations: &AnnotationData,
+        mode: SaveMode,
+        stamp_image: impl Fn(&StampType) -> Option<DynamicImage>,
+    ) -> Result<()> {
+        self.detach()?;
+        let mut document = self.document()?;
+        let mut fonts = Fonts::default();
+        let mut patches = Vec::new();
+
+        for page_index in 0..self.page_count {
+            let stamps: Vec<&Stamp> = annotations.stamps.iter().filter(|s| s.page == page_index).collect();
+            let texts: Vec<&TextAnnotation> = annotations.texts.iter().filter(|t| t.page == page_index).collect();
+            let rects: Vec<&RectAnnotation> = annotations.rects.iter().filter(|r| r.page == page_index).collect();
+            let markups: Vec<&MarkupAnnotation> =
+                annotations.markups.iter().filter(|m| m.page == page_index).collect();
+            let shapes: Vec<&ShapeAnnotation> = annotations.shapes.iter().filter(|s| s.page == page_index).collect();
+            let comments: Vec<&CommentAnnotation> =
+                annotations.comments.iter().filter(|c| c.page == page_index).collect();
+            if stamps.is_empty()
+                && texts.is_empty()
+                && rects.is_empty()
+                && markups.is_empty()
+                && shapes.is_empty()
+                && comments.is_empty()
+            {
+                continue;
+            }
+
+            // ページ取得前にフォントを読み込んでおく（PDF注釈では不要）
+            let text_fonts: Vec<Option<PdfFontToken>> = texts
+                .iter()
+                .map(|t| (mode == SaveMode::Flatten).then(|| fonts.get(&mut document, t.font_type)))
+                .collect();
+            // 印影の文字はPDF注釈の外観にも使う
+            let date_font = stamps
+                .iter()
+                .any(|s| matches!(s.stamp_type, StampType::DateSeal(_)))
+                .then(|| fonts.get(&mut document, FontType::Mincho));
+            let name_font = stamps
+                .iter()
+                .any(|s| matches!(s.stamp_type, StampType::NameSeal(_)))
+                .then(|| fonts.seal(&mut document));
+
+            let mut page = document
+                .pages()
+                .get(page_index as u16)
+                .context("ページを取得できませんでした")?;
+            page.set_content_regeneration_strategy(PdfPageContentRegenerationStrategy::AutomaticOnDrop);
+
+            let frame = self.page_frame(page_index, &page);
+
+            // 墨消しは他の注釈より先に行い、領域内のテキストが消えたことを確認する
+            for rect in rects.iter().filter(|r| r.redact) {
+                let region = frame.bounds(rect.x, rect.y, rect.width, rect.height);
+                let count = redact_region(&document, &mut page, &region, rect.color)?;
+                verify_redaction(&page, &region)?;
+                log::info!("ページ {} を墨消し: {} 個のオブジェクトを削除・加工", page_index + 1, count);
+            }
+
+            for markup in markups {
+                match mode {
+                    SaveMode::Flatten => draw_markup(&mut page, &frame, markup)?,
+                    SaveMode::Annotations => write_markup(&mut page, &frame, markup)?,
+                }
+            }
+            for shape in shapes {
+                match mode {
+                    SaveMode::Flatten => draw_shape(&document, &mut page, &frame, shape)?,
+                    SaveMode::Annotations => patches.extend(write_shape(&document, &mut page, &frame, shape)?),
+                }
+            }
+            for stamp in stamps {
+                let seal_font = match stamp.stamp_type {
+                    StampType::DateSeal(_) => date_font,
+                    StampType::NameSeal(_) => name_font,
+                    _ => None,
+                };
+                if let (Some(layout), Some(font)) = (stamp.stamp_type.seal_layout(stamp.width, stamp.height), seal_font) {
+                    match mode {
+                        SaveMode::Flatten => draw_seal(&document, &mut page, &frame, stamp, &layout, font)?,
+                        SaveMode::Annotations => write_seal(&document, &mut page, &frame, stamp, &layout, font)?,
+                    }
+                    continue;
+                }
+                let Some(image) = stamp_image(&stamp.stamp_type) else {
+                    log::warn!("スタンプ画像が見つかりません: {}", stamp.stamp_type.name());
+                    continue;
+                };
+                match mode {
+                    SaveMode::Flatten => draw_image(
+                        &mut page,
+                        &frame,
+                        (stamp.x, stamp.y, stamp.width, stamp.height),
+                        &image,
+                    )?,
+                    SaveMode::Annotations => write_stamp(&mut page, &frame, stamp, &image)?,
+                }
+            }
+            for (text, font) in texts.into_iter().zip(text_fonts) {
+                match font {
+                    Some(font) => draw_text(&mut page, &frame, text, font)?,
+                    None => write_text(&mut page, &frame, text)?,
+                }
+            }
+            for rect in rects {
+                if rect.redact || mode == SaveMode::Flatten {
+                    draw_rect(&mut page, &frame, rect)?;
+                } else {
+                    write_rect(&mut page, &frame, rect)?;
+                }
+            }
+            for comment in comments {
+                patches.extend(write_comment(&mut page, &frame, comment)?);
+            }
+        }
+        drop(document);
+
+        // PDFium で設定できない注釈の種類や頂点は、増分更新で書き込んでから読み直す
+        if !patches.is_empty() {
+            let bytes = self
+                .document()?
+                .save_to_bytes()
+                .context("PDFを保存できませんでした")?;
+            let bytes = apply_patches(&bytes, &patches)?;
+            let document = get_pdfium()?
+                .load_pdf_from_byte_vec(bytes, None)
+                .context("書き換えた注釈を読み込めませんでした")?;
+            self.loaded.lock().document = document;
+        }
+
+        self.loaded.lock().edited = true;
+        Ok(())
     }
 
-    /// PDFを保存
-    pub fn save(&self, path: &Path) -> Result<()> {
-        if self.path != path {
-            std::fs::copy(&self.path, path).context("PDFを保存できませんでした")?;
+    /// PDFを保存（ページ回転を /Rotate として書き込む）
+    pub fn save(&mut self, path: &Path) -> Result<()> {
+        self.detach()?;
+        let document = self.document()?;
+        self.apply_rotations(&document)?;
+        document
+            .save_to_file(path)
+            .context("PDFを保存できませんでした")?;
+        drop(document);
+
+        // 自身のファイルを上書きした場合は再読み込み不要
+        if self.path == path {
+            let mut loaded = self.loaded.lock();
+            loaded.modified = file_modified(path);
+            loaded.edited = false;
         }
-        // 注: 回転情報は現在ファイルには保存されません
-        // 実際の回転保存にはPDFiumの編集機能が必要です
         Ok(())
     }
 }
 
+/// 読み込み済みドキュメントを共有する複製（どちらかを編集すると切り離される）
 impl Clone for PdfDocument {
     fn clone(&self) -> Self {
         Self {
@@ -231,6 +973,649 @@ impl Clone for PdfDocument {
             page_count: self.page_count,
             page_sizes: self.page_sizes.clone(),
             page_rotations: self.page_rotations.clone(),
+            loaded: Arc::clone(&self.loaded),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdf::{MarkupType, ShapeType};
+
+    #[test]
+    fn move_order_moves_block_forward_and_backward() {
+        // 前方へ: 0,1 を残り 3 ページの末尾へ
+        assert_eq!(move_order(5, 0..2, 3), vec![2, 3, 4, 0, 1]);
+        // 後方へ: 3,4 を先頭へ
+        assert_eq!(move_order(5, 3..5, 0), vec![3, 4, 0, 1, 2]);
+        // 自身の位置へ戻すと順序は変わらない
+        assert_eq!(move_order(5, 1..3, 1), vec![0, 1, 2, 3, 4]);
+        // 移動先が範囲外なら末尾へ
+        assert_eq!(move_order(5, 1..2, 10), vec![0, 2, 3, 4, 1]);
+    }
+
+    /// テスト用の一時ファイルパス
+    fn temp_pdf_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("pdf-viewer-{}-{}.pdf", name, uuid::Uuid::new_v4()))
+    }
+
+    /// 指定ページ数の空白PDFを作成（PDFiumが必要なテストは `#[ignore]` にして `--ignored` で実行する）
+    fn create_blank_pdf(page_count: usize) -> std::path::PathBuf {
+        let pdfium = get_pdfium().expect("PDFiumライブラリが見つかりません");
+        let mut document = pdfium.create_new_pdf().unwrap();
+        for _ in 0..page_count {
+            document
+                .pages_mut()
+                .create_page_at_end(PdfPagePaperSize::a4())
+                .unwrap();
+        }
+        let path = temp_pdf_path("blank");
+        document.save_to_file(&path).unwrap();
+        path
+    }
+
+    #[test]
+    #[ignore = "requires pdfium"]
+    fn save_writes_page_rotations() {
+        let source = create_blank_pdf(4);
+        let output = temp_pdf_path("rotated");
+
+        let mut doc = PdfDocument::open(&source).unwrap();
+        doc.rotate_page(1, 90).unwrap();
+        doc.rotate_page(2, 180).unwrap();
+        doc.rotate_page(3, -90).unwrap();
+        doc.save(&output).unwrap();
+
+        let reopened = PdfDocument::open(&output).unwrap();
+        let rotations: Vec<i32> = (0..reopened.page_count())
+            .map(|i| reopened.get_page_rotation(i))
+            .collect();
+        assert_eq!(rotations, vec![0, 90, 180, 270]);
+
+        // 回転前サイズは保持され、回転後サイズは幅と高さが入れ替わる
+        let (w, h) = reopened.original_page_size(1);
+        assert!(w < h);
+        assert_eq!(reopened.page_size(1), (h, w));
+
+        let _ = std::fs::remove_file(source);
+        let _ = std::fs::remove_file(output);
+    }
+
+    #[test]
+    #[ignore = "requires pdfium"]
+    fn render_tile_follows_page_rotation() {
+        let source = create_blank_pdf(1);
+
+        // 左上に黒い正方形を描いたページ
+        let mut doc = PdfDocument::open(&source).unwrap();
+        {
+            let document = doc.document().unwrap();
+            let mut page = document.pages().get(0).unwrap();
+            let height = page.height().value;
+            page.objects_mut()
+                .create_path_object_rect(
+                    PdfRect::new_from_values(height - 100.0, 0.0, height, 100.0),
+                    None,
+                    None,
+                    Some(PdfColor::BLACK),
+                )
+                .unwrap();
+        }
+
+        let tile = |x| PageTile { scale: 2.0, x, y: 0, width: 64, height: 64 };
+        let is_dark = |x| doc.render_tile(0, &tile(x)).unwrap().pixels[0].r() < 128;
+        let right_edge = |doc: &PdfDocument| (doc.page_size(0).0 * 2.0) as u32 - 64;
+
+        assert!(is_dark(0));
+        assert!(!is_dark(right_edge(&doc)));
+
+        // 90度回転すると左上の正方形は右上に来る
+        doc.rotate_page(0, 90).unwrap();
+        let is_dark = |x| doc.render_tile(0, &tile(x)).unwrap().pixels[0].r() < 128;
+        assert!(is_dark(right_edge(&doc)));
+        assert!(!is_dark(0));
+
+        let _ = std::fs::remove_file(source);
+    }
+
+    #[test]
+    #[ignore = "requires pdfium"]
+    fn insert_pages_restores_deleted_page() {
+        let source = create_blank_pdf(3);
+
+        let mut doc = PdfDocument::open(&source).unwrap();
+        doc.rotate_page(1, 90).unwrap();
+        let removed = PdfDocument::compose(&[(&doc, 1..2)]).unwrap();
+        doc.delete_page(1).unwrap();
+        assert_eq!(doc.page_count(), 2);
+
+        doc.insert_pages(&removed, 1).unwrap();
+        assert_eq!(doc.page_count(), 3);
+        assert_eq!(doc.document().unwrap().pages().len(), 3);
+        let rotations: Vec<i32> = (0..3).map(|i| doc.get_page_rotation(i)).collect();
+        assert_eq!(rotations, vec![0, 90, 0]);
+        assert!(doc.is_edited());
+
+        let _ = std::fs::remove_file(source);
+    }
+
+    #[test]
+    #[ignore = "requires pdfium"]
+    fn clone_is_unaffected_by_edits() {
+        let source = create_blank_pdf(3);
+
+        let mut doc = PdfDocument::open(&source).unwrap();
+        let snapshot = doc.clone();
+        doc.delete_page(1).unwrap();
+        assert_eq!(doc.document().unwrap().pages().len(), 2);
+        assert_eq!(snapshot.document().unwrap().pages().len(), 3);
+        assert!(!snapshot.is_edited());
+
+        let _ = std::fs::remove_file(source);
+    }
+
+    #[test]
+    #[ignore = "requires pdfium"]
+    fn reorder_pages_moves_rotations_with_pages() {
+        let source = create_blank_pdf(3);
+
+        let mut doc = PdfDocument::open(&source).unwrap();
+        doc.rotate_page(0, 90).unwrap();
+        doc.rotate_page(2, 180).unwrap();
+        assert!(doc.reorder_pages(&[0, 0, 1]).is_err());
+
+        doc.reorder_pages(&[2, 0, 1]).unwrap();
+        assert_eq!(doc.document().unwrap().pages().len(), 3);
+        let rotations: Vec<i32> = (0..3).map(|i| doc.get_page_rotation(i)).collect();
+        assert_eq!(rotations, vec![180, 90, 0]);
+        assert!(doc.is_edited());
+
+        let _ = std::fs::remove_file(source);
+    }
+
+    #[test]
+    #[ignore = "requires pdfium"]
+    fn redaction_removes_only_text_inside_region() {
+        let mut document = get_pdfium().unwrap().create_new_pdf().unwrap();
+        let font = document.fonts_mut().helvetica();
+        let mut page = document
+            .pages_mut()
+            .create_page_at_end(PdfPagePaperSize::a4())
+            .unwrap();
+        page.objects_mut()
+            .create_text_object(PdfPoints::new(100.0), PdfPoints::new(700.0), "SECRET PUBLIC", font, PdfPoints::new(24.0))
+            .unwrap();
+
+        // 領域は "SECRET" の右端と "PUBLIC" の左端の間まで
+        let text = page.text().unwrap();
+        let secret_right = text.chars().get(5).unwrap().loose_bounds().unwrap().right().value;
+        let public_left = text.chars().get(7).unwrap().loose_bounds().unwrap().left().value;
+        drop(text);
+        let region = PdfRect::new_from_values(690.0, 90.0, 740.0, (secret_right + public_left) / 2.0);
+        assert!(verify_redaction(&page, &region).is_err());
+
+        redact_region(&document, &mut page, &region, [0, 0, 0, 255]).unwrap();
+        verify_redaction(&page, &region).unwrap();
+        assert_eq!(page.text().unwrap().all().trim(), "PUBLIC");
+    }
+
+    #[test]
+    #[ignore = "requires pdfium"]
+    fn page_text_locates_chars_from_top_left() {
+        let source = create_blank_pdf(1);
+
+        let doc = PdfDocument::open(&source).unwrap();
+        {
+            let mut document = doc.document().unwrap();
+            let font = document.fonts_mut().helvetica();
+            let mut page = document.pages().get(0).unwrap();
+            let height = page.height().value;
+            page.objects_mut()
+                .create_text_object(
+                    PdfPoints::new(100.0),
+                    PdfPoints::new(height - 200.0),
+                    "Invoice 2024",
+                    font,
+                    PdfPoints::new(20.0),
+                )
+                .unwrap();
+        }
+
+        let text = doc.page_text(0).unwrap();
+        let string: String = text.chars.iter().map(|c| c.ch).collect();
+        assert!(string.contains("Invoice 2024"));
+        let hits = text.find("INVOICE");
+        assert_eq!(hits.len(), 1);
+        // ベースラインは上から200pt、文字はその上に載る
+        let rect = hits[0].rects[0];
+        assert!((rect.x - 100.0).abs() < 5.0);
+        assert!(rect.y > 170.0 && rect.y < 200.0);
+
+        let _ = std::fs::remove_file(source);
+    }
+
+    #[test]
+    #[ignore = "requires pdfium"]
+    fn native_annotations_round_trip() {
+        let source = create_blank_pdf(1);
+        let output = temp_pdf_path("annotated");
+
+        let doc = PdfDocument::open(&source).unwrap();
+        let mut annotations = AnnotationData::default();
+        annotations.stamps.push(Stamp::new(0, 100.0, 100.0, StampType::Approved));
+        annotations.texts.push(TextAnnotation::new(
+            0,
+            100.0,
+            300.0,
+            "確認済み".to_string(),
+            12.0,
+            crate::pdf::FontType::Gothic,
+            false,
+        ));
+        annotations.rects.push(RectAnnotation {
+            page: 0,
+            x: 50.0,
+            y: 400.0,
+            width: 200.0,
+            height: 30.0,
+            color: [0, 0, 0, 255],
+            redact: false,
+        });
+
+        let mut exported = PdfDocument::compose(&[(&doc, 0..1)]).unwrap();
+        exported
+            .write_annotations(&annotations, SaveMode::Annotations, |_| {
+                Some(DynamicImage::new_rgba8(4, 4))
+            })
+            .unwrap();
+        exported.save(&output).unwrap();
+
+        let reopened = PdfDocument::open(&output).unwrap();
+        let (_, page_height) = reopened.original_page_size(0);
+        let document = reopened.document().unwrap();
+        let page = document.pages().get(0).unwrap();
+
+        // ページ内容には書き込まれず、注釈として残る
+        assert_eq!(page.objects().len(), 0);
+        let types: Vec<PdfPageAnnotationType> =
+            page.annotations().iter().map(|a| a.annotation_type()).collect();
+        assert_eq!(
+            types,
+            vec![
+                PdfPageAnnotationType::Stamp,
+                PdfPageAnnotationType::FreeText,
+                PdfPageAnnotationType::Square,
+            ]
+        );
+
+        let close = |a: PdfPoints, b: f32| (a.value - b).abs() < 0.01;
+        for annotation in page.annotations().iter() {
+            let bounds = annotation.bounds().unwrap();
+            match annotation.annotation_type() {
+                PdfPageAnnotationType::Stamp => {
+                    assert!(close(bounds.left(), 100.0));
+                    assert!(close(bounds.top(), page_height - 100.0));
+                    assert!(close(bounds.width(), 100.0));
+                    assert!(!annotation.objects().is_empty());
+                }
+                PdfPageAnnotationType::FreeText => {
+                    assert_eq!(annotation.contents().as_deref(), Some("確認済み"));
+                    assert!(close(bounds.left(), 100.0));
+                    assert!(close(bounds.top(), page_height - 300.0));
+                }
+                _ => {
+                    assert!(close(bounds.left(), 50.0));
+                    assert!(close(bounds.right(), 250.0));
+                    assert!(close(bounds.bottom(), page_height - 430.0));
+                }
+            }
+        }
+        drop(page);
+        drop(document);
+
+        let _ = std::fs::remove_file(source);
+        let _ = std::fs::remove_file(output);
+    }
+
+    #[test]
+    #[ignore = "requires pdfium"]
+    fn take_annotations_imports_native_annotations() {
+        let source = create_blank_pdf(1);
+        let output = temp_pdf_path("imported");
+
+        let mut doc = PdfDocument::open(&source).unwrap();
+        doc.rotate_page(0, 90).unwrap();
+        let mut annotations = AnnotationData::default();
+        annotations.stamps.push(Stamp::new(0, 100.0, 120.0, StampType::Rejected));
+        annotations.texts.push(TextAnnotation::new(
+            0,
+            80.0,
+            300.0,
+            "一行目\n二行目".to_string(),
+            18.0,
+            crate::pdf::FontType::Gothic,
+            true,
+        ));
+        annotations.rects.push(RectAnnotation {
+            page: 0,
+            x: 50.0,
+            y: 400.0,
+            width: 200.0,
+            height: 30.0,
+            color: [0, 0, 0, 255],
+            redact: false,
+        });
+
+        let mut exported = PdfDocument::compose(&[(&doc, 0..1)]).unwrap();
+        exported
+            .write_annotations(&annotations, SaveMode::Annotations, |_| {
+                Some(DynamicImage::new_rgba8(4, 4))
+            })
+            .unwrap();
+        exported.save(&output).unwrap();
+
+        let mut reopened = PdfDocument::open(&output).unwrap();
+        let imported = reopened.take_annotations().unwrap();
+        assert!(imported.stamp_images.is_empty());
+
+        let close = |a: f32, b: f32| (a - b).abs() < 0.01;
+        let stamp = &imported.annotations.stamps[0];
+        assert_eq!(stamp.stamp_type, StampType::Rejected);
+        assert!(close(stamp.x, 100.0) && close(stamp.y, 120.0));
+        assert!(close(stamp.width, 100.0) && close(stamp.height, 50.0));
+
+        let text = &imported.annotations.texts[0];
+        assert_eq!(text.text, "一行目\n二行目");
+        assert!(close(text.font_size, 18.0));
+        assert!(text.transparent);
+
+        let rect = &imported.annotations.rects[0];
+        assert!(close(rect.x, 50.0) && close(rect.y, 400.0));
+        assert!(close(rect.width, 200.0) && close(rect.height, 30.0));
+
+        // 取り出した注釈はドキュメントから削除される
+        assert!(reopened.is_edited());
+        let document = reopened.document().unwrap();
+        assert!(document.pages().get(0).unwrap().annotations().is_empty());
+        drop(document);
+
+        let _ = std::fs::remove_file(source);
+        let _ = std::fs::remove_file(output);
+    }
+
+    /// 注釈辞書を直接書いた1ページのPDFを作成（相互参照表はPDFiumが読み込み時に再構築する）
+    fn create_pdf_with_annotations(annotations: &[&str]) -> std::path::PathBuf {
+        let refs: Vec<String> = (0..annotations.len()).map(|i| format!("{} 0 R", i + 4)).collect();
+        let mut content = format!(
+            "%PDF-1.7\n1 0 obj << /Type /Catalog /Pages 2 0 R >> endobj\n\
+             2 0 obj << /Type /Pages /Kids [3 0 R] /Count 1 >> endobj\n\
+             3 0 obj << /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] /Annots [{}] >> endobj\n",
+            refs.join(" ")
+        );
+        for (i, annotation) in annotations.iter().enumerate() {
+            content += &format!("{} 0 obj << /Type /Annot {} >> endobj\n", i + 4, annotation);
+        }
+        content += "trailer << /Root 1 0 R >>\n%%EOF\n";
+        let path = temp_pdf_path("annotations");
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    #[ignore = "requires pdfium"]
+    fn take_annotations_imports_line_circle_and_polygon() {
+        let source = create_pdf_with_annotations(&[
+            "/Subtype /Line /Rect [10 10 110 110] /L [20 180 120 80] /C [1 0 0] /Border [0 0 3]",
+            "/Subtype /Circle /Rect [50 50 150 100] /C [0 0 1]",
+            "/Subtype /Polygon /Rect [0 0 60 60] /Vertices [10 190 50 190 30 150] /C [0 1 0]",
+        ]);
+
+        let mut doc = PdfDocument::open(&source).unwrap();
+        let shapes = doc.take_annotations().unwrap().annotations.shapes;
+        assert_eq!(shapes.len(), 3);
+
+        // 座標は左上原点に変換される
+        assert_eq!(shapes[0].shape_type, ShapeType::Line);
+        assert_eq!(shapes[0].points, vec![(20.0, 20.0), (120.0, 120.0)]);
+        assert_eq!(shapes[0].color, [255, 0, 0, 255]);
+        assert_eq!(shapes[0].width, 3.0);
+
+        assert_eq!(shapes[1].shape_type, ShapeType::Ellipse);
+        assert_eq!(shapes[1].points, vec![(50.0, 100.0), (150.0, 150.0)]);
+
+        assert_eq!(shapes[2].shape_type, ShapeType::Polygon);
+        assert_eq!(shapes[2].points, vec![(10.0, 10.0), (50.0, 10.0), (30.0, 50.0)]);
+        assert_eq!(doc.document().unwrap().pages().get(0).unwrap().annotations().len(), 0);
+
+        let _ = std::fs::remove_file(source);
+    }
+
+    #[test]
+    #[ignore = "requires pdfium"]
+    fn take_annotations_threads_replies_by_irt() {
+        // 返信は親と離れた位置にあり、/IRT でだけ結び付いている
+        let source = create_pdf_with_annotations(&[
+            "/Subtype /Text /Rect [10 170 30 190] /Contents (Question) /T (A) /Popup 5 0 R",
+            "/Subtype /Popup /Rect [40 120 160 190] /Parent 4 0 R",
+            "/Subtype /Text /Rect [150 20 170 40] /Contents (Answer) /T (B) /IRT 4 0 R /Popup 7 0 R",
+            "/Subtype /Popup /Rect [40 10 160 80] /Parent 6 0 R",
+            "/Subtype /Text /Rect [150 150 170 170] /Contents (Note) /T (C) /IRT 6 0 R",
+        ]);
+
+        let mut doc = PdfDocument::open(&source).unwrap();
+        let comments = doc.take_annotations().unwrap().annotations.comments;
+        assert_eq!(comments.len(), 1);
+        assert_eq!(comments[0].text, "Question");
+
+        // 返信への返信も同じスレッドにまとまる
+        let replies: Vec<_> = comments[0]
+            .replies
+            .iter()
+            .map(|r| (r.author.as_str(), r.text.as_str()))
+            .collect();
+        assert_eq!(replies, vec![("B", "Answer"), ("C", "Note")]);
+
+        // /Popup も残らない
+        assert_eq!(doc.document().unwrap().pages().get(0).unwrap().annotations().len(), 0);
+
+        let _ = std::fs::remove_file(source);
+    }
+
+    #[test]
+    #[ignore = "requires pdfium"]
+    fn markup_annotations_round_trip() {
+        let source = create_blank_pdf(1);
+        let output = temp_pdf_path("markup");
+
+        let doc = PdfDocument::open(&source).unwrap();
+        let mut annotations = AnnotationData::default();
+        let quads = vec![
+            TextRect { x: 72.0, y: 100.0, width: 300.0, height: 12.0 },
+            TextRect { x: 72.0, y: 114.0, width: 120.0, height: 12.0 },
+        ];
+        for markup_type in [MarkupType::Highlight, MarkupType::Underline, MarkupType::StrikeOut] {
+            annotations.markups.push(MarkupAnnotation {
+                page: 0,
+                markup_type,
+                quads: quads.clone(),
+                color: markup_type.default_color(),
+                text: "重要な箇所".to_string(),
+            });
+        }
+
+        let mut exported = PdfDocument::compose(&[(&doc, 0..1)]).unwrap();
+        exported
+            .write_annotations(&annotations, SaveMode::Annotations, |_| None)
+            .unwrap();
+        exported.save(&output).unwrap();
+
+        let mut reopened = PdfDocument::open(&output).unwrap();
+        let imported = reopened.take_annotations().unwrap();
+        let markups = &imported.annotations.markups;
+        assert_eq!(markups.len(), 3);
+
+        let close = |a: f32, b: f32| (a - b).abs() < 0.01;
+        for (markup, expected) in markups.iter().zip(&annotations.markups) {
+            assert_eq!(markup.markup_type, expected.markup_type);
+            assert_eq!(markup.color, expected.color);
+            assert_eq!(markup.text, "重要な箇所");
+            // 行ごとの範囲が保たれる
+            assert_eq!(markup.quads.len(), 2);
+            for (quad, rect) in markup.quads.iter().zip(&quads) {
+                assert!(close(quad.x, rect.x) && close(quad.y, rect.y));
+                assert!(close(quad.width, rect.width) && close(quad.height, rect.height));
+            }
+        }
+
+        let _ = std::fs::remove_file(source);
+        let _ = std::fs::remove_file(output);
+    }
+
+    #[test]
+    #[ignore = "requires pdfium"]
+    fn shape_annotations_round_trip() {
+        let source = create_blank_pdf(1);
+        let output = temp_pdf_path("shapes");
+
+        let doc = PdfDocument::open(&source).unwrap();
+        let mut annotations = AnnotationData::default();
+        let shapes = [
+            (ShapeType::Ink, vec![(50.0, 50.0), (60.0, 70.0), (80.0, 65.0)]),
+            (ShapeType::Line, vec![(100.0, 100.0), (200.0, 150.0)]),
+            (ShapeType::Arrow, vec![(200.0, 300.0), (100.0, 250.0)]),
+            (ShapeType::Ellipse, vec![(300.0, 300.0), (400.0, 350.0)]),
+            (ShapeType::Polygon, vec![(100.0, 500.0), (200.0, 500.0), (150.0, 580.0)]),
+        ];
+        for (shape_type, points) in shapes.clone() {
+            annotations.shapes.push(ShapeAnnotation {
+                page: 0,
+                shape_type,
+                points,
+                color: [0, 0, 255, 128],
+                width: 3.0,
+            });
+        }
+
+        let mut exported = PdfDocument::compose(&[(&doc, 0..1)]).unwrap();
+        exported
+            .write_annotations(&annotations, SaveMode::Annotations, |_| None)
+            .unwrap();
+        exported.save(&output).unwrap();
+
+        let mut reopened = PdfDocument::open(&output).unwrap();
+        // 他のアプリでも編集できるよう、図形ごとの種類の注釈になる
+        let subtypes: Vec<PdfPageAnnotationType> = reopened
+            .document()
+            .unwrap()
+            .pages()
+            .get(0)
+            .unwrap()
+            .annotations()
+            .iter()
+            .map(|annotation| annotation.annotation_type())
+            .collect();
+        assert_eq!(
+            subtypes,
+            vec![
+                PdfPageAnnotationType::Ink,
+                PdfPageAnnotationType::Line,
+                PdfPageAnnotationType::Line,
+                PdfPageAnnotationType::Circle,
+                PdfPageAnnotationType::Polygon,
+            ]
+        );
+
+        let imported = reopened.take_annotations().unwrap();
+        let imported = &imported.annotations.shapes;
+        assert_eq!(imported.len(), shapes.len());
+
+        let close = |a: (f32, f32), b: (f32, f32)| (a.0 - b.0).abs() < 0.01 && (a.1 - b.1).abs() < 0.01;
+        for (shape, (shape_type, points)) in imported.iter().zip(&shapes) {
+            assert_eq!(shape.shape_type, *shape_type);
+            assert_eq!(shape.color, [0, 0, 255, 128]);
+            assert!((shape.width - 3.0).abs() < 0.01);
+            assert_eq!(shape.points.len(), points.len(), "{:?}", shape_type);
+            // 楕円は外接矩形の左上・右下として戻る
+            for (&actual, &expected) in shape.points.iter().zip(points) {
+                assert!(close(actual, expected), "{:?}: {:?} != {:?}", shape_type, actual, expected);
+            }
         }
+
+        let _ = std::fs::remove_file(source);
+        let _ = std::fs::remove_file(output);
+    }
+
+    #[test]
+    #[ignore = "requires pdfium"]
+    fn comment_threads_round_trip() {
+        let source = create_blank_pdf(1);
+        let output = temp_pdf_path("comments");
+
+        let doc = PdfDocument::open(&source).unwrap();
+        let mut annotations = AnnotationData::default();
+        let mut comment = CommentAnnotation::new(0, 100.0, 200.0, "山田".to_string(), "数値を確認してください".to_string());
+        comment.created = "2026-10-17T09:30:00+09:00".to_string();
+        comment.replies.push(CommentReply {
+            author: "佐藤".to_string(),
+            created: "2026-10-17T10:00:00+09:00".to_string(),
+            text: "修正しました".to_string(),
+        });
+        annotations.comments.push(comment);
+        // 同じ位置の別のコメントは返信にならない
+        annotations.comments.push(CommentAnnotation::new(0, 100.0, 200.0, "佐藤".to_string(), "別件".to_string()));
+
+        // フラット化でもコメントはPDF注釈として残る
+        let mut exported = PdfDocument::compose(&[(&doc, 0..1)]).unwrap();
+        exported
+            .write_annotations(&annotations, SaveMode::Flatten, |_| None)
+            .unwrap();
+        exported.save(&output).unwrap();
+
+        let mut reopened = PdfDocument::open(&output).unwrap();
+        let subtypes: Vec<PdfPageAnnotationType> = reopened
+            .document()
+            .unwrap()
+            .pages()
+            .get(0)
+            .unwrap()
+            .annotations()
+            .iter()
+            .map(|annotation| annotation.annotation_type())
+            .collect();
+        assert_eq!(
+            subtypes,
+            vec![
+                PdfPageAnnotationType::Text,
+                PdfPageAnnotationType::Text,
+                PdfPageAnnotationType::Popup,
+                PdfPageAnnotationType::Text,
+                PdfPageAnnotationType::Popup,
+            ]
+        );
+
+        let imported = reopened.take_annotations().unwrap();
+        let comments = &imported.annotations.comments;
+        assert_eq!(comments.len(), 2);
+
+        let thread = &comments[0];
+        assert_eq!((thread.author.as_str(), thread.text.as_str()), ("山田", "数値を確認してください"));
+        assert!((thread.x - 100.0).abs() < 0.01 && (thread.y - 200.0).abs() < 0.01);
+        assert_eq!(
+            chrono::DateTime::parse_from_rfc3339(&thread.created).unwrap(),
+            chrono::DateTime::parse_from_rfc3339("2026-10-17T09:30:00+09:00").unwrap()
+        );
+        // /IRT で親を指す注釈は返信として戻る
+        assert_eq!(thread.replies.len(), 1);
+        assert_eq!(thread.replies[0].author, "佐藤");
+        assert_eq!(thread.replies[0].text, "修正しました");
+
+        assert_eq!(comments[1].text, "別件");
+        assert!(comments[1].replies.is_empty());
+        // ポップアップも取り込んだコメントと一緒に消える
+        assert_eq!(reopened.document().unwrap().pages().get(0).unwrap().annotations().len(), 0);
+
+        let _ = std::fs::remove_file(source);
+        let _ = std::fs::remove_file(output);
     }
 }
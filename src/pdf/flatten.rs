@@ -0,0 +1,496 @@
+//! 注釈のページ内容への書き込み（フラット化）
+//!
+//! 注釈の座標は回転前のページ上で左上原点・下向きY軸のポイント単位。
+//! エディタは回転後の表示上で注釈を正立させて描くため、書き込み時も
+//! ページの回転を打ち消す向きに配置する。
+
+use crate::pdf::seal::em_width;
+use crate::pdf::{
+    FontType, MarkupAnnotation, MarkupType, RectAnnotation, SealLayout, ShapeAnnotation, Stamp,
+    TextAnnotation, TextRect,
+};
+use anyhow::{Context, Result};
+use image::DynamicImage;
+use once_cell::sync::Lazy;
+use pdfium_render::prelude::PdfDocument as PdfiumDocument;
+use pdfium_render::prelude::*;
+
+/// 行の高さ（フォントサイズ比、エディタ表示と同じ）
+const LINE_HEIGHT: f32 = 1.2;
+/// 行の上端からベースラインまで（フォントサイズ比）
+const BASELINE: f32 = 0.88;
+/// 1文字あたりの幅（フォントサイズ比、エディタ表示と同じ）
+const CHAR_WIDTH: f32 = 0.6;
+/// 背景の余白（エディタ表示と同じ）
+const BACKGROUND_MARGIN: f32 = 4.0;
+/// 印影の文字の中心からベースラインまで（フォントサイズ比）
+const SEAL_BASELINE: f32 = 0.36;
+
+/// 明朝体として使うシステムフォント（見つからなければ埋め込みフォント）
+const MINCHO_FONT_PATHS: &[&str] = &[
+    "C:\\Windows\\Fonts\\yumin.ttf",
+    "/usr/share/fonts/opentype/ipaexfont-mincho/ipaexm.ttf",
+    "/usr/share/fonts/truetype/ipaexfont-mincho/ipaexm.ttf",
+    "/usr/share/fonts/truetype/fonts-japanese-mincho.ttf",
+];
+
+/// 認印の文字に使う楷書体のシステムフォント（見つからなければゴシック体）
+const SEAL_FONT_PATHS: &[&str] = &[
+    "C:\\Windows\\Fonts\\HGRSKP.TTF",
+];
+
+/// 読み込んだ認印のフォント（エディタ表示とPDFへの書き込みで共有する）
+static SEAL_FONT: Lazy<Option<Vec<u8>>> =
+    Lazy::new(|| SEAL_FONT_PATHS.iter().find_map(|path| std::fs::read(path).ok()));
+
+/// 認印の文字に使う楷書体のフォント（見つからなければ None で、ゴシック体で代用する）
+pub fn seal_font() -> Option<&'static [u8]> {
+    SEAL_FONT.as_deref()
+}
+
+/// 書き込み先ページの配置情報
+pub struct PageFrame {
+    /// 回転前の幅
+    pub width: f32,
+    /// 回転前の高さ
+    pub height: f32,
+    /// ページ左下のPDF座標
+    pub origin: (f32, f32),
+    /// 表示時の回転角度（0, 90, 180, 270）
+    pub rotation: i32,
+}
+
+impl PageFrame {
+    /// 注釈の矩形の、エディタ表示上（回転後・左上原点）の左上座標
+    pub fn display_origin(&self, x: f32, y: f32, width: f32, height: f32) -> (f32, f32) {
+        let (w, h) = (self.width, self.height);
+        match self.rotation {
+            90 => (h - y - height, x),
+            180 => (w - x - width, h - y - height),
+            270 => (y, w - x - width),
+            _ => (x, y),
+        }
+    }
+
+    /// 注釈の矩形内のローカル座標（左下原点・上向きY軸）をページ座標へ写す変換行列
+    pub fn matrix(&self, x: f32, y: f32, width: f32, height: f32) -> PdfMatrix {
+        let (w, h) = (self.width, self.height);
+        let (ox, oy) = self.origin;
+        let (u, v) = self.display_origin(x, y, width, height);
+        let (a, b, c, d, e, f) = match self.rotation {
+            90 => (0.0, 1.0, -1.0, 0.0, v + height, u),
+            180 => (-1.0, 0.0, 0.0, -1.0, w - u, v + height),
+            270 => (0.0, -1.0, 1.0, 0.0, w - v - height, h - u),
+            _ => (1.0, 0.0, 0.0, 1.0, u, h - v - height),
+        };
+        PdfMatrix::new(a, b, c, d, e + ox, f + oy)
+    }
+
+    /// 注釈の矩形がページ上で占める範囲（回転後の外接矩形）
+    pub fn bounds(&self, x: f32, y: f32, width: f32, height: f32) -> PdfRect {
+        PdfRect::new_from_values(0.0, 0.0, height, width).transform(self.matrix(x, y, width, height))
+    }
+
+    /// 回転前のページ上の矩形（テキストの位置など）のページ上の範囲（回転の影響を受けない）
+    pub fn page_rect(&self, rect: &TextRect) -> PdfRect {
+        let (ox, oy) = self.origin;
+        PdfRect::new_from_values(
+            oy + self.height - rect.y - rect.height,
+            ox + rect.x,
+            oy + self.height - rect.y,
+            ox + rect.x + rect.width,
+        )
+    }
+
+    /// ページ上の範囲から回転前のページ上の矩形を求める（`page_rect` の逆変換）
+    pub fn text_rect(&self, bounds: &PdfRect) -> TextRect {
+        let (ox, oy) = self.origin;
+        TextRect {
+            x: bounds.left().value - ox,
+            y: oy + self.height - bounds.top().value,
+            width: bounds.width().value,
+            height: bounds.height().value,
+        }
+    }
+
+    /// 回転前のページ上の点のPDF座標（回転の影響を受けない）
+    pub fn page_point(&self, (x, y): (f32, f32)) -> (PdfPoints, PdfPoints) {
+        let (ox, oy) = self.origin;
+        (PdfPoints::new(ox + x), PdfPoints::new(oy + self.height - y))
+    }
+
+    /// PDF座標から回転前のページ上の点を求める（`page_point` の逆変換）
+    pub fn text_point(&self, x: PdfPoints, y: PdfPoints) -> (f32, f32) {
+        let (ox, oy) = self.origin;
+        (x.value - ox, oy + self.height - y.value)
+    }
+
+    /// ページ上の範囲から注釈の矩形 (x, y, 幅, 高さ) を求める（`bounds` の逆変換）
+    pub fn locate(&self, bounds: &PdfRect) -> (f32, f32, f32, f32) {
+        let (width, height) = if self.rotation == 90 || self.rotation == 270 {
+            (bounds.height().value, bounds.width().value)
+        } else {
+            (bounds.width().value, bounds.height().value)
+        };
+        // 注釈の位置を動かすと、ページ上の範囲は回転によらず (x, -y) だけ平行移動する
+        let base = self.bounds(0.0, 0.0, width, height);
+        (
+            bounds.left().value - base.left().value,
+            base.top().value - bounds.top().value,
+            width,
+            height,
+        )
+    }
+}
+
+/// テキスト注釈の大きさ（エディタ表示と同じ見積もり）
+pub fn text_size(annotation: &TextAnnotation) -> (f32, f32) {
+    let lines: Vec<&str> = annotation.text.lines().collect();
+    let max_line_len = lines.iter().map(|l| l.chars().count()).max().unwrap_or(1);
+    let line_count = lines.len().max(1);
+    (
+        max_line_len as f32 * annotation.font_size * CHAR_WIDTH,
+        annotation.font_size * line_count as f32 * LINE_HEIGHT,
+    )
+}
+
+/// 書き込みに使うフォント（ドキュメントごとに必要な分だけ読み込む）
+#[derive(Default)]
+pub struct Fonts {
+    gothic: Option<PdfFontToken>,
+    mincho: Option<PdfFontToken>,
+    seal: Option<PdfFontToken>,
+}
+
+impl Fonts {
+    /// フォントタイプに対応するフォントを取得
+    pub fn get(&mut self, document: &mut PdfiumDocument, font_type: FontType) -> PdfFontToken {
+        let slot = match font_type {
+            FontType::Gothic => &mut self.gothic,
+            FontType::Mincho => &mut self.mincho,
+        };
+        *slot.get_or_insert_with(|| load_font(document, font_type))
+    }
+
+    /// 認印の文字に使うフォントを取得（楷書体が無ければゴシック体）
+    pub fn seal(&mut self, document: &mut PdfiumDocument) -> PdfFontToken {
+        if let Some(token) = self.seal {
+            return token;
+        }
+        let loaded = seal_font().map(|bytes| document.fonts_mut().load_true_type_from_bytes(bytes, true));
+        let token = match loaded {
+            Some(Ok(token)) => token,
+            Some(Err(e)) => {
+                log::warn!("楷書体を読み込めないためゴシック体を使用: {}", e);
+                self.get(document, FontType::Gothic)
+            }
+            None => self.get(document, FontType::Gothic),
+        };
+        self.seal = Some(token);
+        token
+    }
+}
+
+/// フォントを読み込む（日本語フォントが使えない場合は Helvetica）
+fn load_font(document: &mut PdfiumDocument, font_type: FontType) -> PdfFontToken {
+    if font_type == FontType::Mincho {
+        if let Some(token) = load_system_font(document, MINCHO_FONT_PATHS) {
+            return token;
+        }
+    }
+
+    match document
+        .fonts_mut()
+        .load_true_type_from_bytes(crate::JAPANESE_FONT, true)
+    {
+        Ok(token) => token,
+        Err(e) => {
+            log::warn!("日本語フォントを読み込めないため Helvetica を使用: {}", e);
+            document.fonts_mut().helvetica()
+        }
+    }
+}
+
+/// 候補のうち最初に読み込めたシステムフォント
+fn load_system_font(document: &mut PdfiumDocument, paths: &[&str]) -> Option<PdfFontToken> {
+    paths.iter().find_map(|path| {
+        let bytes = std::fs::read(path).ok()?;
+        document.fonts_mut().load_true_type_from_bytes(&bytes, true).ok()
+    })
+}
+
+/// 画像（スタンプ）を書き込む
+pub fn draw_image(
+    page: &mut PdfPage,
+    frame: &PageFrame,
+    (x, y, width, height): (f32, f32, f32, f32),
+    image: &DynamicImage,
+) -> Result<()> {
+    let mut object = page
+        .objects_mut()
+        .create_image_object(
+            PdfPoints::ZERO,
+            PdfPoints::ZERO,
+            image,
+            Some(PdfPoints::new(width)),
+            Some(PdfPoints::new(height)),
+        )
+        .context("スタンプ画像を追加できませんでした")?;
+    object.apply_matrix(frame.matrix(x, y, width, height))?;
+    Ok(())
+}
+
+/// テキスト注釈を書き込む
+pub fn draw_text(
+    page: &mut PdfPage,
+    frame: &PageFrame,
+    annotation: &TextAnnotation,
+    font: PdfFontToken,
+) -> Result<()> {
+    let (width, height) = text_size(annotation);
+    let matrix = frame.matrix(annotation.x, annotation.y, width, height);
+
+    if !annotation.transparent {
+        let background = PdfRect::new_from_values(
+            -BACKGROUND_MARGIN,
+            -BACKGROUND_MARGIN,
+            height + BACKGROUND_MARGIN,
+            width + BACKGROUND_MARGIN,
+        );
+        let mut object = page
+            .objects_mut()
+            .create_path_object_rect(
+                background,
+                Some(PdfColor::new(180, 180, 180, 255)),
+                Some(PdfPoints::new(1.0)),
+                Some(PdfColor::WHITE),
+            )
+            .context("テキスト背景を追加できませんでした")?;
+        object.apply_matrix(matrix)?;
+    }
+
+    let font_size = annotation.font_size;
+    for (i, line) in annotation.text.lines().enumerate() {
+        if line.is_empty() {
+            continue;
+        }
+        let baseline = height - (i as f32 * LINE_HEIGHT + BASELINE) * font_size;
+        let mut object = page
+            .objects_mut()
+            .create_text_object(
+                PdfPoints::ZERO,
+                PdfPoints::new(baseline),
+                line,
+                font,
+                PdfPoints::new(font_size),
+            )
+            .context("テキストを追加できませんでした")?;
+        object.apply_matrix(matrix)?;
+    }
+    Ok(())
+}
+
+/// 矩形を塗りつぶしとして書き込む
+pub fn draw_rect(page: &mut PdfPage, frame: &PageFrame, rect: &RectAnnotation) -> Result<()> {
+    let [r, g, b, a] = rect.color;
+    let mut object = page
+        .objects_mut()
+        .create_path_object_rect(
+            PdfRect::new_from_values(0.0, 0.0, rect.height, rect.width),
+            None,
+            None,
+            Some(PdfColor::new(r, g, b, a)),
+        )
+        .context("矩形を追加できませんでした")?;
+    object.apply_matrix(frame.matrix(rect.x, rect.y, rect.width, rect.height))?;
+    Ok(())
+}
+
+/// テキストの装飾を書き込む（ハイライトは乗算で重ね、下の文字を隠さない）
+pub fn draw_markup(page: &mut PdfPage, frame: &PageFrame, markup: &MarkupAnnotation) -> Result<()> {
+    let [r, g, b, _] = markup.color;
+    for rect in markup.mark_rects() {
+        let mut object = page
+            .objects_mut()
+            .create_path_object_rect(frame.page_rect(&rect), None, None, Some(PdfColor::new(r, g, b, 255)))
+            .context("テキストの装飾を追加できませんでした")?;
+        if markup.markup_type == MarkupType::Highlight {
+            object.set_blend_mode(PdfPageObjectBlendMode::Multiply)?;
+        }
+    }
+    Ok(())
+}
+
+/// 手書き・図形の線をパスオブジェクトとして作る（ページや注釈の外観に追加する）
+pub fn shape_paths<'a>(
+    document: &PdfiumDocument<'a>,
+    frame: &PageFrame,
+    shape: &ShapeAnnotation,
+) -> Result<Vec<PdfPagePathObject<'a>>> {
+    let [r, g, b, a] = shape.color;
+    let color = PdfColor::new(r, g, b, a);
+    let mut paths = Vec::new();
+    for line in shape.outline() {
+        let Some(&start) = line.first() else {
+            continue;
+        };
+        let (x, y) = frame.page_point(start);
+        let mut path = PdfPagePathObject::new(
+            document,
+            x,
+            y,
+            Some(color),
+            Some(PdfPoints::new(shape.width)),
+            None,
+        )
+        .context("図形を追加できませんでした")?;
+        // 1点だけの手書き（クリック）も点として見えるよう、同じ位置への線を引く
+        for &point in line.iter().skip(1).chain((line.len() == 1).then_some(&start)) {
+            let (x, y) = frame.page_point(point);
+            path.line_to(x, y)?;
+        }
+        path.set_line_cap(PdfPageObjectLineCap::Round)?;
+        path.set_line_join(PdfPageObjectLineJoin::Round)?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+/// 手書き・図形を書き込む
+pub fn draw_shape<'a>(
+    document: &PdfiumDocument<'a>,
+    page: &mut PdfPage<'a>,
+    frame: &PageFrame,
+    shape: &ShapeAnnotation,
+) -> Result<()> {
+    for path in shape_paths(document, frame, shape)? {
+        page.objects_mut()
+            .add_path_object(path)
+            .context("図形を追加できませんでした")?;
+    }
+    Ok(())
+}
+
+/// 印影の枠・線・文字をオブジェクトとして作る（ページや注釈の外観に追加する）
+pub fn seal_objects<'a>(
+    document: &PdfiumDocument<'a>,
+    frame: &PageFrame,
+    stamp: &Stamp,
+    layout: &SealLayout,
+    font: PdfFontToken,
+) -> Result<Vec<PdfPageObject<'a>>> {
+    let matrix = frame.matrix(stamp.x, stamp.y, stamp.width, stamp.height);
+    let [r, g, b] = layout.color;
+    let color = PdfColor::new(r, g, b, 255);
+    let stroke = PdfPoints::new(layout.stroke);
+    // 配置は下向きY軸なので、矩形内のローカル座標（上向きY軸）に直す
+    let local = |(x, y): (f32, f32)| (PdfPoints::new(x), PdfPoints::new(stamp.height - y));
+
+    let mut objects = Vec::new();
+    let (cx, cy) = local(layout.center);
+    let radius = PdfPoints::new(layout.radius);
+    let mut border = if layout.square {
+        let rect = PdfRect::new(cy - radius, cx - radius, cy + radius, cx + radius);
+        PdfPagePathObject::new_rect(document, rect, Some(color), Some(stroke), None)
+    } else {
+        PdfPagePathObject::new_circle_at(document, cx, cy, radius, Some(color), Some(stroke), None)
+    }
+    .context("印影の枠を追加できませんでした")?;
+    border.apply_matrix(matrix)?;
+    objects.push(PdfPageObject::Path(border));
+
+    for &(start, end) in &layout.lines {
+        let (x1, y1) = local(start);
+        let (x2, y2) = local(end);
+        let mut line = PdfPagePathObject::new_line(document, x1, y1, x2, y2, color, stroke)
+            .context("印影の線を追加できませんでした")?;
+        line.apply_matrix(matrix)?;
+        objects.push(PdfPageObject::Path(line));
+    }
+
+    for text in &layout.texts {
+        let mut object = PdfPageTextObject::new(document, &text.text, font, PdfPoints::new(text.font_size))
+            .context("印影の文字を追加できませんでした")?;
+        object.set_fill_color(color)?;
+        // 実際の幅で中央に揃える（測れなければ見積もりの幅）
+        let width = object
+            .width()
+            .ok()
+            .map(|w| w.value)
+            .filter(|&w| w > 0.0)
+            .unwrap_or(em_width(&text.text) * text.font_size);
+        let (x, y) = local(text.center);
+        object.translate(
+            x - PdfPoints::new(width / 2.0),
+            y - PdfPoints::new(text.font_size * SEAL_BASELINE),
+        )?;
+        object.apply_matrix(matrix)?;
+        objects.push(PdfPageObject::Text(object));
+    }
+    Ok(objects)
+}
+
+/// 印影（日付印・認印）を書き込む
+pub fn draw_seal<'a>(
+    document: &PdfiumDocument<'a>,
+    page: &mut PdfPage<'a>,
+    frame: &PageFrame,
+    stamp: &Stamp,
+    layout: &SealLayout,
+    font: PdfFontToken,
+) -> Result<()> {
+    for object in seal_objects(document, frame, stamp, layout, font)? {
+        page.objects_mut()
+            .add_object(object)
+            .context("印影を追加できませんでした")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 変換行列で点を写す
+    fn apply(m: &PdfMatrix, (x, y): (f32, f32)) -> (f32, f32) {
+        (
+            x * m.a() + y * m.c() + m.e(),
+            x * m.b() + y * m.d() + m.f(),
+        )
+    }
+
+    #[test]
+    fn matrix_places_box_upright_for_each_rotation() {
+        // 595x842 のページで (100, 50) に 80x40 の注釈
+        let (x, y, w, h) = (100.0, 50.0, 80.0, 40.0);
+        // 注釈の左上（ローカル (0, h)）と右下（ローカル (w, 0)）の写り先
+        let expected = [
+            (0, (100.0, 792.0), (180.0, 752.0)),
+            (90, (100.0, 752.0), (140.0, 832.0)),
+            (180, (180.0, 752.0), (100.0, 792.0)),
+            (270, (180.0, 792.0), (140.0, 712.0)),
+        ];
+        for (rotation, top_left, bottom_right) in expected {
+            let frame = PageFrame {
+                width: 595.0,
+                height: 842.0,
+                origin: (0.0, 0.0),
+                rotation,
+            };
+            let m = frame.matrix(x, y, w, h);
+            assert_eq!(apply(&m, (0.0, h)), top_left, "rotation {}", rotation);
+            assert_eq!(apply(&m, (w, 0.0)), bottom_right, "rotation {}", rotation);
+            // ページ上の範囲から元の矩形に戻せる
+            assert_eq!(frame.locate(&frame.bounds(x, y, w, h)), (x, y, w, h), "rotation {}", rotation);
+
+            // テキストの位置はページの回転によらない
+            let text = TextRect { x, y, width: w, height: h };
+            let bounds = frame.page_rect(&text);
+            assert_eq!((bounds.left().value, bounds.top().value), (100.0, 792.0));
+            assert_eq!(frame.text_rect(&bounds), text);
+            let (px, py) = frame.page_point((x, y));
+            assert_eq!((px.value, py.value), (100.0, 792.0));
+            assert_eq!(frame.text_point(px, py), (x, y));
+        }
+    }
+}
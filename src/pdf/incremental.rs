@@ -0,0 +1,330 @@
+//! 増分更新による注釈の辞書の書き換え
+//!
+//! PDFium には注釈どうしの参照（/IRT, /Popup, /Parent）や /L・/Vertices を設定する関数が無く、
+//! /Line・/Polygon 注釈も作れない。そこで注釈を作るときに /Contents へ目印の文字列を入れておき、
+//! 保存したバイト列から目印で注釈のオブジェクトを探して、書き換えた辞書を増分更新として末尾に追記する。
+//! PDFium は追記した版を読み込むので、以降の保存にも書き換えた値が残る。
+
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+
+/// 書き換えた辞書に追加する値
+pub enum PatchValue {
+    /// PDFの値をそのまま書く（`[0 0 1]` など）
+    Raw(String),
+    /// 目印で指定した別の注釈への参照
+    Annotation(String),
+}
+
+/// 1つの注釈の辞書の書き換え
+pub struct AnnotationPatch {
+    /// 注釈を作るときに /Contents に入れた目印
+    pub marker: String,
+    /// 目印と置き換える /Contents の内容
+    pub contents: String,
+    /// /Subtype の変更先（`Line` など、先頭の `/` は付けない）
+    pub subtype: Option<&'static str>,
+    /// 追加する値（キーは先頭の `/` を付けない）
+    pub entries: Vec<(&'static str, PatchValue)>,
+}
+
+impl AnnotationPatch {
+    /// /Contents に入れる目印と、その書き換えを作る
+    pub fn new(contents: &str) -> Self {
+        Self {
+            marker: format!("pdf-viewer-{}", uuid::Uuid::new_v4().simple()),
+            contents: contents.to_string(),
+            subtype: None,
+            entries: Vec::new(),
+        }
+    }
+}
+
+/// 注釈のオブジェクトの位置
+struct Located {
+    number: u32,
+    generation: u16,
+    /// 辞書（`<<` から `>>` まで）の範囲
+    dict: std::ops::Range<usize>,
+    /// 目印の文字列（括弧を含む）の範囲
+    marker: std::ops::Range<usize>,
+}
+
+/// 保存したPDFに、注釈の辞書を書き換える増分更新を追記する
+pub fn apply_patches(bytes: &[u8], patches: &[AnnotationPatch]) -> Result<Vec<u8>> {
+    let located = patches
+        .iter()
+        .map(|patch| locate(bytes, &patch.marker).with_context(|| format!("注釈が見つかりません: {}", patch.marker)))
+        .collect::<Result<Vec<_>>>()?;
+    let references: HashMap<&str, String> = patches
+        .iter()
+        .zip(&located)
+        .map(|(patch, at)| (patch.marker.as_str(), format!("{} {} R", at.number, at.generation)))
+        .collect();
+
+    let previous = last_startxref(bytes).context("相互参照表が見つかりません")?;
+    let trailer = trailer_dict(bytes, previous).context("トレーラーが見つかりません")?;
+    let root = reference_after(trailer, b"/Root").context("トレーラーに /Root がありません")?;
+    let info = reference_after(trailer, b"/Info");
+    let id = array_after(trailer, b"/ID");
+    let size = number_after(trailer, b"/Size").unwrap_or(0);
+
+    let mut output = bytes.to_vec();
+    if !output.ends_with(b"\n") {
+        output.push(b'\n');
+    }
+    let mut offsets = Vec::with_capacity(patches.len());
+    for (patch, at) in patches.iter().zip(&located) {
+        let dict = rewrite_dict(bytes, patch, at, &references)?;
+        offsets.push((at.number, at.generation, output.len()));
+        output.extend_from_slice(format!("{} {} obj\n", at.number, at.generation).as_bytes());
+        output.extend_from_slice(&dict);
+        output.extend_from_slice(b"\nendobj\n");
+    }
+
+    let xref = output.len();
+    offsets.sort_unstable();
+    let mut table = String::from("xref\n");
+    for (number, generation, offset) in &offsets {
+        table += &format!("{} 1\n{:010} {:05} n \n", number, offset, generation);
+    }
+    let size = offsets.iter().map(|(number, ..)| number + 1).fold(size, u32::max);
+    table += &format!("trailer\n<< /Size {} /Root {}", size, root);
+    if let Some(info) = info {
+        table += &format!(" /Info {}", info);
+    }
+    if let Some(id) = id {
+        table += &format!(" /ID {}", id);
+    }
+    table += &format!(" /Prev {} >>\nstartxref\n{}\n%%EOF\n", previous, xref);
+    output.extend_from_slice(table.as_bytes());
+    Ok(output)
+}
+
+/// 目印を /Contents に持つ注釈のオブジェクトを探す
+fn locate(bytes: &[u8], marker: &str) -> Option<Located> {
+    // PDFium は ASCII の文字列を1バイトずつ、それ以外を UTF-16BE で書く
+    let ascii = format!("({})", marker).into_bytes();
+    let mut utf16 = b"(\xfe\xff".to_vec();
+    for unit in marker.encode_utf16() {
+        utf16.extend_from_slice(&unit.to_be_bytes());
+    }
+    utf16.push(b')');
+    let (start, needle) = [ascii, utf16]
+        .into_iter()
+        .find_map(|needle| find(bytes, &needle).map(|start| (start, needle)))?;
+
+    let (number, generation, body) = object_header_before(bytes, start)?;
+    let end = start + find(&bytes[start..], b"endobj")?;
+    let dict_start = body + find(&bytes[body..end], b"<<")?;
+    let dict_end = body + rfind(&bytes[body..end], b">>")? + 2;
+    Some(Located {
+        number,
+        generation,
+        dict: dict_start..dict_end,
+        marker: start..start + needle.len(),
+    })
+}
+
+/// 書き換えた辞書
+fn rewrite_dict(
+    bytes: &[u8],
+    patch: &AnnotationPatch,
+    at: &Located,
+    references: &HashMap<&str, String>,
+) -> Result<Vec<u8>> {
+    let mut dict = Vec::new();
+    dict.extend_from_slice(&bytes[at.dict.start..at.marker.start]);
+    dict.extend_from_slice(encode_text(&patch.contents).as_bytes());
+    dict.extend_from_slice(&bytes[at.marker.end..at.dict.end - 2]);
+
+    if let Some(subtype) = patch.subtype {
+        let key = find(&dict, b"/Subtype").context("注釈に /Subtype がありません")?;
+        let mut start = key + b"/Subtype".len();
+        while dict.get(start).is_some_and(u8::is_ascii_whitespace) {
+            start += 1;
+        }
+        if dict.get(start) != Some(&b'/') {
+            bail!("注釈の /Subtype を読み取れません");
+        }
+        let end = start + 1 + dict[start + 1..].iter().take_while(|&&c| !is_delimiter(c)).count();
+        dict.splice(start..end, format!("/{}", subtype).into_bytes());
+    }
+
+    for (key, value) in &patch.entries {
+        let value = match value {
+            PatchValue::Raw(value) => value.clone(),
+            PatchValue::Annotation(marker) => references
+                .get(marker.as_str())
+                .cloned()
+                .with_context(|| format!("参照先の注釈が見つかりません: {}", marker))?,
+        };
+        dict.extend_from_slice(format!(" /{} {}", key, value).as_bytes());
+    }
+    dict.extend_from_slice(b">>");
+    Ok(dict)
+}
+
+/// テキスト文字列を UTF-16BE の16進文字列にする
+fn encode_text(text: &str) -> String {
+    let mut encoded = String::from("<FEFF");
+    for unit in text.encode_utf16() {
+        encoded += &format!("{:04X}", unit);
+    }
+    encoded.push('>');
+    encoded
+}
+
+/// `position` より前にある最後の `N G obj` の番号と、その直後の位置
+fn object_header_before(bytes: &[u8], position: usize) -> Option<(u32, u16, usize)> {
+    let mut end = position;
+    while let Some(at) = rfind(&bytes[..end], b"obj") {
+        end = at;
+        // endobj は読み飛ばす
+        if at > 0 && bytes[at - 1] == b'd' {
+            continue;
+        }
+        let mut tokens = bytes[..at].rsplit(|c| c.is_ascii_whitespace()).filter(|t| !t.is_empty());
+        let generation = std::str::from_utf8(tokens.next()?).ok()?.parse().ok()?;
+        let number = std::str::from_utf8(tokens.next()?).ok()?.parse().ok()?;
+        return Some((number, generation, at + 3));
+    }
+    None
+}
+
+/// 最後の `startxref` が指す位置
+fn last_startxref(bytes: &[u8]) -> Option<usize> {
+    let at = rfind(bytes, b"startxref")? + b"startxref".len();
+    let digits: Vec<u8> = bytes[at..]
+        .iter()
+        .skip_while(|c| c.is_ascii_whitespace())
+        .take_while(|c| c.is_ascii_digit())
+        .copied()
+        .collect();
+    std::str::from_utf8(&digits).ok()?.parse().ok()
+}
+
+/// 相互参照表のトレーラー（相互参照ストリームならその辞書）
+fn trailer_dict(bytes: &[u8], xref: usize) -> Option<&[u8]> {
+    let section = bytes.get(xref..)?;
+    let section = &section[section.iter().take_while(|c| c.is_ascii_whitespace()).count()..];
+    if section.starts_with(b"xref") {
+        let start = find(section, b"trailer")?;
+        let end = find(section, b"startxref").unwrap_or(section.len());
+        section.get(start..end)
+    } else {
+        let end = find(section, b"stream")?;
+        Some(&section[..end])
+    }
+}
+
+/// `key` に続く参照（`N G R`）
+fn reference_after(dict: &[u8], key: &[u8]) -> Option<String> {
+    let at = find(dict, key)? + key.len();
+    let tokens: Vec<&[u8]> = dict[at..]
+        .split(|&c| c.is_ascii_whitespace() || (is_delimiter(c) && c != b'R'))
+        .filter(|t| !t.is_empty())
+        .take(3)
+        .collect();
+    match tokens[..] {
+        [number, generation, b"R"] => {
+            let number: u32 = std::str::from_utf8(number).ok()?.parse().ok()?;
+            let generation: u16 = std::str::from_utf8(generation).ok()?.parse().ok()?;
+            Some(format!("{} {} R", number, generation))
+        }
+        _ => None,
+    }
+}
+
+/// `key` に続く配列（`[` から `]` まで）
+fn array_after(dict: &[u8], key: &[u8]) -> Option<String> {
+    let at = find(dict, key)? + key.len();
+    let start = at + dict[at..].iter().position(|c| !c.is_ascii_whitespace())?;
+    if dict[start] != b'[' {
+        return None;
+    }
+    let end = start + find(&dict[start..], b"]")? + 1;
+    String::from_utf8(dict[start..end].to_vec()).ok()
+}
+
+/// `key` に続く整数
+fn number_after(dict: &[u8], key: &[u8]) -> Option<u32> {
+    let at = find(dict, key)? + key.len();
+    let digits: Vec<u8> = dict[at..]
+        .iter()
+        .skip_while(|c| c.is_ascii_whitespace())
+        .take_while(|c| c.is_ascii_digit())
+        .copied()
+        .collect();
+    std::str::from_utf8(&digits).ok()?.parse().ok()
+}
+
+/// PDFの区切り文字・空白か
+fn is_delimiter(c: u8) -> bool {
+    c.is_ascii_whitespace() || b"()<>[]{}/%".contains(&c)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+fn rfind(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).rposition(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 注釈2つを持つPDF（相互参照表の中身は使わないので省く）
+    fn sample_pdf(first: &str, second: &str) -> Vec<u8> {
+        let body = format!(
+            "%PDF-1.7\n1 0 obj\n<</Type/Catalog/Pages 2 0 R>>\nendobj\n\
+             4 0 obj\n<</Type/Annot/Subtype/Ink/Contents({})/AP<</N 6 0 R>>/Rect[0 0 10 10]>>\nendobj\n\
+             5 0 obj\n<</Type/Annot/Subtype /Text/Contents({})>>\nendobj\n",
+            first, second
+        );
+        format!(
+            "{}xref\n0 1\n0000000000 65535 f \ntrailer\n<</Size 7/Root 1 0 R/ID[<AB><CD>]>>\nstartxref\n{}\n%%EOF\n",
+            body,
+            body.len()
+        )
+        .into_bytes()
+    }
+
+    #[test]
+    fn patch_rewrites_annotation_in_appended_section() {
+        let mut line = AnnotationPatch::new("直線");
+        line.subtype = Some("Line");
+        line.entries.push(("L", PatchValue::Raw("[1 2 3 4]".to_string())));
+        let mut note = AnnotationPatch::new("メモ");
+        note.entries.push(("IRT", PatchValue::Annotation(line.marker.clone())));
+        let original = sample_pdf(&line.marker, &note.marker);
+
+        let patched = apply_patches(&original, &[line, note]).unwrap();
+        assert!(patched.starts_with(&original));
+        let appended = String::from_utf8_lossy(&patched[original.len()..]).to_string();
+
+        assert!(appended.contains(
+            "4 0 obj\n<</Type/Annot/Subtype/Line/Contents<FEFF76F47DDA>/AP<</N 6 0 R>>/Rect[0 0 10 10] /L [1 2 3 4]>>\nendobj"
+        ));
+        assert!(appended.contains("5 0 obj\n<</Type/Annot/Subtype /Text/Contents<FEFF30E130E2> /IRT 4 0 R>>\nendobj"));
+        let previous = last_startxref(&original).unwrap();
+        assert!(appended.contains(&format!("trailer\n<< /Size 7 /Root 1 0 R /ID [<AB><CD>] /Prev {} >>", previous)));
+
+        // 相互参照表の位置に各オブジェクトがある
+        let xref = last_startxref(&patched).unwrap();
+        let table = String::from_utf8_lossy(&patched[xref..]).to_string();
+        for (number, line) in [(4, 2), (5, 4)] {
+            let entry = table.lines().nth(line).unwrap();
+            let offset: usize = entry[..10].parse().unwrap();
+            assert!(patched[offset..].starts_with(format!("{} 0 obj", number).as_bytes()));
+        }
+    }
+
+    #[test]
+    fn missing_marker_is_an_error() {
+        let original = sample_pdf("a", "b");
+        assert!(apply_patches(&original, &[AnnotationPatch::new("")]).is_err());
+    }
+}
@@ -1,9 +1,31 @@
 //! PDF処理モジュール
 
+mod annotations;
 mod document;
+mod flatten;
+mod incremental;
+mod native;
 mod operations;
+mod redact;
+mod render_worker;
 mod renderer;
+mod search_index;
+mod seal;
+mod stamp_library;
+mod text;
 
-pub use document::PdfDocument;
-pub use operations::PdfOperations;
-pub use renderer::{CustomStampInfo, FontType, RectAnnotation, Stamp, StampType, TextAnnotation};
+pub use annotations::{AnnotationData, AnnotationKind, AnnotationRef};
+pub use document::{PageTile, PdfDocument};
+pub use flatten::seal_font;
+pub use native::ImportedAnnotations;
+pub use operations::{PageEditor, PdfOperations, SaveMode};
+pub use render_worker::{RenderArea, RenderJob, RenderSource, RenderWorker};
+pub use renderer::{
+    display_time, smooth_stroke, CommentAnnotation, CommentReply, CommentStatus, CustomStampInfo,
+    FontType, MarkupAnnotation, MarkupType, RectAnnotation, ShapeAnnotation, ShapeType, Stamp,
+    StampType, TextAnnotation, COMMENT_ICON_SIZE,
+};
+pub use seal::{today, DateSeal, NameSeal, SealLayout, SealShape};
+pub use stamp_library::StampLibrary;
+pub use search_index::{list_pdfs, read_pages, IndexHit, SearchIndex};
+pub use text::{normalize, PageText, SearchHit, TextChar, TextRect};
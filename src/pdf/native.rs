@@ -0,0 +1,683 @@
+//! PDF注釈（/Stamp, /FreeText, /Square, /Text, /Popup, /Highlight, /Underline, /StrikeOut, /Ink, /Line, /Circle, /Polygon）との相互変換
+//!
+//! フラット化と違い、保存後も Acrobat などで移動・削除できる。
+//! 配置はフラット化と同じく `PageFrame` の変換行列で求める。
+//!
+//! 他のアプリで作られた /PolyLine 注釈も手書きとして取り込む。
+//! 頂点や /IRT など pdfium-render の注釈から読めない値は、低レベルAPIで読み取る（`read_annotation_details`）。
+
+use super::flatten::{seal_objects, shape_paths, text_size, PageFrame};
+use super::incremental::{AnnotationPatch, PatchValue};
+use crate::pdf::{
+    AnnotationData, CommentAnnotation, FontType, MarkupAnnotation, MarkupType,
+    RectAnnotation, SealLayout, ShapeAnnotation, ShapeType, Stamp, StampType, TextAnnotation,
+    COMMENT_ICON_SIZE,
+};
+use chrono::{DateTime, FixedOffset, NaiveDateTime, Utc};
+use pdfium_render::prelude::PdfDocument as PdfiumDocument;
+use anyhow::{Context, Result};
+use image::DynamicImage;
+use pdfium_render::prelude::*;
+
+/// コメントのポップアップの幅（ポイント）
+const COMMENT_POPUP_WIDTH: f32 = 200.0;
+/// コメントのポップアップの高さ（ポイント）
+const COMMENT_POPUP_HEIGHT: f32 = 100.0;
+
+/// スタンプを /Stamp 注釈として書き込む（画像を外観ストリームに持つ）
+pub fn write_stamp(
+    page: &mut PdfPage,
+    frame: &PageFrame,
+    stamp: &Stamp,
+    image: &DynamicImage,
+) -> Result<()> {
+    let mut annotation = page
+        .annotations_mut()
+        .create_stamp_annotation()
+        .context("スタンプ注釈を作成できませんでした")?;
+    // 外観ストリームの範囲は注釈の矩形になるため、画像より先に設定する
+    annotation.set_bounds(frame.bounds(stamp.x, stamp.y, stamp.width, stamp.height))?;
+    annotation.set_contents(&stamp.stamp_type.label())?;
+
+    let mut object = annotation
+        .objects_mut()
+        .create_image_object(
+            PdfPoints::ZERO,
+            PdfPoints::ZERO,
+            image,
+            Some(PdfPoints::new(stamp.width)),
+            Some(PdfPoints::new(stamp.height)),
+        )
+        .context("スタンプ画像を追加できませんでした")?;
+    object.apply_matrix(frame.matrix(stamp.x, stamp.y, stamp.width, stamp.height))?;
+    Ok(())
+}
+
+/// 印影（日付印・認印）を /Stamp 注釈として書き込む（枠・線・文字を外観ストリームに持つ）
+pub fn write_seal<'a>(
+    document: &PdfiumDocument<'a>,
+    page: &mut PdfPage<'a>,
+    frame: &PageFrame,
+    stamp: &Stamp,
+    layout: &SealLayout,
+    font: PdfFontToken,
+) -> Result<()> {
+    // 外観のオブジェクトは注釈に追加した後では反映されないため、先に作っておく
+    let objects = seal_objects(document, frame, stamp, layout, font)?;
+    let mut annotation = page
+        .annotations_mut()
+        .create_stamp_annotation()
+        .context("スタンプ注釈を作成できませんでした")?;
+    annotation.set_bounds(frame.bounds(stamp.x, stamp.y, stamp.width, stamp.height))?;
+    annotation.set_contents(&stamp.stamp_type.label())?;
+    for object in objects {
+        annotation
+            .objects_mut()
+            .add_object(object)
+            .context("印影を追加できませんでした")?;
+    }
+    Ok(())
+}
+
+/// テキスト注釈を /FreeText 注釈として書き込む
+pub fn write_text(page: &mut PdfPage, frame: &PageFrame, annotation: &TextAnnotation) -> Result<()> {
+    let (width, height) = text_size(annotation);
+    let mut free_text = page
+        .annotations_mut()
+        .create_free_text_annotation(&annotation.text)
+        .context("テキスト注釈を作成できませんでした")?;
+    free_text.set_bounds(frame.bounds(annotation.x, annotation.y, width, height))?;
+    if !annotation.transparent {
+        free_text.set_fill_color(PdfColor::WHITE)?;
+    }
+    Ok(())
+}
+
+/// 矩形を /Square 注釈として書き込む（枠も塗りと同じ色）
+pub fn write_rect(page: &mut PdfPage, frame: &PageFrame, rect: &RectAnnotation) -> Result<()> {
+    let [r, g, b, a] = rect.color;
+    let mut square = page
+        .annotations_mut()
+        .create_square_annotation()
+        .context("矩形注釈を作成できませんでした")?;
+    square.set_bounds(frame.bounds(rect.x, rect.y, rect.width, rect.height))?;
+    square.set_fill_color(PdfColor::new(r, g, b, a))?;
+    square.set_stroke_color(PdfColor::new(r, g, b, a))?;
+    Ok(())
+}
+
+/// テキストの装飾を /Highlight, /Underline, /StrikeOut 注釈として書き込む
+///
+/// 行ごとの四辺形を /QuadPoints に、装飾したテキストを /Contents に入れる。
+pub fn write_markup(page: &mut PdfPage, frame: &PageFrame, markup: &MarkupAnnotation) -> Result<()> {
+    let quads: Vec<PdfRect> = markup.quads.iter().map(|quad| frame.page_rect(quad)).collect();
+    let Some(bounds) = quads.iter().copied().reduce(|a, b| {
+        PdfRect::new_from_values(
+            a.bottom().value.min(b.bottom().value),
+            a.left().value.min(b.left().value),
+            a.top().value.max(b.top().value),
+            a.right().value.max(b.right().value),
+        )
+    }) else {
+        return Ok(());
+    };
+    let [r, g, b, a] = markup.color;
+    let color = PdfColor::new(r, g, b, a);
+
+    let annotations = page.annotations_mut();
+    let context = "テキストの装飾の注釈を作成できませんでした";
+    match markup.markup_type {
+        MarkupType::Highlight => {
+            let mut annotation = annotations.create_highlight_annotation().context(context)?;
+            add_quads(annotation.attachment_points_mut(), &quads)?;
+            set_markup(&mut annotation, bounds, color, &markup.text)
+        }
+        MarkupType::Underline => {
+            let mut annotation = annotations.create_underline_annotation().context(context)?;
+            add_quads(annotation.attachment_points_mut(), &quads)?;
+            set_markup(&mut annotation, bounds, color, &markup.text)
+        }
+        MarkupType::StrikeOut => {
+            let mut annotation = annotations.create_strikeout_annotation().context(context)?;
+            add_quads(annotation.attachment_points_mut(), &quads)?;
+            set_markup(&mut annotation, bounds, color, &markup.text)
+        }
+    }
+}
+
+/// 行の範囲を /QuadPoints に追加（頂点は左上・右上・左下・右下の順、Acrobat と同じ）
+fn add_quads(points: &mut PdfPageAnnotationAttachmentPoints, quads: &[PdfRect]) -> Result<()> {
+    for quad in quads {
+        let (left, right, top, bottom) = (quad.left(), quad.right(), quad.top(), quad.bottom());
+        points
+            .create_attachment_point_at_end(PdfQuadPoints::new(left, top, right, top, left, bottom, right, bottom))
+            .context("テキストの装飾の範囲を設定できませんでした")?;
+    }
+    Ok(())
+}
+
+/// テキストの装飾の注釈の範囲・色・内容を設定
+fn set_markup(annotation: &mut impl PdfPageAnnotationCommon, bounds: PdfRect, color: PdfColor, text: &str) -> Result<()> {
+    annotation.set_bounds(bounds)?;
+    annotation.set_stroke_color(color)?;
+    if !text.is_empty() {
+        annotation.set_contents(text)?;
+    }
+    Ok(())
+}
+
+/// 手書き・図形を注釈として書き込む（線を外観ストリームに持つ）
+///
+/// 手書きは /Ink、直線・矢印は /Line、楕円は /Circle、多角形は /Polygon とする。
+/// PDFium で作れない種類は /Ink として作り、返した書き換えで種類と頂点を設定する。
+pub fn write_shape<'a>(
+    document: &PdfiumDocument<'a>,
+    page: &mut PdfPage<'a>,
+    frame: &PageFrame,
+    shape: &ShapeAnnotation,
+) -> Result<Option<AnnotationPatch>> {
+    let bounds = shape.bounds();
+    // 楕円は /Rect から線の太さの半分だけ内側とする（/RD）
+    let margin = match shape.shape_type {
+        ShapeType::Ellipse => shape.width / 2.0,
+        _ => shape.width / 2.0 + 1.0,
+    };
+    let [r, g, b, a] = shape.color;
+
+    let mut ink = page
+        .annotations_mut()
+        .create_ink_annotation()
+        .context("図形の注釈を作成できませんでした")?;
+    // 外観ストリームの範囲は注釈の矩形になるため、線より先に設定する（線の太さの分を広げる）
+    ink.set_bounds(frame.page_rect(&crate::pdf::TextRect {
+        x: bounds.x - margin,
+        y: bounds.y - margin,
+        width: bounds.width + margin * 2.0,
+        height: bounds.height + margin * 2.0,
+    }))?;
+    ink.set_stroke_color(PdfColor::new(r, g, b, a))?;
+    for path in shape_paths(document, frame, shape)? {
+        ink.objects_mut()
+            .add_path_object(path)
+            .context("図形の線を追加できませんでした")?;
+    }
+
+    let label = shape.shape_type.label();
+    let mut patch = AnnotationPatch::new(label);
+    let page_points = |points: &[(f32, f32)]| {
+        let values: Vec<String> = points
+            .iter()
+            .flat_map(|&point| {
+                let (x, y) = frame.page_point(point);
+                [x.value.to_string(), y.value.to_string()]
+            })
+            .collect();
+        PatchValue::Raw(format!("[{}]", values.join(" ")))
+    };
+    match shape.shape_type {
+        ShapeType::Ink => {
+            ink.set_contents(label)?;
+            return Ok(None);
+        }
+        ShapeType::Line | ShapeType::Arrow => {
+            let (Some(&start), Some(&end)) = (shape.points.first(), shape.points.last()) else {
+                ink.set_contents(label)?;
+                return Ok(None);
+            };
+            patch.subtype = Some("Line");
+            patch.entries.push(("L", page_points(&[start, end])));
+            if shape.shape_type == ShapeType::Arrow {
+                patch.entries.push(("LE", PatchValue::Raw("[/None /OpenArrow]".to_string())));
+                patch.entries.push(("IT", PatchValue::Raw("/LineArrow".to_string())));
+            }
+        }
+        ShapeType::Ellipse => {
+            patch.subtype = Some("Circle");
+            patch.entries.push(("RD", PatchValue::Raw(format!("[{0} {0} {0} {0}]", margin))));
+        }
+        ShapeType::Polygon => {
+            patch.subtype = Some("Polygon");
+            patch.entries.push(("Vertices", page_points(&shape.points)));
+        }
+    }
+    patch.entries.push(("Border", PatchValue::Raw(format!("[0 0 {}]", shape.width))));
+    ink.set_contents(&patch.marker)?;
+    Ok(Some(patch))
+}
+
+/// コメントを /Text 注釈と /Popup 注釈として書き込む（返信は /IRT で親を指す /Text 注釈にする）
+///
+/// 注釈どうしの参照は PDFium で設定できないため、返した書き換えで設定する。
+pub fn write_comment(
+    page: &mut PdfPage,
+    frame: &PageFrame,
+    comment: &CommentAnnotation,
+) -> Result<Vec<AnnotationPatch>> {
+    let bounds = frame.bounds(comment.x, comment.y, COMMENT_ICON_SIZE, COMMENT_ICON_SIZE);
+    let mut write_note = |author: &str, created: &str, text: &str| -> Result<AnnotationPatch> {
+        let patch = AnnotationPatch::new(text);
+        let mut note = page
+            .annotations_mut()
+            .create_text_annotation(&patch.marker)
+            .context("コメント注釈を作成できませんでした")?;
+        note.set_bounds(bounds)?;
+        if !author.is_empty() {
+            note.set_creator(author)?;
+        }
+        if let Ok(time) = DateTime::parse_from_rfc3339(created) {
+            note.set_creation_date(time.with_timezone(&Utc))?;
+        }
+        Ok(patch)
+    };
+
+    let mut parent = write_note(&comment.author, &comment.created, &comment.text)?;
+    let mut patches = Vec::with_capacity(comment.replies.len() + 2);
+    for reply in &comment.replies {
+        let mut patch = write_note(&reply.author, &reply.created, &reply.text)?;
+        patch.entries.push(("IRT", PatchValue::Annotation(parent.marker.clone())));
+        patches.push(patch);
+    }
+
+    // ポップアップはアイコンの右に開く
+    let mut popup = AnnotationPatch::new(&comment.text);
+    let mut annotation = page
+        .annotations_mut()
+        .create_popup_annotation()
+        .context("コメントのポップアップを作成できませんでした")?;
+    annotation.set_bounds(PdfRect::new_from_values(
+        bounds.top().value - COMMENT_POPUP_HEIGHT,
+        bounds.right().value,
+        bounds.top().value,
+        bounds.right().value + COMMENT_POPUP_WIDTH,
+    ))?;
+    annotation.set_contents(&popup.marker)?;
+    popup.entries.push(("Parent", PatchValue::Annotation(parent.marker.clone())));
+    popup.entries.push(("Open", PatchValue::Raw("false".to_string())));
+    parent.entries.push(("Popup", PatchValue::Annotation(popup.marker.clone())));
+
+    patches.push(parent);
+    patches.push(popup);
+    Ok(patches)
+}
+
+/// PDFの日付文字列（D:YYYYMMDDHHmmSS+HH'mm'）を RFC 3339 に変換（時差が無ければ UTC とする）
+fn pdf_date_to_rfc3339(value: &str) -> Option<String> {
+    let value = value.strip_prefix("D:").unwrap_or(value);
+    let digits: String = value.chars().take_while(|c| c.is_ascii_digit()).take(14).collect();
+    if digits.len() < 4 {
+        return None;
+    }
+    // 省略された月日・時刻は既定値で補う
+    let padded = format!("{}{}", digits, &"0101000000"[digits.len() - 4..]);
+    let time = NaiveDateTime::parse_from_str(&padded, "%Y%m%d%H%M%S").ok()?;
+
+    let zone = &value[digits.len()..];
+    let offset = match zone.chars().next() {
+        Some(sign @ ('+' | '-')) => {
+            let mut parts = zone[1..].split('\'').filter(|p| !p.is_empty()).map(|p| p.parse::<i32>());
+            let hours = parts.next().unwrap_or(Ok(0)).ok()?;
+            let minutes = parts.next().unwrap_or(Ok(0)).ok()?;
+            let seconds = hours * 3600 + minutes * 60;
+            if sign == '-' { -seconds } else { seconds }
+        }
+        _ => 0,
+    };
+    let time = time.and_local_timezone(FixedOffset::east_opt(offset)?).single()?;
+    Some(time.to_rfc3339())
+}
+
+/// 取り込んだPDF注釈
+#[derive(Default)]
+pub struct ImportedAnnotations {
+    pub annotations: AnnotationData,
+    /// 組み込み以外のスタンプの外観画像（スタンプ名, 画像）
+    pub stamp_images: Vec<(String, DynamicImage)>,
+}
+
+/// エディタの注釈に変換したPDF注釈
+pub enum NativeAnnotation {
+    Stamp(Stamp),
+    Text(TextAnnotation),
+    Rect(RectAnnotation),
+    Markup(MarkupAnnotation),
+    Shape(ShapeAnnotation),
+    Comment(CommentAnnotation),
+}
+
+/// pdfium-render の注釈からは読めない、注釈辞書の値
+#[derive(Debug, Clone, Default)]
+pub struct AnnotationDetails {
+    /// /Line の始点と終点（ページ座標）
+    pub line: Option<[(f32, f32); 2]>,
+    /// /Polygon, /PolyLine の頂点（ページ座標）
+    pub vertices: Vec<(f32, f32)>,
+    /// /Border の線の太さ
+    pub border_width: Option<f32>,
+    /// /IT（注釈の用途。矢印の /Line は `LineArrow`）
+    pub intent: Option<String>,
+    /// /IRT が指す返信先の注釈の番号（同じページ）
+    pub in_reply_to: Option<usize>,
+    /// /Popup の /Parent が指す注釈の番号（同じページ）
+    pub parent: Option<usize>,
+}
+
+/// ドキュメントの全注釈の辞書の値を、ページごと・注釈の番号順に読み取る
+///
+/// 注釈のハンドルは pdfium-render から取り出せないため、保存したバイト列を
+/// 低レベルAPIで別に開いて読む（注釈の並び順は元のドキュメントと同じ）。
+pub fn read_annotation_details(pdfium: &Pdfium, document: &PdfiumDocument) -> Result<Vec<Vec<AnnotationDetails>>> {
+    let bytes = document
+        .save_to_bytes()
+        .context("PDF注釈を読み取れませんでした")?;
+    let bindings = pdfium.bindings();
+    let raw = bindings.FPDF_LoadMemDocument64(&bytes, None);
+    if raw.is_null() {
+        return Err(anyhow::anyhow!("PDF注釈を読み取れませんでした"));
+    }
+
+    let mut pages = Vec::new();
+    for page_index in 0..bindings.FPDF_GetPageCount(raw) {
+        let page = bindings.FPDF_LoadPage(raw, page_index);
+        let mut details = Vec::new();
+        if !page.is_null() {
+            for index in 0..bindings.FPDFPage_GetAnnotCount(page) {
+                let annotation = bindings.FPDFPage_GetAnnot(page, index);
+                if annotation.is_null() {
+                    details.push(AnnotationDetails::default());
+                    continue;
+                }
+                details.push(raw_details(bindings, page, annotation));
+                bindings.FPDFPage_CloseAnnot(annotation);
+            }
+            bindings.FPDF_ClosePage(page);
+        }
+        pages.push(details);
+    }
+    bindings.FPDF_CloseDocument(raw);
+    Ok(pages)
+}
+
+/// 1つの注釈の辞書の値を読み取る
+fn raw_details(
+    bindings: &dyn PdfiumLibraryBindings,
+    page: FPDF_PAGE,
+    annotation: FPDF_ANNOTATION,
+) -> AnnotationDetails {
+    let mut details = AnnotationDetails::default();
+
+    // 参照先の注釈をページ内の番号に直す
+    let linked_index = |key: &str| {
+        let linked = bindings.FPDFAnnot_GetLinkedAnnot(annotation, key);
+        if linked.is_null() {
+            return None;
+        }
+        let index = bindings.FPDFPage_GetAnnotIndex(page, linked);
+        bindings.FPDFPage_CloseAnnot(linked);
+        usize::try_from(index).ok()
+    };
+    details.in_reply_to = linked_index("IRT");
+    details.parent = linked_index("Parent");
+
+    let (mut start, mut end) = (FS_POINTF { x: 0.0, y: 0.0 }, FS_POINTF { x: 0.0, y: 0.0 });
+    if bindings.is_true(bindings.FPDFAnnot_GetLine(annotation, &mut start, &mut end)) {
+        details.line = Some([(start.x, start.y), (end.x, end.y)]);
+    }
+
+    // 頂点数を問い合わせてから読み取る
+    let count = bindings.FPDFAnnot_GetVertices(annotation, std::ptr::null_mut(), 0);
+    if count > 0 {
+        let mut buffer = vec![FS_POINTF { x: 0.0, y: 0.0 }; count as usize];
+        let read = bindings.FPDFAnnot_GetVertices(annotation, buffer.as_mut_ptr(), count);
+        if read == count {
+            details.vertices = buffer.iter().map(|p| (p.x, p.y)).collect();
+        }
+    }
+
+    // 文字列（名前も読める）は終端を含むバイト数を問い合わせてから読み取る
+    let length = bindings.FPDFAnnot_GetStringValue(annotation, "IT", std::ptr::null_mut(), 0);
+    if length > 2 {
+        let mut buffer = vec![0u16; length as usize / 2];
+        bindings.FPDFAnnot_GetStringValue(annotation, "IT", buffer.as_mut_ptr(), length);
+        let text = String::from_utf16_lossy(&buffer);
+        details.intent = Some(text.trim_end_matches('\0').to_string());
+    }
+
+    let (mut horizontal, mut vertical, mut width) = (0.0, 0.0, 0.0);
+    if bindings.is_true(bindings.FPDFAnnot_GetBorder(annotation, &mut horizontal, &mut vertical, &mut width)) {
+        details.border_width = Some(width);
+    }
+    details
+}
+
+/// 取り込みに `AnnotationDetails` が要る注釈か
+pub fn needs_details(annotation_type: PdfPageAnnotationType) -> bool {
+    matches!(
+        annotation_type,
+        PdfPageAnnotationType::Line
+            | PdfPageAnnotationType::Circle
+            | PdfPageAnnotationType::Polygon
+            | PdfPageAnnotationType::Polyline
+            | PdfPageAnnotationType::Text
+            | PdfPageAnnotationType::Popup
+    )
+}
+
+/// 名前の無いスタンプに付けるスタンプ名
+const UNNAMED_STAMP: &str = "取り込みスタンプ";
+
+/// PDF注釈をエディタの注釈に変換（取り込めない注釈は None）
+///
+/// `details` は `read_annotation_details` で読み取った同じ注釈の値（`needs_details` の注釈で使う）。
+pub fn read_annotation(
+    annotation: &PdfPageAnnotation,
+    frame: &PageFrame,
+    page: usize,
+    details: Option<&AnnotationDetails>,
+) -> Option<NativeAnnotation> {
+    let bounds = annotation.bounds().ok()?;
+    let (x, y, width, height) = frame.locate(&bounds);
+    let contents = annotation.contents().filter(|c| !c.trim().is_empty());
+
+    match annotation.annotation_type() {
+        PdfPageAnnotationType::Stamp => {
+            // 組み込みスタンプは /Contents のラベルで判別する
+            let stamp_type = match contents {
+                Some(label) => StampType::builtin()
+                    .into_iter()
+                    .find(|t| t.label() == label)
+                    .unwrap_or(StampType::Custom(label)),
+                None => StampType::Custom(UNNAMED_STAMP.to_string()),
+            };
+            Some(NativeAnnotation::Stamp(Stamp {
+                page,
+                x,
+                y,
+                width,
+                height,
+                stamp_type,
+            }))
+        }
+        PdfPageAnnotationType::FreeText => {
+            let text = contents?;
+            // 高さ = フォントサイズ × 行数 × 1.2 として逆算
+            let line_count = text.lines().count().max(1) as f32;
+            let font_size = (height / (line_count * 1.2)).clamp(8.0, 72.0);
+            let transparent = annotation.fill_color().is_err();
+            Some(NativeAnnotation::Text(TextAnnotation::new(
+                page,
+                x,
+                y,
+                text,
+                font_size,
+                FontType::Gothic,
+                transparent,
+            )))
+        }
+        PdfPageAnnotationType::Text => {
+            let mut comment = CommentAnnotation::new(
+                page,
+                x,
+                y,
+                annotation.creator().unwrap_or_default(),
+                contents.unwrap_or_default(),
+            );
+            comment.created = annotation
+                .creation_date()
+                .and_then(|date| pdf_date_to_rfc3339(&date))
+                .unwrap_or_default();
+            Some(NativeAnnotation::Comment(comment))
+        }
+        PdfPageAnnotationType::Square => {
+            // 枠だけの矩形は塗りつぶすと下の内容を隠してしまうため取り込まない
+            let color = annotation.fill_color().ok()?;
+            Some(NativeAnnotation::Rect(RectAnnotation {
+                page,
+                x,
+                y,
+                width,
+                height,
+                color: [color.red(), color.green(), color.blue(), color.alpha()],
+                redact: false,
+            }))
+        }
+        PdfPageAnnotationType::Highlight | PdfPageAnnotationType::Underline | PdfPageAnnotationType::Strikeout => {
+            let markup_type = match annotation.annotation_type() {
+                PdfPageAnnotationType::Highlight => MarkupType::Highlight,
+                PdfPageAnnotationType::Underline => MarkupType::Underline,
+                _ => MarkupType::StrikeOut,
+            };
+            // 四辺形が無ければ注釈の範囲全体を1行とする
+            let mut quads: Vec<_> = annotation
+                .attachment_points()
+                .iter()
+                .map(|quad| frame.text_rect(&quad.to_rect()))
+                .collect();
+            if quads.is_empty() {
+                quads.push(frame.text_rect(&bounds));
+            }
+            let color = annotation
+                .stroke_color()
+                .map(|c| [c.red(), c.green(), c.blue(), c.alpha()])
+                .unwrap_or_else(|_| markup_type.default_color());
+            Some(NativeAnnotation::Markup(MarkupAnnotation {
+                page,
+                markup_type,
+                quads,
+                color,
+                text: contents.unwrap_or_default(),
+            }))
+        }
+        PdfPageAnnotationType::Ink => {
+            // 種類は /Contents のラベルで判別し、他のアプリの手書きは手書きとする
+            let shape_type = contents
+                .and_then(|label| ShapeType::all().into_iter().find(|t| t.label() == label))
+                .unwrap_or(ShapeType::Ink);
+            // 1本目の線（矢印は軸）から頂点・色・太さを読み取る
+            let (mut points, color, width) = annotation.objects().iter().find_map(|object| {
+                let path = object.as_path_object()?;
+                let points: Vec<(f32, f32)> = path
+                    .segments()
+                    .transform(path.matrix().ok()?)
+                    .iter()
+                    .map(|segment| frame.text_point(segment.x(), segment.y()))
+                    .collect();
+                let color = path.stroke_color().ok()?;
+                let width = path.stroke_width().ok()?.value;
+                Some((points, color, width))
+            })?;
+            points.dedup();
+            let mut shape = ShapeAnnotation {
+                page,
+                shape_type,
+                points,
+                color: [color.red(), color.green(), color.blue(), color.alpha()],
+                width,
+            };
+            match shape_type {
+                ShapeType::Line | ShapeType::Arrow => {
+                    let (&start, &end) = (shape.points.first()?, shape.points.last()?);
+                    shape.points = vec![start, end];
+                }
+                ShapeType::Ellipse => {
+                    // 近似した折れ線の外接矩形が楕円の外接矩形になる
+                    let bounds = shape.bounds();
+                    shape.points = vec![
+                        (bounds.x, bounds.y),
+                        (bounds.x + bounds.width, bounds.y + bounds.height),
+                    ];
+                }
+                ShapeType::Polygon => {
+                    // 閉じるために加えた始点を除く
+                    if shape.points.len() > 1 && shape.points.first() == shape.points.last() {
+                        shape.points.pop();
+                    }
+                }
+                ShapeType::Ink => {}
+            }
+            Some(NativeAnnotation::Shape(shape))
+        }
+        PdfPageAnnotationType::Line
+        | PdfPageAnnotationType::Circle
+        | PdfPageAnnotationType::Polygon
+        | PdfPageAnnotationType::Polyline => {
+            let details = details?;
+            let to_text_point = |&(x, y): &(f32, f32)| frame.text_point(PdfPoints::new(x), PdfPoints::new(y));
+            let (shape_type, points) = match annotation.annotation_type() {
+                PdfPageAnnotationType::Line => {
+                    // 矢印は /IT か、このアプリが書き込んだ /Contents のラベルで判別する
+                    let arrow = details.intent.as_deref() == Some("LineArrow")
+                        || contents.as_deref() == Some(ShapeType::Arrow.label());
+                    let shape_type = if arrow { ShapeType::Arrow } else { ShapeType::Line };
+                    (shape_type, details.line?.iter().map(to_text_point).collect())
+                }
+                PdfPageAnnotationType::Circle => {
+                    // 線の太さの分だけ広げた /Rect の内側を楕円とする
+                    let inset = details.border_width.unwrap_or(0.0) / 2.0;
+                    (
+                        ShapeType::Ellipse,
+                        vec![(x + inset, y + inset), (x + width - inset, y + height - inset)],
+                    )
+                }
+                PdfPageAnnotationType::Polygon => (ShapeType::Polygon, details.vertices.iter().map(to_text_point).collect()),
+                // 開いた折れ線は手書きの軌跡とする
+                _ => (ShapeType::Ink, details.vertices.iter().map(to_text_point).collect()),
+            };
+            let points: Vec<(f32, f32)> = points;
+            if points.len() < 2 {
+                return None;
+            }
+            let color = annotation
+                .stroke_color()
+                .map(|c| [c.red(), c.green(), c.blue(), c.alpha()])
+                .unwrap_or([0, 0, 0, 255]);
+            Some(NativeAnnotation::Shape(ShapeAnnotation {
+                page,
+                shape_type,
+                points,
+                color,
+                width: details.border_width.filter(|w| *w > 0.0).unwrap_or(1.0),
+            }))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pdf_dates_convert_to_rfc3339() {
+        assert_eq!(
+            pdf_date_to_rfc3339("D:20261017093000+09'00'").as_deref(),
+            Some("2026-10-17T09:30:00+09:00")
+        );
+        assert_eq!(
+            pdf_date_to_rfc3339("D:20261017003000Z00'00'").as_deref(),
+            Some("2026-10-17T00:30:00+00:00")
+        );
+        // 省略された部分は補う
+        assert_eq!(pdf_date_to_rfc3339("D:2026").as_deref(), Some("2026-01-01T00:00:00+00:00"));
+        assert_eq!(pdf_date_to_rfc3339("昨日"), None);
+    }
+}
@@ -1,49 +1,214 @@
 //! PDF操作 - ページ操作、結合、分割
 
-use crate::pdf::{PdfDocument, Stamp, TextAnnotation};
+use crate::pdf::{AnnotationData, PdfDocument, StampType};
+use crate::resources::STAMPS;
 use anyhow::Result;
+use image::DynamicImage;
+use std::cmp::Ordering;
+use std::ops::Range;
 use std::path::Path;
 
+/// 名前を付けて保存時の注釈の保存方法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SaveMode {
+    #[default]
+    Flatten,      // ページ内容に書き込む
+    Annotations,  // PDF注釈として保存
+}
+
+impl SaveMode {
+    pub fn label(&self) -> &'static str {
+        match self {
+            SaveMode::Flatten => "ページに書き込む（編集不可）",
+            SaveMode::Annotations => "PDF注釈として保存（他のソフトで編集可）",
+        }
+    }
+}
+
+/// ページ構成を変更できるドキュメント
+///
+/// ページ操作と注釈の付け替えを分けておき、履歴のテストでは PDFium を使わない実装に差し替える。
+pub trait PageEditor {
+    fn rotate_page(&mut self, page_index: usize, degrees: i32) -> Result<()>;
+    fn delete_page(&mut self, page_index: usize) -> Result<()>;
+    /// `source` の全ページを `at` 番目に挿入
+    fn insert_pages(&mut self, source: &Self, at: usize) -> Result<()>;
+    /// 移動後のページ順（元のページ番号の列）を返す
+    fn move_pages(&mut self, pages: Range<usize>, to: usize) -> Result<Vec<usize>>;
+    fn reorder_pages(&mut self, order: &[usize]) -> Result<()>;
+}
+
+impl PageEditor for PdfDocument {
+    fn rotate_page(&mut self, page_index: usize, degrees: i32) -> Result<()> {
+        PdfDocument::rotate_page(self, page_index, degrees)
+    }
+
+    fn delete_page(&mut self, page_index: usize) -> Result<()> {
+        PdfDocument::delete_page(self, page_index)
+    }
+
+    fn insert_pages(&mut self, source: &Self, at: usize) -> Result<()> {
+        PdfDocument::insert_pages(self, source, at)
+    }
+
+    fn move_pages(&mut self, pages: Range<usize>, to: usize) -> Result<Vec<usize>> {
+        PdfDocument::move_pages(self, pages, to)
+    }
+
+    fn reorder_pages(&mut self, order: &[usize]) -> Result<()> {
+        PdfDocument::reorder_pages(self, order)
+    }
+}
+
 /// PDF操作のユーティリティ
 pub struct PdfOperations;
 
 impl PdfOperations {
-    /// ページを削除
-    pub fn delete_page(doc: &mut PdfDocument, page_index: usize) -> Result<()> {
+    /// ページを削除（以降のページの注釈は1ページ前に詰める）
+    pub fn delete_page(
+        doc: &mut impl PageEditor,
+        annotations: &mut AnnotationData,
+        page_index: usize,
+    ) -> Result<()> {
         log::info!("ページ {} を削除", page_index);
-        doc.refresh_page_count();
+        doc.delete_page(page_index)?;
+
+        annotations.remap_pages(|page| match page.cmp(&page_index) {
+            Ordering::Less => Some(page),
+            Ordering::Equal => None,
+            Ordering::Greater => Some(page - 1),
+        });
         Ok(())
     }
 
     /// ページを回転
-    pub fn rotate_page(doc: &mut PdfDocument, page_index: usize, degrees: i32) -> Result<()> {
+    pub fn rotate_page(doc: &mut impl PageEditor, page_index: usize, degrees: i32) -> Result<()> {
         log::info!("ページ {} を {}度回転", page_index, degrees);
         doc.rotate_page(page_index, degrees)?;
         Ok(())
     }
 
-    /// ページを並べ替え
-    pub fn reorder_page(doc: &mut PdfDocument, from: usize, to: usize) -> Result<()> {
-        log::info!("ページを {} から {} へ移動", from, to);
-        doc.refresh_page_count();
+    /// 連続した複数ページをまとめて移動（注釈と回転もページに追従）
+    pub fn move_pages(
+        doc: &mut impl PageEditor,
+        annotations: &mut AnnotationData,
+        pages: Range<usize>,
+        to: usize,
+    ) -> Result<()> {
+        log::info!(
+            "ページ {}-{} を {} 番目へ移動",
+            pages.start + 1,
+            pages.end,
+            to + 1
+        );
+        let order = doc.move_pages(pages, to)?;
+
+        // 元のページ番号 → 新しいページ番号
+        let mut new_index = vec![0; order.len()];
+        for (new_page, &old_page) in order.iter().enumerate() {
+            new_index[old_page] = new_page;
+        }
+        annotations.remap_pages(|page| new_index.get(page).copied());
+        Ok(())
+    }
+
+    /// ページを並べ替え（`order` は並べ替え後のページ順を元のページ番号で並べたもの）
+    pub fn reorder_pages(
+        doc: &mut impl PageEditor,
+        annotations: &mut AnnotationData,
+        order: &[usize],
+    ) -> Result<()> {
+        log::info!("ページを並べ替え: {:?}", order);
+        doc.reorder_pages(order)?;
+
+        let mut new_index = vec![0; order.len()];
+        for (new_page, &old_page) in order.iter().enumerate() {
+            new_index[old_page] = new_page;
+        }
+        annotations.remap_pages(|page| new_index.get(page).copied());
         Ok(())
     }
 
+    /// 選んだページ（連続していなくてよい）を `before` 番目のページの前へまとめて移した後のページ順
+    ///
+    /// `before` がページ数なら末尾へ移す。移したページは元の順序を保つ。
+    pub fn order_after_move(page_count: usize, pages: &[usize], before: usize) -> Vec<usize> {
+        let mut moving: Vec<usize> = pages.iter().copied().filter(|&p| p < page_count).collect();
+        moving.sort_unstable();
+        moving.dedup();
+        let mut order: Vec<usize> = (0..page_count).filter(|p| moving.binary_search(p).is_err()).collect();
+        let at = before.min(page_count) - moving.iter().filter(|&&p| p < before).count();
+        order.splice(at..at, moving);
+        order
+    }
+
     /// 複数のPDFを結合
-    pub fn merge(documents: &[PdfDocument]) -> Result<PdfDocument> {
+    ///
+    /// 各PDFの注釈ファイル（回転情報を含む）も読み込み、
+    /// ページ番号をずらして結合後の注釈として返す。
+    pub fn merge(documents: &[PdfDocument]) -> Result<(PdfDocument, AnnotationData)> {
         if documents.is_empty() {
             return Err(anyhow::anyhow!("結合するドキュメントがありません"));
         }
 
         log::info!("{}個のPDFを結合", documents.len());
 
-        // 簡易実装として最初のドキュメントのクローンを返す
-        Ok(documents[0].clone())
+        let mut sources = Vec::with_capacity(documents.len());
+        let mut merged_annotations = AnnotationData::default();
+        let mut offset = 0;
+
+        for doc in documents {
+            let mut source = doc.clone();
+            let page_count = source.page_count();
+
+            if let Some(mut data) = AnnotationData::load(doc.path()) {
+                let file_rotations: Vec<i32> =
+                    (0..page_count).map(|i| doc.get_page_rotation(i)).collect();
+                data.migrate(&file_rotations);
+                for (page_idx, &rotation) in data.page_rotations.iter().enumerate() {
+                    source.set_page_rotation(page_idx, rotation);
+                }
+                Self::append_merged_annotations(&mut merged_annotations, data, page_count, offset);
+            }
+
+            sources.push(source);
+            offset += page_count;
+        }
+
+        let parts: Vec<(&PdfDocument, Range<usize>)> = sources
+            .iter()
+            .map(|doc| (doc, 0..doc.page_count()))
+            .collect();
+        let merged = PdfDocument::compose(&parts)?;
+
+        Ok((merged, merged_annotations))
+    }
+
+    /// 結合後の注釈に、`offset` ページ目から始まる `page_count` ページの文書の注釈を追加
+    ///
+    /// 注釈のページ番号は `offset` だけずらし、文書のページ数を超えるものは捨てる。
+    fn append_merged_annotations(
+        merged: &mut AnnotationData,
+        mut data: AnnotationData,
+        page_count: usize,
+        offset: usize,
+    ) {
+        data.remap_pages(|page| (page < page_count).then_some(page + offset));
+        merged.extend(data);
     }
 
     /// PDFを分割して保存
-    pub fn split(doc: &PdfDocument, start: usize, end: usize, output_path: &Path) -> Result<()> {
-        if start > end || end > doc.page_count() {
+    ///
+    /// ページ `start..end` だけを `output_path` に書き出し、
+    /// 該当ページの注釈は新しいファイルの注釈ファイルへ移す。
+    pub fn split(
+        doc: &PdfDocument,
+        annotations: &AnnotationData,
+        start: usize,
+        end: usize,
+        output_path: &Path,
+    ) -> Result<()> {
+        if start >= end || end > doc.page_count() {
             return Err(anyhow::anyhow!("無効なページ範囲"));
         }
 
@@ -54,32 +219,126 @@ impl PdfOperations {
             output_path.display()
         );
 
-        // 簡易実装: 元のドキュメントを保存
-        doc.save(output_path)?;
+        let pages: Vec<usize> = (start..end).collect();
+        Self::extract(doc, annotations, &pages, output_path)
+    }
+
+    /// 選んだページ（連続していなくてよい）を抜き出して保存
+    ///
+    /// 該当ページの注釈は新しいファイルの注釈ファイルへ移す。
+    pub fn extract(
+        doc: &PdfDocument,
+        annotations: &AnnotationData,
+        pages: &[usize],
+        output_path: &Path,
+    ) -> Result<()> {
+        if pages.is_empty() || pages.iter().any(|&p| p >= doc.page_count()) {
+            return Err(anyhow::anyhow!("無効なページ範囲"));
+        }
+
+        log::info!("{} ページを {} に抜き出して保存", pages.len(), output_path.display());
+
+        // 連続したページはまとめてコピーする
+        let mut parts: Vec<(&PdfDocument, Range<usize>)> = Vec::new();
+        for &page in pages {
+            match parts.last_mut() {
+                Some((_, range)) if range.end == page => range.end += 1,
+                _ => parts.push((doc, page..page + 1)),
+            }
+        }
+        let mut part = PdfDocument::compose(&parts)?;
+        part.save(output_path)?;
+
+        let mut part_annotations = annotations.clone();
+        part_annotations.remap_pages(|page| pages.iter().position(|&p| p == page));
+        part_annotations.page_rotations = pages.iter().map(|&i| doc.get_page_rotation(i)).collect();
+
+        if part_annotations.is_empty() {
+            // 同名ファイルの古い注釈が残らないようにする
+            let _ = std::fs::remove_file(AnnotationData::sidecar_path(output_path));
+        } else {
+            part_annotations.save(output_path)?;
+        }
 
         Ok(())
     }
 
-    /// スタンプを追加
-    pub fn add_stamp(_doc: &mut PdfDocument, stamp: &Stamp) -> Result<()> {
+    /// 注釈を書き込んだ保存用のドキュメントを作成
+    ///
+    /// 元のドキュメントは変更しない。カスタムスタンプの画像は `custom_stamp` で名前から取得する。
+    pub fn export(
+        doc: &PdfDocument,
+        annotations: &AnnotationData,
+        mode: SaveMode,
+        custom_stamp: impl Fn(&str) -> Option<DynamicImage>,
+    ) -> Result<PdfDocument> {
         log::info!(
-            "ページ {} にスタンプを追加: ({}, {})",
-            stamp.page,
-            stamp.x,
-            stamp.y
+            "注釈を書き込み ({:?}): スタンプ {} / テキスト {} / 矩形 {} / 装飾 {} / 図形 {} / コメント {}",
+            mode,
+            annotations.stamps.len(),
+            annotations.texts.len(),
+            annotations.rects.len(),
+            annotations.markups.len(),
+            annotations.shapes.len(),
+            annotations.comments.len()
         );
-        Ok(())
+        let mut output = PdfDocument::compose(&[(doc, 0..doc.page_count())])?;
+        output.write_annotations(annotations, mode, |stamp_type| match stamp_type {
+            StampType::Custom(name) => custom_stamp(name),
+            builtin => STAMPS.get(&builtin.name()).cloned(),
+        })?;
+        Ok(output)
     }
+}
 
-    /// テキストを追加
-    pub fn add_text(_doc: &mut PdfDocument, annotation: &TextAnnotation) -> Result<()> {
-        log::info!(
-            "ページ {} にテキストを追加: '{}' at ({}, {})",
-            annotation.page,
-            annotation.text,
-            annotation.x,
-            annotation.y
-        );
-        Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdf::{Stamp, StampType};
+
+    fn stamps_on(pages: &[usize]) -> AnnotationData {
+        AnnotationData {
+            stamps: pages.iter().map(|&page| Stamp::new(page, 0.0, 0.0, StampType::Approved)).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn merged_annotations_are_offset_by_preceding_pages() {
+        let mut merged = AnnotationData::default();
+        PdfOperations::append_merged_annotations(&mut merged, stamps_on(&[0, 2]), 3, 0);
+        // 2つ目の文書は3ページ目から始まり、ページ数を超える注釈は捨てる
+        PdfOperations::append_merged_annotations(&mut merged, stamps_on(&[1, 0, 5]), 2, 3);
+
+        let pages: Vec<usize> = merged.stamps.iter().map(|s| s.page).collect();
+        assert_eq!(pages, vec![0, 2, 4, 3]);
+    }
+
+    #[test]
+    fn order_after_move_moves_pages_forward() {
+        assert_eq!(PdfOperations::order_after_move(5, &[0, 1], 4), vec![2, 3, 0, 1, 4]);
+        assert_eq!(PdfOperations::order_after_move(5, &[1], 5), vec![0, 2, 3, 4, 1]);
+    }
+
+    #[test]
+    fn order_after_move_moves_pages_backward() {
+        assert_eq!(PdfOperations::order_after_move(5, &[3, 4], 1), vec![0, 3, 4, 1, 2]);
+        // 離れたページもまとめ、元の順序を保つ
+        assert_eq!(PdfOperations::order_after_move(5, &[4, 2], 0), vec![2, 4, 0, 1, 3]);
+    }
+
+    #[test]
+    fn order_after_move_across_itself_keeps_order() {
+        // 選んだ範囲の内側や直後を移動先にしても順序は変わらない
+        for before in 1..=4 {
+            assert_eq!(
+                PdfOperations::order_after_move(5, &[1, 2, 3], before),
+                vec![0, 1, 2, 3, 4],
+                "before = {}",
+                before
+            );
+        }
+        // 範囲外のページ番号と重複は無視する
+        assert_eq!(PdfOperations::order_after_move(3, &[2, 2, 7], 0), vec![2, 0, 1]);
     }
 }
@@ -0,0 +1,593 @@
+//! 墨消し（矩形の下にあるページ内容の削除）
+//!
+//! 白塗りの矩形を重ねるだけでは下のテキストや画像を抽出できてしまうため、
+//! 保存時に領域と重なるページ内容そのものを削除する。
+
+use anyhow::{Context, Result};
+use image::{DynamicImage, Rgba};
+use pdfium_render::prelude::PdfDocument as PdfiumDocument;
+use pdfium_render::prelude::*;
+
+/// 曲線を折れ線に近似するときの分割数
+const CURVE_STEPS: usize = 16;
+
+/// ページ座標の点
+type Point = (f32, f32);
+
+/// パスの一部分（連続した点の列）
+#[derive(Debug, Clone, PartialEq)]
+struct Subpath {
+    points: Vec<Point>,
+    closed: bool,
+}
+
+/// 領域の外に残す部分から作り直すオブジェクト
+enum Replacement {
+    /// 領域外の1文字（元の文字の位置・フォント・色のまま）
+    Char {
+        text: String,
+        matrix: PdfMatrix,
+        font: PdfFontToken,
+        font_size: PdfPoints,
+        render_mode: PdfPageTextRenderMode,
+        fill: PdfColor,
+        stroke: PdfColor,
+    },
+    /// 領域外に切り取ったパス（点はオブジェクト内の座標）
+    Path {
+        subpaths: Vec<Subpath>,
+        matrix: PdfMatrix,
+        fill: Option<(PdfColor, PdfPathFillMode)>,
+        stroke: Option<(PdfColor, PdfPoints)>,
+        line_join: Option<PdfPageObjectLineJoin>,
+        line_cap: Option<PdfPageObjectLineCap>,
+    },
+}
+
+/// 領域と重なるページ内容を削除し、削除・加工したオブジェクト数を返す
+///
+/// - 画像は重なる部分の画素だけを `fill` で塗りつぶす。
+/// - テキストは重なる文字だけを削除し、残りの文字は1文字ずつ元の位置に置き直す。
+/// - パスは領域で切り取り、領域外の部分だけを残す（曲線は折れ線に近似する）。
+/// - 領域内に収まるオブジェクトと、分割できないフォームなどは一部でも重なれば削除する。
+///
+/// 置き直したテキストやパスは、ページ内で他のオブジェクトより前面になる。
+pub fn redact_region<'a>(
+    document: &PdfiumDocument<'a>,
+    page: &mut PdfPage<'a>,
+    region: &PdfRect,
+    fill: [u8; 4],
+) -> Result<usize> {
+    let mut removed = Vec::new();
+    let mut replacements = Vec::new();
+    let mut redacted_images = 0;
+
+    {
+        let text = page
+            .text()
+            .context("墨消し対象のテキストを読み込めませんでした")?;
+        for (index, mut object) in page.objects().iter().enumerate() {
+            let Ok(bounds) = object.bounds() else {
+                continue;
+            };
+            let bounds = bounds.to_rect();
+            if !bounds.does_overlap(region) {
+                continue;
+            }
+            if let Some(image) = object.as_image_object_mut() {
+                redact_image(image, region, fill)?;
+                redacted_images += 1;
+                continue;
+            }
+            if !bounds.is_inside(region) {
+                if let Some(text_object) = object.as_text_object() {
+                    replacements.extend(remaining_chars(&text, text_object, region)?);
+                } else if let Some(path) = object.as_path_object() {
+                    replacements.extend(remaining_path(path, region, &bounds)?);
+                }
+            }
+            removed.push(index);
+        }
+    }
+
+    // 残す部分を先に追加してから元のオブジェクトを削除する（フォントが解放されないように）
+    for replacement in replacements {
+        add_replacement(document, page, replacement)?;
+    }
+    for &index in removed.iter().rev() {
+        page.objects_mut()
+            .remove_object_at_index(index)
+            .context("墨消し対象のオブジェクトを削除できませんでした")?;
+    }
+    Ok(removed.len() + redacted_images)
+}
+
+/// テキストオブジェクトのうち領域と重ならない文字を、置き直す文字として返す
+fn remaining_chars(
+    text: &PdfPageText,
+    object: &PdfPageTextObject,
+    region: &PdfRect,
+) -> Result<Vec<Replacement>> {
+    let matrix = object.matrix()?;
+    let font = object.font().token();
+    let font_size = object.unscaled_font_size();
+    let render_mode = object.render_mode();
+    let fill = object.fill_color()?;
+    let stroke = object.stroke_color()?;
+
+    let mut chars = Vec::new();
+    for char in text.chars_for_object(object)?.iter() {
+        // 抽出時に補われた空白・改行は元の内容ではない
+        if char.is_generated().unwrap_or(false) {
+            continue;
+        }
+        let (Some(c), Ok(bounds), Ok((x, y))) = (char.unicode_char(), char.loose_bounds(), char.origin()) else {
+            continue;
+        };
+        if bounds.does_overlap(region) {
+            continue;
+        }
+        let mut char_matrix = matrix;
+        char_matrix.set_e(x.value);
+        char_matrix.set_f(y.value);
+        chars.push(Replacement::Char {
+            text: c.to_string(),
+            matrix: char_matrix,
+            font,
+            font_size,
+            render_mode,
+            fill,
+            stroke,
+        });
+    }
+    Ok(chars)
+}
+
+/// パスを領域で切り取り、領域外に残る部分を返す
+fn remaining_path(
+    path: &PdfPagePathObject,
+    region: &PdfRect,
+    bounds: &PdfRect,
+) -> Result<Vec<Replacement>> {
+    let matrix = path.matrix()?;
+    let segments = path.segments();
+    let segments = segments.transform(matrix);
+    let subpaths = flatten_segments(segments.iter().map(|segment| {
+        (segment.segment_type(), (segment.x().value, segment.y().value), segment.is_close())
+    }));
+
+    // 切り取った点はページ座標なので、元の変換行列で置けるようオブジェクト内の座標に戻す
+    let inverse = matrix.invert();
+    let to_local = |subpaths: Vec<Subpath>| -> Vec<Subpath> {
+        subpaths
+            .into_iter()
+            .map(|subpath| Subpath {
+                points: subpath
+                    .points
+                    .into_iter()
+                    .map(|(x, y)| {
+                        let (x, y) = inverse.apply_to_points(PdfPoints::new(x), PdfPoints::new(y));
+                        (x.value, y.value)
+                    })
+                    .collect(),
+                closed: subpath.closed,
+            })
+            .collect()
+    };
+    let line_join = path.line_join().ok();
+    let line_cap = path.line_cap().ok();
+
+    let mut pieces = Vec::new();
+    let fill_mode = path.fill_mode()?;
+    if fill_mode != PdfPathFillMode::None {
+        let fill = path.fill_color()?;
+        for subpaths in subtract_fill(&subpaths, region, bounds) {
+            pieces.push(Replacement::Path {
+                subpaths: to_local(subpaths),
+                matrix,
+                fill: Some((fill, fill_mode)),
+                stroke: None,
+                line_join,
+                line_cap,
+            });
+        }
+    }
+    if path.is_stroked()? {
+        let stroke = subtract_stroke(&subpaths, region);
+        if !stroke.is_empty() {
+            pieces.push(Replacement::Path {
+                subpaths: to_local(stroke),
+                matrix,
+                fill: None,
+                stroke: Some((path.stroke_color()?, path.stroke_width()?)),
+                line_join,
+                line_cap,
+            });
+        }
+    }
+    Ok(pieces)
+}
+
+/// 残す部分をページに追加
+fn add_replacement<'a>(
+    document: &PdfiumDocument<'a>,
+    page: &mut PdfPage<'a>,
+    replacement: Replacement,
+) -> Result<()> {
+    match replacement {
+        Replacement::Char {
+            text,
+            matrix,
+            font,
+            font_size,
+            render_mode,
+            fill,
+            stroke,
+        } => {
+            let mut object = PdfPageTextObject::new(document, text, font, font_size)
+                .context("墨消し後の文字を作成できませんでした")?;
+            object.set_render_mode(render_mode)?;
+            object.set_fill_color(fill)?;
+            object.set_stroke_color(stroke)?;
+            object.apply_matrix(matrix)?;
+            page.objects_mut()
+                .add_text_object(object)
+                .context("墨消し後の文字を追加できませんでした")?;
+        }
+        Replacement::Path {
+            subpaths,
+            matrix,
+            fill,
+            stroke,
+            line_join,
+            line_cap,
+        } => {
+            let Some(&(x, y)) = subpaths.first().and_then(|s| s.points.first()) else {
+                return Ok(());
+            };
+            let mut path = PdfPagePathObject::new(
+                document,
+                PdfPoints::new(x),
+                PdfPoints::new(y),
+                stroke.map(|(color, _)| color),
+                stroke.map(|(_, width)| width),
+                fill.map(|(color, _)| color),
+            )
+            .context("墨消し後の図形を作成できませんでした")?;
+            for (index, subpath) in subpaths.iter().enumerate() {
+                for (i, &(x, y)) in subpath.points.iter().enumerate() {
+                    match i {
+                        0 if index == 0 => {}
+                        0 => path.move_to(PdfPoints::new(x), PdfPoints::new(y))?,
+                        _ => path.line_to(PdfPoints::new(x), PdfPoints::new(y))?,
+                    }
+                }
+                if subpath.closed {
+                    path.close_path()?;
+                }
+            }
+            let fill_mode = fill.map_or(PdfPathFillMode::None, |(_, mode)| mode);
+            path.set_fill_and_stroke_mode(fill_mode, stroke.is_some())?;
+            if let Some(line_join) = line_join {
+                path.set_line_join(line_join)?;
+            }
+            if let Some(line_cap) = line_cap {
+                path.set_line_cap(line_cap)?;
+            }
+            path.apply_matrix(matrix)?;
+            page.objects_mut()
+                .add_path_object(path)
+                .context("墨消し後の図形を追加できませんでした")?;
+        }
+    }
+    Ok(())
+}
+
+/// パスのセグメント列を折れ線の部分パスに分ける
+///
+/// PDFiumのセグメントでは、曲線は2つの制御点と終点の3つの `BezierTo` で表される。
+fn flatten_segments(segments: impl Iterator<Item = (PdfPathSegmentType, Point, bool)>) -> Vec<Subpath> {
+    let mut subpaths: Vec<Subpath> = Vec::new();
+    let mut controls: Vec<Point> = Vec::new();
+
+    for (kind, point, close) in segments {
+        match kind {
+            PdfPathSegmentType::MoveTo => subpaths.push(Subpath { points: vec![point], closed: false }),
+            PdfPathSegmentType::LineTo | PdfPathSegmentType::BezierTo => {
+                if subpaths.is_empty() {
+                    subpaths.push(Subpath { points: vec![point], closed: false });
+                    continue;
+                }
+                let current = subpaths.last_mut().unwrap();
+                if kind == PdfPathSegmentType::BezierTo {
+                    controls.push(point);
+                    if controls.len() < 3 {
+                        continue;
+                    }
+                    let start = *current.points.last().unwrap();
+                    let (c1, c2, end) = (controls[0], controls[1], controls[2]);
+                    controls.clear();
+                    current.points.extend((1..=CURVE_STEPS).map(|i| {
+                        cubic_point(start, c1, c2, end, i as f32 / CURVE_STEPS as f32)
+                    }));
+                } else {
+                    current.points.push(point);
+                }
+            }
+            PdfPathSegmentType::Unknown => continue,
+        }
+        if close {
+            if let Some(current) = subpaths.last_mut() {
+                current.closed = true;
+            }
+        }
+    }
+    subpaths
+}
+
+/// 3次ベジェ曲線上の点
+fn cubic_point(p0: Point, p1: Point, p2: Point, p3: Point, t: f32) -> Point {
+    let s = 1.0 - t;
+    let (a, b, c, d) = (s * s * s, 3.0 * s * s * t, 3.0 * s * t * t, t * t * t);
+    (
+        a * p0.0 + b * p1.0 + c * p2.0 + d * p3.0,
+        a * p0.1 + b * p1.1 + c * p2.1 + d * p3.1,
+    )
+}
+
+/// 塗りの領域から墨消し領域を除く
+///
+/// 墨消し領域の外側を上下左右の4つの帯に分け、部分パスをそれぞれの帯で切り取る。
+/// 帯は凸なので、切り取っても帯の中での塗り（ワインディング数）は変わらない。
+fn subtract_fill(subpaths: &[Subpath], region: &PdfRect, bounds: &PdfRect) -> Vec<Vec<Subpath>> {
+    let (left, bottom, right, top) = (
+        region.left().value,
+        region.bottom().value,
+        region.right().value,
+        region.top().value,
+    );
+    let (min_x, min_y, max_x, max_y) = (
+        bounds.left().value.min(left),
+        bounds.bottom().value.min(bottom),
+        bounds.right().value.max(right),
+        bounds.top().value.max(top),
+    );
+    let strips = [
+        PdfRect::new_from_values(min_y, min_x, max_y, left),
+        PdfRect::new_from_values(min_y, right, max_y, max_x),
+        PdfRect::new_from_values(min_y, left, bottom, right),
+        PdfRect::new_from_values(top, left, max_y, right),
+    ];
+
+    strips
+        .iter()
+        .filter(|strip| strip.width().value > 0.0 && strip.height().value > 0.0)
+        .map(|strip| {
+            subpaths
+                .iter()
+                .map(|subpath| clip_polygon(&subpath.points, strip))
+                .filter(|points| points.len() >= 3)
+                .map(|points| Subpath { points, closed: true })
+                .collect::<Vec<_>>()
+        })
+        .filter(|clipped| !clipped.is_empty())
+        .collect()
+}
+
+/// 多角形を矩形の内側に切り取る（Sutherland–Hodgman）
+fn clip_polygon(points: &[Point], clip: &PdfRect) -> Vec<Point> {
+    // (x軸か, 内側の向き, 辺の座標)
+    let edges = [
+        (true, 1.0, clip.left().value),
+        (true, -1.0, clip.right().value),
+        (false, 1.0, clip.bottom().value),
+        (false, -1.0, clip.top().value),
+    ];
+
+    let mut output = points.to_vec();
+    for (is_x, sign, value) in edges {
+        // 辺の内側なら正になる距離
+        let distance = |p: Point| sign * (if is_x { p.0 } else { p.1 } - value);
+        let input = std::mem::take(&mut output);
+        let Some(&last) = input.last() else {
+            break;
+        };
+        let mut previous = last;
+        for &point in &input {
+            let (d0, d1) = (distance(previous), distance(point));
+            if (d0 >= 0.0) != (d1 >= 0.0) {
+                output.push(lerp(previous, point, d0 / (d0 - d1)));
+            }
+            if d1 >= 0.0 {
+                output.push(point);
+            }
+            previous = point;
+        }
+    }
+    output
+}
+
+/// 線から墨消し領域の内側を除き、残る折れ線を返す
+fn subtract_stroke(subpaths: &[Subpath], region: &PdfRect) -> Vec<Subpath> {
+    let mut pieces: Vec<Subpath> = Vec::new();
+    for subpath in subpaths {
+        let mut points = subpath.points.clone();
+        if subpath.closed {
+            points.extend(subpath.points.first().copied());
+        }
+        if points.windows(2).all(|pair| segment_inside(pair[0], pair[1], region).is_none()) {
+            pieces.push(subpath.clone());
+            continue;
+        }
+        // 直前の区間の終点が残っていれば、同じ折れ線につなげる
+        let mut connected = false;
+        for pair in points.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            let outside = match segment_inside(a, b, region) {
+                None => vec![(0.0, 1.0)],
+                Some((t0, t1)) => [(0.0, t0), (t1, 1.0)]
+                    .into_iter()
+                    .filter(|(s, e)| e > s)
+                    .collect(),
+            };
+            let joined = connected;
+            connected = false;
+            for (s, e) in outside {
+                let (start, end) = (lerp(a, b, s), lerp(a, b, e));
+                match pieces.last_mut() {
+                    Some(last) if joined && s == 0.0 => last.points.push(end),
+                    _ => pieces.push(Subpath { points: vec![start, end], closed: false }),
+                }
+                connected = e == 1.0;
+            }
+        }
+    }
+    pieces
+}
+
+/// 線分のうち矩形の内側にある範囲をパラメータ (t0, t1) で返す（Liang–Barsky）
+fn segment_inside(a: Point, b: Point, rect: &PdfRect) -> Option<(f32, f32)> {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let mut t0: f32 = 0.0;
+    let mut t1: f32 = 1.0;
+    for (p, q) in [
+        (-dx, a.0 - rect.left().value),
+        (dx, rect.right().value - a.0),
+        (-dy, a.1 - rect.bottom().value),
+        (dy, rect.top().value - a.1),
+    ] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+        } else if p < 0.0 {
+            t0 = t0.max(q / p);
+        } else {
+            t1 = t1.min(q / p);
+        }
+    }
+    (t0 < t1).then_some((t0, t1))
+}
+
+fn lerp(a: Point, b: Point, t: f32) -> Point {
+    (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t)
+}
+
+/// 画像のうち領域と重なる画素を塗りつぶす
+fn redact_image(image: &mut PdfPageImageObject, region: &PdfRect, fill: [u8; 4]) -> Result<()> {
+    // 画像オブジェクトは単位正方形を変換行列でページに配置している
+    let unit = region.transform(image.matrix()?.invert());
+    let mut pixels = image
+        .get_raw_image()
+        .context("画像を読み込めませんでした")?
+        .to_rgba8();
+    let (width, height) = pixels.dimensions();
+
+    let to_pixels = |t: f32, size: u32| (t.clamp(0.0, 1.0) * size as f32) as u32;
+    let x0 = to_pixels(unit.left().value, width);
+    let x1 = to_pixels(unit.right().value, width).max(x0 + 1).min(width);
+    // 画像の1行目は単位正方形の上端 (y = 1)
+    let y0 = to_pixels(1.0 - unit.top().value, height);
+    let y1 = to_pixels(1.0 - unit.bottom().value, height).max(y0 + 1).min(height);
+
+    let [r, g, b, _] = fill;
+    for y in y0..y1 {
+        for x in x0..x1 {
+            // 透過させると元の画素が残るため不透明で塗る
+            pixels.put_pixel(x, y, Rgba([r, g, b, 255]));
+        }
+    }
+
+    image
+        .set_image(&DynamicImage::ImageRgba8(pixels))
+        .context("画像を書き換えできませんでした")?;
+    Ok(())
+}
+
+/// 墨消しした領域からテキストが抽出できないことを確認
+pub fn verify_redaction(page: &PdfPage, region: &PdfRect) -> Result<()> {
+    let text = page
+        .text()
+        .context("墨消しの確認用にテキストを抽出できませんでした")?
+        .inside_rect(*region);
+    if text.trim().is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!("墨消し後も領域内にテキストが残っています"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(left: f32, bottom: f32, right: f32, top: f32) -> PdfRect {
+        PdfRect::new_from_values(bottom, left, top, right)
+    }
+
+    fn area(points: &[Point]) -> f32 {
+        let n = points.len();
+        (0..n)
+            .map(|i| {
+                let (a, b) = (points[i], points[(i + 1) % n]);
+                a.0 * b.1 - b.0 * a.1
+            })
+            .sum::<f32>()
+            .abs()
+            / 2.0
+    }
+
+    #[test]
+    fn fill_keeps_only_area_outside_region() {
+        // 0..10 の正方形から中央の 4..6 を除くと、面積は 100 - 4 = 96
+        let square = Subpath {
+            points: vec![(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)],
+            closed: true,
+        };
+        let region = rect(4.0, 4.0, 6.0, 6.0);
+        let pieces = subtract_fill(&[square], &region, &rect(0.0, 0.0, 10.0, 10.0));
+        assert_eq!(pieces.len(), 4);
+
+        let total: f32 = pieces.iter().flatten().map(|s| area(&s.points)).sum();
+        assert!((total - 96.0).abs() < 1e-3, "total = {}", total);
+        for subpath in pieces.iter().flatten() {
+            for &(x, y) in &subpath.points {
+                assert!(!(x > 4.0 && x < 6.0 && y > 4.0 && y < 6.0), "({}, {}) は領域内", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn stroke_is_split_where_it_crosses_region() {
+        // 横線が領域を横切ると、左右の2本に分かれる
+        let line = Subpath { points: vec![(0.0, 5.0), (10.0, 5.0)], closed: false };
+        let pieces = subtract_stroke(&[line], &rect(4.0, 0.0, 6.0, 10.0));
+        assert_eq!(
+            pieces,
+            vec![
+                Subpath { points: vec![(0.0, 5.0), (4.0, 5.0)], closed: false },
+                Subpath { points: vec![(6.0, 5.0), (10.0, 5.0)], closed: false },
+            ]
+        );
+
+        // 領域に掛からない折れ線はつながったまま残る
+        let polyline = Subpath { points: vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)], closed: false };
+        let pieces = subtract_stroke(std::slice::from_ref(&polyline), &rect(4.0, 4.0, 6.0, 6.0));
+        assert_eq!(pieces, vec![polyline]);
+    }
+
+    #[test]
+    fn curves_are_flattened_through_end_point() {
+        let segments = [
+            (PdfPathSegmentType::MoveTo, (0.0, 0.0), false),
+            (PdfPathSegmentType::BezierTo, (0.0, 10.0), false),
+            (PdfPathSegmentType::BezierTo, (10.0, 10.0), false),
+            (PdfPathSegmentType::BezierTo, (10.0, 0.0), true),
+        ];
+        let subpaths = flatten_segments(segments.into_iter());
+        assert_eq!(subpaths.len(), 1);
+        assert!(subpaths[0].closed);
+        assert_eq!(subpaths[0].points.len(), CURVE_STEPS + 1);
+        assert_eq!(*subpaths[0].points.last().unwrap(), (10.0, 0.0));
+    }
+}
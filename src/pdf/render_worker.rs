@@ -0,0 +1,150 @@
+//! バックグラウンドでのページレンダリング
+//!
+//! UIスレッドを止めないよう、ページ画像の生成をワーカースレッドで行う。
+//! 結果は `try_recv` でUIスレッドから受け取る。
+
+use crate::pdf::{PageTile, PdfDocument};
+use eframe::egui;
+use once_cell::sync::OnceCell;
+use parking_lot::{Condvar, Mutex};
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+
+/// ワーカースレッド数
+const WORKER_THREADS: usize = 2;
+
+/// レンダリング対象のドキュメント
+pub enum RenderSource {
+    /// 開いているドキュメント（編集中のページ構成・回転を反映）
+    Document(PdfDocument),
+    /// ファイル（ワーカー側で開く）
+    File(PathBuf),
+}
+
+/// レンダリングする範囲
+pub enum RenderArea {
+    /// ページ全体を `width` x `height` に収まる大きさで
+    Fit { width: u32, height: u32 },
+    /// ページの一部分
+    Tile(PageTile),
+}
+
+/// レンダリング依頼
+pub struct RenderJob {
+    pub source: RenderSource,
+    pub page: usize,
+    pub area: RenderArea,
+}
+
+impl RenderJob {
+    fn render(self) -> Option<egui::ColorImage> {
+        let doc = match self.source {
+            RenderSource::Document(doc) => doc,
+            RenderSource::File(path) => PdfDocument::open(&path)
+                .map_err(|e| log::warn!("PDFを開けません: {}: {}", path.display(), e))
+                .ok()?,
+        };
+        match self.area {
+            RenderArea::Fit { width, height } => doc.render_page_thumbnail(self.page, width, height),
+            RenderArea::Tile(tile) => doc.render_tile(self.page, &tile),
+        }
+    }
+}
+
+/// ワーカースレッドと共有する依頼キュー
+struct Queue<K> {
+    jobs: Mutex<VecDeque<(K, RenderJob)>>,
+    available: Condvar,
+    closed: Mutex<bool>,
+    /// 結果が届いたら再描画を要求する
+    repaint: OnceCell<egui::Context>,
+}
+
+/// レンダリング用のワーカースレッド群
+pub struct RenderWorker<K> {
+    queue: Arc<Queue<K>>,
+    results: Receiver<(K, Option<egui::ColorImage>)>,
+}
+
+impl<K: Send + 'static> RenderWorker<K> {
+    /// ワーカースレッドを起動
+    pub fn new(name: &str) -> Self {
+        let queue = Arc::new(Queue {
+            jobs: Mutex::new(VecDeque::new()),
+            available: Condvar::new(),
+            closed: Mutex::new(false),
+            repaint: OnceCell::new(),
+        });
+        let (sender, results) = mpsc::channel();
+
+        for i in 0..WORKER_THREADS {
+            let queue = Arc::clone(&queue);
+            let sender = sender.clone();
+            let spawned = std::thread::Builder::new()
+                .name(format!("{}-{}", name, i))
+                .spawn(move || run(&queue, &sender));
+            if let Err(e) = spawned {
+                log::error!("レンダリングスレッドを起動できません: {}", e);
+            }
+        }
+
+        Self { queue, results }
+    }
+
+    /// 依頼を追加（`urgent` なら先頭に割り込む）
+    pub fn request(&self, ctx: &egui::Context, key: K, job: RenderJob, urgent: bool) {
+        self.queue.repaint.get_or_init(|| ctx.clone());
+        let mut jobs = self.queue.jobs.lock();
+        if urgent {
+            jobs.push_front((key, job));
+        } else {
+            jobs.push_back((key, job));
+        }
+        self.queue.available.notify_one();
+    }
+
+    /// まだ処理していない依頼を全て取り消す
+    pub fn cancel_all(&self) {
+        self.queue.jobs.lock().clear();
+    }
+
+    /// 完了した結果を1件受け取る
+    pub fn try_recv(&self) -> Option<(K, Option<egui::ColorImage>)> {
+        self.results.try_recv().ok()
+    }
+}
+
+impl<K> Drop for RenderWorker<K> {
+    fn drop(&mut self) {
+        *self.queue.closed.lock() = true;
+        self.queue.jobs.lock().clear();
+        self.queue.available.notify_all();
+    }
+}
+
+/// ワーカースレッドの処理ループ
+fn run<K>(queue: &Queue<K>, sender: &Sender<(K, Option<egui::ColorImage>)>) {
+    loop {
+        let (key, job) = {
+            let mut jobs = queue.jobs.lock();
+            loop {
+                if *queue.closed.lock() {
+                    return;
+                }
+                if let Some(next) = jobs.pop_front() {
+                    break next;
+                }
+                queue.available.wait(&mut jobs);
+            }
+        };
+
+        if sender.send((key, job.render())).is_err() {
+            return;
+        }
+        if let Some(ctx) = queue.repaint.get() {
+            ctx.request_repaint();
+        }
+    }
+}
@@ -1,5 +1,6 @@
-//! スタンプとテキスト注釈の定義
+//! スタンプ・テキスト・図形などの注釈の定義
 
+use crate::pdf::{DateSeal, NameSeal, SealLayout, TextRect};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
@@ -11,6 +12,8 @@ pub enum StampType {
     Draft,
     Confidential,
     Custom(String), // カスタムスタンプ名
+    DateSeal(DateSeal),  // 日付印（円・線・文字で描く）
+    NameSeal(NameSeal),  // 認印（枠・文字で描く）
 }
 
 impl StampType {
@@ -22,6 +25,8 @@ impl StampType {
             StampType::Draft => "draft".to_string(),
             StampType::Confidential => "confidential".to_string(),
             StampType::Custom(name) => name.clone(),
+            StampType::DateSeal(_) => "date_seal".to_string(),
+            StampType::NameSeal(_) => "name_seal".to_string(),
         }
     }
 
@@ -33,6 +38,17 @@ impl StampType {
             StampType::Draft => "下書き".to_string(),
             StampType::Confidential => "機密".to_string(),
             StampType::Custom(name) => name.clone(),
+            StampType::DateSeal(_) => "日付印".to_string(),
+            StampType::NameSeal(seal) => format!("認印（{}）", seal.name.trim()),
+        }
+    }
+
+    /// 画像を使わず描く印影の配置（幅 `width`・高さ `height` の矩形内）
+    pub fn seal_layout(&self, width: f32, height: f32) -> Option<SealLayout> {
+        match self {
+            StampType::DateSeal(seal) => Some(seal.layout(width, height)),
+            StampType::NameSeal(seal) => Some(seal.layout(width, height)),
+            _ => None,
         }
     }
 
@@ -47,11 +63,16 @@ impl StampType {
     }
 }
 
-/// カスタムスタンプ情報
+/// カスタムスタンプ情報（スタンプライブラリに保存する）
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CustomStampInfo {
     pub name: String,
+    /// 画像ファイル（ライブラリのディレクトリからの相対パス）
     pub path: PathBuf,
+    /// 分類（空なら分類なし）
+    #[serde(default)]
+    pub category: String,
+    /// PNGファイルの内容
     #[serde(skip)]
     pub image_data: Vec<u8>,
 }
@@ -165,8 +186,357 @@ pub struct RectAnnotation {
     /// 塗りつぶし色 (RGBA)
     #[serde(default = "default_rect_color")]
     pub color: [u8; 4],
+    /// 墨消し（保存時に下のページ内容を削除する）
+    #[serde(default)]
+    pub redact: bool,
 }
 
 fn default_rect_color() -> [u8; 4] {
     [255, 255, 255, 255]  // 白色、不透明
 }
+
+/// 下線・取り消し線の太さ（行の高さ比）
+const MARKUP_LINE_WIDTH: f32 = 0.08;
+
+/// テキストの装飾の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MarkupType {
+    Highlight,
+    Underline,
+    StrikeOut,
+}
+
+impl MarkupType {
+    /// 日本語ラベル
+    pub fn label(&self) -> &'static str {
+        match self {
+            MarkupType::Highlight => "ハイライト",
+            MarkupType::Underline => "下線",
+            MarkupType::StrikeOut => "取り消し線",
+        }
+    }
+
+    /// 既定の色 (RGBA)
+    pub fn default_color(&self) -> [u8; 4] {
+        match self {
+            MarkupType::Highlight => [255, 230, 0, 255],
+            MarkupType::Underline => [0, 160, 60, 255],
+            MarkupType::StrikeOut => [220, 0, 0, 255],
+        }
+    }
+}
+
+/// テキストの装飾注釈（ハイライト・下線・取り消し線）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MarkupAnnotation {
+    /// ページ番号 (0-indexed)
+    pub page: usize,
+    /// 装飾の種類
+    pub markup_type: MarkupType,
+    /// 行ごとの四辺形（回転前のページ上、左上原点。文字の並びに沿った矩形の四隅）
+    pub quads: Vec<TextRect>,
+    /// 色 (RGBA)
+    pub color: [u8; 4],
+    /// 装飾したテキスト
+    #[serde(default)]
+    pub text: String,
+}
+
+impl MarkupAnnotation {
+    /// 描画する矩形（ハイライトは行全体、下線・取り消し線は行の下端・中央の線）
+    pub fn mark_rects(&self) -> Vec<TextRect> {
+        self.quads
+            .iter()
+            .map(|quad| {
+                let thickness = (quad.height * MARKUP_LINE_WIDTH).max(1.0);
+                match self.markup_type {
+                    MarkupType::Highlight => *quad,
+                    MarkupType::Underline => TextRect {
+                        y: quad.y + quad.height - thickness,
+                        height: thickness,
+                        ..*quad
+                    },
+                    MarkupType::StrikeOut => TextRect {
+                        y: quad.y + (quad.height - thickness) / 2.0,
+                        height: thickness,
+                        ..*quad
+                    },
+                }
+            })
+            .collect()
+    }
+}
+
+/// 楕円を近似する折れ線の分割数
+const ELLIPSE_SEGMENTS: usize = 64;
+/// 矢じりの開き角（ラジアン）
+const ARROW_HEAD_ANGLE: f32 = std::f32::consts::PI / 6.0;
+
+/// 手書き・図形の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ShapeType {
+    Ink,
+    Line,
+    Arrow,
+    Ellipse,
+    Polygon,
+}
+
+impl ShapeType {
+    /// 日本語ラベル
+    pub fn label(&self) -> &'static str {
+        match self {
+            ShapeType::Ink => "手書き",
+            ShapeType::Line => "直線",
+            ShapeType::Arrow => "矢印",
+            ShapeType::Ellipse => "楕円",
+            ShapeType::Polygon => "多角形",
+        }
+    }
+
+    /// すべての種類
+    pub fn all() -> [ShapeType; 5] {
+        [
+            ShapeType::Ink,
+            ShapeType::Line,
+            ShapeType::Arrow,
+            ShapeType::Ellipse,
+            ShapeType::Polygon,
+        ]
+    }
+}
+
+/// 手書き・図形の注釈
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShapeAnnotation {
+    /// ページ番号 (0-indexed)
+    pub page: usize,
+    /// 図形の種類
+    pub shape_type: ShapeType,
+    /// 頂点（回転前のページ上、左上原点）
+    /// 手書きは軌跡、直線・矢印は始点と終点、楕円は外接矩形の対角、多角形は各頂点
+    pub points: Vec<(f32, f32)>,
+    /// 線の色 (RGBA、A は不透明度)
+    pub color: [u8; 4],
+    /// 線の太さ（ポイント）
+    pub width: f32,
+}
+
+impl ShapeAnnotation {
+    /// 頂点の外接矩形（回転前のページ上、左上原点）
+    pub fn bounds(&self) -> TextRect {
+        let (mut min_x, mut min_y) = (f32::MAX, f32::MAX);
+        let (mut max_x, mut max_y) = (f32::MIN, f32::MIN);
+        for &(x, y) in &self.points {
+            min_x = min_x.min(x);
+            min_y = min_y.min(y);
+            max_x = max_x.max(x);
+            max_y = max_y.max(y);
+        }
+        if self.points.is_empty() {
+            return TextRect { x: 0.0, y: 0.0, width: 0.0, height: 0.0 };
+        }
+        TextRect {
+            x: min_x,
+            y: min_y,
+            width: max_x - min_x,
+            height: max_y - min_y,
+        }
+    }
+
+    /// 描画する折れ線（楕円は折れ線で近似し、矢印は矢じりを加える）
+    pub fn outline(&self) -> Vec<Vec<(f32, f32)>> {
+        match self.shape_type {
+            ShapeType::Ink | ShapeType::Line => vec![self.points.clone()],
+            ShapeType::Arrow => {
+                let (&start, &end) = match (self.points.first(), self.points.last()) {
+                    (Some(start), Some(end)) => (start, end),
+                    _ => return Vec::new(),
+                };
+                let angle = (start.1 - end.1).atan2(start.0 - end.0);
+                let length = (self.width * 4.0).max(10.0);
+                let head = |a: f32| (end.0 + length * a.cos(), end.1 + length * a.sin());
+                vec![
+                    vec![start, end],
+                    vec![head(angle + ARROW_HEAD_ANGLE), end, head(angle - ARROW_HEAD_ANGLE)],
+                ]
+            }
+            ShapeType::Ellipse => {
+                let bounds = self.bounds();
+                let (rx, ry) = (bounds.width / 2.0, bounds.height / 2.0);
+                let (cx, cy) = (bounds.x + rx, bounds.y + ry);
+                let points = (0..=ELLIPSE_SEGMENTS)
+                    .map(|i| {
+                        let t = i as f32 / ELLIPSE_SEGMENTS as f32 * std::f32::consts::TAU;
+                        (cx + rx * t.cos(), cy + ry * t.sin())
+                    })
+                    .collect();
+                vec![points]
+            }
+            ShapeType::Polygon => {
+                let mut points = self.points.clone();
+                if let Some(&first) = self.points.first() {
+                    points.push(first);
+                }
+                vec![points]
+            }
+        }
+    }
+
+    /// 頂点を平行移動
+    pub fn translate(&mut self, dx: f32, dy: f32) {
+        for point in &mut self.points {
+            point.0 += dx;
+            point.1 += dy;
+        }
+    }
+}
+
+/// 手書きの軌跡をなめらかにする
+///
+/// `min_distance` より近い点を間引いてから、前後の点との移動平均を取る（始点と終点は残す）。
+pub fn smooth_stroke(points: &[(f32, f32)], min_distance: f32) -> Vec<(f32, f32)> {
+    let mut thinned: Vec<(f32, f32)> = Vec::with_capacity(points.len());
+    for &point in points {
+        let far = thinned.last().is_none_or(|&(x, y): &(f32, f32)| {
+            (point.0 - x).hypot(point.1 - y) >= min_distance
+        });
+        if far {
+            thinned.push(point);
+        }
+    }
+    if let (Some(&last), Some(&kept)) = (points.last(), thinned.last()) {
+        if last != kept {
+            thinned.push(last);
+        }
+    }
+    if thinned.len() < 3 {
+        return thinned;
+    }
+
+    let mut smoothed = Vec::with_capacity(thinned.len());
+    smoothed.push(thinned[0]);
+    for window in thinned.windows(3) {
+        let [a, b, c] = [window[0], window[1], window[2]];
+        smoothed.push(((a.0 + 2.0 * b.0 + c.0) / 4.0, (a.1 + 2.0 * b.1 + c.1) / 4.0));
+    }
+    smoothed.push(thinned[thinned.len() - 1]);
+    smoothed
+}
+
+/// コメントのアイコンの大きさ（ポイント）
+pub const COMMENT_ICON_SIZE: f32 = 20.0;
+
+/// コメントの状態
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum CommentStatus {
+    #[default]
+    Open,
+    Resolved,
+}
+
+impl CommentStatus {
+    /// 日本語ラベル
+    pub fn label(&self) -> &'static str {
+        match self {
+            CommentStatus::Open => "未対応",
+            CommentStatus::Resolved => "対応済み",
+        }
+    }
+}
+
+/// コメントへの返信
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommentReply {
+    pub author: String,
+    /// 作成日時（RFC 3339）
+    pub created: String,
+    pub text: String,
+}
+
+impl CommentReply {
+    /// 現在時刻で返信を作成
+    pub fn new(author: String, text: String) -> Self {
+        Self {
+            author,
+            created: chrono::Local::now().to_rfc3339(),
+            text,
+        }
+    }
+}
+
+/// 付箋コメント（ページ上のアイコンと、返信を含むスレッド）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommentAnnotation {
+    /// ページ番号 (0-indexed)
+    pub page: usize,
+    /// アイコンのX座標（ポイント）
+    pub x: f32,
+    /// アイコンのY座標（ポイント）
+    pub y: f32,
+    pub author: String,
+    /// 作成日時（RFC 3339）
+    pub created: String,
+    pub text: String,
+    #[serde(default)]
+    pub status: CommentStatus,
+    #[serde(default)]
+    pub replies: Vec<CommentReply>,
+}
+
+impl CommentAnnotation {
+    /// 現在時刻でコメントを作成
+    pub fn new(page: usize, x: f32, y: f32, author: String, text: String) -> Self {
+        Self {
+            page,
+            x,
+            y,
+            author,
+            created: chrono::Local::now().to_rfc3339(),
+            text,
+            status: CommentStatus::Open,
+            replies: Vec::new(),
+        }
+    }
+}
+
+/// 作成日時を表示用（ローカル時刻）に整形
+pub fn display_time(created: &str) -> String {
+    chrono::DateTime::parse_from_rfc3339(created)
+        .map(|time| time.with_timezone(&chrono::Local).format("%Y/%m/%d %H:%M").to_string())
+        .unwrap_or_else(|_| created.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smooth_stroke_thins_and_keeps_endpoints() {
+        let points = [(0.0, 0.0), (0.25, 0.0), (2.0, 2.0), (4.0, 0.0), (4.5, 0.0)];
+        let smoothed = smooth_stroke(&points, 1.0);
+        // 近すぎる点は間引かれ、始点と終点はそのまま残る
+        assert_eq!(smoothed, vec![(0.0, 0.0), (2.0, 1.0), (3.625, 0.5), (4.5, 0.0)]);
+    }
+
+    #[test]
+    fn arrow_outline_adds_head_at_end() {
+        let shape = ShapeAnnotation {
+            page: 0,
+            shape_type: ShapeType::Arrow,
+            points: vec![(0.0, 0.0), (100.0, 0.0)],
+            color: [255, 0, 0, 255],
+            width: 2.0,
+        };
+        let outline = shape.outline();
+        assert_eq!(outline[0], vec![(0.0, 0.0), (100.0, 0.0)]);
+        // 矢じりは終点から始点側へ開く
+        let head = &outline[1];
+        assert_eq!(head[1], (100.0, 0.0));
+        assert!(head[0].0 < 100.0 && head[2].0 < 100.0);
+        assert!((head[0].1 + head[2].1).abs() < 0.001);
+
+        let bounds = shape.bounds();
+        assert_eq!((bounds.width, bounds.height), (100.0, 0.0));
+    }
+}
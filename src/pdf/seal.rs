@@ -0,0 +1,350 @@
+//! 印影 - 日付印（部署名・日付・氏名を円の中に配置）と認印（姓を縦書き）
+//!
+//! 印影は画像ではなく枠・線・文字の配置（`SealLayout`）として組み立て、
+//! エディタ表示とPDFへの書き込みの両方で同じ配置から描く。
+//! 座標はスタンプの矩形内の左上原点・下向きY軸のポイント単位。
+
+use chrono::{Datelike, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+/// 元号と開始日（新しい順）
+const ERAS: &[(&str, i32, u32, u32)] = &[
+    ("令和", 2019, 5, 1),
+    ("平成", 1989, 1, 8),
+    ("昭和", 1926, 12, 25),
+    ("大正", 1912, 7, 30),
+    ("明治", 1868, 10, 23),
+];
+
+/// 枠線の太さ（印影の直径比）
+const STROKE_RATIO: f32 = 0.035;
+/// 中央の帯の半分の高さ（印影の直径比）
+const BAND_RATIO: f32 = 0.19;
+/// 日付の文字の大きさの上限（印影の直径比）
+const DATE_FONT_RATIO: f32 = 0.16;
+/// 部署名・氏名の文字の大きさの上限（印影の直径比）
+const NAME_FONT_RATIO: f32 = 0.2;
+/// 文字を並べる幅（その高さでの円の幅に対する比）
+const TEXT_FILL: f32 = 0.85;
+/// 認印の枠線の太さ（印影の大きさ比）
+const NAME_STROKE_RATIO: f32 = 0.05;
+/// 認印の文字を並べる範囲（枠の内側に対する比）
+const NAME_FILL: f32 = 0.9;
+/// 認印を1列で彫る最大の文字数（超えたら2列）
+const NAME_COLUMN_CHARS: usize = 3;
+
+/// 日付印の内容
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DateSeal {
+    /// 上段（部署名）
+    pub department: String,
+    /// 下段（氏名）
+    pub name: String,
+    /// 日付（YYYY-MM-DD、読めない場合はそのまま表示）
+    pub date: String,
+    /// 和暦で表示
+    pub wareki: bool,
+    /// 印影の色 (RGB)
+    pub color: [u8; 3],
+}
+
+impl Default for DateSeal {
+    fn default() -> Self {
+        Self {
+            department: String::new(),
+            name: String::new(),
+            date: today(),
+            wareki: false,
+            color: [220, 30, 30],
+        }
+    }
+}
+
+/// 今日の日付（YYYY-MM-DD）
+pub fn today() -> String {
+    Local::now().date_naive().format("%Y-%m-%d").to_string()
+}
+
+/// 西暦の日付を元号と年に変換（明治より前は None）
+pub fn to_wareki(date: NaiveDate) -> Option<(&'static str, i32)> {
+    ERAS.iter().find_map(|&(era, year, month, day)| {
+        let start = NaiveDate::from_ymd_opt(year, month, day)?;
+        (date >= start).then_some((era, date.year() - year + 1))
+    })
+}
+
+impl DateSeal {
+    /// 中央の帯に表示する日付（和暦は「令和8.10.17」、元年は「令和元.5.1」）
+    pub fn date_text(&self) -> String {
+        let Ok(date) = NaiveDate::parse_from_str(self.date.trim(), "%Y-%m-%d") else {
+            return self.date.trim().to_string();
+        };
+        match to_wareki(date).filter(|_| self.wareki) {
+            Some((era, 1)) => format!("{}元.{}.{}", era, date.month(), date.day()),
+            Some((era, year)) => format!("{}{}.{}.{}", era, year, date.month(), date.day()),
+            None => format!("{}.{:02}.{:02}", date.year(), date.month(), date.day()),
+        }
+    }
+
+    /// 幅 `width`・高さ `height` の矩形に収まる印影の配置
+    pub fn layout(&self, width: f32, height: f32) -> SealLayout {
+        let size = width.min(height);
+        let stroke = size * STROKE_RATIO;
+        let radius = (size - stroke) / 2.0;
+        let (cx, cy) = (width / 2.0, height / 2.0);
+        let band = size * BAND_RATIO;
+        // 高さ dy での円の幅
+        let chord = |dy: f32| 2.0 * (radius * radius - dy * dy).max(0.0).sqrt();
+
+        let lines = [-band, band]
+            .map(|dy| {
+                let half = chord(dy) / 2.0;
+                ((cx - half, cy + dy), (cx + half, cy + dy))
+            })
+            .to_vec();
+
+        // 上段・下段は帯と円の間の中ほど、日付は帯の中央
+        let outer = (radius + band) / 2.0;
+        let outer_height = radius - band;
+        let mut texts = Vec::new();
+        for (text, dy, max_size) in [
+            (self.department.trim().to_string(), -outer, (size * NAME_FONT_RATIO).min(outer_height * 0.6)),
+            (self.date_text(), 0.0, (size * DATE_FONT_RATIO).min(band * 1.5)),
+            (self.name.trim().to_string(), outer, (size * NAME_FONT_RATIO).min(outer_height * 0.6)),
+        ] {
+            if text.is_empty() {
+                continue;
+            }
+            // 上段・下段は円に近い側の幅で収める
+            let available = chord(dy.abs() + max_size / 2.0) * TEXT_FILL;
+            let font_size = max_size.min(available / em_width(&text));
+            texts.push(SealText {
+                text,
+                center: (cx, cy + dy),
+                font_size,
+            });
+        }
+
+        SealLayout {
+            center: (cx, cy),
+            radius,
+            square: false,
+            stroke,
+            color: self.color,
+            lines,
+            texts,
+        }
+    }
+}
+
+/// 認印の形
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum SealShape {
+    #[default]
+    Round,   // 丸印
+    Square,  // 角印
+}
+
+impl SealShape {
+    pub fn label(&self) -> &'static str {
+        match self {
+            SealShape::Round => "丸印",
+            SealShape::Square => "角印",
+        }
+    }
+}
+
+/// 認印の内容（姓を縦書きにした印影）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NameSeal {
+    /// 彫る文字（姓）
+    pub name: String,
+    pub shape: SealShape,
+    /// 印影の色 (RGB)
+    pub color: [u8; 3],
+}
+
+impl Default for NameSeal {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            shape: SealShape::Round,
+            color: [220, 30, 30],
+        }
+    }
+}
+
+impl NameSeal {
+    /// 幅 `width`・高さ `height` の矩形に収まる印影の配置
+    ///
+    /// 3文字までは1列、4文字以上は右の列から2列に縦書きする。
+    pub fn layout(&self, width: f32, height: f32) -> SealLayout {
+        let size = width.min(height);
+        let stroke = size * NAME_STROKE_RATIO;
+        let radius = (size - stroke) / 2.0;
+        let (cx, cy) = (width / 2.0, height / 2.0);
+
+        let chars: Vec<char> = self.name.chars().filter(|c| !c.is_whitespace()).collect();
+        let columns = if chars.len() > NAME_COLUMN_CHARS { 2 } else { 1 };
+        let rows = chars.len().div_ceil(columns).max(1);
+        // 文字の升目の大きさ（丸印は升目を並べた矩形の対角線が円に収まるように）
+        let inner = 2.0 * (radius - stroke) * NAME_FILL;
+        let cell = match self.shape {
+            SealShape::Round => inner / ((columns * columns + rows * rows) as f32).sqrt(),
+            SealShape::Square => inner / columns.max(rows) as f32,
+        };
+
+        let texts = chars
+            .iter()
+            .enumerate()
+            .map(|(i, c)| {
+                let (column, row) = ((i / rows) as f32, (i % rows) as f32);
+                SealText {
+                    text: c.to_string(),
+                    center: (
+                        cx + ((columns - 1) as f32 / 2.0 - column) * cell,
+                        cy + (row - (rows - 1) as f32 / 2.0) * cell,
+                    ),
+                    font_size: cell,
+                }
+            })
+            .collect();
+
+        SealLayout {
+            center: (cx, cy),
+            radius,
+            square: self.shape == SealShape::Square,
+            stroke,
+            color: self.color,
+            lines: Vec::new(),
+            texts,
+        }
+    }
+}
+
+/// 全角を1、半角を0.55として数えた文字列の幅（文字の大きさ比）
+pub fn em_width(text: &str) -> f32 {
+    text.chars()
+        .map(|c| if c.is_ascii() || ('\u{ff61}'..='\u{ff9f}').contains(&c) { 0.55 } else { 1.0 })
+        .sum()
+}
+
+/// 印影の配置
+#[derive(Debug, Clone, PartialEq)]
+pub struct SealLayout {
+    pub center: (f32, f32),
+    /// 枠の半径（角印は一辺の半分）
+    pub radius: f32,
+    /// 枠が正方形（角印）
+    pub square: bool,
+    /// 枠線・区切り線の太さ
+    pub stroke: f32,
+    /// 印影の色 (RGB)
+    pub color: [u8; 3],
+    /// 区切り線（始点, 終点）
+    pub lines: Vec<((f32, f32), (f32, f32))>,
+    pub texts: Vec<SealText>,
+}
+
+/// 印影の中の1行の文字
+#[derive(Debug, Clone, PartialEq)]
+pub struct SealText {
+    pub text: String,
+    /// 文字の中心
+    pub center: (f32, f32),
+    pub font_size: f32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn era_changes_on_its_first_day() {
+        assert_eq!(to_wareki(date(2019, 4, 30)), Some(("平成", 31)));
+        assert_eq!(to_wareki(date(2019, 5, 1)), Some(("令和", 1)));
+        assert_eq!(to_wareki(date(1989, 1, 7)), Some(("昭和", 64)));
+        assert_eq!(to_wareki(date(1989, 1, 8)), Some(("平成", 1)));
+        assert_eq!(to_wareki(date(2026, 10, 17)), Some(("令和", 8)));
+        assert_eq!(to_wareki(date(1868, 1, 1)), None);
+    }
+
+    #[test]
+    fn date_text_follows_calendar_setting() {
+        let mut seal = DateSeal {
+            date: "2019-05-01".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(seal.date_text(), "2019.05.01");
+        seal.wareki = true;
+        assert_eq!(seal.date_text(), "令和元.5.1");
+        seal.date = "2026-10-17".to_string();
+        assert_eq!(seal.date_text(), "令和8.10.17");
+        // 日付として読めないものはそのまま表示する
+        seal.date = "受付".to_string();
+        assert_eq!(seal.date_text(), "受付");
+    }
+
+    #[test]
+    fn layout_keeps_text_inside_circle() {
+        let seal = DateSeal {
+            department: "株式会社サンプル総務部".to_string(),
+            name: "山田".to_string(),
+            date: "2026-10-17".to_string(),
+            ..Default::default()
+        };
+        let layout = seal.layout(60.0, 80.0);
+        assert_eq!(layout.center, (30.0, 40.0));
+        assert_eq!(layout.texts.len(), 3);
+        for text in &layout.texts {
+            let half_width = em_width(&text.text) * text.font_size / 2.0;
+            let half_height = text.font_size / 2.0;
+            // 文字の外接矩形の四隅が円の中にある
+            for (dx, dy) in [(-half_width, -half_height), (half_width, half_height)] {
+                let x = text.center.0 + dx - layout.center.0;
+                let y = text.center.1 + dy - layout.center.1;
+                assert!(x * x + y * y < layout.radius * layout.radius, "{} がはみ出す", text.text);
+            }
+        }
+        // 長い部署名は小さく、短い氏名は上限の大きさで描く
+        assert!(layout.texts[0].font_size < layout.texts[2].font_size);
+    }
+
+    #[test]
+    fn name_seal_is_written_vertically() {
+        let seal = NameSeal {
+            name: "山 田".to_string(),
+            ..Default::default()
+        };
+        let layout = seal.layout(50.0, 50.0);
+        let [first, second] = &layout.texts[..] else {
+            panic!("空白を除いた2文字になる");
+        };
+        assert_eq!((first.text.as_str(), second.text.as_str()), ("山", "田"));
+        assert_eq!(first.center.0, second.center.0);
+        assert!(first.center.1 < second.center.1);
+        // 升目の四隅が円の中にある
+        for text in &layout.texts {
+            let half = text.font_size / 2.0;
+            let x = (text.center.0 - layout.center.0).abs() + half;
+            let y = (text.center.1 - layout.center.1).abs() + half;
+            assert!(x * x + y * y < layout.radius * layout.radius);
+        }
+
+        // 4文字以上は右の列から2列に並べる
+        let seal = NameSeal {
+            name: "勅使河原".to_string(),
+            shape: SealShape::Square,
+            ..Default::default()
+        };
+        let layout = seal.layout(50.0, 50.0);
+        let columns: Vec<f32> = layout.texts.iter().map(|t| t.center.0).collect();
+        assert!(columns[0] == columns[1] && columns[2] == columns[3]);
+        assert!(columns[0] > columns[2]);
+        assert!(layout.square);
+    }
+}
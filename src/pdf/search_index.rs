@@ -0,0 +1,231 @@
+//! フォルダ内PDFの全文検索用の索引
+//!
+//! ページごとの本文を検索用に正規化して保持し、更新日時が変わったファイルだけ読み直す。
+//! 索引はキャッシュディレクトリにフォルダごとのJSONファイルとして保存する。
+
+use crate::pdf::{normalize, PdfDocument};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// 索引ファイルの形式のバージョン（正規化の方法を変えたら上げて作り直す）
+const INDEX_VERSION: u32 = 1;
+
+/// 索引に登録した1ファイル
+#[derive(Serialize, Deserialize)]
+struct IndexedDocument {
+    modified: SystemTime,
+    /// 正規化したページごとの本文
+    pages: Vec<String>,
+}
+
+/// フォルダ内PDFの全文検索索引
+#[derive(Serialize, Deserialize, Default)]
+pub struct SearchIndex {
+    #[serde(default)]
+    version: u32,
+    folder: PathBuf,
+    documents: HashMap<PathBuf, IndexedDocument>,
+}
+
+/// 検索結果（1ファイル分）
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexHit {
+    pub path: PathBuf,
+    /// 一致したページと一致数（ページ順）
+    pub pages: Vec<(usize, usize)>,
+    /// 一致数の合計（多いほど上位）
+    pub score: usize,
+}
+
+impl SearchIndex {
+    /// フォルダの索引を読み込み（無い・壊れている・形式が古い場合は空の索引）
+    pub fn load(folder: &Path) -> Self {
+        let loaded = Self::index_path(folder)
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|content| serde_json::from_str::<Self>(&content).ok())
+            .filter(|index| index.version == INDEX_VERSION && index.folder == folder);
+        loaded.unwrap_or_else(|| Self {
+            version: INDEX_VERSION,
+            folder: folder.to_path_buf(),
+            documents: HashMap::new(),
+        })
+    }
+
+    /// 索引を保存
+    pub fn save(&self) -> Result<()> {
+        let path = Self::index_path(&self.folder).context("索引の保存先がありません")?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).context("索引の保存先を作成できませんでした")?;
+        }
+        let content = serde_json::to_string(self)?;
+        std::fs::write(&path, content).context("索引を保存できませんでした")
+    }
+
+    /// 索引ファイルのパス（キャッシュディレクトリ内、フォルダのパスから名前を決める）
+    fn index_path(folder: &Path) -> Option<PathBuf> {
+        #[cfg(windows)]
+        let cache_dir = std::env::var_os("LOCALAPPDATA").map(PathBuf::from);
+        #[cfg(not(windows))]
+        let cache_dir = std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")));
+
+        let mut hasher = DefaultHasher::new();
+        folder.hash(&mut hasher);
+        Some(
+            cache_dir?
+                .join("pdf-viewer")
+                .join("index")
+                .join(format!("{:016x}.json", hasher.finish())),
+        )
+    }
+
+    /// 無くなったファイルを索引から除き、読み直しが必要なファイル（新規・更新）を返す
+    pub fn refresh(&mut self, files: &[(PathBuf, SystemTime)]) -> Vec<(PathBuf, SystemTime)> {
+        self.documents
+            .retain(|path, _| files.iter().any(|(p, _)| p == path));
+        files
+            .iter()
+            .filter(|(path, modified)| {
+                self.documents
+                    .get(path)
+                    .is_none_or(|doc| doc.modified != *modified)
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// ファイルの本文を登録（`pages` はページごとの本文、正規化はここで行う）
+    pub fn insert(&mut self, path: PathBuf, modified: SystemTime, pages: &[String]) {
+        let pages = pages.iter().map(|text| normalize(text)).collect();
+        self.documents.insert(path, IndexedDocument { modified, pages });
+    }
+
+    /// 登録済みのファイル数
+    pub fn document_count(&self) -> usize {
+        self.documents.len()
+    }
+
+    /// 語句を含むファイルを一致数の多い順に返す
+    pub fn search(&self, query: &str) -> Vec<IndexHit> {
+        let query = normalize(query.trim());
+        if query.is_empty() {
+            return Vec::new();
+        }
+
+        let mut hits: Vec<IndexHit> = self
+            .documents
+            .iter()
+            .filter_map(|(path, doc)| {
+                let pages: Vec<(usize, usize)> = doc
+                    .pages
+                    .iter()
+                    .enumerate()
+                    .map(|(page, text)| (page, text.matches(&query).count()))
+                    .filter(|&(_, count)| count > 0)
+                    .collect();
+                let score = pages.iter().map(|(_, count)| count).sum();
+                (score > 0).then(|| IndexHit {
+                    path: path.clone(),
+                    pages,
+                    score,
+                })
+            })
+            .collect();
+        hits.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.path.cmp(&b.path)));
+        hits
+    }
+}
+
+/// PDFの全ページの本文を読み取る
+pub fn read_pages(path: &Path) -> Result<Vec<String>> {
+    let doc = PdfDocument::open(path)?;
+    (0..doc.page_count())
+        .map(|page| doc.page_text(page).map(|text| text.text()))
+        .collect()
+}
+
+/// フォルダ以下（サブフォルダを含む）のPDFと更新日時
+pub fn list_pdfs(folder: &Path) -> Vec<(PathBuf, SystemTime)> {
+    let mut files = Vec::new();
+    let mut folders = vec![folder.to_path_buf()];
+    while let Some(dir) = folders.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            // シンボリックリンクのフォルダはたどらない（循環を避ける）
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if file_type.is_dir() {
+                folders.push(path);
+            } else if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("pdf")) {
+                let modified = entry
+                    .metadata()
+                    .and_then(|m| m.modified())
+                    .unwrap_or(SystemTime::UNIX_EPOCH);
+                files.push((path, modified));
+            }
+        }
+    }
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn index_with(documents: &[(&str, &[&str])]) -> SearchIndex {
+        let mut index = SearchIndex::default();
+        for (path, pages) in documents {
+            let pages: Vec<String> = pages.iter().map(|p| p.to_string()).collect();
+            index.insert(PathBuf::from(path), SystemTime::UNIX_EPOCH, &pages);
+        }
+        index
+    }
+
+    #[test]
+    fn search_ranks_documents_by_match_count() {
+        let index = index_with(&[
+            ("a.pdf", &["見積書", "請求書 セイキュウ"]),
+            ("b.pdf", &["せいきゅう", "請求書", "請求書の控え 請求書"]),
+            ("c.pdf", &["納品書"]),
+        ]);
+
+        let hits = index.search("請求書");
+        let paths: Vec<&Path> = hits.iter().map(|h| h.path.as_path()).collect();
+        assert_eq!(paths, vec![Path::new("b.pdf"), Path::new("a.pdf")]);
+        assert_eq!(hits[0].pages, vec![(1, 1), (2, 2)]);
+        assert_eq!(hits[0].score, 3);
+
+        // ひらがな・カタカナ、全角・半角の違いは無視する
+        let hits = index.search("ｾｲｷｭｳ");
+        assert_eq!(hits.len(), 2);
+        assert!(index.search("  ").is_empty());
+    }
+
+    #[test]
+    fn refresh_returns_new_and_modified_files() {
+        let mut index = index_with(&[("a.pdf", &["a"]), ("b.pdf", &["b"]), ("gone.pdf", &["c"])]);
+        let later = SystemTime::UNIX_EPOCH + Duration::from_secs(60);
+        let files = vec![
+            (PathBuf::from("a.pdf"), SystemTime::UNIX_EPOCH),
+            (PathBuf::from("b.pdf"), later),
+            (PathBuf::from("new.pdf"), later),
+        ];
+
+        let mut stale: Vec<PathBuf> = index.refresh(&files).into_iter().map(|(p, _)| p).collect();
+        stale.sort();
+        assert_eq!(stale, vec![PathBuf::from("b.pdf"), PathBuf::from("new.pdf")]);
+        assert_eq!(index.document_count(), 2);
+        assert!(index.search("c").is_empty());
+    }
+}
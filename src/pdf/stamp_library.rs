@@ -0,0 +1,418 @@
+//! スタンプライブラリ - 登録した画像スタンプと作成した認印を設定ディレクトリに保存し、次回の起動時にも使う
+//!
+//! 画像は登録時にライブラリの `images` ディレクトリへコピーする。
+//! チームで共有するときは、画像を埋め込んだ1つのファイル（バンドル）に書き出して読み込む。
+
+use crate::pdf::{CustomStampInfo, NameSeal};
+use anyhow::{bail, Context, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine as _;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+/// 画像をコピーするディレクトリ（ライブラリのディレクトリからの相対パス）
+const IMAGE_DIR: &str = "images";
+/// バンドルの形式のバージョン
+const BUNDLE_VERSION: u32 = 1;
+
+/// 保存したスタンプの一覧
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct StampLibrary {
+    /// 画像スタンプ（パネルに並べる順）
+    #[serde(default)]
+    pub stamps: Vec<CustomStampInfo>,
+    /// 認印（パネルに並べる順）
+    #[serde(default)]
+    pub seals: Vec<NameSeal>,
+    /// 保存先（設定ディレクトリが分からない場合は保存しない）
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+/// 共有用に書き出したライブラリ
+#[derive(Serialize, Deserialize)]
+struct Bundle {
+    version: u32,
+    #[serde(default)]
+    stamps: Vec<BundledStamp>,
+    #[serde(default)]
+    seals: Vec<NameSeal>,
+}
+
+/// バンドル内の画像スタンプ
+#[derive(Serialize, Deserialize)]
+struct BundledStamp {
+    name: String,
+    #[serde(default)]
+    category: String,
+    /// PNGファイルの内容（Base64）
+    png: String,
+}
+
+impl StampLibrary {
+    /// 設定ディレクトリのライブラリを読み込み（無い・壊れている場合は空）
+    ///
+    /// 壊れたファイルは上書きしないよう `.bak` を付けた名前に移して残す。
+    pub fn load() -> Self {
+        match library_path() {
+            Some(path) => Self::load_from(&path),
+            None => Self::default(),
+        }
+    }
+
+    fn load_from(path: &Path) -> Self {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => {
+                // 読めないファイルを空のライブラリで上書きしないよう、保存先を持たせない
+                log::error!("スタンプライブラリを読み込めません: {} ({})", path.display(), e);
+                return Self::default();
+            }
+        };
+        let mut library = if content.is_empty() {
+            Self::default()
+        } else {
+            match serde_json::from_str::<Self>(&content) {
+                Ok(library) => library,
+                Err(e) => {
+                    log::error!("スタンプライブラリが壊れています: {} ({})", path.display(), e);
+                    let backup = path.with_extension("json.bak");
+                    if let Err(e) = std::fs::rename(path, &backup) {
+                        log::error!(
+                            "壊れたスタンプライブラリを退避できません: {} ({})",
+                            backup.display(),
+                            e
+                        );
+                        return Self::default();
+                    }
+                    log::warn!("壊れたスタンプライブラリを退避しました: {}", backup.display());
+                    Self::default()
+                }
+            }
+        };
+        library.path = Some(path.to_path_buf());
+
+        // 画像を読めないスタンプは一覧から外す（次の保存で消える）
+        let dir = library.dir().map(Path::to_path_buf).unwrap_or_default();
+        library.stamps.retain_mut(|stamp| match std::fs::read(dir.join(&stamp.path)) {
+            Ok(data) => {
+                stamp.image_data = data;
+                true
+            }
+            Err(e) => {
+                log::warn!("スタンプ画像を読み込めません: {} ({})", stamp.path.display(), e);
+                false
+            }
+        });
+        library
+    }
+
+    /// ライブラリを保存
+    pub fn save(&self) -> Result<()> {
+        let path = self.path.as_ref().context("スタンプライブラリの保存先がありません")?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).context("スタンプライブラリの保存先を作成できませんでした")?;
+        }
+        let content = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, content).context("スタンプライブラリを保存できませんでした")
+    }
+
+    /// ライブラリのディレクトリ
+    fn dir(&self) -> Option<&Path> {
+        self.path.as_deref().and_then(Path::parent)
+    }
+
+    /// 画像スタンプを追加し、登録した名前を返す（同名があれば番号を付ける）
+    ///
+    /// `png` はPNGファイルの内容で、ライブラリのディレクトリにコピーする。
+    pub fn add_stamp(&mut self, name: &str, category: &str, png: Vec<u8>) -> Result<String> {
+        let dir = self.dir().context("スタンプライブラリの保存先がありません")?;
+        let file = Path::new(IMAGE_DIR).join(format!("{}.png", uuid::Uuid::new_v4()));
+        let image_path = dir.join(&file);
+        if let Some(parent) = image_path.parent() {
+            std::fs::create_dir_all(parent).context("スタンプ画像の保存先を作成できませんでした")?;
+        }
+        std::fs::write(&image_path, &png).context("スタンプ画像をコピーできませんでした")?;
+
+        let name = self.unique_name(name.trim());
+        self.stamps.push(CustomStampInfo {
+            name: name.clone(),
+            path: file,
+            category: category.trim().to_string(),
+            image_data: png,
+        });
+        Ok(name)
+    }
+
+    /// 使われていない名前（使われていれば「名前 (2)」のように番号を付ける）
+    fn unique_name(&self, name: &str) -> String {
+        let taken = |candidate: &str| self.stamps.iter().any(|s| s.name == candidate);
+        if !taken(name) {
+            return name.to_string();
+        }
+        (2..)
+            .map(|n| format!("{} ({})", name, n))
+            .find(|candidate| !taken(candidate))
+            .unwrap_or_default()
+    }
+
+    /// 画像スタンプの名前を変更
+    pub fn rename_stamp(&mut self, index: usize, name: &str) -> Result<()> {
+        let name = name.trim();
+        if name.is_empty() {
+            bail!("スタンプ名が空です");
+        }
+        if self.stamps.iter().enumerate().any(|(i, s)| i != index && s.name == name) {
+            bail!("「{}」という名前のスタンプは既にあります", name);
+        }
+        let stamp = self.stamps.get_mut(index).context("スタンプが見つかりません")?;
+        stamp.name = name.to_string();
+        Ok(())
+    }
+
+    /// 画像スタンプの並び順を変える（`from` のスタンプを `to` の位置へ）
+    pub fn move_stamp(&mut self, from: usize, to: usize) {
+        if from < self.stamps.len() && to < self.stamps.len() {
+            let stamp = self.stamps.remove(from);
+            self.stamps.insert(to, stamp);
+        }
+    }
+
+    /// 画像スタンプを削除（コピーした画像も消す）
+    pub fn remove_stamp(&mut self, index: usize) -> Option<CustomStampInfo> {
+        if index >= self.stamps.len() {
+            return None;
+        }
+        let stamp = self.stamps.remove(index);
+        if let Some(dir) = self.dir() {
+            if let Err(e) = std::fs::remove_file(dir.join(&stamp.path)) {
+                log::warn!("スタンプ画像を削除できません: {} ({})", stamp.path.display(), e);
+            }
+        }
+        Some(stamp)
+    }
+
+    /// 画像スタンプの分類の一覧（名前順）
+    pub fn categories(&self) -> Vec<String> {
+        let categories: BTreeSet<&str> = self
+            .stamps
+            .iter()
+            .map(|s| s.category.as_str())
+            .filter(|c| !c.is_empty())
+            .collect();
+        categories.into_iter().map(str::to_string).collect()
+    }
+
+    /// 認印を追加し、その位置を返す（同じ内容の認印があれば追加しない）
+    pub fn add_seal(&mut self, seal: NameSeal) -> usize {
+        if let Some(index) = self.seals.iter().position(|s| *s == seal) {
+            return index;
+        }
+        self.seals.push(seal);
+        self.seals.len() - 1
+    }
+
+    /// ライブラリ全体を共有用のファイルに書き出す
+    pub fn export(&self, path: &Path) -> Result<()> {
+        let bundle = Bundle {
+            version: BUNDLE_VERSION,
+            stamps: self
+                .stamps
+                .iter()
+                .map(|s| BundledStamp {
+                    name: s.name.clone(),
+                    category: s.category.clone(),
+                    png: BASE64.encode(&s.image_data),
+                })
+                .collect(),
+            seals: self.seals.clone(),
+        };
+        let content = serde_json::to_string(&bundle)?;
+        std::fs::write(path, content).context("スタンプライブラリを書き出せませんでした")
+    }
+
+    /// 共有用のファイルを読み込んでライブラリに追加し、追加した数を返す
+    ///
+    /// 同じ画像のスタンプと同じ内容の認印は追加しない。
+    pub fn import(&mut self, path: &Path) -> Result<usize> {
+        let content = std::fs::read_to_string(path).context("ファイルを読み込めませんでした")?;
+        let bundle: Bundle = serde_json::from_str(&content).context("スタンプライブラリのファイルではありません")?;
+        if bundle.version > BUNDLE_VERSION {
+            bail!("新しいバージョンで書き出されたファイルです");
+        }
+
+        // 壊れた画像があれば何も追加しない
+        let stamps = bundle
+            .stamps
+            .into_iter()
+            .map(|stamp| {
+                let png = BASE64
+                    .decode(&stamp.png)
+                    .ok()
+                    .filter(|png| image::load_from_memory(png).is_ok())
+                    .with_context(|| format!("スタンプ「{}」の画像が壊れています", stamp.name))?;
+                Ok((stamp, png))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut count = 0;
+        for (stamp, png) in stamps {
+            if self.stamps.iter().any(|s| s.image_data == png) {
+                continue;
+            }
+            self.add_stamp(&stamp.name, &stamp.category, png)?;
+            count += 1;
+        }
+        for seal in bundle.seals {
+            if !self.seals.contains(&seal) {
+                self.seals.push(seal);
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+}
+
+/// ライブラリファイルのパス（設定ディレクトリ内）
+fn library_path() -> Option<PathBuf> {
+    #[cfg(windows)]
+    let config_dir = std::env::var_os("APPDATA").map(PathBuf::from);
+    #[cfg(not(windows))]
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+
+    Some(config_dir?.join("pdf-viewer").join("stamps").join("library.json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdf::SealShape;
+
+    /// テスト用の一時ディレクトリ
+    fn temp_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("pdf-viewer-{}-{}", name, uuid::Uuid::new_v4()))
+    }
+
+    /// 指定した色の1ピクセルのPNG
+    fn png(color: [u8; 4]) -> Vec<u8> {
+        let mut png = Vec::new();
+        image::RgbaImage::from_pixel(1, 1, image::Rgba(color))
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        png
+    }
+
+    #[test]
+    fn stamps_and_seals_are_kept_across_loads() {
+        let dir = temp_dir("library");
+        let path = dir.join("library.json");
+
+        let mut library = StampLibrary::load_from(&path);
+        assert!(library.stamps.is_empty() && library.seals.is_empty());
+        let seal = NameSeal {
+            name: "山田".to_string(),
+            shape: SealShape::Square,
+            ..Default::default()
+        };
+        assert_eq!(library.add_seal(seal.clone()), 0);
+        // 同じ内容は重ねて登録しない
+        assert_eq!(library.add_seal(seal.clone()), 0);
+
+        // 同名の画像には番号を付ける
+        assert_eq!(library.add_stamp("承認", "社内", vec![1, 2, 3]).unwrap(), "承認");
+        assert_eq!(library.add_stamp("承認", "", vec![4, 5]).unwrap(), "承認 (2)");
+        assert!(library.rename_stamp(1, "承認").is_err());
+        library.rename_stamp(1, " 確認 ").unwrap();
+        library.move_stamp(1, 0);
+        library.save().unwrap();
+
+        let loaded = StampLibrary::load_from(&path);
+        assert_eq!(loaded.seals, vec![seal]);
+        let stamps: Vec<(&str, &[u8])> = loaded
+            .stamps
+            .iter()
+            .map(|s| (s.name.as_str(), s.image_data.as_slice()))
+            .collect();
+        assert_eq!(stamps, vec![("確認", &[4, 5][..]), ("承認", &[1, 2, 3][..])]);
+        assert_eq!(loaded.categories(), vec!["社内".to_string()]);
+
+        // 削除するとコピーした画像も消える
+        let mut library = loaded;
+        let removed = library.remove_stamp(0).unwrap();
+        assert!(!dir.join(removed.path).exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn broken_library_is_backed_up_instead_of_overwritten() {
+        let dir = temp_dir("library-broken");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("library.json");
+        std::fs::write(&path, "{ broken").unwrap();
+
+        let mut library = StampLibrary::load_from(&path);
+        assert!(library.stamps.is_empty() && library.seals.is_empty());
+        library.add_seal(NameSeal::default());
+        library.save().unwrap();
+
+        // 壊れた内容は退避先に残る
+        let backup = std::fs::read_to_string(dir.join("library.json.bak")).unwrap();
+        assert_eq!(backup, "{ broken");
+        assert_eq!(StampLibrary::load_from(&path).seals.len(), 1);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn bundle_shares_library_without_duplicates() {
+        let source_dir = temp_dir("library-source");
+        let mut source = StampLibrary::load_from(&source_dir.join("library.json"));
+        source.add_stamp("済", "経理", png([255, 0, 0, 255])).unwrap();
+        source.add_seal(NameSeal {
+            name: "佐藤".to_string(),
+            ..Default::default()
+        });
+        let bundle = source_dir.join("team.json");
+        source.export(&bundle).unwrap();
+
+        let target_dir = temp_dir("library-target");
+        let mut target = StampLibrary::load_from(&target_dir.join("library.json"));
+        target.add_stamp("済", "", png([0, 0, 255, 255])).unwrap();
+        assert_eq!(target.import(&bundle).unwrap(), 2);
+        // 同じものをもう一度読み込んでも増えない
+        assert_eq!(target.import(&bundle).unwrap(), 0);
+        let names: Vec<&str> = target.stamps.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["済", "済 (2)"]);
+        assert_eq!(target.stamps[1].category, "経理");
+        assert_eq!(target.stamps[1].image_data, png([255, 0, 0, 255]));
+        assert_eq!(target.seals.len(), 1);
+
+        std::fs::remove_dir_all(source_dir).unwrap();
+        std::fs::remove_dir_all(target_dir).unwrap();
+    }
+
+    #[test]
+    fn bundle_with_broken_image_is_rejected() {
+        let dir = temp_dir("library-bundle");
+        std::fs::create_dir_all(&dir).unwrap();
+        let bundle = dir.join("team.json");
+        let content = serde_json::json!({
+            "version": BUNDLE_VERSION,
+            "stamps": [
+                { "name": "正常", "png": BASE64.encode(png([0, 255, 0, 255])) },
+                { "name": "破損", "png": BASE64.encode([1, 2, 3]) },
+            ],
+        });
+        std::fs::write(&bundle, content.to_string()).unwrap();
+
+        let mut library = StampLibrary::load_from(&dir.join("library.json"));
+        let error = library.import(&bundle).unwrap_err();
+        assert!(error.to_string().contains("破損"));
+        // 正常な画像も含めて何も追加しない
+        assert!(library.stamps.is_empty());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
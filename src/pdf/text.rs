@@ -0,0 +1,351 @@
+//! ページのテキスト抽出と検索
+//!
+//! 検索では大文字・小文字、全角・半角、ひらがな・カタカナの違いを無視する。
+//! 一致した位置は元の文字の範囲に戻し、文字ごとの位置から強調表示する矩形を求める。
+
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// 回転前のページ上の矩形（左上原点、ポイント単位）
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct TextRect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl TextRect {
+    fn is_empty(&self) -> bool {
+        self.width <= 0.0 || self.height <= 0.0
+    }
+
+    fn contains(&self, x: f32, y: f32) -> bool {
+        x >= self.x && x <= self.x + self.width && y >= self.y && y <= self.y + self.height
+    }
+
+    /// 点までの距離の2乗（矩形内なら 0）
+    fn distance_sq(&self, x: f32, y: f32) -> f32 {
+        let dx = (self.x - x).max(x - (self.x + self.width)).max(0.0);
+        let dy = (self.y - y).max(y - (self.y + self.height)).max(0.0);
+        dx * dx + dy * dy
+    }
+
+    fn union(&self, other: &TextRect) -> TextRect {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        TextRect {
+            x,
+            y,
+            width: (self.x + self.width).max(other.x + other.width) - x,
+            height: (self.y + self.height).max(other.y + other.height) - y,
+        }
+    }
+
+    /// 同じ行（縦書きなら同じ列）に並ぶ隣り合った文字か
+    fn is_same_line(&self, other: &TextRect) -> bool {
+        let overlap = |a0: f32, a1: f32, b0: f32, b1: f32| a1.min(b1) - a0.max(b0);
+        let vertical = overlap(self.y, self.y + self.height, other.y, other.y + other.height);
+        let horizontal = overlap(self.x, self.x + self.width, other.x, other.x + other.width);
+        let height = self.height.min(other.height);
+        let width = self.width.min(other.width);
+        (vertical > height * 0.5 && horizontal > -height) || (horizontal > width * 0.5 && vertical > -width)
+    }
+}
+
+/// ページ上の1文字
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextChar {
+    pub ch: char,
+    /// 文字の範囲（改行など位置の無い文字は空）
+    pub rect: TextRect,
+}
+
+/// 1ページ分のテキスト
+#[derive(Debug, Clone, Default)]
+pub struct PageText {
+    pub page: usize,
+    pub chars: Vec<TextChar>,
+}
+
+/// 検索で一致した箇所
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchHit {
+    pub page: usize,
+    /// 一致した文字の範囲（`PageText::chars` の添字）
+    pub chars: Range<usize>,
+    /// 強調表示する矩形（行ごと）
+    pub rects: Vec<TextRect>,
+}
+
+impl PageText {
+    /// ページの文字列
+    pub fn text(&self) -> String {
+        self.text_in(0..self.chars.len())
+    }
+
+    /// 文字範囲の文字列
+    pub fn text_in(&self, range: Range<usize>) -> String {
+        let chars = &self.chars[range.start.min(self.chars.len())..range.end.min(self.chars.len())];
+        chars.iter().map(|c| c.ch).collect()
+    }
+
+    /// 点を含む文字
+    pub fn char_at(&self, x: f32, y: f32) -> Option<usize> {
+        self.chars.iter().position(|c| !c.rect.is_empty() && c.rect.contains(x, y))
+    }
+
+    /// 点に最も近い文字（ドラッグ選択で文字の外を指しているとき）
+    pub fn nearest_char(&self, x: f32, y: f32) -> Option<usize> {
+        self.chars
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| !c.rect.is_empty())
+            .min_by(|(_, a), (_, b)| a.rect.distance_sq(x, y).total_cmp(&b.rect.distance_sq(x, y)))
+            .map(|(i, _)| i)
+    }
+
+    /// 文字を含む単語の範囲（漢字・ひらがな・カタカナ・英数字の並びをそれぞれ1語とする）
+    pub fn word_at(&self, index: usize) -> Range<usize> {
+        let Some(class) = self.chars.get(index).map(|c| CharClass::of(c.ch)) else {
+            return index..index;
+        };
+        if class == CharClass::Symbol {
+            return index..index + 1;
+        }
+        let same = |c: &TextChar| CharClass::of(c.ch) == class;
+        let start = self.chars[..index].iter().rposition(|c| !same(c)).map_or(0, |i| i + 1);
+        let end = self.chars[index..]
+            .iter()
+            .position(|c| !same(c))
+            .map_or(self.chars.len(), |i| index + i);
+        start..end
+    }
+
+    /// 検索語に一致する箇所を全て探す（重なる一致は先のものだけ）
+    pub fn find(&self, query: &str) -> Vec<SearchHit> {
+        let query: Vec<char> = normalize_chars(query.chars()).into_iter().map(|(c, _)| c).collect();
+        if query.is_empty() {
+            return Vec::new();
+        }
+        let normalized = normalize_chars(self.chars.iter().map(|c| c.ch));
+
+        let mut hits = Vec::new();
+        let mut i = 0;
+        while i + query.len() <= normalized.len() {
+            let matched = normalized[i..i + query.len()]
+                .iter()
+                .zip(&query)
+                .all(|((c, _), q)| c == q);
+            if !matched {
+                i += 1;
+                continue;
+            }
+            let start = normalized[i].1.start;
+            let end = normalized[i + query.len() - 1].1.end;
+            hits.push(SearchHit {
+                page: self.page,
+                chars: start..end,
+                rects: self.line_rects(start..end),
+            });
+            i += query.len();
+        }
+        hits
+    }
+
+    /// 文字範囲を覆う矩形（同じ行の文字はまとめる）
+    pub fn line_rects(&self, range: Range<usize>) -> Vec<TextRect> {
+        let mut rects: Vec<TextRect> = Vec::new();
+        let chars = &self.chars[range.start.min(self.chars.len())..range.end.min(self.chars.len())];
+        for rect in chars.iter().map(|c| c.rect).filter(|r| !r.is_empty()) {
+            match rects.last_mut() {
+                Some(line) if line.is_same_line(&rect) => *line = line.union(&rect),
+                _ => rects.push(rect),
+            }
+        }
+        rects
+    }
+}
+
+/// 単語選択での文字の種類
+#[derive(Clone, Copy, PartialEq)]
+enum CharClass {
+    Space,
+    Alphanumeric,
+    Kanji,
+    Hiragana,
+    Katakana,
+    Symbol,
+}
+
+impl CharClass {
+    fn of(c: char) -> Self {
+        match c {
+            c if c.is_whitespace() => CharClass::Space,
+            '々' | '〆' | '\u{3400}'..='\u{4DBF}' | '\u{4E00}'..='\u{9FFF}' | '\u{F900}'..='\u{FAFF}' => {
+                CharClass::Kanji
+            }
+            '\u{3041}'..='\u{309F}' => CharClass::Hiragana,
+            // 長音記号・半角カナを含む
+            '\u{30A0}'..='\u{30FF}' | '\u{31F0}'..='\u{31FF}' | '\u{FF66}'..='\u{FF9F}' => CharClass::Katakana,
+            c if c.is_alphanumeric() || c == '_' => CharClass::Alphanumeric,
+            _ => CharClass::Symbol,
+        }
+    }
+}
+
+/// 半角カタカナ（U+FF61〜U+FF9F）に対応する全角文字
+const HALFWIDTH_KANA: &str = "。「」、・ヲァィゥェォャュョッーアイウエオカキクケコサシスセソタチツテトナニヌネノハヒフヘホマミムメモヤユヨラリルレロワン゛゜";
+/// 濁点を付けられるカタカナと濁音
+const VOICED: (&str, &str) = (
+    "カキクケコサシスセソタチツテトハヒフヘホウ",
+    "ガギグゲゴザジズゼゾダヂヅデドバビブベボヴ",
+);
+/// 半濁点を付けられるカタカナと半濁音
+const SEMI_VOICED: (&str, &str) = ("ハヒフヘホ", "パピプペポ");
+
+/// 検索用に1文字を正規化（全角英数→半角、半角カナ→全角、ひらがな→カタカナ）
+fn normalize_char(c: char) -> char {
+    match c {
+        // 全角英数・記号
+        '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+        '\u{3000}' => ' ',
+        '\u{FF61}'..='\u{FF9F}' => HALFWIDTH_KANA
+            .chars()
+            .nth((c as u32 - 0xFF61) as usize)
+            .unwrap_or(c),
+        // ひらがな
+        '\u{3041}'..='\u{3096}' => char::from_u32(c as u32 + 0x60).unwrap_or(c),
+        // 結合用の濁点・半濁点
+        '\u{3099}' => '゛',
+        '\u{309A}' => '゜',
+        _ => c,
+    }
+}
+
+/// 濁点・半濁点を直前のカナと合成
+fn compose_mark(base: char, mark: char) -> Option<char> {
+    let (from, to) = match mark {
+        '゛' => VOICED,
+        '゜' => SEMI_VOICED,
+        _ => return None,
+    };
+    let index = from.chars().position(|c| c == base)?;
+    to.chars().nth(index)
+}
+
+/// 検索用に文字列を正規化（`PageText::find` と同じ規則）
+pub fn normalize(text: &str) -> String {
+    normalize_chars(text.chars()).into_iter().map(|(c, _)| c).collect()
+}
+
+/// 検索用に正規化した文字列と、各文字に対応する元の文字の範囲
+///
+/// 改行は無視する（行をまたいだ日本語の文も一致させる）。
+fn normalize_chars(chars: impl Iterator<Item = char>) -> Vec<(char, Range<usize>)> {
+    let mut normalized: Vec<(char, Range<usize>)> = Vec::new();
+    for (i, c) in chars.enumerate() {
+        if c == '\r' || c == '\n' {
+            continue;
+        }
+        let c = normalize_char(c);
+        if let Some((last, range)) = normalized.last_mut() {
+            if let Some(composed) = compose_mark(*last, c) {
+                *last = composed;
+                range.end = i + 1;
+                continue;
+            }
+        }
+        for lower in c.to_lowercase() {
+            normalized.push((lower, i..i + 1));
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 1文字ずつ横に並べたページ（1行目は y=0、改行で次の行）
+    fn page_text(text: &str) -> PageText {
+        let mut chars = Vec::new();
+        let (mut x, mut y) = (0.0, 0.0);
+        for ch in text.chars() {
+            if ch == '\n' {
+                chars.push(TextChar { ch, rect: TextRect::default() });
+                x = 0.0;
+                y += 20.0;
+                continue;
+            }
+            chars.push(TextChar {
+                ch,
+                rect: TextRect { x, y, width: 10.0, height: 12.0 },
+            });
+            x += 10.0;
+        }
+        PageText { page: 0, chars }
+    }
+
+    #[test]
+    fn normalize_ignores_width_case_and_kana() {
+        assert_eq!(normalize("ＰＤＦ　Viewer"), "pdf viewer");
+        assert_eq!(normalize("ひらがな"), normalize("ヒラガナ"));
+        assert_eq!(normalize("ｶﾞｲﾄﾞ"), normalize("ガイド"));
+        assert_eq!(normalize("ﾊﾟﾝ"), "パン");
+        assert_eq!(normalize("か\u{3099}"), "ガ");
+    }
+
+    #[test]
+    fn find_maps_hits_back_to_original_chars() {
+        let text = page_text("請求書 ｶﾞｲﾄﾞ と ガイド");
+        let hits = text.find("がいど");
+        assert_eq!(hits.len(), 2);
+        // 半角の濁点も一致範囲に含む
+        assert_eq!(hits[0].chars, 4..9);
+        assert_eq!(hits[1].chars, 12..15);
+        assert_eq!(hits[1].rects, vec![TextRect { x: 120.0, y: 0.0, width: 30.0, height: 12.0 }]);
+
+        assert!(text.find("").is_empty());
+        assert!(text.find("見積書").is_empty());
+    }
+
+    #[test]
+    fn word_at_splits_japanese_by_script() {
+        let text = page_text("請求書のPDFファイル 2024年、ｶﾞｲﾄﾞ");
+        let word = |i| text.text_in(text.word_at(i));
+        assert_eq!(word(1), "請求書");
+        assert_eq!(word(3), "の");
+        assert_eq!(word(5), "PDF");
+        assert_eq!(word(8), "ファイル");
+        assert_eq!(word(13), "2024");
+        assert_eq!(word(16), "年");
+        assert_eq!(word(17), "、");
+        assert_eq!(word(20), "ｶﾞｲﾄﾞ");
+    }
+
+    #[test]
+    fn chars_are_located_by_point() {
+        let text = page_text("abc\ndef");
+        assert_eq!(text.char_at(15.0, 5.0), Some(1));
+        assert_eq!(text.char_at(15.0, 15.0), None);
+        // 行末より右は行の最後の文字、行間は近い方の行
+        assert_eq!(text.nearest_char(100.0, 5.0), Some(2));
+        assert_eq!(text.nearest_char(5.0, 19.0), Some(4));
+        assert_eq!(text.text_in(1..6), "bc\nde");
+    }
+
+    #[test]
+    fn find_across_lines_gives_rect_per_line() {
+        let text = page_text("abc\ndef");
+        let hits = text.find("CD");
+        assert_eq!(hits.len(), 1);
+        assert_eq!(
+            hits[0].rects,
+            vec![
+                TextRect { x: 20.0, y: 0.0, width: 10.0, height: 12.0 },
+                TextRect { x: 0.0, y: 20.0, width: 10.0, height: 12.0 },
+            ]
+        );
+    }
+}
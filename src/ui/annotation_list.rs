@@ -0,0 +1,304 @@
+//! 注釈一覧パネル - 全注釈をページごとに一覧し、移動・まとめて削除・ページ移動・色の変更
+
+use crate::pdf::{normalize, AnnotationData, AnnotationKind, AnnotationRef};
+use eframe::egui::{self, Color32};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// パネルの幅
+pub const ANNOTATION_LIST_WIDTH: f32 = 300.0;
+/// 一覧に表示する内容の最大文字数
+const PREVIEW_CHARS: usize = 30;
+
+/// 注釈一覧の操作結果
+#[derive(Default)]
+pub struct AnnotationListResult {
+    pub jump_to: Option<(usize, AnnotationRef)>,  // (ページ, 注釈)
+    pub delete: Option<Vec<AnnotationRef>>,
+    pub move_to_page: Option<(Vec<AnnotationRef>, usize)>,  // (注釈, 移動先ページ)
+    pub recolor: Option<(Vec<AnnotationRef>, [u8; 3])>,
+}
+
+/// ページ内の並び順
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SortOrder {
+    Position,  // 上から順
+    Kind,
+    Author,
+}
+
+impl SortOrder {
+    fn label(&self) -> &'static str {
+        match self {
+            SortOrder::Position => "位置順",
+            SortOrder::Kind => "種類順",
+            SortOrder::Author => "作成者順",
+        }
+    }
+}
+
+/// 一覧の1行
+struct Row {
+    item: AnnotationRef,
+    page: usize,
+    position: (f32, f32),  // (y, x) ページ上の左上
+    preview: String,
+    author: String,
+    color: Option<Color32>,
+}
+
+/// 注釈一覧パネルの状態
+pub struct AnnotationList {
+    selected: BTreeSet<AnnotationRef>,
+    counts: [usize; 6],  // 選択したときの種類ごとの注釈の数（変わったら番号がずれるので選択を解除）
+    sort: SortOrder,
+    filter_kind: Option<AnnotationKind>,
+    query: String,
+    target_page: usize,  // 移動先のページ（1始まり）
+    color: [u8; 3],
+}
+
+impl AnnotationList {
+    pub fn new() -> Self {
+        Self {
+            selected: BTreeSet::new(),
+            counts: [0; 6],
+            sort: SortOrder::Position,
+            filter_kind: None,
+            query: String::new(),
+            target_page: 1,
+            color: [220, 0, 0],
+        }
+    }
+
+    /// 注釈一覧を描画
+    pub fn show(
+        &mut self,
+        ui: &mut egui::Ui,
+        data: &AnnotationData,
+        page_count: usize,
+        current_page: usize,
+    ) -> AnnotationListResult {
+        let mut result = AnnotationListResult::default();
+        if data.counts() != self.counts {
+            self.counts = data.counts();
+            self.selected.clear();
+        }
+
+        // 絞り込み・並び順
+        egui::Grid::new("annotation_filters").num_columns(2).show(ui, |ui| {
+            ui.label("種類:");
+            egui::ComboBox::from_id_salt("annotation_filter_kind")
+                .selected_text(self.filter_kind.map_or("すべて", |k| k.label()))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut self.filter_kind, None, "すべて");
+                    for kind in AnnotationKind::all() {
+                        ui.selectable_value(&mut self.filter_kind, Some(kind), kind.label());
+                    }
+                });
+            ui.end_row();
+
+            ui.label("並び順:");
+            egui::ComboBox::from_id_salt("annotation_sort")
+                .selected_text(self.sort.label())
+                .show_ui(ui, |ui| {
+                    for sort in [SortOrder::Position, SortOrder::Kind, SortOrder::Author] {
+                        ui.selectable_value(&mut self.sort, sort, sort.label());
+                    }
+                });
+            ui.end_row();
+
+            ui.label("検索:");
+            ui.add(egui::TextEdit::singleline(&mut self.query).hint_text("内容・作成者"));
+            ui.end_row();
+        });
+
+        let query = normalize(self.query.trim());
+        let mut rows: Vec<Row> = rows(data)
+            .into_iter()
+            .filter(|row| self.filter_kind.is_none_or(|k| row.item.kind == k))
+            .filter(|row| {
+                query.is_empty()
+                    || normalize(&row.preview).contains(&query)
+                    || normalize(&row.author).contains(&query)
+            })
+            .collect();
+        match self.sort {
+            SortOrder::Position => rows.sort_by(|a, b| a.position.partial_cmp(&b.position).unwrap_or(std::cmp::Ordering::Equal)),
+            SortOrder::Kind => rows.sort_by_key(|row| row.item),
+            SortOrder::Author => rows.sort_by(|a, b| a.author.cmp(&b.author)),
+        }
+        let mut pages: BTreeMap<usize, Vec<Row>> = BTreeMap::new();
+        for row in rows {
+            pages.entry(row.page).or_default().push(row);
+        }
+        let shown: Vec<AnnotationRef> = pages.values().flatten().map(|row| row.item).collect();
+        let total: usize = data.counts().iter().sum();
+
+        ui.horizontal(|ui| {
+            ui.label(format!("{} / {} 件", shown.len(), total));
+            if ui.small_button("すべて選択").on_hover_text("表示中の注釈をすべて選択します").clicked() {
+                self.selected.extend(shown.iter().copied());
+            }
+            if ui.small_button("選択解除").clicked() {
+                self.selected.clear();
+            }
+        });
+
+        // まとめて操作
+        if !self.selected.is_empty() {
+            let items: Vec<AnnotationRef> = self.selected.iter().copied().collect();
+            ui.horizontal(|ui| {
+                ui.strong(format!("{} 件選択中", items.len()));
+                if ui.button("🗑 削除").clicked() {
+                    result.delete = Some(items.clone());
+                    self.selected.clear();
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.add(egui::DragValue::new(&mut self.target_page).range(1..=page_count.max(1)).prefix("p."));
+                if ui.button("ページへ移動").clicked() {
+                    result.move_to_page = Some((items.clone(), self.target_page - 1));
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.color_edit_button_srgb(&mut self.color);
+                if ui
+                    .button("色を変更")
+                    .on_hover_text("選択中の矩形・装飾・図形の色を変えます")
+                    .clicked()
+                {
+                    result.recolor = Some((items, self.color));
+                }
+            });
+        }
+        ui.separator();
+
+        egui::ScrollArea::vertical()
+            .id_salt("annotation_list")
+            .auto_shrink([false, false])
+            .show(ui, |ui| {
+                for (page, rows) in &pages {
+                    let title = format!("p.{}（{} 件）", page + 1, rows.len());
+                    let title = if *page == current_page {
+                        egui::RichText::new(title).strong()
+                    } else {
+                        egui::RichText::new(title)
+                    };
+                    egui::CollapsingHeader::new(title)
+                        .id_salt(("annotation_page", page))
+                        .default_open(true)
+                        .show(ui, |ui| {
+                            for row in rows {
+                                ui.horizontal(|ui| {
+                                    let mut checked = self.selected.contains(&row.item);
+                                    if ui.checkbox(&mut checked, "").changed() {
+                                        if checked {
+                                            self.selected.insert(row.item);
+                                        } else {
+                                            self.selected.remove(&row.item);
+                                        }
+                                    }
+                                    if let Some(color) = row.color {
+                                        let (swatch, _) = ui.allocate_exact_size(egui::vec2(10.0, 10.0), egui::Sense::hover());
+                                        ui.painter().rect_filled(swatch, 2.0, color);
+                                    }
+                                    let label = format!("{}  {}", row.item.kind.label(), row.preview);
+                                    if ui.selectable_label(false, label).on_hover_text("クリックで表示").clicked() {
+                                        result.jump_to = Some((row.page, row.item));
+                                    }
+                                    if !row.author.is_empty() {
+                                        ui.colored_label(Color32::GRAY, &row.author);
+                                    }
+                                });
+                            }
+                        });
+                }
+            });
+        result
+    }
+}
+
+/// 全注釈の一覧の行を作る
+fn rows(data: &AnnotationData) -> Vec<Row> {
+    let item = |kind, index| AnnotationRef { kind, index };
+    let color = |[r, g, b, _]: [u8; 4]| Some(Color32::from_rgb(r, g, b));
+    let mut rows = Vec::new();
+    for (i, stamp) in data.stamps.iter().enumerate() {
+        rows.push(Row {
+            item: item(AnnotationKind::Stamp, i),
+            page: stamp.page,
+            position: (stamp.y, stamp.x),
+            preview: stamp.stamp_type.label(),
+            author: String::new(),
+            color: None,
+        });
+    }
+    for (i, text) in data.texts.iter().enumerate() {
+        rows.push(Row {
+            item: item(AnnotationKind::Text, i),
+            page: text.page,
+            position: (text.y, text.x),
+            preview: preview(&text.text),
+            author: String::new(),
+            color: None,
+        });
+    }
+    for (i, rect) in data.rects.iter().enumerate() {
+        let label = if rect.redact { "墨消し" } else { "塗りつぶし" };
+        rows.push(Row {
+            item: item(AnnotationKind::Rect, i),
+            page: rect.page,
+            position: (rect.y, rect.x),
+            preview: format!("{} {:.0}×{:.0}", label, rect.width, rect.height),
+            author: String::new(),
+            color: color(rect.color),
+        });
+    }
+    for (i, markup) in data.markups.iter().enumerate() {
+        let position = markup.quads.first().map_or((0.0, 0.0), |q| (q.y, q.x));
+        rows.push(Row {
+            item: item(AnnotationKind::Markup, i),
+            page: markup.page,
+            position,
+            preview: format!("{}「{}」", markup.markup_type.label(), preview(&markup.text)),
+            author: String::new(),
+            color: color(markup.color),
+        });
+    }
+    for (i, shape) in data.shapes.iter().enumerate() {
+        let bounds = shape.bounds();
+        rows.push(Row {
+            item: item(AnnotationKind::Shape, i),
+            page: shape.page,
+            position: (bounds.y, bounds.x),
+            preview: shape.shape_type.label().to_string(),
+            author: String::new(),
+            color: color(shape.color),
+        });
+    }
+    for (i, comment) in data.comments.iter().enumerate() {
+        let mut text = preview(&comment.text);
+        if !comment.replies.is_empty() {
+            text.push_str(&format!(" ↩{}", comment.replies.len()));
+        }
+        rows.push(Row {
+            item: item(AnnotationKind::Comment, i),
+            page: comment.page,
+            position: (comment.y, comment.x),
+            preview: text,
+            author: comment.author.clone(),
+            color: None,
+        });
+    }
+    rows
+}
+
+/// 1行目を表示用に短くする
+fn preview(text: &str) -> String {
+    let line = text.lines().next().unwrap_or_default();
+    let mut preview: String = line.chars().take(PREVIEW_CHARS).collect();
+    if line.chars().count() > PREVIEW_CHARS || text.lines().nth(1).is_some() {
+        preview.push('…');
+    }
+    preview
+}
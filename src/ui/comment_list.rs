@@ -0,0 +1,141 @@
+//! コメント一覧パネル - 付箋コメントをページ・作成者・状態で絞り込んで表示
+
+use crate::pdf::{display_time, CommentAnnotation, CommentStatus};
+use eframe::egui::{self, Color32};
+use std::collections::BTreeSet;
+
+/// パネルの幅
+pub const COMMENT_LIST_WIDTH: f32 = 260.0;
+/// 一覧に表示する本文の最大文字数
+const PREVIEW_CHARS: usize = 60;
+
+/// コメント一覧パネルの状態
+pub struct CommentList {
+    author: String,  // コメント・返信の作成者名
+    filter_page: Option<usize>,
+    filter_author: Option<String>,
+    filter_status: Option<CommentStatus>,
+}
+
+impl CommentList {
+    pub fn new() -> Self {
+        // 既定の作成者名はログインユーザー名
+        let author = std::env::var("USERNAME")
+            .or_else(|_| std::env::var("USER"))
+            .unwrap_or_default();
+        Self {
+            author,
+            filter_page: None,
+            filter_author: None,
+            filter_status: None,
+        }
+    }
+
+    /// コメント・返信の作成者名
+    pub fn author(&self) -> &str {
+        self.author.trim()
+    }
+
+    /// コメント一覧を描画し、クリックされたコメントの番号を返す
+    pub fn show(&mut self, ui: &mut egui::Ui, comments: &[CommentAnnotation]) -> Option<usize> {
+        ui.horizontal(|ui| {
+            ui.label("名前:");
+            ui.add(egui::TextEdit::singleline(&mut self.author).hint_text("作成者名"));
+        });
+        ui.separator();
+
+        // 絞り込み（選択肢はコメントのあるページ・作成者のみ）
+        let pages: BTreeSet<usize> = comments.iter().map(|c| c.page).collect();
+        let authors: BTreeSet<&str> = comments
+            .iter()
+            .flat_map(|c| std::iter::once(c.author.as_str()).chain(c.replies.iter().map(|r| r.author.as_str())))
+            .filter(|a| !a.is_empty())
+            .collect();
+        egui::Grid::new("comment_filters").num_columns(2).show(ui, |ui| {
+            ui.label("ページ:");
+            egui::ComboBox::from_id_salt("comment_filter_page")
+                .selected_text(self.filter_page.map_or("すべて".to_string(), |p| format!("p.{}", p + 1)))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut self.filter_page, None, "すべて");
+                    for &page in &pages {
+                        ui.selectable_value(&mut self.filter_page, Some(page), format!("p.{}", page + 1));
+                    }
+                });
+            ui.end_row();
+
+            ui.label("作成者:");
+            egui::ComboBox::from_id_salt("comment_filter_author")
+                .selected_text(self.filter_author.as_deref().unwrap_or("すべて"))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut self.filter_author, None, "すべて");
+                    for &author in &authors {
+                        ui.selectable_value(&mut self.filter_author, Some(author.to_string()), author);
+                    }
+                });
+            ui.end_row();
+
+            ui.label("状態:");
+            egui::ComboBox::from_id_salt("comment_filter_status")
+                .selected_text(self.filter_status.map_or("すべて", |s| s.label()))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut self.filter_status, None, "すべて");
+                    for status in [CommentStatus::Open, CommentStatus::Resolved] {
+                        ui.selectable_value(&mut self.filter_status, Some(status), status.label());
+                    }
+                });
+            ui.end_row();
+        });
+        ui.separator();
+
+        // 作成者はスレッドの返信者も含めて絞り込む
+        let shown: Vec<(usize, &CommentAnnotation)> = comments
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| self.filter_page.is_none_or(|p| c.page == p))
+            .filter(|(_, c)| self.filter_status.is_none_or(|s| c.status == s))
+            .filter(|(_, c)| {
+                self.filter_author.as_deref().is_none_or(|author| {
+                    c.author == author || c.replies.iter().any(|r| r.author == author)
+                })
+            })
+            .collect();
+        ui.label(format!("{} / {} 件", shown.len(), comments.len()));
+
+        let mut clicked = None;
+        egui::ScrollArea::vertical()
+            .id_salt("comment_list")
+            .auto_shrink([false, false])
+            .show(ui, |ui| {
+                for (index, comment) in shown {
+                    let response = egui::Frame::group(ui.style())
+                        .show(ui, |ui| {
+                            ui.set_width(ui.available_width());
+                            ui.horizontal(|ui| {
+                                ui.strong(format!("p.{}", comment.page + 1));
+                                ui.label(if comment.author.is_empty() { "（名前なし）" } else { &comment.author });
+                                let color = match comment.status {
+                                    CommentStatus::Open => Color32::from_rgb(200, 120, 0),
+                                    CommentStatus::Resolved => Color32::GRAY,
+                                };
+                                ui.colored_label(color, comment.status.label());
+                            });
+                            ui.colored_label(Color32::GRAY, display_time(&comment.created));
+                            let mut preview: String = comment.text.chars().take(PREVIEW_CHARS).collect();
+                            if comment.text.chars().count() > PREVIEW_CHARS {
+                                preview.push('…');
+                            }
+                            ui.label(preview);
+                            if !comment.replies.is_empty() {
+                                ui.colored_label(Color32::GRAY, format!("↩ {} 件の返信", comment.replies.len()));
+                            }
+                        })
+                        .response
+                        .interact(egui::Sense::click());
+                    if response.clicked() {
+                        clicked = Some(index);
+                    }
+                }
+            });
+        clicked
+    }
+}
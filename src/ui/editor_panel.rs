@@ -1,7 +1,176 @@
 //! メイン編集パネル - PDF表示、スタンプ配置、テキスト入力
 
-use crate::pdf::{FontType, PdfDocument, RectAnnotation, Stamp, StampType, TextAnnotation};
+use super::{SearchBar, TextureCache};
+use crate::pdf::{
+    display_time, smooth_stroke, AnnotationData, AnnotationKind, AnnotationRef, CommentAnnotation, CommentStatus,
+    DateSeal, FontType, MarkupAnnotation, MarkupType, NameSeal, PageText, PageTile, PdfDocument,
+    RectAnnotation, RenderArea, RenderJob, RenderSource, SealLayout, SealShape, ShapeAnnotation,
+    ShapeType, Stamp, StampType,
+    TextAnnotation, TextRect, COMMENT_ICON_SIZE,
+};
 use eframe::egui::{self, Color32, TextureHandle, Vec2};
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeSet, HashMap};
+use std::ops::Range;
+
+/// ズーム100%以下での描画解像度（長辺のピクセル数）
+const BASE_RENDER_SIZE: f32 = 800.0;
+/// 描画解像度の上限（長辺のピクセル数）
+const MAX_RENDER_SIZE: f32 = 2048.0;
+/// ページ画像キャッシュの上限（バイト）
+const PAGE_CACHE_BYTES: usize = 192 * 1024 * 1024;
+/// 拡大表示用タイルの一辺（ピクセル）
+const TILE_SIZE: u32 = 512;
+/// タイルキャッシュの上限（バイト、画面を覆うタイル数より十分大きくする）
+const TILE_CACHE_BYTES: usize = 128 * 1024 * 1024;
+/// ズームの上限
+const MAX_ZOOM: f32 = 8.0;
+/// 手書きの軌跡を間引く間隔（画面上のピクセル）
+const INK_MIN_DISTANCE: f32 = 2.0;
+/// 手書き・図形を選択できる範囲の余白（画面上のピクセル）
+const SHAPE_HIT_MARGIN: f32 = 4.0;
+/// コメントのポップアップの幅
+const COMMENT_POPUP_WIDTH: f32 = 240.0;
+/// 認印の作成ダイアログのプレビューの大きさ
+const NAME_SEAL_PREVIEW_SIZE: f32 = 120.0;
+/// 連続表示・見開き表示でのページの間隔
+const PAGE_GAP: f32 = 8.0;
+/// 幅・ページに合わせるときに残す余白（スクロールバーの分）
+const FIT_MARGIN: f32 = 24.0;
+
+/// ページ画像キャッシュのキー
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct PageKey {
+    page: usize,
+    rotation: i32,
+    zoom_bucket: u32,
+}
+
+/// 拡大表示用タイルのキャッシュのキー
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct TileKey {
+    page: usize,
+    rotation: i32,
+    zoom_bucket: u32,
+    column: u32,
+    row: u32,
+}
+
+/// ズーム段階（100%以下は 0、そこから2倍ごとに 1 増える）
+fn zoom_bucket(zoom: f32) -> u32 {
+    zoom.max(1.0).log2().ceil() as u32
+}
+
+/// ズーム段階に応じたページの描画サイズ（回転後のページサイズから）
+fn render_size((width, height): (f32, f32), zoom_bucket: u32) -> (u32, u32) {
+    let factor = (1 << zoom_bucket) as f32;
+    let longest = width.max(height);
+    let scale = (BASE_RENDER_SIZE * factor / longest)
+        .min(factor)
+        .min(MAX_RENDER_SIZE / longest);
+    ((width * scale) as u32, (height * scale) as u32)
+}
+
+/// ページ全体を描画すると解像度の上限を超えるため、タイルに分けて描画するか
+fn is_tiled((width, height): (f32, f32), zoom_bucket: u32) -> bool {
+    zoom_bucket > 0 && width.max(height) * (1 << zoom_bucket) as f32 > MAX_RENDER_SIZE
+}
+
+/// ページの描画依頼
+fn page_job(doc: &PdfDocument, page: usize, (width, height): (u32, u32)) -> RenderJob {
+    RenderJob {
+        source: RenderSource::Document(doc.clone()),
+        page,
+        area: RenderArea::Fit { width, height },
+    }
+}
+
+/// ページの表示方法
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum ViewMode {
+    /// 1ページずつ
+    #[default]
+    Single,
+    /// 全ページを縦に並べてスクロール
+    Continuous,
+    /// 2ページずつ横に並べてスクロール
+    Spread,
+}
+
+/// 行ごとに並べるページ番号（見開きの空き枠は None）
+///
+/// `cover` なら1ページ目を表紙として右側に単独で置く。
+fn page_rows(page_count: usize, spread: bool, cover: bool) -> Vec<Vec<Option<usize>>> {
+    if !spread {
+        return (0..page_count).map(|page| vec![Some(page)]).collect();
+    }
+    let mut rows = Vec::new();
+    let mut start = 0;
+    if cover && page_count > 0 {
+        rows.push(vec![None, Some(0)]);
+        start = 1;
+    }
+    rows.extend(
+        (start..page_count)
+            .step_by(2)
+            .map(|page| (page..(page + 2).min(page_count)).map(Some).collect()),
+    );
+    rows
+}
+
+/// 印影を画面上の矩形に描く（`layout` は矩形の大きさで作った、PDFへの書き込みと同じ配置）
+fn paint_seal(painter: &egui::Painter, layout: &SealLayout, rect: egui::Rect, alpha: u8) {
+    let [r, g, b] = layout.color;
+    let color = Color32::from_rgba_unmultiplied(r, g, b, alpha);
+    let at = |(x, y): (f32, f32)| rect.min + Vec2::new(x, y);
+    let stroke = egui::Stroke::new(layout.stroke, color);
+    if layout.square {
+        let border = egui::Rect::from_center_size(at(layout.center), Vec2::splat(layout.radius * 2.0));
+        painter.rect_stroke(border, 0.0, stroke);
+    } else {
+        painter.circle_stroke(at(layout.center), layout.radius, stroke);
+    }
+    for &(start, end) in &layout.lines {
+        painter.line_segment([at(start), at(end)], stroke);
+    }
+    // PDFへの書き込みと同じフォントで描く
+    let family = egui::FontFamily::Name(crate::SEAL_FONT_FAMILY.into());
+    for text in &layout.texts {
+        painter.text(
+            at(text.center),
+            egui::Align2::CENTER_CENTER,
+            &text.text,
+            egui::FontId::new(text.font_size, family.clone()),
+            color,
+        );
+    }
+}
+
+/// ページの描画に使う注釈
+#[derive(Clone, Copy)]
+struct PageAnnotations<'a> {
+    stamps: &'a [Stamp],
+    text_annotations: &'a [TextAnnotation],
+    rect_annotations: &'a [RectAnnotation],
+    markups: &'a [MarkupAnnotation],
+    shapes: &'a [ShapeAnnotation],
+    comments: &'a [CommentAnnotation],
+    custom_stamps: &'a [(String, Option<TextureHandle>, u32, u32, String)],
+}
+
+/// 選択中のテキスト（ドラッグの起点と終点の文字、どちらも範囲に含む）
+#[derive(Clone, Copy)]
+struct TextSelection {
+    page: usize,
+    anchor: usize,
+    end: usize,
+}
+
+impl TextSelection {
+    fn range(&self) -> Range<usize> {
+        self.anchor.min(self.end)..self.anchor.max(self.end) + 1
+    }
+}
 
 /// リサイズのコーナー
 #[derive(Clone, Copy, PartialEq, Default)]
@@ -11,41 +180,115 @@ pub enum ResizeCorner {
     BottomRight,
 }
 
+/// エディターの描画に渡す注釈とライブラリ
+pub struct EditorContext<'a> {
+    pub annotations: &'a AnnotationData,
+    /// (名前, テクスチャ, 幅, 高さ, 分類)
+    pub custom_stamps: &'a [(String, Option<TextureHandle>, u32, u32, String)],
+    /// ライブラリの認印
+    pub name_seals: &'a [NameSeal],
+    pub show_stamp_panel: bool,
+    pub show_text_panel: bool,
+}
+
 /// エディター操作の結果
 #[derive(Default)]
 pub struct EditorResult {
     pub new_stamp: Option<Stamp>,
     pub new_text: Option<TextAnnotation>,
     pub new_rect: Option<RectAnnotation>,
+    pub new_markup: Option<MarkupAnnotation>,
+    pub new_shape: Option<ShapeAnnotation>,
+    pub new_comment: Option<CommentAnnotation>,  // 作成者はアプリ側で設定する
     pub delete_stamp: Option<usize>,
     pub delete_text: Option<usize>,
     pub delete_rect: Option<usize>,
+    pub delete_markup: Option<usize>,
+    pub delete_shape: Option<usize>,
+    pub delete_comment: Option<usize>,
     pub move_stamp: Option<(usize, f32, f32)>,
     pub move_text: Option<(usize, f32, f32)>,
     pub move_rect: Option<(usize, f32, f32)>,
+    pub move_shape: Option<(usize, f32, f32)>,  // (index, dx, dy) ページ上の移動量
+    pub move_comment: Option<(usize, f32, f32)>,
     pub resize_stamp: Option<(usize, f32, f32)>,  // (index, new_width, new_height)
     pub resize_text: Option<(usize, f32)>,  // (index, new_font_size)
     pub resize_rect: Option<(usize, f32, f32)>,  // (index, new_width, new_height)
+    pub resize_shape: Option<(usize, Vec<(f32, f32)>)>,  // (index, new_points)
+    pub redact_rect: Option<(usize, bool)>,  // (index, redact)
     pub edit_text: Option<(usize, String, FontType, bool)>,  // (index, new_text, font_type, transparent)
+    pub edit_comment: Option<(usize, String)>,  // (index, new_text)
+    pub reply_comment: Option<(usize, String)>,  // (index, reply_text) 作成者はアプリ側で設定する
+    pub comment_status: Option<(usize, CommentStatus)>,
     pub delete_custom_stamp: Option<usize>,
     pub register_stamp_clicked: bool,
+    pub new_name_seal: Option<NameSeal>,  // ライブラリに追加する認印
+    pub delete_name_seal: Option<usize>,
+    pub visible_page: Option<usize>,  // 連続・見開き表示でスクロールにより表示中になったページ
+    pub go_to_page: Option<usize>,  // 検索結果の移動で表示するページ
+    pub copied_text: Option<usize>,  // クリップボードにコピーした文字数
+}
+
+impl EditorResult {
+    /// 注釈を変更する操作を含むか
+    pub fn edits_annotations(&self) -> bool {
+        self.new_stamp.is_some()
+            || self.new_text.is_some()
+            || self.new_rect.is_some()
+            || self.new_markup.is_some()
+            || self.new_shape.is_some()
+            || self.new_comment.is_some()
+            || self.delete_stamp.is_some()
+            || self.delete_text.is_some()
+            || self.delete_rect.is_some()
+            || self.delete_markup.is_some()
+            || self.delete_shape.is_some()
+            || self.delete_comment.is_some()
+            || self.move_stamp.is_some()
+            || self.move_text.is_some()
+            || self.move_rect.is_some()
+            || self.move_shape.is_some()
+            || self.move_comment.is_some()
+            || self.resize_stamp.is_some()
+            || self.resize_text.is_some()
+            || self.resize_rect.is_some()
+            || self.resize_shape.is_some()
+            || self.redact_rect.is_some()
+            || self.edit_text.is_some()
+            || self.edit_comment.is_some()
+            || self.reply_comment.is_some()
+            || self.comment_status.is_some()
+    }
 }
 
 /// エディターパネルの状態
 pub struct EditorPanel {
     // ページテクスチャのキャッシュ
-    page_texture: Option<TextureHandle>,
+    page_cache: TextureCache<PageKey>,
+    tile_cache: TextureCache<TileKey>,
     current_page_index: Option<usize>,
     cached_rotation: i32,
-    cached_base_size: (u32, u32),  // レンダリング時の基本サイズ
 
-    // ズーム
+    // ズーム・表示方法
     zoom: f32,
+    view_mode: ViewMode,
+    spread_cover: bool,  // 見開きで1ページ目を右側に単独で表示
+    shown_page: Option<usize>,  // 連続・見開き表示で表示中として扱っているページ
+
+    // 検索
+    search: SearchBar,
+    hit_scroll_target: Option<egui::Rect>,  // 選択中の検索結果の表示位置（スクロール先）
 
     // スタンプ配置モード
     selected_stamp_type: StampType,
     selected_custom_stamp_index: Option<usize>,
+    stamp_category: Option<String>,  // パネルに表示するカスタムスタンプの分類
     placing_stamp: bool,
+    seal: DateSeal,  // 日付印の設定
+    seal_size: f32,  // 日付印の直径（ポイント）
+    name_seal_size: f32,  // 認印の大きさ（ポイント）
+    name_seal_draft: NameSeal,  // 作成ダイアログで編集中の認印
+    show_name_seal_dialog: bool,
 
     // テキスト入力
     text_input: String,
@@ -58,11 +301,35 @@ pub struct EditorPanel {
     // 矩形配置
     placing_rect: bool,
     rect_start_pos: Option<egui::Pos2>,  // ドラッグ開始位置
+    rect_redact: bool,  // 墨消し矩形として配置
+
+    // 手書き・図形
+    drawing_shape: Option<ShapeType>,
+    shape_color: [u8; 3],
+    shape_width: f32,
+    shape_opacity: f32,
+    shape_smoothing: bool,
+    drawing: Option<ShapeAnnotation>,  // 描画中の図形
+
+    // コメント
+    placing_comment: bool,
+    comment_draft: String,  // ポップアップで編集中の本文
+    comment_draft_for: Option<usize>,  // 本文を読み込んだコメント
+    reply_draft: String,
+
+    // テキスト選択
+    selecting_text: bool,
+    page_texts: HashMap<usize, PageText>,  // 選択のために読み取ったページのテキスト
+    text_selection: Option<TextSelection>,
 
     // 選択・ドラッグ
     selected_stamp_index: Option<usize>,
     selected_text_index: Option<usize>,
     selected_rect_index: Option<usize>,
+    selected_markup_index: Option<usize>,
+    selected_shape_index: Option<usize>,
+    selected_comment_index: Option<usize>,
+    pending_selection: Option<AnnotationRef>,  // 一覧から選んだ注釈（次の描画で選択する）
     dragging: bool,
     drag_offset: Vec2,
 
@@ -75,14 +342,25 @@ pub struct EditorPanel {
 impl EditorPanel {
     pub fn new() -> Self {
         Self {
-            page_texture: None,
+            page_cache: TextureCache::new("page", PAGE_CACHE_BYTES),
+            tile_cache: TextureCache::new("page_tile", TILE_CACHE_BYTES),
             current_page_index: None,
             cached_rotation: 0,
-            cached_base_size: (0, 0),
             zoom: 1.0,
+            view_mode: ViewMode::Single,
+            spread_cover: false,
+            shown_page: None,
+            search: SearchBar::new(),
+            hit_scroll_target: None,
             selected_stamp_type: StampType::Approved,
             selected_custom_stamp_index: None,
+            stamp_category: None,
             placing_stamp: false,
+            seal: DateSeal::default(),
+            seal_size: 60.0,
+            name_seal_size: 36.0,
+            name_seal_draft: NameSeal::default(),
+            show_name_seal_dialog: false,
             text_input: String::new(),
             text_font_size: 24.0,
             text_font_type: FontType::Gothic,
@@ -91,9 +369,27 @@ impl EditorPanel {
             editing_text: false,
             placing_rect: false,
             rect_start_pos: None,
+            rect_redact: false,
+            drawing_shape: None,
+            shape_color: [220, 0, 0],
+            shape_width: 2.0,
+            shape_opacity: 1.0,
+            shape_smoothing: true,
+            drawing: None,
+            placing_comment: false,
+            comment_draft: String::new(),
+            comment_draft_for: None,
+            reply_draft: String::new(),
+            selecting_text: false,
+            page_texts: HashMap::new(),
+            text_selection: None,
             selected_stamp_index: None,
             selected_text_index: None,
             selected_rect_index: None,
+            selected_markup_index: None,
+            selected_shape_index: None,
+            selected_comment_index: None,
+            pending_selection: None,
             dragging: false,
             drag_offset: Vec2::ZERO,
             resizing: false,
@@ -173,25 +469,41 @@ impl EditorPanel {
     }
 
     /// カスタムスタンプ付きでUIを描画
-    /// custom_stamps: (名前, テクスチャ, 幅, 高さ)
     pub fn show_with_custom_stamps(
         &mut self,
         ui: &mut egui::Ui,
         doc: &PdfDocument,
         page_index: usize,
-        stamps: &[Stamp],
-        text_annotations: &[TextAnnotation],
-        rect_annotations: &[RectAnnotation],
-        show_stamp_panel: bool,
-        show_text_panel: bool,
-        custom_stamps: &[(String, Option<TextureHandle>, u32, u32)],
+        context: EditorContext,
     ) -> EditorResult {
+        let EditorContext {
+            annotations: data,
+            custom_stamps,
+            name_seals,
+            show_stamp_panel,
+            show_text_panel,
+        } = context;
+        let stamps = &data.stamps[..];
+        let text_annotations = &data.texts[..];
+        let rect_annotations = &data.rects[..];
+        let markups = &data.markups[..];
+        let shapes = &data.shapes[..];
+        let comments = &data.comments[..];
         let mut result = EditorResult::default();
 
+        // ライブラリの並べ替え・削除で番号が変わっても、選択中のカスタムスタンプを名前で選び直す
+        if let StampType::Custom(name) = &self.selected_stamp_type {
+            self.selected_custom_stamp_index = custom_stamps.iter().position(|(n, ..)| n == name);
+            if self.selected_custom_stamp_index.is_none() {
+                self.selected_stamp_type = StampType::Approved;
+            }
+        }
+
         // 回転情報を取得
         let rotation = doc.get_page_rotation(page_index);
-        let orig_size = doc.original_page_size(page_index);
-        let (orig_w, orig_h) = orig_size;
+
+        // 検索
+        result.go_to_page = self.search.show(ui, doc);
 
         // ズームコントロール
         ui.horizontal(|ui| {
@@ -202,13 +514,37 @@ impl EditorPanel {
             }
             ui.label(format!("{:.0}%", self.zoom * 100.0));
             if ui.button("＋").clicked() {
-                self.zoom = (self.zoom + 0.25).min(4.0);
+                self.zoom = (self.zoom + 0.25).min(MAX_ZOOM);
                 // ズームは表示スケーリングで対応、キャッシュ無効化不要
             }
             if ui.button("リセット").clicked() {
                 self.zoom = 1.0;
                 // ズームは表示スケーリングで対応、キャッシュ無効化不要
             }
+            let viewport = ui.clip_rect().size();
+            if ui.button("↔ 幅に合わせる").clicked() {
+                self.zoom = self.fit_zoom(viewport, doc, page_index, false);
+                self.shown_page = None;
+            }
+            if ui.button("⬜ ページに合わせる").clicked() {
+                self.zoom = self.fit_zoom(viewport, doc, page_index, true);
+                self.shown_page = None;
+            }
+
+            ui.separator();
+            let mode_changed = ui.selectable_value(&mut self.view_mode, ViewMode::Single, "単ページ").changed()
+                | ui.selectable_value(&mut self.view_mode, ViewMode::Continuous, "連続").changed()
+                | ui.selectable_value(&mut self.view_mode, ViewMode::Spread, "見開き").changed();
+            if self.view_mode == ViewMode::Spread
+                && ui
+                    .checkbox(&mut self.spread_cover, "表紙を右に")
+                    .on_hover_text("1ページ目を表紙として単独で右側に表示します")
+                    .changed()
+                || mode_changed
+            {
+                // 表示方法を変えたら選択中のページまでスクロール
+                self.shown_page = None;
+            }
             
             ui.separator();
             ui.label(format!("回転: {}°", rotation));
@@ -236,6 +572,12 @@ impl EditorPanel {
                 }
             } else if let Some(idx) = self.selected_rect_index {
                 ui.label(format!("矩形#{} 選択中", idx + 1));
+                if let Some(rect_ann) = rect_annotations.get(idx) {
+                    let mut redact = rect_ann.redact;
+                    if ui.checkbox(&mut redact, "墨消し").changed() {
+                        result.redact_rect = Some((idx, redact));
+                    }
+                }
                 if ui.button("🗑 削除").clicked() {
                     result.delete_rect = Some(idx);
                     self.selected_rect_index = None;
@@ -243,8 +585,80 @@ impl EditorPanel {
                 if ui.button("✕").clicked() {
                     self.selected_rect_index = None;
                 }
+            } else if let Some(idx) = self.selected_markup_index {
+                let label = markups.get(idx).map_or("装飾", |m| m.markup_type.label());
+                ui.label(format!("{}#{} 選択中", label, idx + 1));
+                if ui.button("🗑 削除").clicked() {
+                    result.delete_markup = Some(idx);
+                    self.selected_markup_index = None;
+                }
+                if ui.button("✕").clicked() {
+                    self.selected_markup_index = None;
+                }
+            } else if let Some(idx) = self.selected_shape_index {
+                let label = shapes.get(idx).map_or("図形", |s| s.shape_type.label());
+                ui.label(format!("{}#{} 選択中", label, idx + 1));
+                if ui.button("🗑 削除").clicked() {
+                    result.delete_shape = Some(idx);
+                    self.selected_shape_index = None;
+                }
+                if ui.button("✕").clicked() {
+                    self.selected_shape_index = None;
+                }
+            } else if let Some(idx) = self.selected_comment_index {
+                ui.label(format!("コメント#{} 選択中", idx + 1));
+                if ui.button("🗑 削除").clicked() {
+                    result.delete_comment = Some(idx);
+                    self.selected_comment_index = None;
+                }
+                if ui.button("✕").clicked() {
+                    self.selected_comment_index = None;
+                }
             }
             
+            ui.separator();
+
+            // テキスト選択ボタン
+            let select_btn_color = if self.selecting_text {
+                Color32::from_rgb(50, 180, 80)
+            } else {
+                Color32::from_rgb(180, 180, 180)
+            };
+            if ui
+                .add(egui::Button::new(egui::RichText::new("🔤 テキスト選択").color(Color32::BLACK)).fill(select_btn_color))
+                .on_hover_text("ドラッグで範囲を、ダブルクリックで単語を選択します")
+                .clicked()
+            {
+                self.selecting_text = !self.selecting_text;
+                self.placing_stamp = false;
+                self.placing_comment = false;
+                self.placing_text = false;
+                self.placing_rect = false;
+                self.drawing_shape = None;
+                self.editing_text = false;
+                self.text_selection = None;
+                self.clear_selection();
+            }
+            if self.text_selection.is_some() && ui.button("📋 コピー").on_hover_text("Ctrl+C").clicked() {
+                self.copy_selection(ui.ctx(), &mut result);
+            }
+            if self.text_selection.is_some() {
+                for (markup_type, icon) in [
+                    (MarkupType::Highlight, "🖍"),
+                    (MarkupType::Underline, "U̲"),
+                    (MarkupType::StrikeOut, "S̶"),
+                ] {
+                    if ui
+                        .button(format!("{} {}", icon, markup_type.label()))
+                        .on_hover_text("選択中のテキストを装飾します")
+                        .clicked()
+                    {
+                        result.new_markup = self.markup_selection(markup_type);
+                        self.text_selection = None;
+                    }
+                }
+            }
+
             ui.separator();
             
             // 矩形（白塗り）配置ボタン
@@ -257,13 +671,80 @@ impl EditorPanel {
             if ui.add(egui::Button::new(egui::RichText::new(rect_btn_text).color(Color32::BLACK)).fill(rect_btn_color)).clicked() {
                 self.placing_rect = !self.placing_rect;
                 self.placing_stamp = false;
+                self.placing_comment = false;
                 self.placing_text = false;
+                self.selecting_text = false;
+                self.drawing_shape = None;
                 self.editing_text = false;
                 self.selected_stamp_index = None;
                 self.selected_text_index = None;
                 self.selected_rect_index = None;
+                self.selected_markup_index = None;
+                self.selected_shape_index = None;
+                self.selected_comment_index = None;
                 self.rect_start_pos = None;
             }
+            ui.checkbox(&mut self.rect_redact, "墨消し")
+                .on_hover_text("保存時に矩形の下のテキスト・画像・図形を削除します");
+
+            ui.separator();
+
+            // コメント配置ボタン
+            let comment_btn_text = if self.placing_comment { "🎯 コメント配置中（クリックで配置）" } else { "💬 コメント" };
+            let comment_btn_color = if self.placing_comment {
+                Color32::from_rgb(50, 180, 80)
+            } else {
+                Color32::from_rgb(180, 180, 180)
+            };
+            if ui.add(egui::Button::new(egui::RichText::new(comment_btn_text).color(Color32::BLACK)).fill(comment_btn_color)).clicked() {
+                self.placing_comment = !self.placing_comment;
+                self.placing_stamp = false;
+                self.placing_text = false;
+                self.placing_rect = false;
+                self.selecting_text = false;
+                self.drawing_shape = None;
+                self.editing_text = false;
+                self.text_selection = None;
+                self.clear_selection();
+            }
+        });
+
+        // 手書き・図形ツール
+        ui.horizontal(|ui| {
+            ui.label("描画:");
+            for shape_type in ShapeType::all() {
+                let selected = self.drawing_shape == Some(shape_type);
+                if ui.selectable_label(selected, shape_type.label()).clicked() {
+                    self.drawing_shape = if selected { None } else { Some(shape_type) };
+                    self.drawing = None;
+                    self.placing_stamp = false;
+                    self.placing_comment = false;
+                    self.placing_text = false;
+                    self.placing_rect = false;
+                    self.selecting_text = false;
+                    self.editing_text = false;
+                    self.text_selection = None;
+                    self.clear_selection();
+                }
+            }
+
+            ui.separator();
+            ui.label("色:");
+            ui.color_edit_button_srgb(&mut self.shape_color);
+            ui.label("太さ:");
+            ui.add(egui::DragValue::new(&mut self.shape_width).range(0.5..=20.0).speed(0.1).suffix("pt"));
+            ui.label("不透明度:");
+            ui.add(egui::Slider::new(&mut self.shape_opacity, 0.1..=1.0).show_value(false));
+            match self.drawing_shape {
+                Some(ShapeType::Ink) => {
+                    ui.checkbox(&mut self.shape_smoothing, "なめらか")
+                        .on_hover_text("手書きの線の細かな揺れを抑えます");
+                }
+                Some(ShapeType::Polygon) => {
+                    ui.colored_label(Color32::GRAY, "クリックで頂点を追加、ダブルクリックで完了（Escで取り消し）");
+                }
+                _ => {}
+            }
         });
 
         // スタンプパネル（サムネイル表示）
@@ -282,6 +763,9 @@ impl EditorPanel {
                 if ui.add(egui::Button::new(egui::RichText::new(btn_text).color(text_color)).fill(btn_color)).clicked() {
                     self.placing_stamp = !self.placing_stamp;
                     self.placing_text = false;
+                    self.placing_comment = false;
+                    self.selecting_text = false;
+                    self.drawing_shape = None;
                     self.selected_stamp_index = None;
                     self.selected_text_index = None;
                 }
@@ -291,6 +775,9 @@ impl EditorPanel {
                 if ui.add(egui::Button::new(egui::RichText::new("➕ スタンプ登録").color(Color32::WHITE)).fill(Color32::from_rgb(100, 80, 160))).clicked() {
                     result.register_stamp_clicked = true;
                 }
+                if ui.add(egui::Button::new(egui::RichText::new("🔴 認印作成").color(Color32::WHITE)).fill(Color32::from_rgb(190, 60, 60))).clicked() {
+                    self.show_name_seal_dialog = true;
+                }
             });
             
             ui.add_space(4.0);
@@ -335,12 +822,95 @@ impl EditorPanel {
                             });
                     }
 
+                    // 日付印（設定は下の行で変更）
+                    let selected = matches!(self.selected_stamp_type, StampType::DateSeal(_));
+                    let frame_color = if selected { Color32::YELLOW } else { Color32::from_gray(60) };
+                    let seal_tile = egui::Frame::none()
+                        .fill(Color32::from_gray(230))
+                        .stroke(egui::Stroke::new(if selected { 3.0 } else { 1.0 }, frame_color))
+                        .rounding(4.0)
+                        .inner_margin(4.0)
+                        .show(ui, |ui| {
+                            ui.set_width(thumb_size);
+                            ui.set_height(thumb_size + 16.0);
+                            ui.vertical_centered(|ui| {
+                                let (seal_rect, _) = ui.allocate_exact_size(Vec2::splat(thumb_size - 8.0), egui::Sense::hover());
+                                let layout = self.seal.layout(seal_rect.width(), seal_rect.height());
+                                paint_seal(ui.painter(), &layout, seal_rect, 255);
+                                ui.label(egui::RichText::new("日付印").size(10.0).color(Color32::DARK_GRAY));
+                            });
+                        })
+                        .response;
+                    if ui.interact(seal_tile.rect, egui::Id::new("date_seal_stamp"), egui::Sense::click()).clicked() {
+                        self.selected_stamp_type = StampType::DateSeal(self.seal.clone());
+                        self.selected_custom_stamp_index = None;
+                    }
+
+                    // ライブラリの認印
+                    for (i, seal) in name_seals.iter().enumerate() {
+                        let stamp_type = StampType::NameSeal(seal.clone());
+                        let selected = self.selected_stamp_type == stamp_type;
+                        let frame_color = if selected { Color32::YELLOW } else { Color32::from_gray(60) };
+                        let seal_tile = egui::Frame::none()
+                            .fill(Color32::from_gray(230))
+                            .stroke(egui::Stroke::new(if selected { 3.0 } else { 1.0 }, frame_color))
+                            .rounding(4.0)
+                            .inner_margin(4.0)
+                            .show(ui, |ui| {
+                                ui.set_width(thumb_size);
+                                ui.set_height(thumb_size + 16.0);
+                                ui.vertical_centered(|ui| {
+                                    let (seal_rect, _) = ui.allocate_exact_size(Vec2::splat(thumb_size - 8.0), egui::Sense::hover());
+                                    paint_seal(ui.painter(), &seal.layout(seal_rect.width(), seal_rect.height()), seal_rect, 255);
+                                    let short_name: String = seal.name.chars().take(6).collect();
+                                    ui.label(egui::RichText::new(short_name).size(9.0).color(Color32::DARK_GRAY));
+                                });
+                            })
+                            .response;
+                        let response = ui.interact(seal_tile.rect, egui::Id::new(("name_seal_stamp", i)), egui::Sense::click());
+                        if response.clicked() {
+                            self.selected_stamp_type = stamp_type.clone();
+                            self.selected_custom_stamp_index = None;
+                        }
+                        response.context_menu(|ui| {
+                            if ui.button("🗑 削除").clicked() {
+                                result.delete_name_seal = Some(i);
+                                if selected {
+                                    self.selected_stamp_type = StampType::Approved;
+                                }
+                                ui.close_menu();
+                            }
+                        });
+                    }
+
                     ui.add_space(8.0);
                     ui.separator();
                     ui.add_space(8.0);
 
-                    // カスタムスタンプ
-                    for (i, (name, tex, w, h)) in custom_stamps.iter().enumerate() {
+                    // カスタムスタンプ（分類で絞り込み）
+                    let categories: BTreeSet<&str> = custom_stamps
+                        .iter()
+                        .map(|(.., category)| category.as_str())
+                        .filter(|c| !c.is_empty())
+                        .collect();
+                    if self.stamp_category.as_deref().is_some_and(|c| !categories.contains(c)) {
+                        self.stamp_category = None;
+                    }
+                    if !categories.is_empty() {
+                        egui::ComboBox::from_id_salt("stamp_category")
+                            .selected_text(self.stamp_category.as_deref().unwrap_or("すべて"))
+                            .width(70.0)
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut self.stamp_category, None, "すべて");
+                                for category in &categories {
+                                    ui.selectable_value(&mut self.stamp_category, Some(category.to_string()), *category);
+                                }
+                            });
+                    }
+                    for (i, (name, tex, w, h, category)) in custom_stamps.iter().enumerate() {
+                        if self.stamp_category.as_ref().is_some_and(|c| c != category) {
+                            continue;
+                        }
                         let selected = self.selected_custom_stamp_index == Some(i);
                         let frame_color = if selected { Color32::YELLOW } else { Color32::from_gray(60) };
                         
@@ -398,6 +968,47 @@ impl EditorPanel {
                     }
                 });
             });
+
+            // 日付印の設定
+            if matches!(self.selected_stamp_type, StampType::DateSeal(_)) {
+                ui.horizontal(|ui| {
+                    ui.label("部署:");
+                    ui.add(egui::TextEdit::singleline(&mut self.seal.department).desired_width(90.0));
+                    ui.label("日付:");
+                    ui.add(egui::TextEdit::singleline(&mut self.seal.date).desired_width(80.0).hint_text("YYYY-MM-DD"));
+                    if ui.small_button("今日").clicked() {
+                        self.seal.date = crate::pdf::today();
+                    }
+                    ui.checkbox(&mut self.seal.wareki, "和暦");
+                    ui.label("氏名:");
+                    ui.add(egui::TextEdit::singleline(&mut self.seal.name).desired_width(70.0));
+                });
+                ui.horizontal(|ui| {
+                    ui.label("色:");
+                    ui.color_edit_button_srgb(&mut self.seal.color);
+                    ui.label("直径:");
+                    ui.add(egui::DragValue::new(&mut self.seal_size).range(30.0..=150.0).speed(1.0).suffix("pt"));
+                    ui.colored_label(Color32::GRAY, format!("日付: {}", self.seal.date_text()));
+                });
+                // 配置するスタンプに設定を反映
+                self.selected_stamp_type = StampType::DateSeal(self.seal.clone());
+            }
+            // 認印の大きさ
+            if matches!(self.selected_stamp_type, StampType::NameSeal(_)) {
+                ui.horizontal(|ui| {
+                    ui.label("認印の大きさ:");
+                    ui.add(egui::DragValue::new(&mut self.name_seal_size).range(15.0..=100.0).speed(1.0).suffix("pt"));
+                });
+            }
+        }
+
+        // 認印の作成ダイアログ
+        if self.show_name_seal_dialog {
+            if let Some(seal) = self.name_seal_dialog(ui.ctx()) {
+                self.selected_stamp_type = StampType::NameSeal(seal.clone());
+                self.selected_custom_stamp_index = None;
+                result.new_name_seal = Some(seal);
+            }
         }
 
         // テキストパネル
@@ -468,6 +1079,9 @@ impl EditorPanel {
                 if ui.add(egui::Button::new(egui::RichText::new(btn_text).color(Color32::WHITE)).fill(btn_color)).clicked() && !self.text_input.is_empty() {
                     self.placing_text = !self.placing_text;
                     self.placing_stamp = false;
+                    self.placing_comment = false;
+                    self.selecting_text = false;
+                    self.drawing_shape = None;
                     self.editing_text = false;
                     self.selected_stamp_index = None;
                     self.selected_text_index = None;
@@ -493,48 +1107,205 @@ impl EditorPanel {
 
         ui.separator();
 
-        // ページサイズ計算（回転後）
-        let page_size = doc.page_size(page_index);
-        
-        // 基本レンダリング解像度（最大800px、高解像度は不要）
-        let max_render_size = 800.0;
-        let scale_factor = (max_render_size / page_size.0.max(page_size.1)).min(1.0);
-        let base_width = (page_size.0 * scale_factor) as u32;
-        let base_height = (page_size.1 * scale_factor) as u32;
-        
-        // ページテクスチャを更新（ページ変更または回転変更時のみ）
-        if self.current_page_index != Some(page_index) 
-            || self.cached_rotation != rotation 
-            || self.cached_base_size != (base_width, base_height)
-        {
-            self.current_page_index = Some(page_index);
-            self.cached_rotation = rotation;
-            self.cached_base_size = (base_width, base_height);
-            self.page_texture = None;
-            self.selected_stamp_index = None;
-            self.selected_text_index = None;
+        let ctx = ui.ctx().clone();
+        self.page_cache.poll(&ctx);
+        self.tile_cache.poll(&ctx);
+
+        let annotations = PageAnnotations {
+            stamps,
+            text_annotations,
+            rect_annotations,
+            markups,
+            shapes,
+            comments,
+            custom_stamps,
+        };
+        if self.view_mode == ViewMode::Single {
+            // ページまたは回転が変わったら選択を解除
+            if self.current_page_index != Some(page_index) || self.cached_rotation != rotation {
+                self.current_page_index = Some(page_index);
+                self.cached_rotation = rotation;
+                self.selected_stamp_index = None;
+                self.selected_text_index = None;
+            }
+        }
+        // 一覧から選んだ注釈はページを切り替えた後で選択する
+        if let Some(item) = self.pending_selection.take() {
+            self.clear_selection();
+            let index = Some(item.index);
+            match item.kind {
+                AnnotationKind::Stamp => self.selected_stamp_index = index,
+                AnnotationKind::Text => self.selected_text_index = index,
+                AnnotationKind::Rect => self.selected_rect_index = index,
+                AnnotationKind::Markup => self.selected_markup_index = index,
+                AnnotationKind::Shape => self.selected_shape_index = index,
+                AnnotationKind::Comment => self.selected_comment_index = index,
+            }
+        }
+        if self.selected_comment_index.is_none() {
+            self.comment_draft_for = None;
+        }
+        if self.view_mode == ViewMode::Single {
+            self.show_page(ui, doc, page_index, &annotations, &mut result);
+        } else {
+            self.show_pages(ui, doc, page_index, &annotations, &mut result);
+        }
+
+        // ページへのスクロールより後に指定し、検索結果の位置を優先する
+        if let Some(target) = self.hit_scroll_target.take() {
+            ui.scroll_to_rect(target, Some(egui::Align::Center));
         }
 
-        // ページをレンダリング（基本解像度で1回だけ）
-        if self.page_texture.is_none() {
-            if let Some(image) = doc.render_page(page_index, base_width, base_height) {
-                self.page_texture = Some(ui.ctx().load_texture(
-                    format!("page_{}", page_index),
-                    image,
-                    egui::TextureOptions::LINEAR,  // スケーリング時に滑らかに
-                ));
+        // Deleteキーで削除
+        // 入力欄の編集中（コメントの本文など）は入力欄に任せる
+        let delete_pressed = ui.input(|i| i.key_pressed(egui::Key::Delete) || i.key_pressed(egui::Key::Backspace));
+        if delete_pressed && !ui.ctx().wants_keyboard_input() {
+            if let Some(idx) = self.selected_comment_index {
+                result.delete_comment = Some(idx);
+                self.selected_comment_index = None;
+            } else if let Some(idx) = self.selected_stamp_index {
+                result.delete_stamp = Some(idx);
+                self.selected_stamp_index = None;
+            } else if let Some(idx) = self.selected_text_index {
+                result.delete_text = Some(idx);
+                self.selected_text_index = None;
+            } else if let Some(idx) = self.selected_rect_index {
+                result.delete_rect = Some(idx);
+                self.selected_rect_index = None;
+            } else if let Some(idx) = self.selected_shape_index {
+                result.delete_shape = Some(idx);
+                self.selected_shape_index = None;
+            } else if let Some(idx) = self.selected_markup_index {
+                result.delete_markup = Some(idx);
+                self.selected_markup_index = None;
             }
         }
 
+        // Escで描画中の図形を取り消す
+        if self.drawing.is_some() && ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+            self.drawing = None;
+        }
+
+        // Ctrl+C で選択中のテキストをコピー（入力欄の編集中は入力欄に任せる）
+        let copy = ui.input(|i| i.events.iter().any(|e| matches!(e, egui::Event::Copy)));
+        if copy && self.text_selection.is_some() && !ui.ctx().wants_keyboard_input() {
+            self.copy_selection(ui.ctx(), &mut result);
+        }
+
+        result
+    }
+
+    /// 全ページを縦に並べて描画（見開きでは2ページずつ横に並べる）
+    ///
+    /// 前へ・次へなどで `selected_page` が変わったらそのページまでスクロールし、
+    /// スクロールで表示中のページが変わったら `EditorResult::visible_page` で知らせる。
+    fn show_pages(
+        &mut self,
+        ui: &mut egui::Ui,
+        doc: &PdfDocument,
+        selected_page: usize,
+        annotations: &PageAnnotations,
+        result: &mut EditorResult,
+    ) {
+        let scroll_to = self.shown_page != Some(selected_page);
+        self.shown_page = Some(selected_page);
+
+        let spread = self.view_mode == ViewMode::Spread;
+        let rows = page_rows(doc.page_count(), spread, spread && self.spread_cover);
+        let center_y = ui.clip_rect().center().y;
+        let mut visible_page = None;
+        for row in rows {
+            ui.horizontal(|ui| {
+                ui.spacing_mut().item_spacing.x = PAGE_GAP;
+                for slot in row {
+                    let Some(page) = slot else {
+                        // 表紙を右に置くための空き
+                        ui.add_space(doc.page_size(0).0 * self.zoom);
+                        continue;
+                    };
+                    let rect = self.show_page(ui, doc, page, annotations, result);
+                    if scroll_to && page == selected_page {
+                        ui.scroll_to_rect(rect, Some(egui::Align::TOP));
+                    }
+                    // 表示領域の中央にかかる最初のページを表示中とする
+                    if visible_page.is_none() && rect.max.y >= center_y {
+                        visible_page = Some(page);
+                    }
+                }
+            });
+            ui.add_space(PAGE_GAP);
+        }
+
+        // スクロールを依頼したフレームの配置は古いため、次のフレームで判定する
+        if let Some(page) = visible_page.filter(|&p| !scroll_to && p != selected_page) {
+            self.shown_page = Some(page);
+            result.visible_page = Some(page);
+        }
+    }
+
+    /// 1ページ分を描画し、注釈の表示・選択・配置を処理（ページの表示範囲を返す）
+    fn show_page(
+        &mut self,
+        ui: &mut egui::Ui,
+        doc: &PdfDocument,
+        page_index: usize,
+        annotations: &PageAnnotations,
+        result: &mut EditorResult,
+    ) -> egui::Rect {
+        let PageAnnotations {
+            stamps,
+            text_annotations,
+            rect_annotations,
+            markups,
+            shapes,
+            comments,
+            custom_stamps,
+        } = *annotations;
+        let rotation = doc.get_page_rotation(page_index);
+        let (orig_w, orig_h) = doc.original_page_size(page_index);
+
+        // ページサイズ計算（回転後）
+        let page_size = doc.page_size(page_index);
+
         // 表示サイズ（ズームは表示スケーリングで対応）
         let display_width = page_size.0 * self.zoom;
         let display_height = page_size.1 * self.zoom;
+        let size = Vec2::new(display_width, display_height);
+        let (rect, response) = ui.allocate_exact_size(size, egui::Sense::click_and_drag());
 
-        // ページ描画
-        if let Some(ref texture) = self.page_texture {
-            let size = Vec2::new(display_width, display_height);
-            let (rect, response) = ui.allocate_exact_size(size, egui::Sense::click_and_drag());
+        // 画面外のページは描画しない（ドラッグ中は画面外でも処理を続ける）
+        if !ui.is_rect_visible(rect) && !response.dragged() && !response.drag_stopped() {
+            return rect;
+        }
+
+        // ページ画像はワーカースレッドで描画する
+        // 届くまでは同じページの別のズーム段階の画像で代用する
+        let ctx = ui.ctx().clone();
+        let zoom_bucket = zoom_bucket(self.zoom);
+        // タイル表示ではページ全体は低解像度で描き、タイルが届くまでの下地にする
+        let tiled = is_tiled(page_size, zoom_bucket);
+        let base_bucket = if tiled { 0 } else { zoom_bucket };
+        let page_key = PageKey { page: page_index, rotation, zoom_bucket: base_bucket };
+        let size = render_size(page_size, base_bucket);
+        let page_texture = self
+            .page_cache
+            .get_or_request(&ctx, &page_key, || page_job(doc, page_index, size))
+            .or_else(|| {
+                self.page_cache
+                    .find(|k| k.page == page_index && k.rotation == rotation)
+            });
 
+        // 前後のページを先読み
+        let neighbours = [page_index.checked_sub(1), Some(page_index + 1)];
+        for page in neighbours.into_iter().flatten().filter(|&p| p < doc.page_count()) {
+            let rotation = doc.get_page_rotation(page);
+            let key = PageKey { page, rotation, zoom_bucket: base_bucket };
+            let size = render_size(doc.page_size(page), base_bucket);
+            self.page_cache.request(&ctx, &key, || page_job(doc, page, size), false);
+        }
+
+        // ページ描画
+        if let Some(ref texture) = page_texture {
             // ページ画像描画
             ui.painter().image(
                 texture.id(),
@@ -542,220 +1313,371 @@ impl EditorPanel {
                 egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
                 Color32::WHITE,
             );
+            if tiled {
+                self.draw_tiles(ui, doc, page_index, zoom_bucket, rect);
+            }
+        } else {
+            // 描画結果が届くまでページの大きさで仮表示
+            ui.painter().rect_filled(rect, 0.0, Color32::from_gray(230));
+            let message = if self.page_cache.has_failed(&page_key) {
+                "ページを表示できません"
+            } else {
+                "読み込み中..."
+            };
+            ui.painter().text(
+                rect.center(),
+                egui::Align2::CENTER_CENTER,
+                message,
+                egui::FontId::proportional(16.0),
+                Color32::GRAY,
+            );
+        }
 
-            // 現在のページのスタンプをフィルタ
-            let page_stamps: Vec<(usize, &Stamp)> = stamps
-                .iter()
-                .enumerate()
-                .filter(|(_, s)| s.page == page_index)
-                .collect();
-
-            // 既存のスタンプを描画（回転変換を適用、サイズは維持）
-            for (global_idx, stamp) in &page_stamps {
-                // PDF座標から表示座標に変換（位置のみ、サイズは維持）
-                let (display_x, display_y) = self.pdf_to_display_pos(
-                    stamp.x, stamp.y, stamp.width, stamp.height,
-                    orig_w, orig_h, rotation
-                );
-
-                let stamp_pos = egui::pos2(
-                    rect.min.x + display_x * self.zoom,
-                    rect.min.y + display_y * self.zoom,
-                );
-                // サイズは元のまま維持
-                let stamp_size = Vec2::new(stamp.width * self.zoom, stamp.height * self.zoom);
-                let stamp_rect = egui::Rect::from_min_size(stamp_pos, stamp_size);
-
-                let is_selected = self.selected_stamp_index == Some(*global_idx);
-
-                // カスタムスタンプの場合
-                if let StampType::Custom(ref name) = stamp.stamp_type {
-                    if let Some((_, Some(tex), _, _)) = custom_stamps.iter().find(|(n, _, _, _)| n == name) {
-                        ui.painter().image(
-                            tex.id(),
-                            stamp_rect,
-                            egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
-                            Color32::WHITE,
-                        );
-                    }
-                } else {
-                    // 組み込みスタンプ
-                    let (bg_color, border_color) = match &stamp.stamp_type {
-                        StampType::Approved => (Color32::from_rgba_unmultiplied(200, 255, 200, 180), Color32::GREEN),
-                        StampType::Rejected => (Color32::from_rgba_unmultiplied(255, 200, 200, 180), Color32::RED),
-                        StampType::Draft => (Color32::from_rgba_unmultiplied(255, 255, 200, 180), Color32::from_rgb(200, 150, 0)),
-                        StampType::Confidential => (Color32::from_rgba_unmultiplied(200, 200, 255, 180), Color32::BLUE),
-                        StampType::Custom(_) => (Color32::from_rgba_unmultiplied(220, 220, 220, 180), Color32::GRAY),
-                    };
-
-                    ui.painter().rect_filled(stamp_rect, 4.0, bg_color);
-                    ui.painter().rect_stroke(stamp_rect, 4.0, egui::Stroke::new(2.0, border_color));
-
-                    ui.painter().text(
-                        stamp_rect.center(),
-                        egui::Align2::CENTER_CENTER,
-                        stamp.stamp_type.label(),
-                        egui::FontId::proportional(14.0 * self.zoom),
-                        border_color,
-                    );
+        // 検索結果の強調表示
+        let mut current_hit = None;
+        for (hit, is_current) in self.search.hits_on(page_index) {
+            let color = if is_current {
+                Color32::from_rgba_unmultiplied(255, 140, 0, 110)
+            } else {
+                Color32::from_rgba_unmultiplied(255, 230, 0, 90)
+            };
+            for text_rect in &hit.rects {
+                let screen_rect = self.page_rect_to_screen(text_rect, rect, (orig_w, orig_h), rotation);
+                ui.painter().rect_filled(screen_rect, 1.0, color);
+                if is_current {
+                    ui.painter().rect_stroke(screen_rect, 1.0, egui::Stroke::new(1.5, Color32::from_rgb(230, 100, 0)));
+                    current_hit = Some(current_hit.map_or(screen_rect, |r: egui::Rect| r.union(screen_rect)));
                 }
+            }
+        }
+        if let Some(target) = current_hit {
+            if self.search.take_scroll_request() {
+                self.hit_scroll_target = Some(target);
+            }
+        }
 
-                // 選択枠とリサイズハンドル
-                if is_selected {
-                    ui.painter().rect_stroke(
-                        stamp_rect.expand(3.0),
-                        4.0,
-                        egui::Stroke::new(3.0, Color32::YELLOW),
-                    );
-                    
-                    // リサイズハンドル（右下）
-                    let handle_size = 12.0;
-                    let handle_rect = egui::Rect::from_min_size(
-                        egui::pos2(stamp_rect.max.x - handle_size / 2.0, stamp_rect.max.y - handle_size / 2.0),
-                        Vec2::splat(handle_size),
-                    );
-                    ui.painter().rect_filled(handle_rect, 2.0, Color32::from_rgb(60, 120, 200));
-                    ui.painter().rect_stroke(handle_rect, 2.0, egui::Stroke::new(1.0, Color32::WHITE));
+        // 選択中のテキスト
+        if let Some(selection) = self.text_selection.filter(|s| s.page == page_index) {
+            if let Some(text) = self.page_texts.get(&page_index) {
+                for text_rect in text.line_rects(selection.range()) {
+                    let screen_rect = self.page_rect_to_screen(&text_rect, rect, (orig_w, orig_h), rotation);
+                    ui.painter().rect_filled(screen_rect, 0.0, Color32::from_rgba_unmultiplied(51, 144, 255, 90));
                 }
             }
+        }
 
-            // 現在のページのテキストをフィルタ
-            let page_texts: Vec<(usize, &TextAnnotation)> = text_annotations
-                .iter()
-                .enumerate()
-                .filter(|(_, t)| t.page == page_index)
-                .collect();
+        // テキストの装飾（他の注釈より下に描く）
+        let page_markups: Vec<(usize, &MarkupAnnotation)> = markups
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| m.page == page_index)
+            .collect();
+        for (global_idx, markup) in &page_markups {
+            let [r, g, b, _] = markup.color;
+            // ハイライトは下の文字が見えるよう半透明にする
+            let alpha = if markup.markup_type == MarkupType::Highlight { 90 } else { 220 };
+            let color = Color32::from_rgba_unmultiplied(r, g, b, alpha);
+            for mark_rect in markup.mark_rects() {
+                let screen_rect = self.page_rect_to_screen(&mark_rect, rect, (orig_w, orig_h), rotation);
+                ui.painter().rect_filled(screen_rect, 0.0, color);
+            }
+            if self.selected_markup_index == Some(*global_idx) {
+                for quad in &markup.quads {
+                    let screen_rect = self.page_rect_to_screen(quad, rect, (orig_w, orig_h), rotation);
+                    ui.painter().rect_stroke(screen_rect.expand(2.0), 0.0, egui::Stroke::new(2.0, Color32::YELLOW));
+                }
+            }
+        }
 
-            // 既存のテキスト注釈を描画（回転変換を適用）
-            for (global_idx, annotation) in &page_texts {
-                // 複数行テキストの場合、最長行の幅と行数で計算
-                let lines: Vec<&str> = annotation.text.lines().collect();
-                let max_line_len = lines.iter().map(|l| l.chars().count()).max().unwrap_or(1);
-                let line_count = lines.len().max(1);
-                let text_width = max_line_len as f32 * annotation.font_size * 0.6;
-                let text_height = annotation.font_size * line_count as f32 * 1.2;
-
-                let (display_x, display_y) = self.pdf_to_display_pos(
-                    annotation.x, annotation.y, text_width, text_height,
-                    orig_w, orig_h, rotation
+        // 手書き・図形
+        let page_shapes: Vec<(usize, &ShapeAnnotation)> = shapes
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| s.page == page_index)
+            .collect();
+        for (global_idx, shape) in &page_shapes {
+            self.paint_shape(ui.painter(), shape, rect, (orig_w, orig_h), rotation);
+            if self.selected_shape_index == Some(*global_idx) {
+                let display_rect = self.shape_screen_rect(shape, rect, (orig_w, orig_h), rotation);
+                ui.painter().rect_stroke(display_rect, 0.0, egui::Stroke::new(2.0, Color32::YELLOW));
+
+                // リサイズハンドル（右下）
+                let handle_size = 12.0;
+                let handle_rect = egui::Rect::from_min_size(
+                    egui::pos2(display_rect.max.x - handle_size / 2.0, display_rect.max.y - handle_size / 2.0),
+                    Vec2::splat(handle_size),
                 );
+                ui.painter().rect_filled(handle_rect, 2.0, Color32::from_rgb(60, 120, 200));
+                ui.painter().rect_stroke(handle_rect, 2.0, egui::Stroke::new(1.0, Color32::WHITE));
+            }
+        }
 
-                let text_pos = egui::pos2(
-                    rect.min.x + display_x * self.zoom,
-                    rect.min.y + display_y * self.zoom,
-                );
-                
-                let is_selected = self.selected_text_index == Some(*global_idx);
-                
-                // フォントタイプに応じたフォント選択
-                let font = match annotation.font_type {
-                    FontType::Gothic => egui::FontId::proportional(annotation.font_size * self.zoom),
-                    FontType::Mincho => egui::FontId::monospace(annotation.font_size * self.zoom),
-                };
-                
-                // 複数行対応でレイアウト
-                let galley = ui.painter().layout(
-                    annotation.text.clone(),
-                    font.clone(),
-                    Color32::BLACK,
-                    f32::INFINITY,
-                );
-                let text_rect = egui::Rect::from_min_size(text_pos, galley.size());
-
-                // 背景（透過設定に応じて）
-                if !annotation.transparent {
-                    ui.painter().rect_filled(
-                        text_rect.expand(4.0),
-                        2.0,
-                        Color32::from_rgb(255, 255, 255),
-                    );
-                    ui.painter().rect_stroke(
-                        text_rect.expand(4.0),
-                        2.0,
-                        egui::Stroke::new(1.0, Color32::from_gray(180)),
-                    );
-                }
+        // 現在のページのスタンプをフィルタ
+        let page_stamps: Vec<(usize, &Stamp)> = stamps
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| s.page == page_index)
+            .collect();
+
+        // 既存のスタンプを描画（回転変換を適用、サイズは維持）
+        for (global_idx, stamp) in &page_stamps {
+            // PDF座標から表示座標に変換（位置のみ、サイズは維持）
+            let (display_x, display_y) = self.pdf_to_display_pos(
+                stamp.x, stamp.y, stamp.width, stamp.height,
+                orig_w, orig_h, rotation
+            );
 
-                if is_selected {
-                    ui.painter().rect_filled(
-                        text_rect.expand(2.0),
-                        2.0,
-                        Color32::from_rgba_unmultiplied(255, 255, 0, 100),
-                    );
-                    ui.painter().rect_stroke(
-                        text_rect.expand(2.0),
-                        2.0,
-                        egui::Stroke::new(2.0, Color32::YELLOW),
-                    );
-                    
-                    // リサイズハンドル（右下）- フォントサイズ変更用
-                    let handle_size = 10.0;
-                    let handle_rect = egui::Rect::from_min_size(
-                        egui::pos2(text_rect.max.x - handle_size / 2.0, text_rect.max.y - handle_size / 2.0),
-                        Vec2::splat(handle_size),
+            let stamp_pos = egui::pos2(
+                rect.min.x + display_x * self.zoom,
+                rect.min.y + display_y * self.zoom,
+            );
+            // サイズは元のまま維持
+            let stamp_size = Vec2::new(stamp.width * self.zoom, stamp.height * self.zoom);
+            let stamp_rect = egui::Rect::from_min_size(stamp_pos, stamp_size);
+
+            let is_selected = self.selected_stamp_index == Some(*global_idx);
+
+            // カスタムスタンプの場合
+            if let StampType::Custom(ref name) = stamp.stamp_type {
+                if let Some((_, Some(tex), _, _, _)) = custom_stamps.iter().find(|(n, ..)| n == name) {
+                    ui.painter().image(
+                        tex.id(),
+                        stamp_rect,
+                        egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
+                        Color32::WHITE,
                     );
-                    ui.painter().rect_filled(handle_rect, 2.0, Color32::from_rgb(200, 120, 60));
-                    ui.painter().rect_stroke(handle_rect, 2.0, egui::Stroke::new(1.0, Color32::WHITE));
                 }
+            } else if let Some(layout) = stamp.stamp_type.seal_layout(stamp_rect.width(), stamp_rect.height()) {
+                paint_seal(ui.painter(), &layout, stamp_rect, 255);
+            } else {
+                // 組み込みスタンプ
+                let (bg_color, border_color) = match &stamp.stamp_type {
+                    StampType::Approved => (Color32::from_rgba_unmultiplied(200, 255, 200, 180), Color32::GREEN),
+                    StampType::Rejected => (Color32::from_rgba_unmultiplied(255, 200, 200, 180), Color32::RED),
+                    StampType::Draft => (Color32::from_rgba_unmultiplied(255, 255, 200, 180), Color32::from_rgb(200, 150, 0)),
+                    StampType::Confidential => (Color32::from_rgba_unmultiplied(200, 200, 255, 180), Color32::BLUE),
+                    StampType::Custom(_) | StampType::DateSeal(_) | StampType::NameSeal(_) => (Color32::from_rgba_unmultiplied(220, 220, 220, 180), Color32::GRAY),
+                };
 
-                ui.painter().galley(text_pos, galley, Color32::BLACK);
-            }
-
-            // 現在のページの矩形をフィルタ
-            let page_rects: Vec<(usize, &RectAnnotation)> = rect_annotations
-                .iter()
-                .enumerate()
-                .filter(|(_, r)| r.page == page_index)
-                .collect();
+                ui.painter().rect_filled(stamp_rect, 4.0, bg_color);
+                ui.painter().rect_stroke(stamp_rect, 4.0, egui::Stroke::new(2.0, border_color));
 
-            // 既存の矩形を描画（回転変換を適用）
-            for (global_idx, rect_ann) in &page_rects {
-                let (display_x, display_y) = self.pdf_to_display_pos(
-                    rect_ann.x, rect_ann.y, rect_ann.width, rect_ann.height,
-                    orig_w, orig_h, rotation
+                ui.painter().text(
+                    stamp_rect.center(),
+                    egui::Align2::CENTER_CENTER,
+                    stamp.stamp_type.label(),
+                    egui::FontId::proportional(14.0 * self.zoom),
+                    border_color,
                 );
+            }
 
-                let rect_pos = egui::pos2(
-                    rect.min.x + display_x * self.zoom,
-                    rect.min.y + display_y * self.zoom,
+            // 選択枠とリサイズハンドル
+            if is_selected {
+                ui.painter().rect_stroke(
+                    stamp_rect.expand(3.0),
+                    4.0,
+                    egui::Stroke::new(3.0, Color32::YELLOW),
                 );
-                let rect_size = Vec2::new(rect_ann.width * self.zoom, rect_ann.height * self.zoom);
-                let display_rect = egui::Rect::from_min_size(rect_pos, rect_size);
-                
-                let is_selected = self.selected_rect_index == Some(*global_idx);
                 
-                // 矩形を描画（白塗り、枠なし）
-                let fill_color = Color32::from_rgba_unmultiplied(
-                    rect_ann.color[0], rect_ann.color[1], rect_ann.color[2], rect_ann.color[3]
+                // リサイズハンドル（右下）
+                let handle_size = 12.0;
+                let handle_rect = egui::Rect::from_min_size(
+                    egui::pos2(stamp_rect.max.x - handle_size / 2.0, stamp_rect.max.y - handle_size / 2.0),
+                    Vec2::splat(handle_size),
                 );
-                ui.painter().rect_filled(display_rect, 0.0, fill_color);
-                
-                // 選択枠とリサイズハンドル
-                if is_selected {
-                    ui.painter().rect_stroke(
-                        display_rect.expand(2.0),
-                        0.0,
-                        egui::Stroke::new(2.0, Color32::YELLOW),
-                    );
-                    
-                    // リサイズハンドル（右下）
-                    let handle_size = 12.0;
-                    let handle_rect = egui::Rect::from_min_size(
-                        egui::pos2(display_rect.max.x - handle_size / 2.0, display_rect.max.y - handle_size / 2.0),
-                        Vec2::splat(handle_size),
-                    );
-                    ui.painter().rect_filled(handle_rect, 2.0, Color32::from_rgb(60, 120, 200));
-                    ui.painter().rect_stroke(handle_rect, 2.0, egui::Stroke::new(1.0, Color32::WHITE));
-                }
+                ui.painter().rect_filled(handle_rect, 2.0, Color32::from_rgb(60, 120, 200));
+                ui.painter().rect_stroke(handle_rect, 2.0, egui::Stroke::new(1.0, Color32::WHITE));
             }
+        }
 
-            // クリック・ドラッグ処理
-            if !self.placing_stamp && !self.placing_text && !self.placing_rect {
-                if response.clicked() {
-                    if let Some(pos) = response.interact_pointer_pos() {
-                        let mut found = false;
-                        
-                        // 矩形の選択（最前面のものから）
+        // 現在のページのテキストをフィルタ
+        let page_texts: Vec<(usize, &TextAnnotation)> = text_annotations
+            .iter()
+            .enumerate()
+            .filter(|(_, t)| t.page == page_index)
+            .collect();
+
+        // 既存のテキスト注釈を描画（回転変換を適用）
+        for (global_idx, annotation) in &page_texts {
+            // 複数行テキストの場合、最長行の幅と行数で計算
+            let lines: Vec<&str> = annotation.text.lines().collect();
+            let max_line_len = lines.iter().map(|l| l.chars().count()).max().unwrap_or(1);
+            let line_count = lines.len().max(1);
+            let text_width = max_line_len as f32 * annotation.font_size * 0.6;
+            let text_height = annotation.font_size * line_count as f32 * 1.2;
+
+            let (display_x, display_y) = self.pdf_to_display_pos(
+                annotation.x, annotation.y, text_width, text_height,
+                orig_w, orig_h, rotation
+            );
+
+            let text_pos = egui::pos2(
+                rect.min.x + display_x * self.zoom,
+                rect.min.y + display_y * self.zoom,
+            );
+            
+            let is_selected = self.selected_text_index == Some(*global_idx);
+            
+            // フォントタイプに応じたフォント選択
+            let font = match annotation.font_type {
+                FontType::Gothic => egui::FontId::proportional(annotation.font_size * self.zoom),
+                FontType::Mincho => egui::FontId::monospace(annotation.font_size * self.zoom),
+            };
+            
+            // 複数行対応でレイアウト
+            let galley = ui.painter().layout(
+                annotation.text.clone(),
+                font.clone(),
+                Color32::BLACK,
+                f32::INFINITY,
+            );
+            let text_rect = egui::Rect::from_min_size(text_pos, galley.size());
+
+            // 背景（透過設定に応じて）
+            if !annotation.transparent {
+                ui.painter().rect_filled(
+                    text_rect.expand(4.0),
+                    2.0,
+                    Color32::from_rgb(255, 255, 255),
+                );
+                ui.painter().rect_stroke(
+                    text_rect.expand(4.0),
+                    2.0,
+                    egui::Stroke::new(1.0, Color32::from_gray(180)),
+                );
+            }
+
+            if is_selected {
+                ui.painter().rect_filled(
+                    text_rect.expand(2.0),
+                    2.0,
+                    Color32::from_rgba_unmultiplied(255, 255, 0, 100),
+                );
+                ui.painter().rect_stroke(
+                    text_rect.expand(2.0),
+                    2.0,
+                    egui::Stroke::new(2.0, Color32::YELLOW),
+                );
+                
+                // リサイズハンドル（右下）- フォントサイズ変更用
+                let handle_size = 10.0;
+                let handle_rect = egui::Rect::from_min_size(
+                    egui::pos2(text_rect.max.x - handle_size / 2.0, text_rect.max.y - handle_size / 2.0),
+                    Vec2::splat(handle_size),
+                );
+                ui.painter().rect_filled(handle_rect, 2.0, Color32::from_rgb(200, 120, 60));
+                ui.painter().rect_stroke(handle_rect, 2.0, egui::Stroke::new(1.0, Color32::WHITE));
+            }
+
+            ui.painter().galley(text_pos, galley, Color32::BLACK);
+        }
+
+        // 現在のページの矩形をフィルタ
+        let page_rects: Vec<(usize, &RectAnnotation)> = rect_annotations
+            .iter()
+            .enumerate()
+            .filter(|(_, r)| r.page == page_index)
+            .collect();
+
+        // 既存の矩形を描画（回転変換を適用）
+        for (global_idx, rect_ann) in &page_rects {
+            let (display_x, display_y) = self.pdf_to_display_pos(
+                rect_ann.x, rect_ann.y, rect_ann.width, rect_ann.height,
+                orig_w, orig_h, rotation
+            );
+
+            let rect_pos = egui::pos2(
+                rect.min.x + display_x * self.zoom,
+                rect.min.y + display_y * self.zoom,
+            );
+            let rect_size = Vec2::new(rect_ann.width * self.zoom, rect_ann.height * self.zoom);
+            let display_rect = egui::Rect::from_min_size(rect_pos, rect_size);
+            
+            let is_selected = self.selected_rect_index == Some(*global_idx);
+            
+            // 矩形を描画（白塗り、枠なし）
+            let fill_color = Color32::from_rgba_unmultiplied(
+                rect_ann.color[0], rect_ann.color[1], rect_ann.color[2], rect_ann.color[3]
+            );
+            ui.painter().rect_filled(display_rect, 0.0, fill_color);
+            // 墨消し矩形は赤枠で区別
+            if rect_ann.redact {
+                ui.painter().rect_stroke(display_rect, 0.0, egui::Stroke::new(1.5, Color32::RED));
+            }
+            
+            // 選択枠とリサイズハンドル
+            if is_selected {
+                ui.painter().rect_stroke(
+                    display_rect.expand(2.0),
+                    0.0,
+                    egui::Stroke::new(2.0, Color32::YELLOW),
+                );
+                
+                // リサイズハンドル（右下）
+                let handle_size = 12.0;
+                let handle_rect = egui::Rect::from_min_size(
+                    egui::pos2(display_rect.max.x - handle_size / 2.0, display_rect.max.y - handle_size / 2.0),
+                    Vec2::splat(handle_size),
+                );
+                ui.painter().rect_filled(handle_rect, 2.0, Color32::from_rgb(60, 120, 200));
+                ui.painter().rect_stroke(handle_rect, 2.0, egui::Stroke::new(1.0, Color32::WHITE));
+            }
+        }
+
+        // コメントのアイコン（最前面に描く）
+        let page_comments: Vec<(usize, &CommentAnnotation)> = comments
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| c.page == page_index)
+            .collect();
+        for (global_idx, comment) in &page_comments {
+            let icon_rect = self.comment_screen_rect(comment, rect, (orig_w, orig_h), rotation);
+            let fill = match comment.status {
+                CommentStatus::Open => Color32::from_rgb(255, 220, 80),
+                CommentStatus::Resolved => Color32::from_gray(190),
+            };
+            ui.painter().rect_filled(icon_rect, 3.0, fill);
+            ui.painter().rect_stroke(icon_rect, 3.0, egui::Stroke::new(1.0, Color32::from_gray(90)));
+            ui.painter().text(
+                icon_rect.center(),
+                egui::Align2::CENTER_CENTER,
+                "💬",
+                egui::FontId::proportional(COMMENT_ICON_SIZE * 0.6 * self.zoom),
+                Color32::BLACK,
+            );
+            if self.selected_comment_index == Some(*global_idx) {
+                ui.painter().rect_stroke(icon_rect.expand(3.0), 3.0, egui::Stroke::new(3.0, Color32::YELLOW));
+            }
+        }
+
+        // クリック・ドラッグ処理
+        if !self.placing_stamp
+            && !self.placing_text
+            && !self.placing_rect
+            && !self.selecting_text
+            && self.drawing_shape.is_none()
+            && !self.placing_comment
+        {
+            if response.clicked() {
+                if let Some(pos) = response.interact_pointer_pos() {
+                    let mut found = false;
+                    
+                    // コメントの選択（最前面なので最初）
+                    for (global_idx, comment) in page_comments.iter().rev() {
+                        if self.comment_screen_rect(comment, rect, (orig_w, orig_h), rotation).contains(pos) {
+                            self.selected_comment_index = Some(*global_idx);
+                            self.selected_stamp_index = None;
+                            self.selected_text_index = None;
+                            self.selected_rect_index = None;
+                            self.selected_markup_index = None;
+                            self.selected_shape_index = None;
+                            found = true;
+                            break;
+                        }
+                    }
+
+                    // 矩形の選択（最前面のものから）
+                    if !found {
                         for (global_idx, rect_ann) in page_rects.iter().rev() {
                             let (display_x, display_y) = self.pdf_to_display_pos(
                                 rect_ann.x, rect_ann.y, rect_ann.width, rect_ann.height,
@@ -769,503 +1691,1155 @@ impl EditorPanel {
                                 self.selected_rect_index = Some(*global_idx);
                                 self.selected_stamp_index = None;
                                 self.selected_text_index = None;
+                                self.selected_markup_index = None;
+                                self.selected_shape_index = None;
+                                self.selected_comment_index = None;
                                 found = true;
                                 break;
                             }
                         }
-                        
-                        // スタンプの選択
-                        if !found {
-                            for (global_idx, stamp) in page_stamps.iter().rev() {
-                                let (display_x, display_y) = self.pdf_to_display_pos(
-                                    stamp.x, stamp.y, stamp.width, stamp.height,
-                                    orig_w, orig_h, rotation
-                                );
-                                let stamp_rect = egui::Rect::from_min_size(
-                                    egui::pos2(rect.min.x + display_x * self.zoom, rect.min.y + display_y * self.zoom),
-                                    Vec2::new(stamp.width * self.zoom, stamp.height * self.zoom),
-                                );
-                                if stamp_rect.contains(pos) {
-                                    self.selected_stamp_index = Some(*global_idx);
-                                    self.selected_text_index = None;
-                                    self.selected_rect_index = None;
-                                    found = true;
-                                    break;
-                                }
+                    }
+                    
+                    // スタンプの選択
+                    if !found {
+                        for (global_idx, stamp) in page_stamps.iter().rev() {
+                            let (display_x, display_y) = self.pdf_to_display_pos(
+                                stamp.x, stamp.y, stamp.width, stamp.height,
+                                orig_w, orig_h, rotation
+                            );
+                            let stamp_rect = egui::Rect::from_min_size(
+                                egui::pos2(rect.min.x + display_x * self.zoom, rect.min.y + display_y * self.zoom),
+                                Vec2::new(stamp.width * self.zoom, stamp.height * self.zoom),
+                            );
+                            if stamp_rect.contains(pos) {
+                                self.selected_stamp_index = Some(*global_idx);
+                                self.selected_text_index = None;
+                                self.selected_rect_index = None;
+                                self.selected_markup_index = None;
+                                self.selected_shape_index = None;
+                                self.selected_comment_index = None;
+                                found = true;
+                                break;
                             }
                         }
-                        
-                        // テキストの選択
-                        if !found {
-                            for (global_idx, annotation) in page_texts.iter().rev() {
-                                let text_width = annotation.text.len() as f32 * annotation.font_size * 0.6;
-                                let text_height = annotation.font_size;
-                                let (display_x, display_y) = self.pdf_to_display_pos(
-                                    annotation.x, annotation.y, text_width, text_height,
-                                    orig_w, orig_h, rotation
-                                );
-                                let text_pos = egui::pos2(
-                                    rect.min.x + display_x * self.zoom,
-                                    rect.min.y + display_y * self.zoom,
-                                );
-                                let font = egui::FontId::proportional(annotation.font_size * self.zoom);
-                                let galley = ui.painter().layout_no_wrap(
-                                    annotation.text.clone(),
-                                    font,
-                                    Color32::BLACK,
-                                );
-                                let text_rect = egui::Rect::from_min_size(text_pos, galley.size());
-                                
-                                if text_rect.contains(pos) {
-                                    self.selected_text_index = Some(*global_idx);
-                                    self.selected_stamp_index = None;
-                                    self.selected_rect_index = None;
-                                    found = true;
-                                    break;
-                                }
+                    }
+                    
+                    // テキストの選択
+                    if !found {
+                        for (global_idx, annotation) in page_texts.iter().rev() {
+                            let text_width = annotation.text.len() as f32 * annotation.font_size * 0.6;
+                            let text_height = annotation.font_size;
+                            let (display_x, display_y) = self.pdf_to_display_pos(
+                                annotation.x, annotation.y, text_width, text_height,
+                                orig_w, orig_h, rotation
+                            );
+                            let text_pos = egui::pos2(
+                                rect.min.x + display_x * self.zoom,
+                                rect.min.y + display_y * self.zoom,
+                            );
+                            let font = egui::FontId::proportional(annotation.font_size * self.zoom);
+                            let galley = ui.painter().layout_no_wrap(
+                                annotation.text.clone(),
+                                font,
+                                Color32::BLACK,
+                            );
+                            let text_rect = egui::Rect::from_min_size(text_pos, galley.size());
+                            
+                            if text_rect.contains(pos) {
+                                self.selected_text_index = Some(*global_idx);
+                                self.selected_stamp_index = None;
+                                self.selected_rect_index = None;
+                                self.selected_markup_index = None;
+                                self.selected_shape_index = None;
+                                self.selected_comment_index = None;
+                                found = true;
+                                break;
                             }
                         }
-                        
-                        if !found {
-                            self.selected_stamp_index = None;
-                            self.selected_text_index = None;
-                            self.selected_rect_index = None;
-                        }
                     }
-                }
 
-                // ドラッグ開始
-                if response.drag_started() {
-                    if let Some(pos) = response.interact_pointer_pos() {
-                        let handle_size = 12.0;
-                        
-                        // スタンプのリサイズハンドルチェック
-                        if let Some(idx) = self.selected_stamp_index {
-                            if let Some(stamp) = stamps.get(idx) {
-                                let (display_x, display_y) = self.pdf_to_display_pos(
-                                    stamp.x, stamp.y, stamp.width, stamp.height,
-                                    orig_w, orig_h, rotation
-                                );
-                                let stamp_rect = egui::Rect::from_min_size(
-                                    egui::pos2(rect.min.x + display_x * self.zoom, rect.min.y + display_y * self.zoom),
-                                    Vec2::new(stamp.width * self.zoom, stamp.height * self.zoom),
-                                );
-                                
-                                // リサイズハンドル（右下）
-                                let handle_rect = egui::Rect::from_min_size(
-                                    egui::pos2(stamp_rect.max.x - handle_size / 2.0, stamp_rect.max.y - handle_size / 2.0),
-                                    Vec2::splat(handle_size),
-                                );
-                                
-                                if handle_rect.contains(pos) {
-                                    // リサイズモード
-                                    self.resizing = true;
-                                    self.resize_corner = ResizeCorner::BottomRight;
-                                    self.resize_start_size = Vec2::new(stamp.width, stamp.height);
-                                    self.drag_offset = Vec2::new(pos.x - stamp_rect.max.x, pos.y - stamp_rect.max.y);
-                                } else if stamp_rect.contains(pos) {
-                                    // 移動モード
-                                    let stamp_pos = stamp_rect.min;
-                                    self.drag_offset = Vec2::new(pos.x - stamp_pos.x, pos.y - stamp_pos.y);
-                                    self.dragging = true;
-                                }
-                            }
-                        } else if let Some(idx) = self.selected_text_index {
-                            if let Some(annotation) = text_annotations.get(idx) {
-                                let text_width = annotation.text.len() as f32 * annotation.font_size * 0.6;
-                                let text_height = annotation.font_size;
-                                let (display_x, display_y) = self.pdf_to_display_pos(
-                                    annotation.x, annotation.y, text_width, text_height,
-                                    orig_w, orig_h, rotation
-                                );
-                                let text_pos = egui::pos2(
-                                    rect.min.x + display_x * self.zoom,
-                                    rect.min.y + display_y * self.zoom,
-                                );
-                                let font = egui::FontId::proportional(annotation.font_size * self.zoom);
-                                let galley = ui.painter().layout_no_wrap(
-                                    annotation.text.clone(),
-                                    font,
-                                    Color32::BLACK,
-                                );
-                                let text_rect = egui::Rect::from_min_size(text_pos, galley.size());
-                                
-                                // リサイズハンドル（右下）
-                                let handle_rect = egui::Rect::from_min_size(
-                                    egui::pos2(text_rect.max.x - handle_size / 2.0, text_rect.max.y - handle_size / 2.0),
-                                    Vec2::splat(handle_size),
-                                );
-                                
-                                if handle_rect.contains(pos) {
-                                    // リサイズモード（フォントサイズ変更）
-                                    self.resizing = true;
-                                    self.resize_corner = ResizeCorner::BottomRight;
-                                    self.resize_start_size = Vec2::new(annotation.font_size, 0.0);
-                                    self.drag_offset = Vec2::new(pos.x - text_rect.max.x, pos.y - text_rect.max.y);
-                                } else if text_rect.contains(pos) {
-                                    // 移動モード
-                                    self.drag_offset = Vec2::new(pos.x - text_pos.x, pos.y - text_pos.y);
-                                    self.dragging = true;
-                                }
-                            }
-                        } else if let Some(idx) = self.selected_rect_index {
-                            if let Some(rect_ann) = rect_annotations.get(idx) {
-                                let (display_x, display_y) = self.pdf_to_display_pos(
-                                    rect_ann.x, rect_ann.y, rect_ann.width, rect_ann.height,
-                                    orig_w, orig_h, rotation
-                                );
-                                let display_rect = egui::Rect::from_min_size(
-                                    egui::pos2(rect.min.x + display_x * self.zoom, rect.min.y + display_y * self.zoom),
-                                    Vec2::new(rect_ann.width * self.zoom, rect_ann.height * self.zoom),
-                                );
-                                
-                                // リサイズハンドル（右下）
-                                let handle_rect = egui::Rect::from_min_size(
-                                    egui::pos2(display_rect.max.x - handle_size / 2.0, display_rect.max.y - handle_size / 2.0),
-                                    Vec2::splat(handle_size),
-                                );
-                                
-                                if handle_rect.contains(pos) {
-                                    // リサイズモード
-                                    self.resizing = true;
-                                    self.resize_corner = ResizeCorner::BottomRight;
-                                    self.resize_start_size = Vec2::new(rect_ann.width, rect_ann.height);
-                                    self.drag_offset = Vec2::new(pos.x - display_rect.max.x, pos.y - display_rect.max.y);
-                                } else if display_rect.contains(pos) {
-                                    // 移動モード
-                                    self.drag_offset = Vec2::new(pos.x - display_rect.min.x, pos.y - display_rect.min.y);
-                                    self.dragging = true;
-                                }
+                    // 手書き・図形の選択
+                    if !found {
+                        for (global_idx, shape) in page_shapes.iter().rev() {
+                            if self.shape_screen_rect(shape, rect, (orig_w, orig_h), rotation).contains(pos) {
+                                self.selected_shape_index = Some(*global_idx);
+                                self.selected_stamp_index = None;
+                                self.selected_text_index = None;
+                                self.selected_rect_index = None;
+                                self.selected_comment_index = None;
+                                found = true;
+                                break;
                             }
                         }
                     }
-                }
 
-                if response.dragged() && self.dragging {
-                    ui.ctx().set_cursor_icon(egui::CursorIcon::Grabbing);
-                }
-                
-                if response.dragged() && self.resizing {
-                    ui.ctx().set_cursor_icon(egui::CursorIcon::ResizeNwSe);
+                    // テキストの装飾の選択（最背面なので最後）
+                    if !found {
+                        for (global_idx, markup) in page_markups.iter().rev() {
+                            let hit = markup.quads.iter().any(|quad| {
+                                self.page_rect_to_screen(quad, rect, (orig_w, orig_h), rotation).contains(pos)
+                            });
+                            if hit {
+                                self.selected_markup_index = Some(*global_idx);
+                                self.selected_stamp_index = None;
+                                self.selected_text_index = None;
+                                self.selected_rect_index = None;
+                                self.selected_shape_index = None;
+                                self.selected_comment_index = None;
+                                found = true;
+                                break;
+                            }
+                        }
+                    }
+                    
+                    if !found {
+                        self.selected_stamp_index = None;
+                        self.selected_text_index = None;
+                        self.selected_rect_index = None;
+                        self.selected_markup_index = None;
+                        self.selected_shape_index = None;
+                        self.selected_comment_index = None;
+                    }
                 }
+            }
 
-                // ドラッグ終了 - 移動
-                if response.drag_stopped() && self.dragging {
-                    if let Some(pos) = ui.input(|i| i.pointer.hover_pos()) {
-                        let display_x = (pos.x - rect.min.x - self.drag_offset.x) / self.zoom;
-                        let display_y = (pos.y - rect.min.y - self.drag_offset.y) / self.zoom;
-                        
-                        if let Some(idx) = self.selected_stamp_index {
-                            if let Some(stamp) = stamps.get(idx) {
-                                let (pdf_x, pdf_y) = self.display_to_pdf(
-                                    display_x, display_y, stamp.width, stamp.height,
-                                    orig_w, orig_h, rotation
-                                );
-                                result.move_stamp = Some((idx, pdf_x, pdf_y));
-                            }
-                        } else if let Some(idx) = self.selected_text_index {
-                            if let Some(annotation) = text_annotations.get(idx) {
-                                let text_width = annotation.text.len() as f32 * annotation.font_size * 0.6;
-                                let text_height = annotation.font_size;
-                                let (pdf_x, pdf_y) = self.display_to_pdf(
-                                    display_x, display_y, text_width, text_height,
-                                    orig_w, orig_h, rotation
-                                );
-                                result.move_text = Some((idx, pdf_x, pdf_y));
+            // ドラッグ開始
+            if response.drag_started() {
+                if let Some(pos) = response.interact_pointer_pos() {
+                    let handle_size = 12.0;
+                    
+                    // スタンプのリサイズハンドルチェック
+                    if let Some(idx) = self.selected_stamp_index {
+                        if let Some(stamp) = stamps.get(idx).filter(|a| a.page == page_index) {
+                            let (display_x, display_y) = self.pdf_to_display_pos(
+                                stamp.x, stamp.y, stamp.width, stamp.height,
+                                orig_w, orig_h, rotation
+                            );
+                            let stamp_rect = egui::Rect::from_min_size(
+                                egui::pos2(rect.min.x + display_x * self.zoom, rect.min.y + display_y * self.zoom),
+                                Vec2::new(stamp.width * self.zoom, stamp.height * self.zoom),
+                            );
+                            
+                            // リサイズハンドル（右下）
+                            let handle_rect = egui::Rect::from_min_size(
+                                egui::pos2(stamp_rect.max.x - handle_size / 2.0, stamp_rect.max.y - handle_size / 2.0),
+                                Vec2::splat(handle_size),
+                            );
+                            
+                            if handle_rect.contains(pos) {
+                                // リサイズモード
+                                self.resizing = true;
+                                self.resize_corner = ResizeCorner::BottomRight;
+                                self.resize_start_size = Vec2::new(stamp.width, stamp.height);
+                                self.drag_offset = Vec2::new(pos.x - stamp_rect.max.x, pos.y - stamp_rect.max.y);
+                            } else if stamp_rect.contains(pos) {
+                                // 移動モード
+                                let stamp_pos = stamp_rect.min;
+                                self.drag_offset = Vec2::new(pos.x - stamp_pos.x, pos.y - stamp_pos.y);
+                                self.dragging = true;
                             }
-                        } else if let Some(idx) = self.selected_rect_index {
-                            if let Some(rect_ann) = rect_annotations.get(idx) {
-                                let (pdf_x, pdf_y) = self.display_to_pdf(
-                                    display_x, display_y, rect_ann.width, rect_ann.height,
-                                    orig_w, orig_h, rotation
-                                );
-                                result.move_rect = Some((idx, pdf_x, pdf_y));
+                        }
+                    } else if let Some(idx) = self.selected_text_index {
+                        if let Some(annotation) = text_annotations.get(idx).filter(|a| a.page == page_index) {
+                            let text_width = annotation.text.len() as f32 * annotation.font_size * 0.6;
+                            let text_height = annotation.font_size;
+                            let (display_x, display_y) = self.pdf_to_display_pos(
+                                annotation.x, annotation.y, text_width, text_height,
+                                orig_w, orig_h, rotation
+                            );
+                            let text_pos = egui::pos2(
+                                rect.min.x + display_x * self.zoom,
+                                rect.min.y + display_y * self.zoom,
+                            );
+                            let font = egui::FontId::proportional(annotation.font_size * self.zoom);
+                            let galley = ui.painter().layout_no_wrap(
+                                annotation.text.clone(),
+                                font,
+                                Color32::BLACK,
+                            );
+                            let text_rect = egui::Rect::from_min_size(text_pos, galley.size());
+                            
+                            // リサイズハンドル（右下）
+                            let handle_rect = egui::Rect::from_min_size(
+                                egui::pos2(text_rect.max.x - handle_size / 2.0, text_rect.max.y - handle_size / 2.0),
+                                Vec2::splat(handle_size),
+                            );
+                            
+                            if handle_rect.contains(pos) {
+                                // リサイズモード（フォントサイズ変更）
+                                self.resizing = true;
+                                self.resize_corner = ResizeCorner::BottomRight;
+                                self.resize_start_size = Vec2::new(annotation.font_size, 0.0);
+                                self.drag_offset = Vec2::new(pos.x - text_rect.max.x, pos.y - text_rect.max.y);
+                            } else if text_rect.contains(pos) {
+                                // 移動モード
+                                self.drag_offset = Vec2::new(pos.x - text_pos.x, pos.y - text_pos.y);
+                                self.dragging = true;
                             }
                         }
-                    }
-                    self.dragging = false;
-                }
-                
-                // ドラッグ終了 - リサイズ
-                if response.drag_stopped() && self.resizing {
-                    if let Some(pos) = ui.input(|i| i.pointer.hover_pos()) {
-                        if let Some(idx) = self.selected_stamp_index {
-                            if let Some(stamp) = stamps.get(idx) {
-                                let (display_x, display_y) = self.pdf_to_display_pos(
-                                    stamp.x, stamp.y, stamp.width, stamp.height,
-                                    orig_w, orig_h, rotation
-                                );
-                                let stamp_min = egui::pos2(
-                                    rect.min.x + display_x * self.zoom,
-                                    rect.min.y + display_y * self.zoom,
-                                );
-                                
-                                // 新しいサイズを計算（最小サイズ制限付き）
-                                let new_width = ((pos.x - self.drag_offset.x - stamp_min.x) / self.zoom).max(20.0);
-                                let new_height = ((pos.y - self.drag_offset.y - stamp_min.y) / self.zoom).max(20.0);
-                                
-                                result.resize_stamp = Some((idx, new_width, new_height));
+                    } else if let Some(idx) = self.selected_rect_index {
+                        if let Some(rect_ann) = rect_annotations.get(idx).filter(|a| a.page == page_index) {
+                            let (display_x, display_y) = self.pdf_to_display_pos(
+                                rect_ann.x, rect_ann.y, rect_ann.width, rect_ann.height,
+                                orig_w, orig_h, rotation
+                            );
+                            let display_rect = egui::Rect::from_min_size(
+                                egui::pos2(rect.min.x + display_x * self.zoom, rect.min.y + display_y * self.zoom),
+                                Vec2::new(rect_ann.width * self.zoom, rect_ann.height * self.zoom),
+                            );
+                            
+                            // リサイズハンドル（右下）
+                            let handle_rect = egui::Rect::from_min_size(
+                                egui::pos2(display_rect.max.x - handle_size / 2.0, display_rect.max.y - handle_size / 2.0),
+                                Vec2::splat(handle_size),
+                            );
+                            
+                            if handle_rect.contains(pos) {
+                                // リサイズモード
+                                self.resizing = true;
+                                self.resize_corner = ResizeCorner::BottomRight;
+                                self.resize_start_size = Vec2::new(rect_ann.width, rect_ann.height);
+                                self.drag_offset = Vec2::new(pos.x - display_rect.max.x, pos.y - display_rect.max.y);
+                            } else if display_rect.contains(pos) {
+                                // 移動モード
+                                self.drag_offset = Vec2::new(pos.x - display_rect.min.x, pos.y - display_rect.min.y);
+                                self.dragging = true;
                             }
-                        } else if let Some(idx) = self.selected_text_index {
-                            if let Some(annotation) = text_annotations.get(idx) {
-                                let (display_x, display_y) = self.pdf_to_display_pos(
-                                    annotation.x, annotation.y, 
-                                    annotation.text.len() as f32 * annotation.font_size * 0.6, 
-                                    annotation.font_size,
-                                    orig_w, orig_h, rotation
-                                );
-                                let text_min = egui::pos2(
-                                    rect.min.x + display_x * self.zoom,
-                                    rect.min.y + display_y * self.zoom,
-                                );
-                                
-                                // 新しいフォントサイズを計算（高さの変化量から）
-                                let delta_y = (pos.y - self.drag_offset.y - text_min.y) / self.zoom;
-                                let new_font_size = (delta_y).max(8.0).min(72.0);
-                                
-                                result.resize_text = Some((idx, new_font_size));
+                        }
+                    } else if let Some(idx) = self.selected_shape_index {
+                        if let Some(shape) = shapes.get(idx).filter(|s| s.page == page_index) {
+                            let display_rect = self.shape_screen_rect(shape, rect, (orig_w, orig_h), rotation);
+
+                            // リサイズハンドル（右下）
+                            let handle_rect = egui::Rect::from_min_size(
+                                egui::pos2(display_rect.max.x - handle_size / 2.0, display_rect.max.y - handle_size / 2.0),
+                                Vec2::splat(handle_size),
+                            );
+
+                            if handle_rect.contains(pos) {
+                                // リサイズモード
+                                self.resizing = true;
+                                self.resize_corner = ResizeCorner::BottomRight;
+                                self.resize_start_size = display_rect.size();
+                                self.drag_offset = Vec2::new(pos.x - display_rect.max.x, pos.y - display_rect.max.y);
+                            } else if display_rect.contains(pos) {
+                                // 移動モード
+                                self.drag_offset = Vec2::new(pos.x - display_rect.min.x, pos.y - display_rect.min.y);
+                                self.dragging = true;
                             }
-                        } else if let Some(idx) = self.selected_rect_index {
-                            if let Some(rect_ann) = rect_annotations.get(idx) {
-                                let (display_x, display_y) = self.pdf_to_display_pos(
-                                    rect_ann.x, rect_ann.y, rect_ann.width, rect_ann.height,
-                                    orig_w, orig_h, rotation
-                                );
-                                let rect_min = egui::pos2(
-                                    rect.min.x + display_x * self.zoom,
-                                    rect.min.y + display_y * self.zoom,
-                                );
-                                
-                                // 新しいサイズを計算（最小サイズ制限付き）
-                                let new_width = ((pos.x - self.drag_offset.x - rect_min.x) / self.zoom).max(10.0);
-                                let new_height = ((pos.y - self.drag_offset.y - rect_min.y) / self.zoom).max(10.0);
-                                
-                                result.resize_rect = Some((idx, new_width, new_height));
+                        }
+                    } else if let Some(idx) = self.selected_comment_index {
+                        if let Some(comment) = comments.get(idx).filter(|c| c.page == page_index) {
+                            // アイコンは大きさを変えないので移動のみ
+                            let icon_rect = self.comment_screen_rect(comment, rect, (orig_w, orig_h), rotation);
+                            if icon_rect.contains(pos) {
+                                self.drag_offset = Vec2::new(pos.x - icon_rect.min.x, pos.y - icon_rect.min.y);
+                                self.dragging = true;
                             }
                         }
                     }
-                    self.resizing = false;
-                    self.resize_corner = ResizeCorner::None;
                 }
             }
 
-            // スタンプ配置モード
-            if self.placing_stamp {
-                // カスタムスタンプの場合、元のサイズを使用（スケール調整）
-                let (stamp_w, stamp_h) = if let Some(idx) = self.selected_custom_stamp_index {
-                    if let Some((_, _, w, h)) = custom_stamps.get(idx) {
-                        // 最大100ピクセル幅にスケーリング、比率維持
-                        let max_size = 100.0;
-                        let scale = max_size / (*w as f32).max(*h as f32);
-                        (*w as f32 * scale, *h as f32 * scale)
-                    } else {
-                        (100.0, 50.0)
+            if response.dragged() && self.dragging {
+                ui.ctx().set_cursor_icon(egui::CursorIcon::Grabbing);
+            }
+            
+            if response.dragged() && self.resizing {
+                ui.ctx().set_cursor_icon(egui::CursorIcon::ResizeNwSe);
+            }
+
+            // ドラッグ終了 - 移動
+            if response.drag_stopped() && self.dragging {
+                if let Some(pos) = ui.input(|i| i.pointer.hover_pos()) {
+                    let display_x = (pos.x - rect.min.x - self.drag_offset.x) / self.zoom;
+                    let display_y = (pos.y - rect.min.y - self.drag_offset.y) / self.zoom;
+                    
+                    if let Some(idx) = self.selected_stamp_index {
+                        if let Some(stamp) = stamps.get(idx) {
+                            let (pdf_x, pdf_y) = self.display_to_pdf(
+                                display_x, display_y, stamp.width, stamp.height,
+                                orig_w, orig_h, rotation
+                            );
+                            result.move_stamp = Some((idx, pdf_x, pdf_y));
+                        }
+                    } else if let Some(idx) = self.selected_text_index {
+                        if let Some(annotation) = text_annotations.get(idx) {
+                            let text_width = annotation.text.len() as f32 * annotation.font_size * 0.6;
+                            let text_height = annotation.font_size;
+                            let (pdf_x, pdf_y) = self.display_to_pdf(
+                                display_x, display_y, text_width, text_height,
+                                orig_w, orig_h, rotation
+                            );
+                            result.move_text = Some((idx, pdf_x, pdf_y));
+                        }
+                    } else if let Some(idx) = self.selected_rect_index {
+                        if let Some(rect_ann) = rect_annotations.get(idx) {
+                            let (pdf_x, pdf_y) = self.display_to_pdf(
+                                display_x, display_y, rect_ann.width, rect_ann.height,
+                                orig_w, orig_h, rotation
+                            );
+                            result.move_rect = Some((idx, pdf_x, pdf_y));
+                        }
+                    } else if let Some(idx) = self.selected_shape_index {
+                        if let Some(shape) = shapes.get(idx) {
+                            // 表示上の移動量をページ上の移動量に変換（回転を考慮）
+                            let old_min = self.shape_screen_rect(shape, rect, (orig_w, orig_h), rotation).min;
+                            let new_min = pos - self.drag_offset;
+                            let (old_x, old_y) = self.screen_to_page(old_min, rect, (orig_w, orig_h), rotation);
+                            let (new_x, new_y) = self.screen_to_page(new_min, rect, (orig_w, orig_h), rotation);
+                            result.move_shape = Some((idx, new_x - old_x, new_y - old_y));
+                        }
+                    } else if let Some(idx) = self.selected_comment_index {
+                        if comments.get(idx).is_some() {
+                            let (pdf_x, pdf_y) = self.display_to_pdf(
+                                display_x, display_y, COMMENT_ICON_SIZE, COMMENT_ICON_SIZE,
+                                orig_w, orig_h, rotation
+                            );
+                            result.move_comment = Some((idx, pdf_x, pdf_y));
+                        }
+                    }
+                }
+                self.dragging = false;
+            }
+            
+            // ドラッグ終了 - リサイズ
+            if response.drag_stopped() && self.resizing {
+                if let Some(pos) = ui.input(|i| i.pointer.hover_pos()) {
+                    if let Some(idx) = self.selected_stamp_index {
+                        if let Some(stamp) = stamps.get(idx) {
+                            let (display_x, display_y) = self.pdf_to_display_pos(
+                                stamp.x, stamp.y, stamp.width, stamp.height,
+                                orig_w, orig_h, rotation
+                            );
+                            let stamp_min = egui::pos2(
+                                rect.min.x + display_x * self.zoom,
+                                rect.min.y + display_y * self.zoom,
+                            );
+                            
+                            // 新しいサイズを計算（最小サイズ制限付き）
+                            let new_width = ((pos.x - self.drag_offset.x - stamp_min.x) / self.zoom).max(20.0);
+                            let new_height = ((pos.y - self.drag_offset.y - stamp_min.y) / self.zoom).max(20.0);
+                            
+                            result.resize_stamp = Some((idx, new_width, new_height));
+                        }
+                    } else if let Some(idx) = self.selected_text_index {
+                        if let Some(annotation) = text_annotations.get(idx) {
+                            let (display_x, display_y) = self.pdf_to_display_pos(
+                                annotation.x, annotation.y, 
+                                annotation.text.len() as f32 * annotation.font_size * 0.6, 
+                                annotation.font_size,
+                                orig_w, orig_h, rotation
+                            );
+                            let text_min = egui::pos2(
+                                rect.min.x + display_x * self.zoom,
+                                rect.min.y + display_y * self.zoom,
+                            );
+                            
+                            // 新しいフォントサイズを計算（高さの変化量から）
+                            let delta_y = (pos.y - self.drag_offset.y - text_min.y) / self.zoom;
+                            let new_font_size = (delta_y).max(8.0).min(72.0);
+                            
+                            result.resize_text = Some((idx, new_font_size));
+                        }
+                    } else if let Some(idx) = self.selected_rect_index {
+                        if let Some(rect_ann) = rect_annotations.get(idx) {
+                            let (display_x, display_y) = self.pdf_to_display_pos(
+                                rect_ann.x, rect_ann.y, rect_ann.width, rect_ann.height,
+                                orig_w, orig_h, rotation
+                            );
+                            let rect_min = egui::pos2(
+                                rect.min.x + display_x * self.zoom,
+                                rect.min.y + display_y * self.zoom,
+                            );
+                            
+                            // 新しいサイズを計算（最小サイズ制限付き）
+                            let new_width = ((pos.x - self.drag_offset.x - rect_min.x) / self.zoom).max(10.0);
+                            let new_height = ((pos.y - self.drag_offset.y - rect_min.y) / self.zoom).max(10.0);
+                            
+                            result.resize_rect = Some((idx, new_width, new_height));
+                        }
+                    } else if let Some(idx) = self.selected_shape_index {
+                        if let Some(shape) = shapes.get(idx) {
+                            let display_rect = self.shape_screen_rect(shape, rect, (orig_w, orig_h), rotation);
+                            let margin = display_rect.size() - self.page_rect_to_screen(&shape.bounds(), rect, (orig_w, orig_h), rotation).size();
+
+                            // 表示上で左上を固定して拡大・縮小（幅・高さの無い直線の向きは変えない）
+                            let old_size = self.resize_start_size - margin;
+                            let new_size = (pos - self.drag_offset - display_rect.min - margin).max(Vec2::splat(10.0));
+                            let scale = Vec2::new(
+                                if old_size.x >= 1.0 { new_size.x / old_size.x } else { 1.0 },
+                                if old_size.y >= 1.0 { new_size.y / old_size.y } else { 1.0 },
+                            );
+                            let origin = display_rect.min + margin / 2.0;
+                            let points = shape
+                                .points
+                                .iter()
+                                .map(|&point| {
+                                    let screen = self.page_to_screen(point, rect, (orig_w, orig_h), rotation);
+                                    let scaled = origin + (screen - origin) * scale;
+                                    self.screen_to_page(scaled, rect, (orig_w, orig_h), rotation)
+                                })
+                                .collect();
+                            result.resize_shape = Some((idx, points));
+                        }
                     }
+                }
+                self.resizing = false;
+                self.resize_corner = ResizeCorner::None;
+            }
+        }
+
+        // スタンプ配置モード
+        if self.placing_stamp {
+            // カスタムスタンプの場合、元のサイズを使用（スケール調整）
+            let (stamp_w, stamp_h) = if let Some(idx) = self.selected_custom_stamp_index {
+                if let Some((_, _, w, h, _)) = custom_stamps.get(idx) {
+                    // 最大100ピクセル幅にスケーリング、比率維持
+                    let max_size = 100.0;
+                    let scale = max_size / (*w as f32).max(*h as f32);
+                    (*w as f32 * scale, *h as f32 * scale)
                 } else {
-                    (100.0, 50.0) // 組み込みスタンプは固定サイズ
-                };
+                    (100.0, 50.0)
+                }
+            } else if matches!(self.selected_stamp_type, StampType::DateSeal(_)) {
+                (self.seal_size, self.seal_size)
+            } else if matches!(self.selected_stamp_type, StampType::NameSeal(_)) {
+                (self.name_seal_size, self.name_seal_size)
+            } else {
+                (100.0, 50.0) // 組み込みスタンプは固定サイズ
+            };
 
-                if let Some(hover_pos) = ui.input(|i| i.pointer.hover_pos()) {
-                    if rect.contains(hover_pos) {
-                        let preview_w = stamp_w * self.zoom;
-                        let preview_h = stamp_h * self.zoom;
-                        let preview_rect = egui::Rect::from_center_size(hover_pos, Vec2::new(preview_w, preview_h));
-                        
-                        if let Some(idx) = self.selected_custom_stamp_index {
-                            if let Some((_, Some(tex), _, _)) = custom_stamps.get(idx) {
-                                ui.painter().image(
-                                    tex.id(),
-                                    preview_rect,
-                                    egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
-                                    Color32::from_rgba_unmultiplied(255, 255, 255, 150),
-                                );
-                            }
-                        } else {
-                            let (bg_color, border_color) = match &self.selected_stamp_type {
-                                StampType::Approved => (Color32::from_rgba_unmultiplied(200, 255, 200, 100), Color32::from_rgba_unmultiplied(0, 200, 0, 150)),
-                                StampType::Rejected => (Color32::from_rgba_unmultiplied(255, 200, 200, 100), Color32::from_rgba_unmultiplied(200, 0, 0, 150)),
-                                StampType::Draft => (Color32::from_rgba_unmultiplied(255, 255, 200, 100), Color32::from_rgba_unmultiplied(200, 150, 0, 150)),
-                                StampType::Confidential => (Color32::from_rgba_unmultiplied(200, 200, 255, 100), Color32::from_rgba_unmultiplied(0, 0, 200, 150)),
-                                StampType::Custom(_) => (Color32::from_rgba_unmultiplied(220, 220, 220, 100), Color32::from_rgba_unmultiplied(128, 128, 128, 150)),
-                            };
-
-                            ui.painter().rect_filled(preview_rect, 4.0, bg_color);
-                            ui.painter().rect_stroke(preview_rect, 4.0, egui::Stroke::new(2.0, border_color));
-
-                            ui.painter().text(
-                                preview_rect.center(),
-                                egui::Align2::CENTER_CENTER,
-                                self.selected_stamp_type.label(),
-                                egui::FontId::proportional(12.0 * self.zoom),
-                                border_color,
+            if let Some(hover_pos) = ui.input(|i| i.pointer.hover_pos()) {
+                if rect.contains(hover_pos) {
+                    let preview_w = stamp_w * self.zoom;
+                    let preview_h = stamp_h * self.zoom;
+                    let preview_rect = egui::Rect::from_center_size(hover_pos, Vec2::new(preview_w, preview_h));
+                    
+                    if let Some(idx) = self.selected_custom_stamp_index {
+                        if let Some((_, Some(tex), _, _, _)) = custom_stamps.get(idx) {
+                            ui.painter().image(
+                                tex.id(),
+                                preview_rect,
+                                egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
+                                Color32::from_rgba_unmultiplied(255, 255, 255, 150),
                             );
                         }
-                        
-                        ui.ctx().set_cursor_icon(egui::CursorIcon::Crosshair);
+                    } else if let Some(layout) = self.selected_stamp_type.seal_layout(preview_w, preview_h) {
+                        paint_seal(ui.painter(), &layout, preview_rect, 150);
+                    } else {
+                        let (bg_color, border_color) = match &self.selected_stamp_type {
+                            StampType::Approved => (Color32::from_rgba_unmultiplied(200, 255, 200, 100), Color32::from_rgba_unmultiplied(0, 200, 0, 150)),
+                            StampType::Rejected => (Color32::from_rgba_unmultiplied(255, 200, 200, 100), Color32::from_rgba_unmultiplied(200, 0, 0, 150)),
+                            StampType::Draft => (Color32::from_rgba_unmultiplied(255, 255, 200, 100), Color32::from_rgba_unmultiplied(200, 150, 0, 150)),
+                            StampType::Confidential => (Color32::from_rgba_unmultiplied(200, 200, 255, 100), Color32::from_rgba_unmultiplied(0, 0, 200, 150)),
+                            StampType::Custom(_) | StampType::DateSeal(_) | StampType::NameSeal(_) => (Color32::from_rgba_unmultiplied(220, 220, 220, 100), Color32::from_rgba_unmultiplied(128, 128, 128, 150)),
+                        };
+
+                        ui.painter().rect_filled(preview_rect, 4.0, bg_color);
+                        ui.painter().rect_stroke(preview_rect, 4.0, egui::Stroke::new(2.0, border_color));
+
+                        ui.painter().text(
+                            preview_rect.center(),
+                            egui::Align2::CENTER_CENTER,
+                            self.selected_stamp_type.label(),
+                            egui::FontId::proportional(12.0 * self.zoom),
+                            border_color,
+                        );
                     }
+                    
+                    ui.ctx().set_cursor_icon(egui::CursorIcon::Crosshair);
                 }
+            }
 
-                if response.clicked() {
-                    if let Some(pos) = response.interact_pointer_pos() {
-                        let display_x = (pos.x - rect.min.x) / self.zoom - stamp_w / 2.0;
-                        let display_y = (pos.y - rect.min.y) / self.zoom - stamp_h / 2.0;
+            if response.clicked() {
+                if let Some(pos) = response.interact_pointer_pos() {
+                    let display_x = (pos.x - rect.min.x) / self.zoom - stamp_w / 2.0;
+                    let display_y = (pos.y - rect.min.y) / self.zoom - stamp_h / 2.0;
 
-                        let (pdf_x, pdf_y) = self.display_to_pdf(
-                            display_x, display_y, stamp_w, stamp_h,
-                            orig_w, orig_h, rotation
-                        );
+                    let (pdf_x, pdf_y) = self.display_to_pdf(
+                        display_x, display_y, stamp_w, stamp_h,
+                        orig_w, orig_h, rotation
+                    );
 
-                        result.new_stamp = Some(Stamp {
-                            page: page_index,
-                            x: pdf_x,
-                            y: pdf_y,
-                            width: stamp_w,
-                            height: stamp_h,
-                            stamp_type: self.selected_stamp_type.clone(),
-                        });
-                        self.placing_stamp = false;
-                    }
+                    result.new_stamp = Some(Stamp {
+                        page: page_index,
+                        x: pdf_x,
+                        y: pdf_y,
+                        width: stamp_w,
+                        height: stamp_h,
+                        stamp_type: self.selected_stamp_type.clone(),
+                    });
+                    self.placing_stamp = false;
                 }
             }
+        }
 
-            // テキスト配置モード
-            if self.placing_text {
-                if let Some(hover_pos) = ui.input(|i| i.pointer.hover_pos()) {
-                    if rect.contains(hover_pos) {
-                        ui.painter().text(
-                            hover_pos,
-                            egui::Align2::LEFT_TOP,
-                            &self.text_input,
-                            egui::FontId::proportional(self.text_font_size * self.zoom),
-                            Color32::from_rgba_unmultiplied(0, 0, 0, 150),
-                        );
-                        ui.ctx().set_cursor_icon(egui::CursorIcon::Text);
-                    }
+        // テキスト配置モード
+        if self.placing_text {
+            if let Some(hover_pos) = ui.input(|i| i.pointer.hover_pos()) {
+                if rect.contains(hover_pos) {
+                    ui.painter().text(
+                        hover_pos,
+                        egui::Align2::LEFT_TOP,
+                        &self.text_input,
+                        egui::FontId::proportional(self.text_font_size * self.zoom),
+                        Color32::from_rgba_unmultiplied(0, 0, 0, 150),
+                    );
+                    ui.ctx().set_cursor_icon(egui::CursorIcon::Text);
                 }
+            }
 
-                if response.clicked() {
-                    if let Some(pos) = response.interact_pointer_pos() {
-                        let display_x = (pos.x - rect.min.x) / self.zoom;
-                        let display_y = (pos.y - rect.min.y) / self.zoom;
+            if response.clicked() {
+                if let Some(pos) = response.interact_pointer_pos() {
+                    let display_x = (pos.x - rect.min.x) / self.zoom;
+                    let display_y = (pos.y - rect.min.y) / self.zoom;
 
-                        let text_width = self.text_input.len() as f32 * self.text_font_size * 0.6;
-                        let text_height = self.text_font_size;
+                    let text_width = self.text_input.len() as f32 * self.text_font_size * 0.6;
+                    let text_height = self.text_font_size;
 
-                        let (pdf_x, pdf_y) = self.display_to_pdf(
-                            display_x, display_y, text_width, text_height,
-                            orig_w, orig_h, rotation
-                        );
+                    let (pdf_x, pdf_y) = self.display_to_pdf(
+                        display_x, display_y, text_width, text_height,
+                        orig_w, orig_h, rotation
+                    );
 
-                        result.new_text = Some(TextAnnotation {
-                            page: page_index,
-                            x: pdf_x,
-                            y: pdf_y,
-                            text: self.text_input.clone(),
-                            font_size: self.text_font_size,
-                            font_type: self.text_font_type,
-                            transparent: self.text_transparent,
-                        });
-                        self.placing_text = false;
-                        self.text_input.clear();
-                    }
+                    result.new_text = Some(TextAnnotation {
+                        page: page_index,
+                        x: pdf_x,
+                        y: pdf_y,
+                        text: self.text_input.clone(),
+                        font_size: self.text_font_size,
+                        font_type: self.text_font_type,
+                        transparent: self.text_transparent,
+                    });
+                    self.placing_text = false;
+                    self.text_input.clear();
                 }
             }
+        }
 
-            // 矩形配置モード（ドラッグで描画）
-            if self.placing_rect {
+        // コメント配置モード（クリックした位置にアイコンを置き、ポップアップを開く）
+        if self.placing_comment {
+            if response.hovered() {
                 ui.ctx().set_cursor_icon(egui::CursorIcon::Crosshair);
-                
-                // ドラッグ開始
-                if response.drag_started() {
-                    if let Some(pos) = response.interact_pointer_pos() {
-                        if rect.contains(pos) {
-                            self.rect_start_pos = Some(pos);
-                        }
+            }
+            if response.clicked() {
+                if let Some(pos) = response.interact_pointer_pos() {
+                    let display_x = (pos.x - rect.min.x) / self.zoom - COMMENT_ICON_SIZE / 2.0;
+                    let display_y = (pos.y - rect.min.y) / self.zoom - COMMENT_ICON_SIZE / 2.0;
+                    let (pdf_x, pdf_y) = self.display_to_pdf(
+                        display_x, display_y, COMMENT_ICON_SIZE, COMMENT_ICON_SIZE,
+                        orig_w, orig_h, rotation
+                    );
+                    result.new_comment = Some(CommentAnnotation::new(page_index, pdf_x, pdf_y, String::new(), String::new()));
+                    // 追加後の番号で選択し、続けて本文を入力できるようにする
+                    self.selected_comment_index = Some(comments.len());
+                    self.comment_draft_for = None;
+                    self.placing_comment = false;
+                }
+            }
+        }
+
+        // 選択中のコメントのポップアップ
+        if let Some(idx) = self.selected_comment_index {
+            if let Some(comment) = comments.get(idx).filter(|c| c.page == page_index) {
+                let icon_rect = self.comment_screen_rect(comment, rect, (orig_w, orig_h), rotation);
+                self.show_comment_popup(ui.ctx(), idx, comment, icon_rect, result);
+            }
+        }
+
+        // 矩形配置モード（ドラッグで描画）
+        if self.placing_rect {
+            ui.ctx().set_cursor_icon(egui::CursorIcon::Crosshair);
+            
+            // ドラッグ開始
+            if response.drag_started() {
+                if let Some(pos) = response.interact_pointer_pos() {
+                    if rect.contains(pos) {
+                        self.rect_start_pos = Some(pos);
                     }
                 }
-                
-                // ドラッグ中のプレビュー
+            }
+            
+            // ドラッグ中のプレビュー
+            if let Some(start_pos) = self.rect_start_pos.filter(|p| rect.contains(*p)) {
+                if let Some(current_pos) = ui.input(|i| i.pointer.hover_pos()) {
+                    let min_x = start_pos.x.min(current_pos.x);
+                    let min_y = start_pos.y.min(current_pos.y);
+                    let max_x = start_pos.x.max(current_pos.x);
+                    let max_y = start_pos.y.max(current_pos.y);
+                    
+                    let preview_rect = egui::Rect::from_min_max(
+                        egui::pos2(min_x, min_y),
+                        egui::pos2(max_x, max_y),
+                    );
+                    
+                    // プレビュー描画
+                    ui.painter().rect_filled(preview_rect, 0.0, Color32::from_rgba_unmultiplied(255, 255, 255, 200));
+                    ui.painter().rect_stroke(preview_rect, 0.0, egui::Stroke::new(1.0, Color32::GRAY));
+                }
+            }
+            
+            // ドラッグ終了で矩形を確定
+            if response.drag_stopped() {
                 if let Some(start_pos) = self.rect_start_pos {
-                    if let Some(current_pos) = ui.input(|i| i.pointer.hover_pos()) {
-                        let min_x = start_pos.x.min(current_pos.x);
-                        let min_y = start_pos.y.min(current_pos.y);
-                        let max_x = start_pos.x.max(current_pos.x);
-                        let max_y = start_pos.y.max(current_pos.y);
+                    if let Some(end_pos) = ui.input(|i| i.pointer.hover_pos()) {
+                        let min_x = start_pos.x.min(end_pos.x);
+                        let min_y = start_pos.y.min(end_pos.y);
+                        let max_x = start_pos.x.max(end_pos.x);
+                        let max_y = start_pos.y.max(end_pos.y);
                         
-                        let preview_rect = egui::Rect::from_min_max(
-                            egui::pos2(min_x, min_y),
-                            egui::pos2(max_x, max_y),
-                        );
+                        let display_x = (min_x - rect.min.x) / self.zoom;
+                        let display_y = (min_y - rect.min.y) / self.zoom;
+                        let width = (max_x - min_x) / self.zoom;
+                        let height = (max_y - min_y) / self.zoom;
                         
-                        // プレビュー描画
-                        ui.painter().rect_filled(preview_rect, 0.0, Color32::from_rgba_unmultiplied(255, 255, 255, 200));
-                        ui.painter().rect_stroke(preview_rect, 0.0, egui::Stroke::new(1.0, Color32::GRAY));
+                        // 最小サイズチェック
+                        if width > 5.0 && height > 5.0 {
+                            let (pdf_x, pdf_y) = self.display_to_pdf(
+                                display_x, display_y, width, height,
+                                orig_w, orig_h, rotation
+                            );
+                            
+                            result.new_rect = Some(RectAnnotation {
+                                page: page_index,
+                                x: pdf_x,
+                                y: pdf_y,
+                                width,
+                                height,
+                                // 墨消しは黒、通常は白
+                                color: if self.rect_redact { [0, 0, 0, 255] } else { [255, 255, 255, 255] },
+                                redact: self.rect_redact,
+                            });
+                        }
+                    }
+                    self.rect_start_pos = None;
+                }
+            }
+        }
+
+        // 手書き・図形の描画モード
+        if let Some(shape_type) = self.drawing_shape {
+            if response.hovered() {
+                ui.ctx().set_cursor_icon(egui::CursorIcon::Crosshair);
+            }
+            let page_size = (orig_w, orig_h);
+
+            if shape_type == ShapeType::Polygon {
+                // クリックで頂点を追加、ダブルクリックで完了
+                if response.double_clicked() {
+                    if let Some(shape) = self.drawing.take_if(|s| s.page == page_index) {
+                        if shape.points.len() >= 3 {
+                            result.new_shape = Some(shape);
+                        }
+                    }
+                } else if response.clicked() {
+                    if let Some(pos) = response.interact_pointer_pos() {
+                        let point = self.screen_to_page(pos, rect, page_size, rotation);
+                        match self.drawing.as_mut().filter(|s| s.page == page_index) {
+                            Some(shape) => shape.points.push(point),
+                            None => self.drawing = Some(self.new_shape(page_index, shape_type, vec![point])),
+                        }
+                    }
+                }
+            } else {
+                // ドラッグで描画（手書きは軌跡、それ以外は始点と終点）
+                if response.drag_started() {
+                    if let Some(pos) = ui.input(|i| i.pointer.press_origin()).filter(|p| rect.contains(*p)) {
+                        let point = self.screen_to_page(pos, rect, page_size, rotation);
+                        let points = if shape_type == ShapeType::Ink { vec![point] } else { vec![point, point] };
+                        self.drawing = Some(self.new_shape(page_index, shape_type, points));
+                    }
+                }
+                if response.dragged() {
+                    if let Some(pos) = response.interact_pointer_pos() {
+                        let point = self.screen_to_page(pos, rect, page_size, rotation);
+                        if let Some(shape) = self.drawing.as_mut().filter(|s| s.page == page_index) {
+                            match shape_type {
+                                ShapeType::Ink => shape.points.push(point),
+                                _ => shape.points[1] = point,
+                            }
+                        }
                     }
                 }
-                
-                // ドラッグ終了で矩形を確定
                 if response.drag_stopped() {
-                    if let Some(start_pos) = self.rect_start_pos {
-                        if let Some(end_pos) = ui.input(|i| i.pointer.hover_pos()) {
-                            let min_x = start_pos.x.min(end_pos.x);
-                            let min_y = start_pos.y.min(end_pos.y);
-                            let max_x = start_pos.x.max(end_pos.x);
-                            let max_y = start_pos.y.max(end_pos.y);
-                            
-                            let display_x = (min_x - rect.min.x) / self.zoom;
-                            let display_y = (min_y - rect.min.y) / self.zoom;
-                            let width = (max_x - min_x) / self.zoom;
-                            let height = (max_y - min_y) / self.zoom;
-                            
-                            // 最小サイズチェック
-                            if width > 5.0 && height > 5.0 {
-                                let (pdf_x, pdf_y) = self.display_to_pdf(
-                                    display_x, display_y, width, height,
-                                    orig_w, orig_h, rotation
-                                );
-                                
-                                result.new_rect = Some(RectAnnotation {
-                                    page: page_index,
-                                    x: pdf_x,
-                                    y: pdf_y,
-                                    width,
-                                    height,
-                                    color: [255, 255, 255, 255],  // 白色
-                                });
+                    if let Some(mut shape) = self.drawing.take_if(|s| s.page == page_index) {
+                        if shape_type == ShapeType::Ink {
+                            if self.shape_smoothing {
+                                shape.points = smooth_stroke(&shape.points, INK_MIN_DISTANCE / self.zoom);
+                            }
+                            result.new_shape = Some(shape);
+                        } else {
+                            // 小さすぎる図形（クリックのみ）は作らない
+                            let bounds = shape.bounds();
+                            if bounds.width.hypot(bounds.height) > 5.0 {
+                                result.new_shape = Some(shape);
                             }
                         }
-                        self.rect_start_pos = None;
                     }
                 }
             }
 
-            // Deleteキーで削除
-            if ui.input(|i| i.key_pressed(egui::Key::Delete) || i.key_pressed(egui::Key::Backspace)) {
-                if let Some(idx) = self.selected_stamp_index {
-                    result.delete_stamp = Some(idx);
-                    self.selected_stamp_index = None;
-                } else if let Some(idx) = self.selected_text_index {
-                    result.delete_text = Some(idx);
-                    self.selected_text_index = None;
-                } else if let Some(idx) = self.selected_rect_index {
-                    result.delete_rect = Some(idx);
-                    self.selected_rect_index = None;
+            // 描画中のプレビュー（多角形は次の頂点までの線も描く）
+            if let Some(shape) = self.drawing.as_ref().filter(|s| s.page == page_index) {
+                let mut preview = shape.clone();
+                if shape_type == ShapeType::Polygon {
+                    if let Some(pos) = response.hover_pos() {
+                        preview.points.push(self.screen_to_page(pos, rect, page_size, rotation));
+                        preview.shape_type = ShapeType::Ink;
+                    }
                 }
+                self.paint_shape(ui.painter(), &preview, rect, page_size, rotation);
+            }
+        }
+
+        // テキスト選択モード（ドラッグで範囲、ダブルクリックで単語を選択）
+        if self.selecting_text {
+            if response.hovered() {
+                ui.ctx().set_cursor_icon(egui::CursorIcon::Text);
             }
 
+            if response.double_clicked() {
+                if let Some(pos) = response.interact_pointer_pos() {
+                    let (x, y) = self.screen_to_page(pos, rect, (orig_w, orig_h), rotation);
+                    self.text_selection = self
+                        .load_page_text(doc, page_index)
+                        .and_then(|text| text.char_at(x, y).map(|i| text.word_at(i)))
+                        .filter(|word| !word.is_empty())
+                        .map(|word| TextSelection { page: page_index, anchor: word.start, end: word.end - 1 });
+                }
+            } else if response.clicked() {
+                self.text_selection = None;
+            }
+
+            // ドラッグの起点と現在位置に最も近い文字までを選択
+            if response.drag_started() {
+                if let Some(pos) = ui.input(|i| i.pointer.press_origin()) {
+                    let (x, y) = self.screen_to_page(pos, rect, (orig_w, orig_h), rotation);
+                    self.text_selection = self
+                        .load_page_text(doc, page_index)
+                        .and_then(|text| text.nearest_char(x, y))
+                        .map(|i| TextSelection { page: page_index, anchor: i, end: i });
+                }
+            }
+            if response.dragged() {
+                if let Some(pos) = response.interact_pointer_pos() {
+                    let (x, y) = self.screen_to_page(pos, rect, (orig_w, orig_h), rotation);
+                    let end = self.page_texts.get(&page_index).and_then(|text| text.nearest_char(x, y));
+                    if let (Some(selection), Some(end)) = (self.text_selection.as_mut(), end) {
+                        selection.end = end;
+                    }
+                }
+            }
+        }
+
+        rect
+    }
+
+    /// ページのテキストを読み取る（読み取ったものは保持する）
+    fn load_page_text(&mut self, doc: &PdfDocument, page: usize) -> Option<&PageText> {
+        if let Entry::Vacant(entry) = self.page_texts.entry(page) {
+            match doc.page_text(page) {
+                Ok(text) => {
+                    entry.insert(text);
+                }
+                Err(e) => {
+                    log::warn!("ページ {} のテキストを読み取れません: {}", page + 1, e);
+                    return None;
+                }
+            }
+        }
+        self.page_texts.get(&page)
+    }
+
+    /// 選択中のテキストをクリップボードにコピー
+    fn copy_selection(&self, ctx: &egui::Context, result: &mut EditorResult) {
+        let Some(selection) = self.text_selection else {
+            return;
+        };
+        if let Some(text) = self.page_texts.get(&selection.page) {
+            let copied = text.text_in(selection.range());
+            result.copied_text = Some(copied.chars().count());
+            ctx.copy_text(copied);
+        }
+    }
+
+    /// 選択中のテキストからテキストの装飾を作る
+    fn markup_selection(&self, markup_type: MarkupType) -> Option<MarkupAnnotation> {
+        let selection = self.text_selection?;
+        let text = self.page_texts.get(&selection.page)?;
+        let quads = text.line_rects(selection.range());
+        if quads.is_empty() {
+            return None;
+        }
+        Some(MarkupAnnotation {
+            page: selection.page,
+            markup_type,
+            quads,
+            color: markup_type.default_color(),
+            text: text.text_in(selection.range()),
+        })
+    }
+
+    /// 回転前のページ上の矩形（テキストの位置など）を画面上の矩形に変換
+    fn page_rect_to_screen(
+        &self,
+        text_rect: &TextRect,
+        page_rect: egui::Rect,
+        (orig_w, orig_h): (f32, f32),
+        rotation: i32,
+    ) -> egui::Rect {
+        let (x, y) = self.pdf_to_display_pos(
+            text_rect.x, text_rect.y, text_rect.width, text_rect.height,
+            orig_w, orig_h, rotation
+        );
+        // 横向きに回転すると幅と高さが入れ替わる
+        let size = if rotation == 90 || rotation == 270 {
+            Vec2::new(text_rect.height, text_rect.width)
         } else {
-            ui.spinner();
-            ui.label("読み込み中...");
+            Vec2::new(text_rect.width, text_rect.height)
+        };
+        egui::Rect::from_min_size(page_rect.min + Vec2::new(x, y) * self.zoom, size * self.zoom)
+    }
+
+    /// 画面上の点を回転前のページ上の点に変換
+    fn screen_to_page(
+        &self,
+        pos: egui::Pos2,
+        page_rect: egui::Rect,
+        (orig_w, orig_h): (f32, f32),
+        rotation: i32,
+    ) -> (f32, f32) {
+        let display = (pos - page_rect.min) / self.zoom;
+        self.display_to_pdf(display.x, display.y, 0.0, 0.0, orig_w, orig_h, rotation)
+    }
+
+    /// 回転前のページ上の点を画面上の点に変換（`screen_to_page` の逆変換）
+    fn page_to_screen(
+        &self,
+        (x, y): (f32, f32),
+        page_rect: egui::Rect,
+        (orig_w, orig_h): (f32, f32),
+        rotation: i32,
+    ) -> egui::Pos2 {
+        let (display_x, display_y) = self.pdf_to_display_pos(x, y, 0.0, 0.0, orig_w, orig_h, rotation);
+        page_rect.min + Vec2::new(display_x, display_y) * self.zoom
+    }
+
+    /// 現在の設定で図形を作る
+    fn new_shape(&self, page: usize, shape_type: ShapeType, points: Vec<(f32, f32)>) -> ShapeAnnotation {
+        let [r, g, b] = self.shape_color;
+        ShapeAnnotation {
+            page,
+            shape_type,
+            points,
+            color: [r, g, b, (self.shape_opacity * 255.0).round() as u8],
+            width: self.shape_width,
         }
+    }
 
-        result
+    /// 手書き・図形を描画
+    fn paint_shape(
+        &self,
+        painter: &egui::Painter,
+        shape: &ShapeAnnotation,
+        page_rect: egui::Rect,
+        page_size: (f32, f32),
+        rotation: i32,
+    ) {
+        let [r, g, b, a] = shape.color;
+        let stroke = egui::Stroke::new(
+            (shape.width * self.zoom).max(1.0),
+            Color32::from_rgba_unmultiplied(r, g, b, a),
+        );
+        for line in shape.outline() {
+            let points: Vec<egui::Pos2> = line
+                .iter()
+                .map(|&point| self.page_to_screen(point, page_rect, page_size, rotation))
+                .collect();
+            if let [point] = points[..] {
+                painter.circle_filled(point, stroke.width / 2.0, stroke.color);
+            } else {
+                painter.add(egui::Shape::line(points, stroke));
+            }
+        }
+    }
+
+    /// 手書き・図形の選択範囲（線の太さと選択しやすさの余白を含む）
+    fn shape_screen_rect(
+        &self,
+        shape: &ShapeAnnotation,
+        page_rect: egui::Rect,
+        page_size: (f32, f32),
+        rotation: i32,
+    ) -> egui::Rect {
+        self.page_rect_to_screen(&shape.bounds(), page_rect, page_size, rotation)
+            .expand(shape.width * self.zoom / 2.0 + SHAPE_HIT_MARGIN)
+    }
+
+    /// コメントのアイコンの表示範囲
+    fn comment_screen_rect(
+        &self,
+        comment: &CommentAnnotation,
+        page_rect: egui::Rect,
+        (orig_w, orig_h): (f32, f32),
+        rotation: i32,
+    ) -> egui::Rect {
+        let (display_x, display_y) = self.pdf_to_display_pos(
+            comment.x, comment.y, COMMENT_ICON_SIZE, COMMENT_ICON_SIZE,
+            orig_w, orig_h, rotation
+        );
+        egui::Rect::from_min_size(
+            egui::pos2(page_rect.min.x + display_x * self.zoom, page_rect.min.y + display_y * self.zoom),
+            Vec2::splat(COMMENT_ICON_SIZE * self.zoom),
+        )
+    }
+
+    /// 認印の作成ダイアログ（ライブラリに追加する認印を返す）
+    fn name_seal_dialog(&mut self, ctx: &egui::Context) -> Option<NameSeal> {
+        let mut added = None;
+        let mut open = true;
+        let mut close = false;
+        egui::Window::new("🔴 認印の作成")
+            .collapsible(false)
+            .resizable(false)
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("名前:");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.name_seal_draft.name)
+                            .hint_text("姓（例: 山田）")
+                            .desired_width(120.0),
+                    );
+                });
+                ui.horizontal(|ui| {
+                    ui.label("形:");
+                    for shape in [SealShape::Round, SealShape::Square] {
+                        ui.radio_value(&mut self.name_seal_draft.shape, shape, shape.label());
+                    }
+                    ui.label("色:");
+                    ui.color_edit_button_srgb(&mut self.name_seal_draft.color);
+                });
+                ui.separator();
+
+                // プレビュー（4文字以上は2列）
+                ui.vertical_centered(|ui| {
+                    let (rect, _) = ui.allocate_exact_size(Vec2::splat(NAME_SEAL_PREVIEW_SIZE), egui::Sense::hover());
+                    ui.painter().rect_filled(rect, 4.0, Color32::WHITE);
+                    let seal_rect = rect.shrink(8.0);
+                    let layout = self.name_seal_draft.layout(seal_rect.width(), seal_rect.height());
+                    paint_seal(ui.painter(), &layout, seal_rect, 255);
+                });
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    let has_name = !self.name_seal_draft.name.trim().is_empty();
+                    if ui.add_enabled(has_name, egui::Button::new("📚 ライブラリに追加")).clicked() {
+                        added = Some(NameSeal {
+                            name: self.name_seal_draft.name.trim().to_string(),
+                            ..self.name_seal_draft.clone()
+                        });
+                    }
+                    if ui.button("閉じる").clicked() {
+                        close = true;
+                    }
+                });
+            });
+        if !open || close || added.is_some() {
+            self.show_name_seal_dialog = false;
+        }
+        added
+    }
+
+    /// コメントのポップアップ（本文の編集、状態の切り替え、返信のスレッド）
+    fn show_comment_popup(
+        &mut self,
+        ctx: &egui::Context,
+        idx: usize,
+        comment: &CommentAnnotation,
+        icon_rect: egui::Rect,
+        result: &mut EditorResult,
+    ) {
+        // 選択したコメントが変わったら本文を読み込み直す
+        if self.comment_draft_for != Some(idx) {
+            self.comment_draft = comment.text.clone();
+            self.reply_draft.clear();
+            self.comment_draft_for = Some(idx);
+        }
+
+        let mut close = false;
+        egui::Area::new(egui::Id::new("comment_popup"))
+            .order(egui::Order::Foreground)
+            .fixed_pos(icon_rect.right_top() + Vec2::new(6.0, 0.0))
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.set_width(COMMENT_POPUP_WIDTH);
+                    ui.horizontal(|ui| {
+                        ui.strong(if comment.author.is_empty() { "（名前なし）" } else { &comment.author });
+                        ui.colored_label(Color32::GRAY, display_time(&comment.created));
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                            close = ui.small_button("✕").clicked();
+                        });
+                    });
+                    ui.add(
+                        egui::TextEdit::multiline(&mut self.comment_draft)
+                            .desired_width(f32::INFINITY)
+                            .desired_rows(3)
+                            .hint_text("コメントを入力"),
+                    );
+                    ui.horizontal(|ui| {
+                        if ui.add_enabled(self.comment_draft != comment.text, egui::Button::new("✓ 保存")).clicked() {
+                            result.edit_comment = Some((idx, self.comment_draft.clone()));
+                        }
+                        let mut resolved = comment.status == CommentStatus::Resolved;
+                        if ui.checkbox(&mut resolved, CommentStatus::Resolved.label()).changed() {
+                            let status = if resolved { CommentStatus::Resolved } else { CommentStatus::Open };
+                            result.comment_status = Some((idx, status));
+                        }
+                    });
+
+                    // 返信のスレッド
+                    if !comment.replies.is_empty() {
+                        ui.separator();
+                        egui::ScrollArea::vertical().max_height(160.0).show(ui, |ui| {
+                            for reply in &comment.replies {
+                                ui.horizontal(|ui| {
+                                    ui.strong(if reply.author.is_empty() { "（名前なし）" } else { &reply.author });
+                                    ui.colored_label(Color32::GRAY, display_time(&reply.created));
+                                });
+                                ui.label(&reply.text);
+                                ui.add_space(4.0);
+                            }
+                        });
+                    }
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            egui::TextEdit::singleline(&mut self.reply_draft)
+                                .desired_width(COMMENT_POPUP_WIDTH - 70.0)
+                                .hint_text("返信を入力"),
+                        );
+                        if ui.add_enabled(!self.reply_draft.trim().is_empty(), egui::Button::new("↩ 返信")).clicked() {
+                            result.reply_comment = Some((idx, self.reply_draft.trim().to_string()));
+                            self.reply_draft.clear();
+                        }
+                    });
+                });
+            });
+        if close {
+            self.selected_comment_index = None;
+        }
+    }
+
+    /// 表示領域の幅（`whole_page` ならページ全体）に収まるズーム
+    fn fit_zoom(&self, viewport: Vec2, doc: &PdfDocument, page_index: usize, whole_page: bool) -> f32 {
+        let (mut width, height) = doc.page_size(page_index);
+        let mut available = viewport - Vec2::splat(FIT_MARGIN);
+        if self.view_mode == ViewMode::Spread {
+            width *= 2.0;
+            available.x -= PAGE_GAP;
+        }
+        let zoom = if whole_page {
+            (available.x / width).min(available.y / height)
+        } else {
+            available.x / width
+        };
+        zoom.clamp(0.25, MAX_ZOOM)
+    }
+
+    /// 注釈の選択を解除
+    pub fn clear_selection(&mut self) {
+        self.selected_stamp_index = None;
+        self.selected_text_index = None;
+        self.selected_rect_index = None;
+        self.selected_markup_index = None;
+        self.selected_shape_index = None;
+        self.selected_comment_index = None;
+        self.dragging = false;
+        self.resizing = false;
+    }
+
+    /// 注釈を選択する（注釈一覧・コメント一覧から。コメントはポップアップを開く）
+    pub fn select_annotation(&mut self, item: AnnotationRef) {
+        self.placing_comment = false;
+        self.pending_selection = Some(item);
+    }
+
+    /// 表示範囲にかかるタイルを描画（届いていないタイルは描画を依頼し、下地のまま）
+    fn draw_tiles(
+        &mut self,
+        ui: &egui::Ui,
+        doc: &PdfDocument,
+        page: usize,
+        zoom_bucket: u32,
+        page_rect: egui::Rect,
+    ) {
+        let visible = ui.clip_rect().intersect(page_rect);
+        if !visible.is_positive() {
+            return;
+        }
+
+        let scale = (1 << zoom_bucket) as f32;
+        // 表示上の1単位あたりのタイル画像のピクセル数
+        let pixels_per_unit = scale / self.zoom;
+        let (page_width, page_height) = doc.page_size(page);
+        let full_width = (page_width * scale) as u32;
+        let full_height = (page_height * scale) as u32;
+        let to_tile = |offset: f32, full: u32| {
+            ((offset * pixels_per_unit) as u32).min(full.saturating_sub(1)) / TILE_SIZE
+        };
+        let columns = to_tile(visible.min.x - page_rect.min.x, full_width)
+            ..=to_tile(visible.max.x - page_rect.min.x, full_width);
+        let rows = to_tile(visible.min.y - page_rect.min.y, full_height)
+            ..=to_tile(visible.max.y - page_rect.min.y, full_height);
+
+        let rotation = doc.get_page_rotation(page);
+        for row in rows {
+            for column in columns.clone() {
+                let key = TileKey { page, rotation, zoom_bucket, column, row };
+                let tile = PageTile {
+                    scale,
+                    x: column * TILE_SIZE,
+                    y: row * TILE_SIZE,
+                    width: TILE_SIZE.min(full_width - column * TILE_SIZE),
+                    height: TILE_SIZE.min(full_height - row * TILE_SIZE),
+                };
+                let job = || RenderJob {
+                    source: RenderSource::Document(doc.clone()),
+                    page,
+                    area: RenderArea::Tile(tile),
+                };
+                if let Some(texture) = self.tile_cache.get_or_request(ui.ctx(), &key, job) {
+                    let min = page_rect.min
+                        + Vec2::new(tile.x as f32, tile.y as f32) / pixels_per_unit;
+                    let size = Vec2::new(tile.width as f32, tile.height as f32) / pixels_per_unit;
+                    ui.painter().image(
+                        texture.id(),
+                        egui::Rect::from_min_size(min, size),
+                        egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
+                        Color32::WHITE,
+                    );
+                }
+            }
+        }
     }
 
     fn invalidate_page_cache(&mut self) {
-        self.page_texture = None;
+        self.page_cache.clear();
+        self.tile_cache.clear();
+        // ページ番号が変わると検索結果・選択中のテキストの位置も合わなくなる
+        self.search.clear_results();
+        self.page_texts.clear();
+        self.text_selection = None;
     }
 
     pub fn invalidate_cache(&mut self) {
         self.invalidate_page_cache();
     }
+
+    /// 検索語で検索し、`page` 以降の最初の結果を表示（フォルダ内検索の結果を開いたとき）
+    pub fn find_text(&mut self, query: &str, page: usize) {
+        self.search.find(query, page);
+    }
 }
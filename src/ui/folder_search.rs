@@ -0,0 +1,207 @@
+//! フォルダ内検索 - 選択中のフォルダ以下のPDFを全文索引で検索
+
+use crate::pdf::{list_pdfs, read_pages, IndexHit, SearchIndex};
+use eframe::egui::{self, Color32};
+use parking_lot::Mutex;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 結果一覧の最大の高さ
+const RESULTS_MAX_HEIGHT: f32 = 200.0;
+
+/// 索引の更新状況
+#[derive(Default, Clone, Copy)]
+struct IndexProgress {
+    done: usize,
+    total: usize,
+    running: bool,
+    /// 索引が変わるたびに増える（検索結果を作り直す目安）
+    generation: usize,
+}
+
+/// クリックされた検索結果
+pub struct FolderSearchHit {
+    pub path: PathBuf,
+    pub page: usize,
+    pub query: String,
+}
+
+/// フォルダ内検索の状態
+pub struct FolderSearch {
+    folder: Option<PathBuf>,
+    // 索引の更新はワーカースレッドで行い、検索はこちらで行う
+    index: Arc<Mutex<SearchIndex>>,
+    progress: Arc<Mutex<IndexProgress>>,
+    cancel: Arc<AtomicBool>,
+
+    query: String,
+    results: Option<Vec<IndexHit>>,  // None: 未検索
+    results_generation: usize,
+}
+
+impl FolderSearch {
+    pub fn new() -> Self {
+        Self {
+            folder: None,
+            index: Arc::new(Mutex::new(SearchIndex::default())),
+            progress: Arc::new(Mutex::new(IndexProgress::default())),
+            cancel: Arc::new(AtomicBool::new(false)),
+            query: String::new(),
+            results: None,
+            results_generation: 0,
+        }
+    }
+
+    /// フォルダの索引を更新（保存済みの索引を読み込み、新規・更新されたPDFだけ読み直す）
+    pub fn set_folder(&mut self, folder: &Path) {
+        // 前のフォルダの更新は止める（それまでの分は保存される）
+        self.cancel.store(true, Ordering::Relaxed);
+        let cancel = Arc::new(AtomicBool::new(false));
+        let index = Arc::new(Mutex::new(SearchIndex::default()));
+        let progress = Arc::new(Mutex::new(IndexProgress {
+            running: true,
+            ..Default::default()
+        }));
+        self.cancel = cancel.clone();
+        self.index = index.clone();
+        self.progress = progress.clone();
+        if self.folder.as_deref() != Some(folder) {
+            self.results = None;
+        }
+        self.folder = Some(folder.to_path_buf());
+
+        let folder = folder.to_path_buf();
+        let spawned = std::thread::Builder::new()
+            .name("folder_index".to_string())
+            .spawn(move || {
+                let mut loaded = SearchIndex::load(&folder);
+                let stale = loaded.refresh(&list_pdfs(&folder));
+                *index.lock() = loaded;
+                {
+                    let mut progress = progress.lock();
+                    progress.total = stale.len();
+                    progress.generation += 1;
+                }
+
+                for (path, modified) in stale {
+                    if cancel.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    // 読めないPDF（パスワード付きなど）も空で登録し、更新されるまで読み直さない
+                    let pages = read_pages(&path).unwrap_or_else(|e| {
+                        log::warn!("索引を作成できません: {}: {}", path.display(), e);
+                        Vec::new()
+                    });
+                    index.lock().insert(path, modified, &pages);
+                    let mut progress = progress.lock();
+                    progress.done += 1;
+                    progress.generation += 1;
+                }
+
+                if let Err(e) = index.lock().save() {
+                    log::warn!("{:#}", e);
+                }
+                progress.lock().running = false;
+            });
+        if let Err(e) = spawned {
+            log::error!("索引スレッドを起動できません: {}", e);
+            self.progress.lock().running = false;
+        }
+    }
+
+    /// 検索欄と結果一覧を描画し、クリックされた結果を返す
+    pub fn show(&mut self, ui: &mut egui::Ui) -> Option<FolderSearchHit> {
+        let progress = *self.progress.lock();
+        if progress.running {
+            ui.ctx().request_repaint_after(Duration::from_millis(250));
+        }
+        // 索引の作成中に検索した場合は、索引が増えるたびに結果を作り直す
+        if self.results.is_some() && self.results_generation != progress.generation {
+            self.search(progress.generation);
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("🔎");
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut self.query)
+                    .hint_text("フォルダ内を全文検索")
+                    .desired_width(220.0),
+            );
+            let enter = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            if ui.button("検索").clicked() || enter {
+                self.search(progress.generation);
+            }
+            if response.changed() {
+                self.results = None;
+            }
+
+            if progress.running {
+                ui.spinner();
+                ui.label(format!("索引を作成中 {} / {}", progress.done, progress.total));
+            } else {
+                let count = self.index.lock().document_count();
+                ui.colored_label(Color32::GRAY, format!("{} ファイルを索引済み", count));
+            }
+        });
+
+        let mut clicked = None;
+        if let Some(results) = &self.results {
+            if results.is_empty() {
+                ui.colored_label(Color32::GRAY, "見つかりません");
+            } else {
+                ui.label(format!("{} ファイルで見つかりました", results.len()));
+                egui::ScrollArea::vertical()
+                    .id_salt("folder_search_results")
+                    .max_height(RESULTS_MAX_HEIGHT)
+                    .auto_shrink([false, true])
+                    .show(ui, |ui| {
+                        for hit in results {
+                            let name = self
+                                .folder
+                                .as_deref()
+                                .and_then(|folder| hit.path.strip_prefix(folder).ok())
+                                .unwrap_or(&hit.path);
+                            ui.horizontal_wrapped(|ui| {
+                                let mut open = |page| {
+                                    clicked = Some(FolderSearchHit {
+                                        path: hit.path.clone(),
+                                        page,
+                                        query: self.query.trim().to_string(),
+                                    });
+                                };
+                                if ui.link(format!("📄 {}", name.display())).clicked() {
+                                    open(hit.pages[0].0);
+                                }
+                                ui.colored_label(Color32::GRAY, format!("({} 件)", hit.score));
+                                for &(page, count) in &hit.pages {
+                                    if ui
+                                        .small_button(format!("p.{}", page + 1))
+                                        .on_hover_text(format!("{} 件", count))
+                                        .clicked()
+                                    {
+                                        open(page);
+                                    }
+                                }
+                            });
+                        }
+                    });
+            }
+            ui.separator();
+        }
+
+        clicked
+    }
+
+    /// 索引から検索
+    fn search(&mut self, generation: usize) {
+        let query = self.query.trim();
+        self.results = if query.is_empty() {
+            None
+        } else {
+            Some(self.index.lock().search(query))
+        };
+        self.results_generation = generation;
+    }
+}
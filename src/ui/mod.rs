@@ -1,7 +1,19 @@
 //! UI モジュール
 
+mod annotation_list;
+mod comment_list;
 mod editor_panel;
 mod file_explorer;
+mod folder_search;
+mod page_strip;
+mod search_bar;
+mod texture_cache;
 
-pub use editor_panel::EditorPanel;
+pub use annotation_list::{AnnotationList, AnnotationListResult, ANNOTATION_LIST_WIDTH};
+pub use comment_list::{CommentList, COMMENT_LIST_WIDTH};
+pub use editor_panel::{EditorContext, EditorPanel};
 pub use file_explorer::FileExplorer;
+pub use folder_search::FolderSearch;
+pub use page_strip::{PageStrip, PAGE_STRIP_WIDTH};
+pub use search_bar::SearchBar;
+pub use texture_cache::TextureCache;
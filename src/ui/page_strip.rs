@@ -0,0 +1,350 @@
+//! ページ一覧パネル - 開いているPDFのページサムネイル、選択・並べ替え・回転・削除・抽出
+
+use super::TextureCache;
+use crate::pdf::{PdfDocument, RenderArea, RenderJob, RenderSource};
+use eframe::egui::{self, Color32, Vec2};
+use std::collections::BTreeSet;
+
+/// パネルの幅
+pub const PAGE_STRIP_WIDTH: f32 = 140.0;
+/// サムネイルの幅（表示サイズ）
+const THUMB_WIDTH: f32 = 100.0;
+/// サムネイルの描画サイズ（ピクセル、この大きさに収まるよう描画）
+const THUMB_RENDER_SIZE: u32 = 200;
+/// サムネイルキャッシュの上限（バイト）
+const THUMB_CACHE_BYTES: usize = 48 * 1024 * 1024;
+/// PageUp / PageDown で移動するページ数
+const PAGE_JUMP: usize = 5;
+
+/// ページ一覧の操作結果
+#[derive(Default)]
+pub struct PageStripResult {
+    pub select_page: Option<usize>,  // 表示するページ
+    pub rotate: Option<(Vec<usize>, i32)>,  // (ページ, 角度)
+    pub delete: Option<Vec<usize>>,
+    pub extract: Option<Vec<usize>>,
+    pub reorder: Option<(Vec<usize>, usize)>,  // (ページ, このページの前へ移動)
+}
+
+/// ページ一覧パネルの状態
+pub struct PageStrip {
+    // サムネイル（ページ番号, 回転）
+    thumbnails: TextureCache<(usize, i32)>,
+
+    // 選択
+    selected: BTreeSet<usize>,
+    anchor: Option<usize>,  // Shift+クリックの範囲選択の起点
+    shown_page: Option<usize>,  // 最後に把握した表示中のページ
+    focused: bool,  // キー操作をこのパネルで受け付けるか
+    scroll_to_page: bool,
+
+    // ドラッグによる並べ替え
+    dragging: bool,
+}
+
+impl PageStrip {
+    pub fn new() -> Self {
+        Self {
+            thumbnails: TextureCache::new("page_thumb", THUMB_CACHE_BYTES),
+            selected: BTreeSet::new(),
+            anchor: None,
+            shown_page: None,
+            focused: false,
+            scroll_to_page: false,
+            dragging: false,
+        }
+    }
+
+    /// ページ一覧を描画
+    pub fn show(&mut self, ui: &mut egui::Ui, doc: &PdfDocument, current_page: usize) -> PageStripResult {
+        let mut result = PageStripResult::default();
+        let page_count = doc.page_count();
+        let ctx = ui.ctx().clone();
+        self.thumbnails.poll(&ctx);
+
+        // 前へ・次へなど他の操作で表示ページが変わったら選択を合わせる
+        self.selected.retain(|&p| p < page_count);
+        if self.shown_page != Some(current_page) {
+            self.shown_page = Some(current_page);
+            if !self.selected.contains(&current_page) {
+                self.select_only(current_page);
+            }
+            self.scroll_to_page = true;
+        }
+
+        if self.focused && !ctx.wants_keyboard_input() {
+            self.handle_keys(ui, page_count, &mut result);
+        }
+
+        ui.label(format!("{} ページ", page_count));
+        if self.selected.len() > 1 {
+            ui.label(format!("{} ページ選択中", self.selected.len()));
+        }
+        ui.separator();
+
+        let mut page_rects = Vec::with_capacity(page_count);
+        let scroll = egui::ScrollArea::vertical()
+            .auto_shrink([false, false])
+            .show(ui, |ui| {
+                ui.vertical_centered(|ui| {
+                    for page in 0..page_count {
+                        let rect = self.show_thumbnail(ui, doc, page, &mut result);
+                        if self.scroll_to_page && page == current_page {
+                            ui.scroll_to_rect(rect, None);
+                            self.scroll_to_page = false;
+                        }
+                        page_rects.push(rect);
+                    }
+                });
+            });
+
+        // ドラッグ中は挿入位置を示し、離したら並べ替える
+        if self.dragging {
+            let pointer = ui.input(|i| i.pointer.interact_pos());
+            let before = pointer.map(|pos| {
+                page_rects
+                    .iter()
+                    .position(|rect| pos.y < rect.center().y)
+                    .unwrap_or(page_count)
+            });
+            if let Some(before) = before {
+                let y = match page_rects.get(before) {
+                    Some(rect) => rect.min.y - 4.0,
+                    None => page_rects.last().map_or(0.0, |rect| rect.max.y + 4.0),
+                };
+                let x = scroll.inner_rect.x_range();
+                ui.painter().with_clip_rect(scroll.inner_rect).hline(
+                    x,
+                    y,
+                    egui::Stroke::new(3.0, Color32::from_rgb(100, 149, 237)),
+                );
+                ui.ctx().set_cursor_icon(egui::CursorIcon::Grabbing);
+
+                // 端に近づいたらスクロール
+                if let Some(pos) = pointer {
+                    let area = scroll.inner_rect;
+                    if pos.y < area.min.y + 20.0 {
+                        ui.scroll_with_delta(Vec2::new(0.0, 10.0));
+                    } else if pos.y > area.max.y - 20.0 {
+                        ui.scroll_with_delta(Vec2::new(0.0, -10.0));
+                    }
+                }
+            }
+            if ui.input(|i| i.pointer.any_released()) {
+                self.dragging = false;
+                if let Some(before) = before {
+                    result.reorder = Some((self.selected_pages(), before));
+                }
+            }
+        }
+
+        // パネルの外をクリックしたらキー操作は受け付けない
+        if ui.input(|i| i.pointer.any_pressed()) {
+            if let Some(pos) = ui.input(|i| i.pointer.interact_pos()) {
+                self.focused = ui.max_rect().contains(pos);
+            }
+        }
+
+        result
+    }
+
+    /// 1ページ分のサムネイルとページ番号を描画し、クリック・ドラッグ・右クリックを処理
+    fn show_thumbnail(
+        &mut self,
+        ui: &mut egui::Ui,
+        doc: &PdfDocument,
+        page: usize,
+        result: &mut PageStripResult,
+    ) -> egui::Rect {
+        let (width, height) = doc.page_size(page);
+        let size = Vec2::new(THUMB_WIDTH, THUMB_WIDTH * height / width);
+        let (rect, response) = ui.allocate_exact_size(size, egui::Sense::click_and_drag());
+        let is_selected = self.selected.contains(&page);
+
+        if ui.is_rect_visible(rect) {
+            let rotation = doc.get_page_rotation(page);
+            let key = (page, rotation);
+            let texture = self.thumbnails.get(&key);
+            if texture.is_none() {
+                let job = || RenderJob {
+                    source: RenderSource::Document(doc.clone()),
+                    page,
+                    area: RenderArea::Fit {
+                        width: THUMB_RENDER_SIZE,
+                        height: THUMB_RENDER_SIZE,
+                    },
+                };
+                self.thumbnails.request(ui.ctx(), &key, job, false);
+            }
+
+            if is_selected {
+                ui.painter().rect_filled(rect.expand(4.0), 4.0, Color32::from_rgb(70, 130, 180));
+            }
+            match texture {
+                Some(texture) => {
+                    ui.painter().image(
+                        texture.id(),
+                        rect,
+                        egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
+                        Color32::WHITE,
+                    );
+                }
+                None => {
+                    ui.painter().rect_filled(rect, 0.0, Color32::from_gray(230));
+                }
+            }
+            ui.painter().rect_stroke(rect, 0.0, egui::Stroke::new(1.0, Color32::from_gray(120)));
+        }
+        ui.label(format!("{}", page + 1));
+        ui.add_space(6.0);
+
+        if response.clicked() {
+            let modifiers = ui.input(|i| i.modifiers);
+            if modifiers.shift {
+                self.select_range(page);
+            } else if modifiers.command {
+                // Ctrl+クリックで選択に追加・選択から除外
+                if !self.selected.remove(&page) {
+                    self.selected.insert(page);
+                }
+                self.anchor = Some(page);
+            } else {
+                self.select_only(page);
+            }
+            self.show_page(page, result);
+            self.focused = true;
+        }
+
+        if response.drag_started() {
+            if !is_selected {
+                self.select_only(page);
+                self.show_page(page, result);
+            }
+            self.dragging = true;
+            self.focused = true;
+        }
+
+        if response.secondary_clicked() && !is_selected {
+            self.select_only(page);
+            self.show_page(page, result);
+        }
+        response.context_menu(|ui| {
+            let pages = self.selected_pages();
+            if pages.len() > 1 {
+                ui.label(format!("{} ページ", pages.len()));
+                ui.separator();
+            }
+            if ui.button("🔄 右に90°回転").clicked() {
+                result.rotate = Some((pages.clone(), 90));
+                ui.close_menu();
+            }
+            if ui.button("🔄 左に90°回転").clicked() {
+                result.rotate = Some((pages.clone(), 270));
+                ui.close_menu();
+            }
+            if ui.button("🔄 180°回転").clicked() {
+                result.rotate = Some((pages.clone(), 180));
+                ui.close_menu();
+            }
+            ui.separator();
+            if ui.button("📤 抽出して保存...").clicked() {
+                result.extract = Some(pages.clone());
+                ui.close_menu();
+            }
+            if ui.button("🗑 削除").clicked() {
+                result.delete = Some(pages);
+                ui.close_menu();
+            }
+        });
+
+        rect
+    }
+
+    /// キー操作（上下・Home/End・PageUp/PageDown で移動、Shift で範囲選択、Ctrl+A で全選択、Delete で削除）
+    ///
+    /// 処理したキーは消費し、エディターの Delete などに渡さない。
+    fn handle_keys(&mut self, ui: &egui::Ui, page_count: usize, result: &mut PageStripResult) {
+        if page_count == 0 {
+            return;
+        }
+        let current = self.shown_page.unwrap_or(0);
+        let last = page_count - 1;
+        let (target, shift) = ui.input_mut(|i| {
+            let shift = i.modifiers.shift;
+            // Shift 付きも一致する
+            let mut take = |key| i.consume_key(egui::Modifiers::NONE, key);
+            let target = if take(egui::Key::ArrowUp) || take(egui::Key::ArrowLeft) {
+                Some(current.saturating_sub(1))
+            } else if take(egui::Key::ArrowDown) || take(egui::Key::ArrowRight) {
+                Some((current + 1).min(last))
+            } else if take(egui::Key::PageUp) {
+                Some(current.saturating_sub(PAGE_JUMP))
+            } else if take(egui::Key::PageDown) {
+                Some((current + PAGE_JUMP).min(last))
+            } else if take(egui::Key::Home) {
+                Some(0)
+            } else if take(egui::Key::End) {
+                Some(last)
+            } else {
+                None
+            };
+            (target, shift)
+        });
+
+        if let Some(page) = target {
+            if shift {
+                self.select_range(page);
+            } else {
+                self.select_only(page);
+            }
+            self.show_page(page, result);
+            self.scroll_to_page = true;
+        }
+
+        if ui.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::A)) {
+            self.selected = (0..page_count).collect();
+        }
+        let delete = ui.input_mut(|i| {
+            i.consume_key(egui::Modifiers::NONE, egui::Key::Delete)
+                || i.consume_key(egui::Modifiers::NONE, egui::Key::Backspace)
+        });
+        if delete && !self.selected.is_empty() {
+            result.delete = Some(self.selected_pages());
+        }
+    }
+
+    /// 選択中のページ（昇順）
+    pub fn selected_pages(&self) -> Vec<usize> {
+        self.selected.iter().copied().collect()
+    }
+
+    /// 選択を置き換える（並べ替え後に移動したページを選択し直すときなど）
+    pub fn select_pages(&mut self, pages: &[usize]) {
+        self.selected = pages.iter().copied().collect();
+        self.anchor = pages.first().copied();
+    }
+
+    /// サムネイルを全て破棄（ページ構成の変更時など）
+    pub fn invalidate_cache(&mut self) {
+        self.thumbnails.clear();
+    }
+
+    /// 1ページだけを選択
+    fn select_only(&mut self, page: usize) {
+        self.selected.clear();
+        self.selected.insert(page);
+        self.anchor = Some(page);
+    }
+
+    /// 起点から `page` までを選択
+    fn select_range(&mut self, page: usize) {
+        let anchor = self.anchor.unwrap_or(page);
+        self.selected = (anchor.min(page)..=anchor.max(page)).collect();
+    }
+
+    /// エディターにページを表示させる
+    fn show_page(&mut self, page: usize, result: &mut PageStripResult) {
+        self.shown_page = Some(page);
+        result.select_page = Some(page);
+    }
+}
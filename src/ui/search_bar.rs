@@ -0,0 +1,214 @@
+//! 検索バー - 開いているPDFのテキスト検索と結果の移動
+
+use crate::pdf::{PdfDocument, SearchHit};
+use eframe::egui::{self, Color32};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+
+/// 検索バーの状態
+pub struct SearchBar {
+    query: String,
+    hits: Vec<SearchHit>,
+    current: Option<usize>,
+    // 検索中（ページごとの結果が届く。検索を始め直すと破棄され、検索スレッドも止まる）
+    receiver: Option<Receiver<Vec<SearchHit>>>,
+    searched_pages: usize,
+    focus_requested: bool,
+    scroll_requested: bool,  // 選択中の結果までスクロールする
+    from_page: usize,  // このページ以降の最初の結果を選択する
+    pending_start: Option<usize>,  // 次の描画で検索を始める（開始ページ）
+}
+
+impl SearchBar {
+    pub fn new() -> Self {
+        Self {
+            query: String::new(),
+            hits: Vec::new(),
+            current: None,
+            receiver: None,
+            searched_pages: 0,
+            focus_requested: false,
+            scroll_requested: false,
+            from_page: 0,
+            pending_start: None,
+        }
+    }
+
+    /// 検索バーを描画し、結果を移動したらそのページを返す
+    pub fn show(&mut self, ui: &mut egui::Ui, doc: &PdfDocument) -> Option<usize> {
+        if let Some(page) = self.pending_start.take() {
+            self.start(ui.ctx(), doc, page);
+        }
+        let mut go_to = self.receive();
+
+        // Ctrl+F で検索欄へ
+        let find = egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::F);
+        if ui.input_mut(|i| i.consume_shortcut(&find)) {
+            self.focus_requested = true;
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("🔍");
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut self.query)
+                    .hint_text("ページ内を検索")
+                    .desired_width(180.0),
+            );
+            if std::mem::take(&mut self.focus_requested) {
+                response.request_focus();
+            }
+            // Enter で検索（検索済みなら次へ、Shift+Enter で前へ）
+            if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                if self.receiver.is_none() && self.hits.is_empty() {
+                    self.start(ui.ctx(), doc, 0);
+                } else {
+                    let backward = ui.input(|i| i.modifiers.shift);
+                    go_to = go_to.or(self.step(backward));
+                }
+                response.request_focus();
+            }
+            if response.changed() {
+                self.clear_results();
+            }
+
+            let has_hits = !self.hits.is_empty();
+            if ui.add_enabled(has_hits, egui::Button::new("▲")).on_hover_text("前の結果 (Shift+Enter)").clicked() {
+                go_to = self.step(true);
+            }
+            if ui.add_enabled(has_hits, egui::Button::new("▼")).on_hover_text("次の結果 (Enter)").clicked() {
+                go_to = self.step(false);
+            }
+
+            if self.receiver.is_some() {
+                ui.spinner();
+                ui.label(format!("{} / {} ページ", self.searched_pages, doc.page_count()));
+            }
+            match self.current {
+                Some(current) => {
+                    ui.label(format!("{} / {} 件", current + 1, self.hits.len()));
+                }
+                None if self.receiver.is_none() && self.searched_pages > 0 => {
+                    ui.colored_label(Color32::GRAY, "見つかりません");
+                }
+                None => {}
+            }
+        });
+
+        go_to
+    }
+
+    /// 検索語を設定し、`page` 以降の最初の結果を選択するよう検索を予約
+    pub fn find(&mut self, query: &str, page: usize) {
+        self.query = query.to_string();
+        self.clear_results();
+        self.pending_start = Some(page);
+    }
+
+    /// 検索を始める（ワーカースレッドでページごとに検索し、`from_page` 以降の最初の結果を選択）
+    fn start(&mut self, ctx: &egui::Context, doc: &PdfDocument, from_page: usize) {
+        self.clear_results();
+        self.from_page = from_page;
+        let query = self.query.trim().to_string();
+        if query.is_empty() {
+            return;
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        let doc = doc.clone();
+        let ctx = ctx.clone();
+        let spawned = std::thread::Builder::new()
+            .name("text_search".to_string())
+            .spawn(move || {
+                for page in 0..doc.page_count() {
+                    let hits = match doc.page_text(page) {
+                        Ok(text) => text.find(&query),
+                        Err(e) => {
+                            log::warn!("ページ {} のテキストを読み取れません: {}", page + 1, e);
+                            Vec::new()
+                        }
+                    };
+                    // 受け取り側が無くなったら（検索し直したら）やめる
+                    if sender.send(hits).is_err() {
+                        return;
+                    }
+                    ctx.request_repaint();
+                }
+            });
+        match spawned {
+            Ok(_) => self.receiver = Some(receiver),
+            Err(e) => log::error!("検索スレッドを起動できません: {}", e),
+        }
+    }
+
+    /// 届いた検索結果を取り込み、最初に選択する結果が届いたらそのページを返す
+    ///
+    /// 開始ページ以降に結果が無ければ、検索し終えたところで先頭の結果を選択する。
+    fn receive(&mut self) -> Option<usize> {
+        let mut go_to = None;
+        while let Some(receiver) = &self.receiver {
+            match receiver.try_recv() {
+                Ok(hits) => {
+                    self.searched_pages += 1;
+                    self.hits.extend(hits);
+                    if self.current.is_none() {
+                        let from_page = self.from_page;
+                        if let Some(i) = self.hits.iter().position(|hit| hit.page >= from_page) {
+                            go_to = self.select(i);
+                        }
+                    }
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.receiver = None;
+                    if self.current.is_none() && !self.hits.is_empty() {
+                        go_to = self.select(0);
+                    }
+                }
+            }
+        }
+        go_to
+    }
+
+    /// 結果を選択し、そのページを返す
+    fn select(&mut self, index: usize) -> Option<usize> {
+        self.current = Some(index);
+        self.scroll_requested = true;
+        Some(self.hits[index].page)
+    }
+
+    /// 前後の結果へ移動し、そのページを返す
+    fn step(&mut self, backward: bool) -> Option<usize> {
+        let count = self.hits.len();
+        if count == 0 {
+            return None;
+        }
+        let next = match (self.current, backward) {
+            (None, _) => 0,
+            (Some(i), false) => (i + 1) % count,
+            (Some(i), true) => (i + count - 1) % count,
+        };
+        self.select(next)
+    }
+
+    /// ページ上の検索結果（選択中の結果なら true）
+    pub fn hits_on(&self, page: usize) -> impl Iterator<Item = (&SearchHit, bool)> {
+        self.hits
+            .iter()
+            .enumerate()
+            .filter(move |(_, hit)| hit.page == page)
+            .map(|(i, hit)| (hit, self.current == Some(i)))
+    }
+
+    /// 選択中の結果までスクロールする必要があれば true（一度だけ）
+    pub fn take_scroll_request(&mut self) -> bool {
+        std::mem::take(&mut self.scroll_requested)
+    }
+
+    /// 検索結果を破棄（検索語の変更やページ構成の変更時）
+    pub fn clear_results(&mut self) {
+        self.receiver = None;
+        self.hits.clear();
+        self.current = None;
+        self.searched_pages = 0;
+        self.scroll_requested = false;
+    }
+}
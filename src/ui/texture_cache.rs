@@ -0,0 +1,153 @@
+//! バックグラウンドで描画したページ画像のテクスチャキャッシュ（LRU）
+
+use crate::pdf::{RenderJob, RenderWorker};
+use eframe::egui::{self, TextureHandle};
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+/// キャッシュ済みのテクスチャ
+struct Entry {
+    texture: TextureHandle,
+    bytes: usize,
+    last_used: u64,
+}
+
+/// ページ画像のテクスチャキャッシュ
+///
+/// 無いテクスチャはワーカーに描画を依頼し、届いた結果をテクスチャにする。
+/// 合計サイズが上限を超えたら最後に使ってから最も時間が経ったものから破棄する。
+pub struct TextureCache<K> {
+    name: &'static str,
+    worker: RenderWorker<(u64, K)>,
+    entries: HashMap<K, Entry>,
+    pending: HashSet<K>,
+    // 描画に失敗したキー（`clear` まで再依頼しない）
+    failed: HashSet<K>,
+    // `clear` のたびに進め、それ以前の依頼の結果は捨てる
+    generation: u64,
+    tick: u64,
+    max_bytes: usize,
+}
+
+impl<K: Eq + Hash + Clone + Send + 'static> TextureCache<K> {
+    pub fn new(name: &'static str, max_bytes: usize) -> Self {
+        Self {
+            name,
+            worker: RenderWorker::new(name),
+            entries: HashMap::new(),
+            pending: HashSet::new(),
+            failed: HashSet::new(),
+            generation: 0,
+            tick: 0,
+            max_bytes,
+        }
+    }
+
+    /// 届いた描画結果をテクスチャに取り込む（毎フレーム呼ぶ）
+    pub fn poll(&mut self, ctx: &egui::Context) {
+        while let Some(((generation, key), image)) = self.worker.try_recv() {
+            if generation != self.generation {
+                continue;
+            }
+            self.pending.remove(&key);
+            let Some(image) = image else {
+                self.failed.insert(key);
+                continue;
+            };
+            let bytes = image.pixels.len() * 4;
+            let texture = ctx.load_texture(
+                format!("{}_{}", self.name, self.tick),
+                image,
+                egui::TextureOptions::LINEAR,  // スケーリング時に滑らかに
+            );
+            self.tick += 1;
+            self.entries.insert(
+                key,
+                Entry {
+                    texture,
+                    bytes,
+                    last_used: self.tick,
+                },
+            );
+            self.evict();
+        }
+    }
+
+    /// キャッシュ済みのテクスチャを取得
+    pub fn get(&mut self, key: &K) -> Option<TextureHandle> {
+        self.tick += 1;
+        let entry = self.entries.get_mut(key)?;
+        entry.last_used = self.tick;
+        Some(entry.texture.clone())
+    }
+
+    /// テクスチャを取得（無ければ `job` で描画を依頼して None）
+    pub fn get_or_request(
+        &mut self,
+        ctx: &egui::Context,
+        key: &K,
+        job: impl FnOnce() -> RenderJob,
+    ) -> Option<TextureHandle> {
+        let texture = self.get(key);
+        if texture.is_none() {
+            self.request(ctx, key, job, true);
+        }
+        texture
+    }
+
+    /// 描画を依頼（描画済み・依頼済み・失敗済みなら何もしない）
+    ///
+    /// `urgent` なら他の依頼より先に描画する。先読みなど急がないものは false。
+    pub fn request(
+        &mut self,
+        ctx: &egui::Context,
+        key: &K,
+        job: impl FnOnce() -> RenderJob,
+        urgent: bool,
+    ) {
+        if self.entries.contains_key(key) || self.failed.contains(key) {
+            return;
+        }
+        if self.pending.insert(key.clone()) {
+            self.worker.request(ctx, (self.generation, key.clone()), job(), urgent);
+        }
+    }
+
+    /// 条件に合うキャッシュ済みテクスチャを探す（描画待ちの間の代替表示用）
+    pub fn find(&mut self, matches: impl Fn(&K) -> bool) -> Option<TextureHandle> {
+        let key = self.entries.keys().find(|k| matches(k))?.clone();
+        self.get(&key)
+    }
+
+    /// 描画に失敗したか
+    pub fn has_failed(&self, key: &K) -> bool {
+        self.failed.contains(key)
+    }
+
+    /// 全て破棄（ページ構成の変更時など）
+    pub fn clear(&mut self) {
+        self.generation += 1;
+        self.worker.cancel_all();
+        self.entries.clear();
+        self.pending.clear();
+        self.failed.clear();
+    }
+
+    /// 上限を超えた分を古い順に破棄
+    fn evict(&mut self) {
+        let mut total: usize = self.entries.values().map(|e| e.bytes).sum();
+        while total > self.max_bytes && self.entries.len() > 1 {
+            let Some(oldest) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, e)| e.last_used)
+                .map(|(k, _)| k.clone())
+            else {
+                break;
+            };
+            if let Some(entry) = self.entries.remove(&oldest) {
+                total -= entry.bytes;
+            }
+        }
+    }
+}